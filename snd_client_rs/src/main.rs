@@ -2,7 +2,7 @@ mod ui;
 
 use std::io::{stdout, Write};
 use std::net::TcpStream;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, mpsc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
@@ -18,16 +18,29 @@ use tui::style::Style;
 use tui::Terminal;
 use tui::text::Span;
 use tui::widgets::{Block, Borders, BorderType, Paragraph};
-use snd_network_lib::client_event::{write_client_disconnect, write_client_drop_item, write_client_inspect_item, write_client_keepalive, write_client_open_inv, write_client_step};
+use snd_network_lib::auction_data::{AuctionActionData, AuctionActionKind, AuctionSearchData};
+use snd_network_lib::broadcast_data::BroadcastKind;
+use snd_network_lib::chat_data::{ChatChannel, ChatData};
+use snd_network_lib::client_event::{write_client_achievements, write_client_attack, write_client_auction, write_client_bank, write_client_bestiary, write_client_chat, write_client_command, write_client_consumables, write_client_craft, write_client_disconnect, write_client_drop_item, write_client_duel, write_client_expand_inv, write_client_guild, write_client_inspect_item, write_client_keepalive, write_client_leaderboard, write_client_mail, write_client_open_inv, write_client_party, write_client_quest, write_client_salvage, write_client_shop, write_client_skill, write_client_step, write_client_try_flee, write_client_upgrade, write_client_use_item};
+use snd_network_lib::consumable_data::{ShopActionData, ShopActionKind};
 use snd_network_lib::encounter_data::EncounterData;
 use snd_network_lib::enemy_data::EnemyData;
 use snd_network_lib::entry_point_io::{write_entry_login_attempt, write_entry_point_ver};
 use snd_network_lib::entry_response::read_entry_response;
+use snd_network_lib::inventory_data::{BankActionData, BankActionKind, InventoryRequestData, InventorySort};
+use snd_network_lib::mail_data::{MailActionData, MailActionKind};
+use snd_network_lib::guild_data::{GuildActionData, GuildActionKind};
+use snd_network_lib::skill_data::{SkillActionData, SkillActionKind};
+use snd_network_lib::status_data::StatusKind;
 use snd_network_lib::item_data::ItemData;
 use snd_network_lib::login_data::LoginData;
+use snd_network_lib::duel_data::{DuelActionData, DuelActionKind};
+use snd_network_lib::leaderboard_data::{LeaderboardCategory, LeaderboardRequestData};
+use snd_network_lib::party_data::{PartyActionData, PartyActionKind, PartyMemberData};
 use snd_network_lib::player_data::PlayerData;
+use snd_network_lib::quest_data::{QuestActionData, QuestActionKind, QuestData};
 use snd_network_lib::server_event::{read_server_event, ServerEvent};
-use crate::ui::{draw_home, Event};
+use crate::ui::{ChatLog, Panel, draw_achievements, draw_auctions, draw_bank, draw_bestiary, draw_crafting, draw_guild, draw_guild_bank, draw_home, draw_inventory, draw_leaderboard, draw_mail, draw_quests, draw_shop, draw_skills, Event, ITEM_TYPES, RARITIES, reward_text};
 
 fn get_ip() -> String {
     let ip_pattern =
@@ -60,8 +73,10 @@ fn get_login() -> LoginData {
     print!("Password: ");
     stdout().flush().expect("failed to flush stdout!");
     let passwd = read_password().expect("Failed to get password");
+    // the server gives players who do not pick a class its default one
+    let class = if signup { read_input!("Class (e.g. Warrior, Rogue or Mage, empty for the default): ") } else { String::new() };
     LoginData {
-        signup, username, passwd, client_ver: VERSION.to_string()
+        signup, username, passwd, client_ver: VERSION.to_string(), class
     }
 }
 
@@ -87,6 +102,24 @@ fn print_enemy(ed: &EnemyData) {
              "=".repeat(6 + ed.name.len()));
 }
 
+/// the status effects in a fight grouped by who they are on, e.g. `Grubnak: Poison 4 (3) | eric: Stun (1)`
+fn describe_effects(ed: &EncounterData) -> String {
+    let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+    for e in &ed.effects {
+        let who = if e.target.is_empty() { ed.enemy.name.clone() } else { e.target.clone() };
+        let effect = match e.kind {
+            StatusKind::Stun => format!("Stun ({})", e.turns),
+            StatusKind::Poison => format!("Poison {} ({})", e.amount, e.turns),
+            kind => format!("{} {}% ({})", kind.to_string(), e.amount, e.turns),
+        };
+        match groups.iter_mut().find(|(name, _)| *name == who) {
+            Some((_, effects)) => effects.push(effect),
+            None => groups.push((who, vec![effect])),
+        }
+    }
+    groups.iter().map(|(name, effects)| format!("{}: {}", name, effects.join(", "))).collect::<Vec<String>>().join(" | ")
+}
+
 struct BuffWrapper<T> {
     wrapped: T
 }
@@ -201,6 +234,7 @@ fn main() {
     // todo(eric): have a system for inputting login information
     // todo(eric): have a system to cache login data
     let login = get_login();
+    let username = login.username.clone();
 
     // connect to server and send login data
     let stream_res = TcpStream::connect(ip);
@@ -269,6 +303,15 @@ fn main() {
     });
 
     let output = Arc::new(Mutex::new(Output::new()));
+    let chat = Arc::new(Mutex::new(ChatLog::new()));
+    let party: Arc<Mutex<Vec<PartyMemberData>>> = Arc::new(Mutex::new(Vec::new()));
+    let stats: Arc<Mutex<Option<PlayerData>>> = Arc::new(Mutex::new(None));
+    // the screen shown in place of the home screen, the home screen is shown when there is none
+    let panel: Arc<Mutex<Option<Panel>>> = Arc::new(Mutex::new(None));
+    // the quest log is kept up to date by the server, but only shown when the player opens it
+    let quests: Arc<Mutex<Vec<QuestData>>> = Arc::new(Mutex::new(Vec::new()));
+    // the first row shown in the table of a panel that scrolls
+    let inventory_scroll = Arc::new(AtomicUsize::new(0));
     let mut ending_output = Arc::new(Mutex::new(BuffWrapper::new(String::new())));
 
     let mut encounter: Arc<Mutex<BuffWrapper<Option<EncounterData>>>> = Arc::new(Mutex::new(BuffWrapper::new(None)));
@@ -276,6 +319,12 @@ fn main() {
     let tarc = Arc::clone(&terminate);
     let op = Arc::clone(&output);
     let enctr = Arc::clone(&encounter);
+    let chat2 = Arc::clone(&chat);
+    let party2 = Arc::clone(&party);
+    let stats2 = Arc::clone(&stats);
+    let panel2 = Arc::clone(&panel);
+    let quests2 = Arc::clone(&quests);
+    let inventory_scroll2 = Arc::clone(&inventory_scroll);
     let eop = Arc::clone(&ending_output);
     let stream2 = stream.try_clone().expect("Failed to clone stream for server handler");
     let handler2 = thread::spawn(move || {
@@ -295,7 +344,19 @@ fn main() {
                     }
                 }
                 ServerEvent::Event(s) => {
-                    (op.lock().unwrap()).one(format!("{}", s));
+                    // multi-line events such as command output fill the output lines from the top
+                    if s.contains('\n') {
+                        let mut o = op.lock().unwrap();
+                        let lines = s.lines().collect::<Vec<&str>>();
+                        for x in 0..5 {
+                            o.set(x, *lines.get(x).unwrap_or(&""));
+                        }
+                        if lines.len() > 5 {
+                            o.append(4, format!(" (+{} more)", lines.len() - 5));
+                        }
+                    } else {
+                        (op.lock().unwrap()).one(format!("{}", s));
+                    }
                 }
                 ServerEvent::GainExp(amt) => {
                     (op.lock().unwrap()).one(format!("You gained {} exp!", amt));
@@ -323,7 +384,11 @@ fn main() {
                     (op.lock().unwrap()).set(4, format!("Enter 'inspect {}' to view more about this item!", id.name));
                 }
                 ServerEvent::Update(pd) => {
-                    // todo(eric)
+                    // the first update is sent when the player logs in, new mail after that is announced by the server
+                    if stats2.lock().unwrap().is_none() && pd.unread_mail > 0 {
+                        (op.lock().unwrap()).one(format!("You have {} unread mail, type 'mail' to read it", pd.unread_mail));
+                    }
+                    *stats2.lock().unwrap() = Some(pd);
                 }
                 ServerEvent::ItemView(id) => {
                     let itype = match id.itype {
@@ -342,41 +407,159 @@ fn main() {
                     };
                     (op.lock().unwrap()).set(0, format!("Name:   {}", id.name));
                     (op.lock().unwrap()).set(1, format!("Type:   {}", itype));
-                    (op.lock().unwrap()).set(2, format!("Level:  {}", id.level));
+                    if id.upgrades > 0 {
+                        (op.lock().unwrap()).set(2, format!("Level:  {} (+{})", id.level, id.upgrades));
+                    } else {
+                        (op.lock().unwrap()).set(2, format!("Level:  {}", id.level));
+                    }
                     (op.lock().unwrap()).set(3, format!("Rarity: {}", rarity));
-                    if id.defense.is_some() {
-                        (op.lock().unwrap()).set(4, format!("Defense: {}", id.defense.unwrap()));
+                    let stat = if id.defense.is_some() {
+                        format!("Defense: {}", id.defense.unwrap())
                     } else {
-                        (op.lock().unwrap()).set(4, format!("Damage: {}", id.damage.unwrap()));
-                    }
-                }
-                ServerEvent::Inventory(items) => {
-                    (op.lock().unwrap()).set(0, "INVENTORY | To view an item, enter 'inspect <item name>'");
-                    (op.lock().unwrap()).set(1, "");
-                    (op.lock().unwrap()).set(2, if items.is_empty() { "Your inventory is empty" } else { "" });
-                    (op.lock().unwrap()).set(3, "");
-                    (op.lock().unwrap()).set(4, "");
-                    let line_size = items.len() / 4;
-                    let mut line = 1;
-                    for x in 0..items.len() {
-                        let current = items.get(x).unwrap();
-                        let mut l = format!("'{}'", current.name);
-                        if x != items.len() - 1 {
-                            l.push_str(", ");
-
-                        }
-                        (op.lock().unwrap()).set(line, format!("{}", l));
-                        if x == line_size * line { line = (line + 1).min(4) }
+                        format!("Damage: {}", id.damage.unwrap())
+                    };
+                    // the special ability shares the last line since there are only 5
+                    if id.ability.is_empty() {
+                        (op.lock().unwrap()).set(4, stat);
+                    } else {
+                        (op.lock().unwrap()).set(4, format!("{} | {}", stat, id.ability));
                     }
                 }
+                ServerEvent::Inventory(inv) => {
+                    inventory_scroll2.store(0, Ordering::SeqCst);
+                    *panel2.lock().unwrap() = Some(Panel::Inventory(inv));
+                }
+                ServerEvent::Bank(items) => {
+                    inventory_scroll2.store(0, Ordering::SeqCst);
+                    *panel2.lock().unwrap() = Some(Panel::Bank(items));
+                }
+                ServerEvent::Auctions(list) => {
+                    // keep the player's place when the same listings are sent again after they bid or buy
+                    let last = list.listings.len().saturating_sub(1);
+                    inventory_scroll2.store(inventory_scroll2.load(Ordering::SeqCst).min(last), Ordering::SeqCst);
+                    *panel2.lock().unwrap() = Some(Panel::Auctions(list));
+                }
+                ServerEvent::Mail(letters) => {
+                    // keep the player's place when the mailbox is sent again after they read or claim a letter
+                    let last = letters.len().saturating_sub(1);
+                    inventory_scroll2.store(inventory_scroll2.load(Ordering::SeqCst).min(last), Ordering::SeqCst);
+                    *panel2.lock().unwrap() = Some(Panel::Mail(letters));
+                }
+                ServerEvent::Guild(info) => {
+                    inventory_scroll2.store(0, Ordering::SeqCst);
+                    *panel2.lock().unwrap() = Some(Panel::Guild(info));
+                }
+                ServerEvent::GuildBank(items) => {
+                    inventory_scroll2.store(0, Ordering::SeqCst);
+                    *panel2.lock().unwrap() = Some(Panel::GuildBank(items));
+                }
+                ServerEvent::Skills(tree) => {
+                    // keep the player's place when the tree is sent again after they learn a skill
+                    let last = tree.skills.len().saturating_sub(1);
+                    inventory_scroll2.store(inventory_scroll2.load(Ordering::SeqCst).min(last), Ordering::SeqCst);
+                    *panel2.lock().unwrap() = Some(Panel::Skills(tree));
+                }
+                ServerEvent::Bestiary(list) => {
+                    inventory_scroll2.store(0, Ordering::SeqCst);
+                    *panel2.lock().unwrap() = Some(Panel::Bestiary(list));
+                }
                 ServerEvent::Encounter(ed) => {
-                    // todo(eric): handle different encounter actions that are received here.
-                    (op.lock().unwrap()).set(0, "You encountered an enemy!");
-                    (op.lock().unwrap()).set(1, format!("Name: {}", ed.enemy.name));
-                    (op.lock().unwrap()).set(2, format!("Level: {}", ed.enemy.level));
-                    (op.lock().unwrap()).set(3, format!("Race: {}", ed.enemy.race));
-                    (op.lock().unwrap()).set(4, format!("Health: {}", ed.enemy.health));
-                    enctr.lock().unwrap().set(Some(ed));
+                    let mut o = op.lock().unwrap();
+                    for x in 0..5 {
+                        o.set(x, "");
+                    }
+                    o.set(0, ed.message.clone());
+                    o.set(1, format!("{} the {} (Level {}) | Health: {} / {}",
+                                     ed.enemy.name, ed.enemy.race, ed.enemy.level, ed.enemy.health, ed.enemy.max_health));
+                    if let Some(loot) = &ed.win {
+                        if loot.gold > 0 {
+                            o.set(2, format!("You gained {} exp and {} gold!", loot.exp, loot.gold));
+                        } else {
+                            o.set(2, format!("You gained {} exp!", loot.exp));
+                        }
+                        let got = loot.items.iter().map(|i| format!("'{}'", i.name))
+                            .chain(loot.consumables.iter().map(|c| format!("a {}", c)))
+                            .chain(loot.materials.iter().cloned())
+                            .collect::<Vec<String>>();
+                        if got.is_empty() {
+                            o.set(3, "You did not get any items this time");
+                        } else {
+                            o.set(3, format!("You got: {}", got.join(", ")));
+                        }
+                    } else if ed.lost.is_some() {
+                        o.set(2, "You were defeated!");
+                    } else if !ed.turn.is_empty() {
+                        if !ed.effects.is_empty() {
+                            o.set(2, format!("Effects: {}", describe_effects(&ed)));
+                        }
+                        o.set(3, format!("It is {}'s turn.", ed.turn));
+                        o.set(4, "Type 'attack', 'flee' or 'use <item>' on your turn.");
+                    }
+                    // the server stops sending whose turn it is once the fight is over for this player
+                    let over = ed.turn.is_empty();
+                    enctr.lock().unwrap().set(if over { None } else { Some(ed) });
+                }
+                ServerEvent::Leaderboard(lb) => {
+                    *panel2.lock().unwrap() = Some(Panel::Leaderboard(lb));
+                }
+                ServerEvent::Achievement(achievement) => {
+                    let line = format!("Achievement unlocked: {}! {}", achievement.name, achievement.description);
+                    (op.lock().unwrap()).one(line.clone());
+                    chat2.lock().unwrap().push(ChatData {
+                        channel: ChatChannel::System, sender: String::new(), target: String::new(), message: line,
+                    });
+                }
+                ServerEvent::Achievements(list) => {
+                    *panel2.lock().unwrap() = Some(Panel::Achievements(list));
+                }
+                ServerEvent::Shop(stock) => {
+                    *panel2.lock().unwrap() = Some(Panel::Shop(stock));
+                }
+                ServerEvent::Crafting(view) => {
+                    *panel2.lock().unwrap() = Some(Panel::Crafting(view));
+                }
+                ServerEvent::Consumables(owned) => {
+                    let mut o = op.lock().unwrap();
+                    o.one("");
+                    o.set(0, "BAG | To use an item, enter 'use <item name>'");
+                    if owned.is_empty() {
+                        o.set(2, "Your bag is empty, the merchant sells potions and food with 'shop'");
+                    }
+                    // a few stacks on each line so they fit
+                    for (line, stacks) in owned.chunks(4).take(4).enumerate() {
+                        o.set(line + 1, stacks.iter().map(|c| format!("{} x{}", c.name, c.quantity))
+                            .collect::<Vec<String>>().join(", "));
+                    }
+                }
+                ServerEvent::QuestOffer(quest) => {
+                    let mut o = op.lock().unwrap();
+                    o.one("");
+                    o.set(0, format!("{} offers you a quest: '{}'", quest.giver, quest.name));
+                    o.set(1, quest.description.clone());
+                    o.set(2, format!("{}. Reward: {}", quest.objective, reward_text(&quest)));
+                    o.set(4, "Type 'quest accept' or 'quest decline'.");
+                }
+                ServerEvent::Quests(list) => {
+                    *quests2.lock().unwrap() = list;
+                }
+                ServerEvent::Party(members) => {
+                    *party2.lock().unwrap() = members;
+                }
+                ServerEvent::Chat(cd) => {
+                    chat2.lock().unwrap().push(cd);
+                }
+                ServerEvent::Broadcast(bd) => {
+                    let label = match bd.kind {
+                        BroadcastKind::Announcement => "Server",
+                        BroadcastKind::Shutdown => "Shutdown",
+                        BroadcastKind::WorldBoss => "World Boss",
+                        BroadcastKind::Loot => "Loot",
+                    };
+                    let line = format!("[{}] {}", label, bd.message);
+                    (op.lock().unwrap()).one(line.clone());
+                    chat2.lock().unwrap().push(ChatData {
+                        channel: ChatChannel::System, sender: String::new(), target: String::new(), message: line,
+                    });
                 }
                 ServerEvent::Error(ed) => {
                     (op.lock().unwrap()).one(format!("Error from the server: {}", ed.msg));
@@ -392,6 +575,10 @@ fn main() {
     let mut input_mode = false;
     let mut input_ready = false;
     let mut user_input = String::new();
+    // the last auction house search, shown again after the player sells, bids or buys
+    let mut auction_search = AuctionSearchData::default();
+    // the letter shown under the mailbox
+    let mut open_mail: Option<u32> = None;
 
     loop {
         if terminate.load(Ordering::SeqCst) {
@@ -421,7 +608,23 @@ fn main() {
                 .split(size);
 
             // handle the main page
-            draw_home(&mut rect, &chunks, &output);
+            let scroll = inventory_scroll.load(Ordering::SeqCst);
+            match panel.lock().unwrap().as_ref() {
+                Some(Panel::Leaderboard(lb)) => draw_leaderboard(&mut rect, &chunks, lb),
+                Some(Panel::Achievements(list)) => draw_achievements(&mut rect, &chunks, list),
+                Some(Panel::Quests) => draw_quests(&mut rect, &chunks, &quests.lock().unwrap()),
+                Some(Panel::Shop(stock)) => draw_shop(&mut rect, &chunks, stock),
+                Some(Panel::Crafting(view)) => draw_crafting(&mut rect, &chunks, view),
+                Some(Panel::Inventory(inv)) => draw_inventory(&mut rect, &chunks, inv, scroll),
+                Some(Panel::Bank(items)) => draw_bank(&mut rect, &chunks, items, scroll),
+                Some(Panel::Auctions(list)) => draw_auctions(&mut rect, &chunks, list, scroll),
+                Some(Panel::Mail(letters)) => draw_mail(&mut rect, &chunks, letters, open_mail, scroll),
+                Some(Panel::Guild(info)) => draw_guild(&mut rect, &chunks, info, scroll),
+                Some(Panel::GuildBank(items)) => draw_guild_bank(&mut rect, &chunks, items, scroll),
+                Some(Panel::Skills(tree)) => draw_skills(&mut rect, &chunks, tree, scroll),
+                Some(Panel::Bestiary(list)) => draw_bestiary(&mut rect, &chunks, list, scroll),
+                None => draw_home(&mut rect, &chunks, &output, &chat, &party, username.as_str(), &stats),
+            }
 
             if input_mode {
                 let input = Paragraph::new(user_input.clone())
@@ -456,6 +659,12 @@ fn main() {
                             break;
                         }
                     }
+                    // read what is open up front so the panel is not locked while a key changes it
+                    let (open, rows, leaderboard) = match panel.lock().unwrap().as_ref() {
+                        Some(Panel::Leaderboard(lb)) => (true, None, Some((lb.category, lb.page, lb.pages))),
+                        Some(p) => (true, p.rows(), None),
+                        None => (false, None, None),
+                    };
                     if input_mode {
                         match event.code {
                             KeyCode::Char(c) => {
@@ -475,10 +684,55 @@ fn main() {
                             }
                             _ => {}
                         }
+                    } else if let Some(rows) = rows {
+                        // the panels with tables all scroll the same way
+                        let last = rows.saturating_sub(1);
+                        let scroll = inventory_scroll.load(Ordering::SeqCst);
+                        match event.code {
+                            KeyCode::Esc | KeyCode::Char('q') => *panel.lock().unwrap() = None,
+                            KeyCode::Enter => input_mode = true,
+                            KeyCode::Up => inventory_scroll.store(scroll.saturating_sub(1), Ordering::SeqCst),
+                            KeyCode::Down => inventory_scroll.store((scroll + 1).min(last), Ordering::SeqCst),
+                            KeyCode::PageUp => inventory_scroll.store(scroll.saturating_sub(10), Ordering::SeqCst),
+                            KeyCode::PageDown => inventory_scroll.store((scroll + 10).min(last), Ordering::SeqCst),
+                            _ => {}
+                        }
+                    } else if let Some((category, page, pages)) = leaderboard {
+                        // page through the leaderboard, or switch to the next category
+                        let request = match event.code {
+                            KeyCode::Left if page > 0 => Some(LeaderboardRequestData { category, page: page - 1 }),
+                            KeyCode::Right if page + 1 < pages => Some(LeaderboardRequestData { category, page: page + 1 }),
+                            KeyCode::Tab => {
+                                let next = LeaderboardCategory::ALL.iter().position(|c| *c == category).unwrap_or(0) + 1;
+                                Some(LeaderboardRequestData { category: LeaderboardCategory::ALL[next % LeaderboardCategory::ALL.len()], page: 0 })
+                            }
+                            _ => None,
+                        };
+                        match event.code {
+                            KeyCode::Esc | KeyCode::Char('q') => *panel.lock().unwrap() = None,
+                            KeyCode::Enter => input_mode = true,
+                            _ => {}
+                        }
+                        if let Some(request) = request {
+                            if let Err(e) = write_client_leaderboard(&stream, request) {
+                                ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                                return;
+                            }
+                        }
+                    } else if open {
+                        match event.code {
+                            KeyCode::Esc | KeyCode::Char('q') => *panel.lock().unwrap() = None,
+                            KeyCode::Enter => input_mode = true,
+                            _ => {}
+                        }
                     } else {
                         match event.code {
                             KeyCode::Char('q') => break,
                             KeyCode::Enter => input_mode = true,
+                            KeyCode::Up => chat.lock().unwrap().scroll_up(1),
+                            KeyCode::Down => chat.lock().unwrap().scroll_down(1),
+                            KeyCode::PageUp => chat.lock().unwrap().scroll_up(10),
+                            KeyCode::PageDown => chat.lock().unwrap().scroll_down(10),
                             _ => {}
                         }
                     }
@@ -489,6 +743,16 @@ fn main() {
 
         // handle input
         if input_ready {
+            // staff commands are sent to the server as typed
+            if let Some(command) = user_input.strip_prefix('/') {
+                if let Err(e) = write_client_command(&stream, command.to_string()) {
+                    ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                    return;
+                }
+                input_ready = false;
+                user_input.clear();
+                continue;
+            }
             let mut args = user_input.split(" ").collect::<Vec<&str>>();
             let cmd = args.remove(0);
             match cmd.to_ascii_lowercase().as_str() {
@@ -498,8 +762,237 @@ fn main() {
                         return;
                     }
                 }
+                "inv" if args.first().map_or(false, |a| a.eq_ignore_ascii_case("expand")) => {
+                    if let Err(e) = write_client_expand_inv(&stream) {
+                        ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                        return;
+                    }
+                }
                 "inv" => {
-                    if let Err(e) = write_client_open_inv(&stream) {
+                    // e.g. 'inv epic swords by level'
+                    let mut request = InventoryRequestData::default();
+                    let mut words = args.iter().map(|a| a.to_ascii_lowercase()).filter(|a| !a.is_empty());
+                    let mut valid = true;
+                    while let Some(word) = words.next() {
+                        let singular = word.trim_end_matches('s');
+                        match word.as_str() {
+                            "by" => request.sort = match words.next().as_deref() {
+                                Some("type") => InventorySort::Type,
+                                Some("rarity") => InventorySort::Rarity,
+                                Some("level") => InventorySort::Level,
+                                _ => { valid = false; break; }
+                            },
+                            "equipped" => request.equipped = true,
+                            _ => if let Some(t) = ITEM_TYPES.iter().position(|t| t.eq_ignore_ascii_case(singular)) {
+                                request.itype = Some(t as u32);
+                            } else if let Some(r) = RARITIES.iter().position(|r| r.eq_ignore_ascii_case(word.as_str())) {
+                                request.rarity = Some(r as u32);
+                            } else {
+                                valid = false;
+                                break;
+                            },
+                        }
+                    }
+                    if !valid {
+                        (output.lock().unwrap()).one("Usage: 'inv [type] [rarity] [equipped] [by type|rarity|level]' or 'inv expand'");
+                        input_ready = false;
+                        user_input.clear();
+                        continue;
+                    }
+                    if let Err(e) = write_client_open_inv(&stream, request) {
+                        ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                        return;
+                    }
+                }
+                "bank" => {
+                    if let Err(e) = write_client_bank(&stream, BankActionData { kind: BankActionKind::List, item: String::new() }) {
+                        ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                        return;
+                    }
+                }
+                "deposit" | "withdraw" => {
+                    if args.len() < 1 {
+                        let action = cmd.to_ascii_lowercase();
+                        (output.lock().unwrap()).one(format!("You must specify which item to {}! '{} <item name>'", action, action));
+                        input_ready = false;
+                        user_input.clear();
+                        continue;
+                    }
+                    let kind = if cmd.eq_ignore_ascii_case("deposit") { BankActionKind::Deposit } else { BankActionKind::Withdraw };
+                    if let Err(e) = write_client_bank(&stream, BankActionData { kind, item: args.join(" ") }) {
+                        ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                        return;
+                    }
+                }
+                "ah" | "auction" => {
+                    let sub = args.first().map(|a| a.to_ascii_lowercase()).unwrap_or_default();
+                    // the listing number, and the gold when bidding
+                    let numbers = args.iter().skip(1).map(|a| a.trim_start_matches('#').parse::<u32>().ok()).collect::<Vec<Option<u32>>>();
+                    let mut action = AuctionActionData::search(auction_search.clone());
+                    match (sub.as_str(), numbers.as_slice()) {
+                        ("sell", _) => {
+                            // e.g. 'ah sell Spiky Hat 500 100 12', the numbers after the item are the buyout, starting bid and hours
+                            let trailing = numbers.iter().rev().take_while(|n| n.is_some()).take(3).count();
+                            let mut prices = numbers[numbers.len() - trailing..].iter().flatten();
+                            action.kind = AuctionActionKind::Sell;
+                            action.item = args[1..args.len() - trailing].join(" ");
+                            action.price = prices.next().copied().unwrap_or(0);
+                            action.start_bid = prices.next().copied().unwrap_or(0);
+                            action.hours = prices.next().copied().unwrap_or(0);
+                            if action.item.is_empty() || action.price == 0 {
+                                (output.lock().unwrap()).one("Usage: 'ah sell <item> <buyout> [starting bid, 0 for none] [hours]'");
+                                input_ready = false;
+                                user_input.clear();
+                                continue;
+                            }
+                        }
+                        ("bid", [Some(listing), Some(gold)]) => {
+                            action.kind = AuctionActionKind::Bid;
+                            action.listing = *listing;
+                            action.price = *gold;
+                        }
+                        ("buy", [Some(listing)]) | ("cancel", [Some(listing)]) => {
+                            action.kind = if sub == "buy" { AuctionActionKind::Buyout } else { AuctionActionKind::Cancel };
+                            action.listing = *listing;
+                        }
+                        ("bid", _) | ("buy", _) | ("cancel", _) => {
+                            (output.lock().unwrap()).one("Usage: 'ah bid <#> <gold>', 'ah buy <#>' or 'ah cancel <#>'");
+                            input_ready = false;
+                            user_input.clear();
+                            continue;
+                        }
+                        _ => {
+                            // e.g. 'ah epic sword 10-20 edge' or 'ah mine'
+                            let mut search = AuctionSearchData::default();
+                            let mut name = Vec::new();
+                            for word in args.iter().filter(|a| !a.is_empty()) {
+                                let lower = word.to_ascii_lowercase();
+                                let singular = lower.trim_end_matches('s');
+                                let levels = lower.split_once('-').map_or((lower.parse::<u32>().ok(), lower.parse::<u32>().ok()),
+                                                                          |(min, max)| (min.parse::<u32>().ok(), max.parse::<u32>().ok()));
+                                if lower == "mine" {
+                                    search.mine = true;
+                                } else if let Some(t) = ITEM_TYPES.iter().position(|t| t.eq_ignore_ascii_case(singular)) {
+                                    search.itype = Some(t as u32);
+                                } else if let Some(r) = RARITIES.iter().position(|r| r.eq_ignore_ascii_case(lower.as_str())) {
+                                    search.rarity = Some(r as u32);
+                                } else if let (Some(min), Some(max)) = levels {
+                                    search.min_level = Some(min);
+                                    search.max_level = Some(max);
+                                } else {
+                                    name.push(*word);
+                                }
+                            }
+                            search.name = name.join(" ");
+                            auction_search = search.clone();
+                            action = AuctionActionData::search(search);
+                        }
+                    }
+                    if let Err(e) = write_client_auction(&stream, action) {
+                        ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                        return;
+                    }
+                }
+                "mail" => {
+                    let sub = args.first().map(|a| a.to_ascii_lowercase()).unwrap_or_default();
+                    let number = args.get(1).and_then(|a| a.trim_start_matches('#').parse::<u32>().ok());
+                    let action = match (sub.as_str(), number) {
+                        ("" | "list", _) => {
+                            open_mail = None;
+                            MailActionData::new(MailActionKind::List, 0)
+                        }
+                        ("read", Some(n)) => {
+                            open_mail = Some(n);
+                            MailActionData::new(MailActionKind::Read, n)
+                        }
+                        ("claim", Some(n)) => MailActionData::new(MailActionKind::Claim, n),
+                        ("delete", Some(n)) => MailActionData::new(MailActionKind::Delete, n),
+                        ("send", _) if args.len() > 2 => {
+                            // e.g. 'mail send bob 50 Edge, Spiky Hat: thanks for the help', without a ':' it is all message
+                            let rest = args[2..].join(" ");
+                            let (attached, message) = rest.split_once(':').unwrap_or(("", rest.as_str()));
+                            let mut attached = attached.trim();
+                            let mut action = MailActionData::new(MailActionKind::Send, 0);
+                            let first = attached.split(|c: char| c.is_whitespace() || c == ',').next().unwrap_or("");
+                            if let Ok(gold) = first.parse::<u32>() {
+                                action.gold = gold;
+                                attached = attached[first.len()..].trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+                            }
+                            action.to = args[1].to_string();
+                            action.message = message.trim().to_string();
+                            action.items = attached.split(',').map(|i| i.trim().to_string()).filter(|i| !i.is_empty()).collect();
+                            action
+                        }
+                        _ => {
+                            (output.lock().unwrap()).one("Usage: 'mail', 'mail read|claim|delete <#>' or 'mail send <user> [gold] [item, item]: <message>'");
+                            input_ready = false;
+                            user_input.clear();
+                            continue;
+                        }
+                    };
+                    if let Err(e) = write_client_mail(&stream, action) {
+                        ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                        return;
+                    }
+                }
+                "skills" => {
+                    let sub = args.first().map(|a| a.to_ascii_lowercase()).unwrap_or_default();
+                    let skill = args.iter().skip(1).map(|a| a.to_string()).collect::<Vec<String>>().join(" ");
+                    let kind = match (sub.as_str(), skill.is_empty()) {
+                        ("" | "list", _) => SkillActionKind::List,
+                        ("reset", _) => SkillActionKind::Reset,
+                        ("learn", false) => SkillActionKind::Learn,
+                        ("class", false) => SkillActionKind::Class,
+                        _ => {
+                            (output.lock().unwrap()).one("Usage: 'skills', 'skills learn <skill>', 'skills class <class>' or 'skills reset [class]'");
+                            input_ready = false;
+                            user_input.clear();
+                            continue;
+                        }
+                    };
+                    if let Err(e) = write_client_skill(&stream, SkillActionData { kind, skill }) {
+                        ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                        return;
+                    }
+                }
+                "skill" => {
+                    if args.is_empty() {
+                        (output.lock().unwrap()).one("You must specify which skill to use! 'skill <skill>'");
+                        input_ready = false;
+                        user_input.clear();
+                        continue;
+                    }
+                    if let Err(e) = write_client_skill(&stream, SkillActionData { kind: SkillActionKind::Use, skill: args.join(" ") }) {
+                        ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                        return;
+                    }
+                }
+                "guild" => {
+                    let sub = args.first().map(|a| a.to_ascii_lowercase()).unwrap_or_default();
+                    let target = args.iter().skip(1).map(|a| a.to_string()).collect::<Vec<String>>().join(" ");
+                    let kind = match (sub.as_str(), target.is_empty()) {
+                        ("" | "info", _) => GuildActionKind::Info,
+                        ("accept", _) => GuildActionKind::Accept,
+                        ("decline", _) => GuildActionKind::Decline,
+                        ("leave", true) => GuildActionKind::Leave,
+                        ("disband", true) => GuildActionKind::Disband,
+                        ("bank", true) => GuildActionKind::Bank,
+                        ("create", false) => GuildActionKind::Create,
+                        ("invite", false) => GuildActionKind::Invite,
+                        ("kick", false) => GuildActionKind::Kick,
+                        ("promote", false) => GuildActionKind::Promote,
+                        ("demote", false) => GuildActionKind::Demote,
+                        ("deposit", false) => GuildActionKind::Deposit,
+                        ("withdraw", false) => GuildActionKind::Withdraw,
+                        _ => {
+                            (output.lock().unwrap()).one("Usage: 'guild [info <guild>]', 'guild create <name>', 'guild invite|kick|promote|demote <user>', \
+                                'guild accept|decline [guild]', 'guild leave|disband|bank' or 'guild deposit|withdraw <item>'");
+                            input_ready = false;
+                            user_input.clear();
+                            continue;
+                        }
+                    };
+                    if let Err(e) = write_client_guild(&stream, GuildActionData { kind, target }) {
                         ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
                         return;
                     }
@@ -520,6 +1013,228 @@ fn main() {
                         return;
                     }
                 }
+                "g" | "global" | "r" | "region" | "gc" | "guildchat" => {
+                    if args.is_empty() {
+                        (output.lock().unwrap()).one(format!("You must enter a message! '{} <message>'", cmd));
+                        input_ready = false;
+                        user_input.clear();
+                        continue;
+                    }
+                    let channel = match cmd.to_ascii_lowercase().as_str() {
+                        "g" | "global" => ChatChannel::Global,
+                        "gc" | "guildchat" => ChatChannel::Guild,
+                        _ => ChatChannel::Region,
+                    };
+                    let chat_data = ChatData {
+                        channel, sender: String::new(), target: String::new(), message: args.join(" "),
+                    };
+                    if let Err(e) = write_client_chat(&stream, chat_data) {
+                        ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                        return;
+                    }
+                }
+                "w" | "whisper" => {
+                    if args.len() < 2 {
+                        (output.lock().unwrap()).one(format!("You must enter who to whisper to and a message! '{} <user> <message>'", cmd));
+                        input_ready = false;
+                        user_input.clear();
+                        continue;
+                    }
+                    let chat_data = ChatData {
+                        channel: ChatChannel::Whisper, sender: String::new(),
+                        target: args[0].to_string(), message: args[1..].join(" "),
+                    };
+                    if let Err(e) = write_client_chat(&stream, chat_data) {
+                        ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                        return;
+                    }
+                }
+                "attack" | "a" => {
+                    if let Err(e) = write_client_attack(&stream) {
+                        ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                        return;
+                    }
+                }
+                "flee" => {
+                    if let Err(e) = write_client_try_flee(&stream) {
+                        ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                        return;
+                    }
+                }
+                "party" | "p" => {
+                    let kind = match args.get(0).map(|a| a.to_ascii_lowercase()).as_deref() {
+                        None | Some("") | Some("status") => PartyActionKind::Status,
+                        Some("invite") => PartyActionKind::Invite,
+                        Some("accept") => PartyActionKind::Accept,
+                        Some("decline") => PartyActionKind::Decline,
+                        Some("leave") => PartyActionKind::Leave,
+                        Some("kick") => PartyActionKind::Kick,
+                        Some(_) => {
+                            (output.lock().unwrap()).one("Usage: 'party [invite <user>|accept|decline|leave|kick <user>]'");
+                            input_ready = false;
+                            user_input.clear();
+                            continue;
+                        }
+                    };
+                    let target = args.get(1).unwrap_or(&"").to_string();
+                    if (kind == PartyActionKind::Invite || kind == PartyActionKind::Kick) && target.is_empty() {
+                        (output.lock().unwrap()).one(format!("You must specify a player! 'party {} <user>'", args[0]));
+                        input_ready = false;
+                        user_input.clear();
+                        continue;
+                    }
+                    if let Err(e) = write_client_party(&stream, PartyActionData { kind, target }) {
+                        ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                        return;
+                    }
+                }
+                "duel" | "d" => {
+                    // item names can have spaces so wagered items are separated by commas
+                    let item_list = |from: usize| -> Vec<String> {
+                        args.get(from..).unwrap_or(&[]).join(" ").split(',')
+                            .map(|i| i.trim().to_string()).filter(|i| !i.is_empty()).collect()
+                    };
+                    let action = match args.get(0).map(|a| a.to_ascii_lowercase()).as_deref() {
+                        None | Some("") => {
+                            (output.lock().unwrap()).one("Usage: 'duel <user> [gold] [item, item...]', 'duel accept [item, item...]', 'duel decline' or 'duel forfeit'");
+                            input_ready = false;
+                            user_input.clear();
+                            continue;
+                        }
+                        Some("accept") => DuelActionData { kind: DuelActionKind::Accept, target: String::new(), gold: 0, items: item_list(1) },
+                        Some("decline") => DuelActionData { kind: DuelActionKind::Decline, target: String::new(), gold: 0, items: Vec::new() },
+                        Some("forfeit") => DuelActionData { kind: DuelActionKind::Forfeit, target: String::new(), gold: 0, items: Vec::new() },
+                        Some(_) => {
+                            // the gold is optional, anything after the player's name that is not a number is an item
+                            let gold = args.get(1).and_then(|g| g.parse::<u32>().ok());
+                            let items = item_list(if gold.is_some() { 2 } else { 1 });
+                            DuelActionData { kind: DuelActionKind::Challenge, target: args[0].to_string(), gold: gold.unwrap_or(0), items }
+                        }
+                    };
+                    if let Err(e) = write_client_duel(&stream, action) {
+                        ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                        return;
+                    }
+                }
+                "quest" | "quests" | "q" => {
+                    let name = args.get(1..).unwrap_or(&[]).join(" ");
+                    let kind = match args.get(0).map(|a| a.to_ascii_lowercase()).as_deref() {
+                        None | Some("") | Some("log") => QuestActionKind::Log,
+                        Some("accept") => QuestActionKind::Accept,
+                        Some("decline") => QuestActionKind::Decline,
+                        Some("abandon") if !name.is_empty() => QuestActionKind::Abandon,
+                        Some("turnin") if !name.is_empty() => QuestActionKind::TurnIn,
+                        Some(_) => {
+                            (output.lock().unwrap()).one("Usage: 'quest [log|accept|decline|abandon <quest>|turnin <quest>]'");
+                            input_ready = false;
+                            user_input.clear();
+                            continue;
+                        }
+                    };
+                    if kind == QuestActionKind::Log {
+                        *panel.lock().unwrap() = Some(Panel::Quests);
+                    }
+                    if let Err(e) = write_client_quest(&stream, QuestActionData { kind, quest: name }) {
+                        ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                        return;
+                    }
+                }
+                "use" | "bag" => {
+                    // with no item the contents of the bag are shown instead
+                    let item = args.join(" ");
+                    let sent = if cmd.eq_ignore_ascii_case("bag") || item.trim().is_empty() {
+                        write_client_consumables(&stream)
+                    } else {
+                        write_client_use_item(&stream, item)
+                    };
+                    if let Err(e) = sent {
+                        ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                        return;
+                    }
+                }
+                "shop" | "buy" => {
+                    let action = if cmd.eq_ignore_ascii_case("shop") {
+                        ShopActionData { kind: ShopActionKind::List, item: String::new(), quantity: 0 }
+                    } else {
+                        // the amount is optional and comes after the item's name
+                        let quantity = args.last().and_then(|q| q.parse::<u32>().ok());
+                        let item = args[..args.len() - quantity.map_or(0, |_| 1)].join(" ");
+                        if item.trim().is_empty() {
+                            (output.lock().unwrap()).one("You must specify what to buy! 'buy <item> [amount]'");
+                            input_ready = false;
+                            user_input.clear();
+                            continue;
+                        }
+                        ShopActionData { kind: ShopActionKind::Buy, item, quantity: quantity.unwrap_or(1) }
+                    };
+                    if let Err(e) = write_client_shop(&stream, action) {
+                        ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                        return;
+                    }
+                }
+                "salvage" => {
+                    if args.join(" ").trim().is_empty() {
+                        (output.lock().unwrap()).one("You must specify which item to salvage! 'salvage <item name>'");
+                        input_ready = false;
+                        user_input.clear();
+                        continue;
+                    }
+                    if let Err(e) = write_client_salvage(&stream, args.join(" ")) {
+                        ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                        return;
+                    }
+                }
+                "upgrade" => {
+                    if args.join(" ").trim().is_empty() {
+                        (output.lock().unwrap()).one("You must specify which item to upgrade! 'upgrade <item name>'");
+                        input_ready = false;
+                        user_input.clear();
+                        continue;
+                    }
+                    if let Err(e) = write_client_upgrade(&stream, args.join(" ")) {
+                        ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                        return;
+                    }
+                }
+                "craft" => {
+                    // with no recipe the list of recipes is shown
+                    if let Err(e) = write_client_craft(&stream, args.join(" ").trim().to_string()) {
+                        ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                        return;
+                    }
+                }
+                "achievements" | "ach" => {
+                    if let Err(e) = write_client_achievements(&stream) {
+                        ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                        return;
+                    }
+                }
+                "bestiary" => {
+                    if let Err(e) = write_client_bestiary(&stream) {
+                        ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                        return;
+                    }
+                }
+                "leaderboard" | "lb" => {
+                    let category = match args.get(0).filter(|a| !a.is_empty()) {
+                        Some(name) => match LeaderboardCategory::ALL.iter().find(|c| c.to_string() == name.to_ascii_lowercase()) {
+                            Some(c) => *c,
+                            None => {
+                                (output.lock().unwrap()).one("Usage: 'leaderboard [level|steps|kills|wealth|duels] [page]'");
+                                input_ready = false;
+                                user_input.clear();
+                                continue;
+                            }
+                        },
+                        None => LeaderboardCategory::Level,
+                    };
+                    // pages start at 1 for the player but 0 for the server
+                    let page = args.get(1).and_then(|p| p.parse::<u32>().ok()).unwrap_or(1).max(1) - 1;
+                    if let Err(e) = write_client_leaderboard(&stream, LeaderboardRequestData { category, page }) {
+                        ending_output.lock().unwrap().set(format!("Failed to send packet to server: {}", e));
+                        return;
+                    }
+                }
                 "inspect" => {
                     // make sure there is an item name specified
                     if args.len() < 1 {
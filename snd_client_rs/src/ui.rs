@@ -1,3 +1,4 @@
+use std::collections::VecDeque;
 use std::io::Stdout;
 use std::sync::{Arc, Mutex};
 use tui::backend::CrosstermBackend;
@@ -5,21 +6,816 @@ use tui::Frame;
 use tui::layout::{Alignment, Constraint, Direction, Layout, Rect};
 use tui::style::{Color, Modifier, Style};
 use tui::text::{Span, Spans};
-use tui::widgets::{Block, Borders, BorderType, Paragraph, Row, Table};
+use tui::widgets::{Block, Borders, BorderType, Paragraph, Row, Table, Wrap};
+use snd_network_lib::achievement_data::AchievementData;
+use snd_network_lib::auction_data::AuctionListData;
+use snd_network_lib::bestiary_data::BestiaryEntryData;
+use snd_network_lib::chat_data::{ChatChannel, ChatData};
+use snd_network_lib::consumable_data::ConsumableData;
+use snd_network_lib::crafting_data::CraftingData;
+use snd_network_lib::guild_data::GuildData;
+use snd_network_lib::skill_data::SkillTreeData;
+use snd_network_lib::inventory_data::InventoryData;
+use snd_network_lib::item_data::ItemData;
+use snd_network_lib::leaderboard_data::{LeaderboardCategory, LeaderboardData};
+use snd_network_lib::mail_data::MailData;
+use snd_network_lib::party_data::PartyMemberData;
+use snd_network_lib::player_data::PlayerData;
+use snd_network_lib::quest_data::{QuestData, QuestState};
+use snd_network_lib::systime;
 use crate::Output;
 
+/// how many chat messages are kept for scrolling back through
+const CHAT_HISTORY: usize = 200;
+
 pub(crate) enum Event<I> {
     Input(I),
     Tick,
 }
 
-pub(crate) fn draw_home(rect: &mut Frame<CrosstermBackend<Stdout>>, chunks: &Vec<Rect>, output: &Arc<Mutex<Output>>) {
+/// The chat messages received from the server, newest last
+pub(crate) struct ChatLog {
+    messages: VecDeque<(ChatChannel, String)>,
+    /// how many lines the view is scrolled up from the newest message
+    scroll: usize,
+}
+
+impl ChatLog {
+    pub fn new() -> Self {
+        Self {
+            messages: VecDeque::new(),
+            scroll: 0,
+        }
+    }
+
+    pub fn push(&mut self, chat: ChatData) {
+        let line = match chat.channel {
+            ChatChannel::Global => format!("[Global] {}: {}", chat.sender, chat.message),
+            ChatChannel::Region => format!("[{}] {}: {}", chat.target, chat.sender, chat.message),
+            ChatChannel::Whisper => format!("[{} -> {}] {}", chat.sender, chat.target, chat.message),
+            ChatChannel::System => format!("[!] {}", chat.message),
+            ChatChannel::Guild => format!("<{}> {}: {}", chat.target, chat.sender, chat.message),
+        };
+        self.messages.push_back((chat.channel, line));
+        if self.messages.len() > CHAT_HISTORY {
+            self.messages.pop_front();
+        }
+        // keep the view still if the player has scrolled up to read older messages
+        if self.scroll > 0 {
+            self.scroll += 1;
+        }
+    }
+
+    pub fn scroll_up(&mut self, amt: usize) {
+        self.scroll = (self.scroll + amt).min(self.messages.len().saturating_sub(1));
+    }
+
+    pub fn scroll_down(&mut self, amt: usize) {
+        self.scroll = self.scroll.saturating_sub(amt);
+    }
+}
+
+/// A screen shown in place of the home screen, only one is open at a time
+pub(crate) enum Panel {
+    Leaderboard(LeaderboardData),
+    Achievements(Vec<AchievementData>),
+    /// the quest log is kept up to date by the server even while it is closed, so it is not stored here
+    Quests,
+    Shop(Vec<ConsumableData>),
+    Crafting(CraftingData),
+    Inventory(InventoryData),
+    Bank(InventoryData),
+    Auctions(AuctionListData),
+    Mail(Vec<MailData>),
+    Guild(GuildData),
+    GuildBank(InventoryData),
+    Skills(SkillTreeData),
+    Bestiary(Vec<BestiaryEntryData>),
+}
+
+impl Panel {
+    /// how many rows the panel's table has, None if it does not scroll
+    pub fn rows(&self) -> Option<usize> {
+        match self {
+            Self::Inventory(inv) | Self::Bank(inv) | Self::GuildBank(inv) => Some(inv.items.len()),
+            Self::Auctions(list) => Some(list.listings.len()),
+            Self::Mail(letters) => Some(letters.len()),
+            Self::Guild(info) => Some(info.members.len()),
+            Self::Skills(tree) => Some(tree.skills.len()),
+            Self::Bestiary(list) => Some(list.len()),
+            _ => None,
+        }
+    }
+}
+
+fn chat_color(channel: ChatChannel) -> Color {
+    match channel {
+        ChatChannel::Global => Color::White,
+        ChatChannel::Region => Color::LightGreen,
+        ChatChannel::Whisper => Color::LightMagenta,
+        ChatChannel::System => Color::Yellow,
+        ChatChannel::Guild => Color::LightCyan,
+    }
+}
+
+fn draw_chat(rect: &mut Frame<CrosstermBackend<Stdout>>, area: Rect, chat: &Arc<Mutex<ChatLog>>) {
+    let chat = chat.lock().unwrap();
+    let width = area.width.saturating_sub(2).max(1) as usize;
+    let height = area.height.saturating_sub(2) as usize;
+
+    // wrap the messages by hand so the newest ones are always at the bottom of the pane
+    let mut lines = Vec::new();
+    for (channel, message) in chat.messages.iter() {
+        let chars = message.chars().collect::<Vec<char>>();
+        for part in chars.chunks(width) {
+            lines.push(Spans::from(vec![Span::styled(part.iter().collect::<String>(),
+                                                     Style::default().fg(chat_color(*channel)))]));
+        }
+    }
+    let end = lines.len().saturating_sub(chat.scroll.min(lines.len()));
+    let start = end.saturating_sub(height);
+    let visible = lines.drain(start..end).collect::<Vec<Spans>>();
+
+    let title = if chat.scroll > 0 { format!("Chat (scrolled up {})", chat.scroll) } else { format!("Chat") };
+    let pane = Paragraph::new(visible)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(title)
+                .border_type(BorderType::Plain),
+        );
+    rect.render_widget(pane, area);
+}
+
+fn draw_party(rect: &mut Frame<CrosstermBackend<Stdout>>, area: Rect, party: &Arc<Mutex<Vec<PartyMemberData>>>) {
+    let party = party.lock().unwrap();
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .style(Style::default().fg(Color::White))
+        .title("Party")
+        .border_type(BorderType::Plain);
+
+    if party.is_empty() {
+        let pane = Paragraph::new("Not in a party, type 'party invite <user>' to start one")
+            .style(Style::default().fg(Color::Gray))
+            .block(block);
+        rect.render_widget(pane, area);
+        return;
+    }
+
+    let rows = party.iter().map(|m| {
+        // members close to death are shown in red
+        let health_color = if m.health * 4 <= m.max_health { Color::Red } else { Color::White };
+        Row::new(vec![
+            Span::raw(if m.leader { format!("{} *", m.name) } else { m.name.clone() }),
+            Span::raw(format!("{}", m.level)),
+            Span::styled(format!("{} / {}", m.health, m.max_health), Style::default().fg(health_color)),
+        ])
+    }).collect::<Vec<Row>>();
+    let table = Table::new(rows)
+        .header(Row::new(vec![
+            Span::styled("Name", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("Level", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("Health", Style::default().add_modifier(Modifier::BOLD)),
+        ]))
+        .block(block)
+        .widths(&[
+            Constraint::Percentage(50),
+            Constraint::Percentage(20),
+            Constraint::Percentage(30),
+        ]);
+    rect.render_widget(table, area);
+}
+
+/// draws the leaderboard screen in place of the home screen
+pub(crate) fn draw_leaderboard(rect: &mut Frame<CrosstermBackend<Stdout>>, chunks: &Vec<Rect>, leaderboard: &LeaderboardData) {
+    let value_name = match leaderboard.category {
+        LeaderboardCategory::Level => "Level",
+        LeaderboardCategory::Steps => "Steps",
+        LeaderboardCategory::Kills => "Kills",
+        LeaderboardCategory::Wealth => "Gold",
+        LeaderboardCategory::Duels => "Duel Wins",
+    };
+    let rows = leaderboard.entries.iter().map(|e| Row::new(vec![
+        Span::raw(format!("#{}", e.rank)),
+        Span::raw(e.name.clone()),
+        Span::raw(format!("{}", e.value)),
+    ])).collect::<Vec<Row>>();
+
+    let table = Table::new(rows)
+        .header(Row::new(vec![
+            Span::styled("Rank", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("Player", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled(value_name, Style::default().add_modifier(Modifier::BOLD)),
+        ]))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(format!("Leaderboard: {} (page {} / {}) | [Left/Right] page, [Tab] category, [Esc] close",
+                               leaderboard.category.to_string(), leaderboard.page + 1, leaderboard.pages))
+                .border_type(BorderType::Plain),
+        )
+        .widths(&[
+            Constraint::Percentage(15),
+            Constraint::Percentage(50),
+            Constraint::Percentage(35),
+        ]);
+    rect.render_widget(table, chunks[0]);
+}
+
+/// how long ago an achievement was unlocked, to the day
+fn unlocked_ago(time: u64) -> String {
+    match systime().as_secs().saturating_sub(time) / (60 * 60 * 24) {
+        0 => format!("Today"),
+        1 => format!("Yesterday"),
+        days => format!("{} days ago", days),
+    }
+}
+
+/// draws the list of achievements in place of the home screen, unlocked ones first
+pub(crate) fn draw_achievements(rect: &mut Frame<CrosstermBackend<Stdout>>, chunks: &Vec<Rect>, achievements: &Vec<AchievementData>) {
+    let mut sorted = achievements.iter().collect::<Vec<&AchievementData>>();
+    sorted.sort_by_key(|a| !a.unlocked);
+    let rows = sorted.iter().map(|a| {
+        let style = if a.unlocked { Style::default().fg(Color::LightYellow) } else { Style::default().fg(Color::DarkGray) };
+        Row::new(vec![
+            Span::raw(a.name.clone()),
+            Span::raw(a.description.clone()),
+            Span::raw(if a.unlocked { unlocked_ago(a.time) } else { format!("Locked") }),
+        ]).style(style)
+    }).collect::<Vec<Row>>();
+
+    let table = Table::new(rows)
+        .header(Row::new(vec![
+            Span::styled("Achievement", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("How to unlock", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("Unlocked", Style::default().add_modifier(Modifier::BOLD)),
+        ]))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(format!("Achievements ({} / {}) | [Esc] close",
+                               achievements.iter().filter(|a| a.unlocked).count(), achievements.len()))
+                .border_type(BorderType::Plain),
+        )
+        .widths(&[
+            Constraint::Percentage(25),
+            Constraint::Percentage(55),
+            Constraint::Percentage(20),
+        ]);
+    rect.render_widget(table, chunks[0]);
+}
+
+/// draws the enemies the player has discovered in place of the home screen, ones they have not defeated yet are hidden
+pub(crate) fn draw_bestiary(rect: &mut Frame<CrosstermBackend<Stdout>>, chunks: &Vec<Rect>, bestiary: &Vec<BestiaryEntryData>, scroll: usize) {
+    let rows = bestiary.iter().skip(scroll).map(|e| {
+        if !e.unlocked {
+            return Row::new(vec![Span::raw("???")]).style(Style::default().fg(Color::DarkGray));
+        }
+        let levels = match e.max_level {
+            0 => format!("{}+", e.min_level),
+            max => format!("{} - {}", e.min_level, max),
+        };
+        Row::new(vec![
+            Span::raw(e.race.clone()),
+            Span::raw(format!("{}", e.kills)),
+            Span::raw(levels),
+            Span::raw(if e.ability.is_empty() { format!("None") } else { e.ability.clone() }),
+            Span::raw(e.loot.join(", ")),
+            Span::raw(e.description.clone()),
+        ]).style(Style::default().fg(Color::White))
+    }).collect::<Vec<Row>>();
+
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let table = Table::new(rows)
+        .header(Row::new(vec![
+            Span::styled("Enemy", bold),
+            Span::styled("Kills", bold),
+            Span::styled("Levels", bold),
+            Span::styled("Ability", bold),
+            Span::styled("Drops", bold),
+            Span::styled("Description", bold),
+        ]))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(format!("Bestiary ({} / {}) | [Up/Down] scroll, [Esc] close",
+                               bestiary.iter().filter(|e| e.unlocked).count(), bestiary.len()))
+                .border_type(BorderType::Plain),
+        )
+        .widths(&[
+            Constraint::Percentage(10),
+            Constraint::Percentage(6),
+            Constraint::Percentage(8),
+            Constraint::Percentage(24),
+            Constraint::Percentage(22),
+            Constraint::Percentage(30),
+        ]);
+    rect.render_widget(table, chunks[0]);
+}
+
+/// the rewards for turning in a quest, e.g. "40 gold, 60 exp and a rare boots"
+pub(crate) fn reward_text(quest: &QuestData) -> String {
+    let mut reward = format!("{} gold, {} exp", quest.reward_gold, quest.reward_exp);
+    if !quest.reward_item.is_empty() {
+        reward.push_str(format!(" and a {}", quest.reward_item).as_str());
+    }
+    reward
+}
+
+pub(crate) fn draw_quests(rect: &mut Frame<CrosstermBackend<Stdout>>, chunks: &Vec<Rect>, quests: &Vec<QuestData>) {
+    let rows = quests.iter().map(|q| {
+        let style = if q.state == QuestState::Complete { Style::default().fg(Color::LightGreen) } else { Style::default() };
+        let progress = if q.state == QuestState::Complete { format!("Turn in") } else { format!("{} / {}", q.progress, q.goal) };
+        Row::new(vec![
+            Span::raw(q.name.clone()),
+            Span::raw(q.giver.clone()),
+            Span::raw(q.objective.clone()),
+            Span::raw(progress),
+            Span::raw(reward_text(q)),
+        ]).style(style)
+    }).collect::<Vec<Row>>();
+
+    let table = Table::new(rows)
+        .header(Row::new(vec![
+            Span::styled("Quest", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("Giver", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("Objective", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("Progress", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("Reward", Style::default().add_modifier(Modifier::BOLD)),
+        ]))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(format!("Quests ({}) | 'quest turnin <quest>' or 'quest abandon <quest>' | [Esc] close", quests.len()))
+                .border_type(BorderType::Plain),
+        )
+        .widths(&[
+            Constraint::Percentage(20),
+            Constraint::Percentage(15),
+            Constraint::Percentage(25),
+            Constraint::Percentage(10),
+            Constraint::Percentage(30),
+        ]);
+    rect.render_widget(table, chunks[0]);
+}
+
+/// draws the merchant's stock in place of the home screen
+pub(crate) fn draw_shop(rect: &mut Frame<CrosstermBackend<Stdout>>, chunks: &Vec<Rect>, stock: &Vec<ConsumableData>) {
+    let rows = stock.iter().map(|c| Row::new(vec![
+        Span::raw(c.name.clone()),
+        Span::raw(c.kind.clone()),
+        Span::raw(c.description.clone()),
+        Span::raw(format!("{} gold", c.price)),
+    ])).collect::<Vec<Row>>();
+
+    let table = Table::new(rows)
+        .header(Row::new(vec![
+            Span::styled("Item", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("Kind", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("Effect", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("Price", Style::default().add_modifier(Modifier::BOLD)),
+        ]))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title("Merchant | 'buy <item> [amount]' | [Esc] close")
+                .border_type(BorderType::Plain),
+        )
+        .widths(&[
+            Constraint::Percentage(25),
+            Constraint::Percentage(10),
+            Constraint::Percentage(50),
+            Constraint::Percentage(15),
+        ]);
+    rect.render_widget(table, chunks[0]);
+}
+
+/// the names of the item types and rarities, by the numbers the server sends
+pub(crate) const ITEM_TYPES: [&str; 6] = ["Sword", "Shield", "Helmet", "Chestplate", "Leggings", "Boots"];
+pub(crate) const RARITIES: [&str; 4] = ["Common", "Rare", "Epic", "Legendary"];
+
+/// draws the player's inventory in place of the home screen, starting from the scrolled to item
+pub(crate) fn draw_inventory(rect: &mut Frame<CrosstermBackend<Stdout>>, chunks: &Vec<Rect>, inventory: &InventoryData, scroll: usize) {
+    let title = format!("Inventory {} / {} ({} shown) | 'inv [type] [rarity] [equipped] [by type|rarity|level]' | 'inv expand' | [Up/Down] scroll, [Esc] close",
+                        inventory.count, inventory.capacity, inventory.items.len());
+    rect.render_widget(item_table(&inventory.items, scroll, title), chunks[0]);
+}
+
+/// draws the items in the player's bank in place of the home screen
+pub(crate) fn draw_bank(rect: &mut Frame<CrosstermBackend<Stdout>>, chunks: &Vec<Rect>, bank: &InventoryData, scroll: usize) {
+    let title = format!("Bank {} / {} | 'deposit <item>' | 'withdraw <item>' | [Up/Down] scroll, [Esc] close",
+                        bank.count, bank.capacity);
+    rect.render_widget(item_table(&bank.items, scroll, title), chunks[0]);
+}
+
+/// draws the player's skill tree in place of the home screen, or the classes to pick from if they do not have one
+pub(crate) fn draw_skills(rect: &mut Frame<CrosstermBackend<Stdout>>, chunks: &Vec<Rect>, tree: &SkillTreeData, scroll: usize) {
+    if tree.class.is_empty() {
+        let mut lines = vec![
+            Spans::from("You do not have a class yet, your first one is free."),
+            Spans::from("Type 'skills class <class>' to pick one of:"),
+            Spans::from(""),
+        ];
+        lines.extend(tree.classes.iter().map(|c| Spans::from(c.clone())));
+        let paragraph = Paragraph::new(lines)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true })
+            .block(Block::default().borders(Borders::ALL).style(Style::default().fg(Color::White))
+                .title("Classes | [Esc] close").border_type(BorderType::Plain));
+        rect.render_widget(paragraph, chunks[0]);
+        return;
+    }
+
+    let rows = tree.skills.iter().skip(scroll).map(|s| {
+        // mastered skills are green and ones not learned yet are gray
+        let style = if s.rank >= s.max_rank { Style::default().fg(Color::LightGreen) }
+            else if s.rank == 0 { Style::default().fg(Color::DarkGray) }
+            else { Style::default().fg(Color::White) };
+        let kind = if !s.active { format!("Passive") }
+            else if s.cooldown > 0 { format!("Active, {} round cooldown", s.cooldown) }
+            else { format!("Active") };
+        Row::new(vec![
+            Span::raw(s.name.clone()),
+            Span::raw(format!("{} / {}", s.rank, s.max_rank)),
+            Span::raw(kind),
+            Span::raw(format!("{}", s.level)),
+            Span::raw(s.requires.clone()),
+            Span::raw(s.description.clone()),
+        ]).style(style)
+    }).collect::<Vec<Row>>();
+
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let table = Table::new(rows)
+        .header(Row::new(vec![
+            Span::styled("Skill", bold),
+            Span::styled("Rank", bold),
+            Span::styled("Type", bold),
+            Span::styled("Level", bold),
+            Span::styled("Requires", bold),
+            Span::styled("Description", bold),
+        ]))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(format!("{} | {} skill points | 'skills learn <skill>' | 'skills reset [class]' | 'skill <skill>' in fights | [Esc] close",
+                               tree.class, tree.points))
+                .border_type(BorderType::Plain),
+        )
+        .widths(&[
+            Constraint::Percentage(15),
+            Constraint::Percentage(7),
+            Constraint::Percentage(18),
+            Constraint::Percentage(6),
+            Constraint::Percentage(14),
+            Constraint::Percentage(40),
+        ]);
+    rect.render_widget(table, chunks[0]);
+}
+
+/// draws the items in the bank of the player's guild in place of the home screen
+pub(crate) fn draw_guild_bank(rect: &mut Frame<CrosstermBackend<Stdout>>, chunks: &Vec<Rect>, bank: &InventoryData, scroll: usize) {
+    let title = format!("Guild Bank {} / {} | 'guild deposit <item>' | 'guild withdraw <item>' (officers) | [Up/Down] scroll, [Esc] close",
+                        bank.count, bank.capacity);
+    rect.render_widget(item_table(&bank.items, scroll, title), chunks[0]);
+}
+
+/// draws a guild's members in place of the home screen, or the player's invites if they are not in a guild
+pub(crate) fn draw_guild(rect: &mut Frame<CrosstermBackend<Stdout>>, chunks: &Vec<Rect>, guild: &GuildData, scroll: usize) {
+    if guild.name.is_empty() {
+        let mut lines = vec![
+            Spans::from("You are not in a guild."),
+            Spans::from("Type 'guild create <name>' to start one, or ask a guild's officers for an invite."),
+            Spans::from(""),
+        ];
+        if guild.invites.is_empty() {
+            lines.push(Spans::from("You do not have any guild invites."));
+        } else {
+            lines.push(Spans::from(format!("You were invited to: {}", guild.invites.join(", "))));
+            lines.push(Spans::from("Type 'guild accept <guild>' or 'guild decline <guild>'."));
+        }
+        let paragraph = Paragraph::new(lines)
+            .alignment(Alignment::Center)
+            .block(Block::default().borders(Borders::ALL).style(Style::default().fg(Color::White))
+                .title("Guild | 'guild info <guild>' to look at a guild | [Esc] close").border_type(BorderType::Plain));
+        rect.render_widget(paragraph, chunks[0]);
+        return;
+    }
+
+    let rows = guild.members.iter().skip(scroll).map(|m| {
+        let style = if m.online { Style::default().fg(Color::LightGreen) } else { Style::default().fg(Color::DarkGray) };
+        Row::new(vec![
+            Span::raw(m.name.clone()),
+            Span::raw(m.rank.to_string()),
+            Span::raw(format!("{}", m.level)),
+            Span::raw(if m.online { "Online" } else { "Offline" }),
+        ]).style(style)
+    }).collect::<Vec<Row>>();
+
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let online = guild.members.iter().filter(|m| m.online).count();
+    let table = Table::new(rows)
+        .header(Row::new(vec![
+            Span::styled("Name", bold),
+            Span::styled("Rank", bold),
+            Span::styled("Level", bold),
+            Span::styled("Status", bold),
+        ]))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(format!("{} ({} members, {} online) | 'guild invite|kick|promote|demote <user>' | 'guild bank' | 'guild leave' | 'gc <message>' | [Esc] close",
+                               guild.name, guild.members.len(), online))
+                .border_type(BorderType::Plain),
+        )
+        .widths(&[
+            Constraint::Percentage(40),
+            Constraint::Percentage(20),
+            Constraint::Percentage(15),
+            Constraint::Percentage(25),
+        ]);
+    rect.render_widget(table, chunks[0]);
+}
+
+/// items are colored by their rarity in tables
+fn rarity_style(rarity: u32) -> Style {
+    match rarity {
+        1 => Style::default().fg(Color::LightBlue),
+        2 => Style::default().fg(Color::LightMagenta),
+        3 => Style::default().fg(Color::LightYellow),
+        _ => Style::default().fg(Color::White),
+    }
+}
+
+/// the name, type, rarity, level and stat columns of an item in a table
+fn item_cells(i: &ItemData) -> Vec<Span<'_>> {
+    let stat = match (i.damage, i.defense) {
+        (Some(damage), _) => format!("{} damage", damage),
+        (_, Some(defense)) => format!("{} defense", defense),
+        _ => String::new(),
+    };
+    vec![
+        Span::raw(if i.upgrades > 0 { format!("{} +{}", i.name, i.upgrades) } else { i.name.clone() }),
+        Span::raw(ITEM_TYPES.get(i.itype as usize).unwrap_or(&ITEM_TYPES[0]).to_string()),
+        Span::raw(RARITIES.get(i.rarity as usize).unwrap_or(&RARITIES[0]).to_string()),
+        Span::raw(format!("{}", i.level)),
+        Span::raw(stat),
+    ]
+}
+
+/// a table of the items from the scroll position on, shared by the inventory and the bank
+fn item_table(items: &[ItemData], scroll: usize, title: String) -> Table<'_> {
+    let rows = items.iter().skip(scroll).map(|i| {
+        let mut cells = item_cells(i);
+        cells.push(Span::raw(if i.equipped { "Equipped" } else { "" }));
+        Row::new(cells).style(rarity_style(i.rarity))
+    }).collect::<Vec<Row>>();
+
+    Table::new(rows)
+        .header(Row::new(vec![
+            Span::styled("Name", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("Type", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("Rarity", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("Level", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("Stat", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(""),
+        ]))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(title)
+                .border_type(BorderType::Plain),
+        )
+        .widths(&[
+            Constraint::Percentage(35),
+            Constraint::Percentage(13),
+            Constraint::Percentage(12),
+            Constraint::Percentage(8),
+            Constraint::Percentage(17),
+            Constraint::Percentage(15),
+        ])
+}
+
+/// draws the auction house listings the player searched for in place of the home screen
+pub(crate) fn draw_auctions(rect: &mut Frame<CrosstermBackend<Stdout>>, chunks: &Vec<Rect>, auctions: &AuctionListData, scroll: usize) {
+    let rows = auctions.listings.iter().skip(scroll).map(|l| {
+        let bid = if l.min_bid == 0 {
+            format!("-")
+        } else if l.winning {
+            format!("{} (yours)", l.bid)
+        } else if l.bid == 0 {
+            format!("from {}", l.min_bid)
+        } else {
+            format!("{}", l.bid)
+        };
+        let time_left = match l.time_left {
+            t if t >= 60 * 60 => format!("{}h {}m", t / (60 * 60), t % (60 * 60) / 60),
+            t if t >= 60 => format!("{}m", t / 60),
+            _ => format!("<1m"),
+        };
+        let mut cells = vec![Span::raw(format!("#{}", l.number))];
+        cells.extend(item_cells(&l.item));
+        cells.push(Span::raw(bid));
+        cells.push(Span::raw(format!("{}", l.buyout)));
+        cells.push(Span::raw(time_left));
+        cells.push(Span::raw(if l.yours { format!("you") } else { l.seller.clone() }));
+        Row::new(cells).style(rarity_style(l.item.rarity))
+    }).collect::<Vec<Row>>();
+
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let table = Table::new(rows)
+        .header(Row::new(vec![
+            Span::styled("#", bold),
+            Span::styled("Name", bold),
+            Span::styled("Type", bold),
+            Span::styled("Rarity", bold),
+            Span::styled("Level", bold),
+            Span::styled("Stat", bold),
+            Span::styled("Bid", bold),
+            Span::styled("Buyout", bold),
+            Span::styled("Ends in", bold),
+            Span::styled("Seller", bold),
+        ]))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(format!("Auction House ({} of {} shown) | 'ah [mine] [type] [rarity] [level|min-max] [name]' | 'ah sell <item> <buyout> [bid] [hours]' | 'ah bid|buy|cancel <#>' | [Esc] close",
+                               auctions.listings.len(), auctions.total))
+                .border_type(BorderType::Plain),
+        )
+        .widths(&[
+            Constraint::Percentage(5),
+            Constraint::Percentage(20),
+            Constraint::Percentage(8),
+            Constraint::Percentage(9),
+            Constraint::Percentage(6),
+            Constraint::Percentage(11),
+            Constraint::Percentage(11),
+            Constraint::Percentage(8),
+            Constraint::Percentage(8),
+            Constraint::Percentage(14),
+        ]);
+    rect.render_widget(table, chunks[0]);
+}
+
+/// how long ago a letter was sent, to the minute
+fn sent_ago(age: u64) -> String {
+    match age {
+        a if a >= 60 * 60 * 24 => format!("{}d ago", a / (60 * 60 * 24)),
+        a if a >= 60 * 60 => format!("{}h ago", a / (60 * 60)),
+        a if a >= 60 => format!("{}m ago", a / 60),
+        _ => format!("just now"),
+    }
+}
+
+/// what is still attached to a letter, empty if nothing is
+fn attachments(mail: &MailData) -> String {
+    let mut parts = Vec::new();
+    if mail.gold > 0 {
+        parts.push(format!("{} gold", mail.gold));
+    }
+    parts.extend(mail.items.iter().map(|i| i.name.clone()));
+    parts.join(", ")
+}
+
+/// draws the player's mailbox in place of the home screen, with the opened letter below it
+pub(crate) fn draw_mail(rect: &mut Frame<CrosstermBackend<Stdout>>, chunks: &Vec<Rect>, mail: &Vec<MailData>, open: Option<u32>, scroll: usize) {
+    let mail_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+        .split(chunks[0]);
+
+    let rows = mail.iter().skip(scroll).map(|m| {
+        // unread letters stand out, ones with nothing left to claim are dimmed
+        let style = if !m.read {
+            Style::default().fg(Color::LightYellow).add_modifier(Modifier::BOLD)
+        } else if m.claimed || attachments(m).is_empty() {
+            Style::default().fg(Color::DarkGray)
+        } else {
+            Style::default()
+        };
+        Row::new(vec![
+            Span::raw(format!("#{}", m.number)),
+            Span::raw(m.sender.clone()),
+            Span::raw(m.message.lines().next().unwrap_or("").to_string()),
+            Span::raw(attachments(m)),
+            Span::raw(sent_ago(m.age)),
+        ]).style(style)
+    }).collect::<Vec<Row>>();
+
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let unread = mail.iter().filter(|m| !m.read).count();
+    let table = Table::new(rows)
+        .header(Row::new(vec![
+            Span::styled("#", bold),
+            Span::styled("From", bold),
+            Span::styled("Message", bold),
+            Span::styled("Attached", bold),
+            Span::styled("Sent", bold),
+        ]))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(format!("Mailbox ({}, {} unread) | 'mail read|claim|delete <#>' | 'mail send <user> [gold] [item, item]: <message>' | [Up/Down] scroll, [Esc] close",
+                               mail.len(), unread))
+                .border_type(BorderType::Plain),
+        )
+        .widths(&[
+            Constraint::Percentage(6),
+            Constraint::Percentage(14),
+            Constraint::Percentage(45),
+            Constraint::Percentage(25),
+            Constraint::Percentage(10),
+        ]);
+    rect.render_widget(table, mail_chunks[0]);
+
+    let letter = match open.and_then(|n| mail.iter().find(|m| m.number == n)) {
+        Some(m) => {
+            let mut lines = vec![Spans::from(Span::styled(format!("From {}, {}", m.sender, sent_ago(m.age)), bold))];
+            lines.extend(m.message.lines().map(|l| Spans::from(l.to_string())));
+            lines.push(Spans::from(""));
+            lines.push(Spans::from(match (attachments(m), m.claimed) {
+                (_, true) => format!("You already took what was attached"),
+                (a, false) if a.is_empty() => format!("Nothing is attached"),
+                (a, false) => format!("Attached: {} | 'mail claim {}' to take it", a, m.number),
+            }));
+            Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(format!("Letter #{}", m.number)))
+        }
+        None => Paragraph::new("Type 'mail read <#>' to open a letter")
+            .block(Block::default().borders(Borders::ALL).title("Letter")),
+    };
+    rect.render_widget(letter.wrap(Wrap { trim: false }).style(Style::default().fg(Color::White)), mail_chunks[1]);
+}
+
+/// draws the recipes in place of the home screen, the ones the player can craft now are highlighted
+pub(crate) fn draw_crafting(rect: &mut Frame<CrosstermBackend<Stdout>>, chunks: &Vec<Rect>, crafting: &CraftingData) {
+    let rows = crafting.recipes.iter().map(|r| {
+        let style = if r.craftable { Style::default().fg(Color::LightGreen) } else { Style::default().fg(Color::DarkGray) };
+        Row::new(vec![
+            Span::raw(r.name.clone()),
+            Span::raw(r.result.clone()),
+            Span::raw(r.cost.clone()),
+        ]).style(style)
+    }).collect::<Vec<Row>>();
+
+    let materials = if crafting.materials.is_empty() {
+        format!("none, 'salvage <item>' to get some")
+    } else {
+        crafting.materials.iter().map(|m| format!("{} {}", m.quantity, m.name)).collect::<Vec<String>>().join(", ")
+    };
+    let table = Table::new(rows)
+        .header(Row::new(vec![
+            Span::styled("Recipe", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("Makes", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("Takes", Style::default().add_modifier(Modifier::BOLD)),
+        ]))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::White))
+                .title(format!("Crafting | Materials: {} | 'craft <recipe>' | [Esc] close", materials))
+                .border_type(BorderType::Plain),
+        )
+        .widths(&[
+            Constraint::Percentage(25),
+            Constraint::Percentage(25),
+            Constraint::Percentage(50),
+        ]);
+    rect.render_widget(table, chunks[0]);
+}
+
+pub(crate) fn draw_home(rect: &mut Frame<CrosstermBackend<Stdout>>, chunks: &Vec<Rect>, output: &Arc<Mutex<Output>>,
+                        chat: &Arc<Mutex<ChatLog>>, party: &Arc<Mutex<Vec<PartyMemberData>>>,
+                        username: &str, stats: &Arc<Mutex<Option<PlayerData>>>) {
     let home_chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints(
             [Constraint::Percentage(70), Constraint::Percentage(30)].as_ref(),
         )
         .split(chunks[0]);
+    let top_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [Constraint::Percentage(60), Constraint::Percentage(40)].as_ref(),
+        )
+        .split(home_chunks[0]);
+    let bottom_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [Constraint::Percentage(65), Constraint::Percentage(35)].as_ref(),
+        )
+        .split(home_chunks[1]);
 
     let home = {
         let output_temp = output.lock().unwrap();
@@ -38,9 +834,27 @@ pub(crate) fn draw_home(rect: &mut Frame<CrosstermBackend<Stdout>>, chunks: &Vec
             Spans::from(vec![Span::raw("Press [Enter] to type an action.")]),
             Spans::from(vec![Span::raw("Actions:")]),
             Spans::from(vec![Span::raw("* Type 'step' to take a step. *")]),
-            Spans::from(vec![Span::raw("* Type 'inv' to view your inventory. *")]),
+            Spans::from(vec![Span::raw("* Type 'inv [type] [rarity] [equipped] [by type|rarity|level]' to view your inventory, 'inv expand' for more space. *")]),
+            Spans::from(vec![Span::raw("* In a town, type 'bank' to see your bank, 'deposit <item>' or 'withdraw <item>'. *")]),
+            Spans::from(vec![Span::raw("* Type 'ah' to browse the auction house, 'ah sell <item> <buyout> [bid] [hours]', 'ah bid <#> <gold>', 'ah buy <#>' or 'ah cancel <#>'. *")]),
+            Spans::from(vec![Span::raw("* Type 'guild' to see your guild, 'guild create|invite|accept|leave|bank' to manage it or 'gc' and a message to talk to it. *")]),
+            Spans::from(vec![Span::raw("* Type 'mail' to read your mail, or 'mail send <user> [gold] [item, item]: <message>'. *")]),
             Spans::from(vec![Span::raw("* Type 'inspect <item>' to inspect an item. *")]),
             Spans::from(vec![Span::raw("* Type 'drop <item>' to drop an item. (THIS CAN'T BE UNDONE) *")]),
+            Spans::from(vec![Span::raw("* Type 'g', 'r' or 'w <user>' and a message to chat globally, in your region or privately. *")]),
+            Spans::from(vec![Span::raw("* In a fight, type 'attack', 'flee', 'skill <skill>' or 'use <item>' on your turn. *")]),
+            Spans::from(vec![Span::raw("* Type 'skills' to see your skill tree, 'skills learn <skill>' to spend points or 'skills reset [class]'. *")]),
+            Spans::from(vec![Span::raw("* Type 'bag' to see your consumables, 'shop' to visit the merchant or 'buy <item> [amount]'. *")]),
+            Spans::from(vec![Span::raw("* Type 'salvage <item>' to break an item into materials, or 'craft [recipe]' to make one. *")]),
+            Spans::from(vec![Span::raw("* Type 'upgrade <item>' to spend gold and materials making an item stronger, it can fail. *")]),
+            Spans::from(vec![Span::raw("* Type 'party invite|accept|decline|leave|kick' to manage your party. *")]),
+            Spans::from(vec![Span::raw("* Type 'duel <user> [gold] [items]' to challenge a player, or 'duel accept|decline|forfeit'. *")]),
+            Spans::from(vec![Span::raw("* Type 'leaderboard [level|steps|kills|wealth|duels] [page]' to see the top players. *")]),
+            Spans::from(vec![Span::raw("* Type 'achievements' to see the achievements you have unlocked. *")]),
+            Spans::from(vec![Span::raw("* Type 'bestiary' to see the enemies you have defeated and what they drop. *")]),
+            Spans::from(vec![Span::raw("* Type 'quest' to see your quests, or 'quest accept|decline|abandon|turnin'. *")]),
+            Spans::from(vec![Span::raw("* Staff can type '/help' to list their commands. *")]),
+            Spans::from(vec![Span::raw("Use the arrow keys or page up/down to scroll the chat")]),
             Spans::from(vec![Span::raw("Press 'q' to quit")]),
         ])
             .alignment(Alignment::Center)
@@ -53,14 +867,40 @@ pub(crate) fn draw_home(rect: &mut Frame<CrosstermBackend<Stdout>>, chunks: &Vec
             )
     };
 
-    let home_details = Table::new(vec![Row::new(vec![
-        Span::raw(format!("{}", "?")), // Player's Username
-        Span::raw(format!("{}", "?")), // Player's Level
-        Span::raw(format!("{} / {}", "?", "?")), // Player's EXP
-        Span::raw(format!("{} / {}", "?", "?")), // Health
-        Span::raw(format!("{}", "?")), // Current Region
-        Span::raw(format!("{}", "?")), // Total Steps
-    ])])
+    // the stats are shown as '?' until the server sends them
+    let row = match stats.lock().unwrap().as_ref() {
+        Some(pd) => {
+            // close to death is shown in red
+            let health_color = if pd.health * 4 <= pd.max_health { Color::Red } else { Color::White };
+            // the guild and unread mail are shown next to the name so they are not missed
+            let mut name = if pd.guild.is_empty() { username.to_string() } else { format!("{} <{}>", username, pd.guild) };
+            if pd.unread_mail > 0 {
+                name.push_str(format!(" ({} mail)", pd.unread_mail).as_str());
+            }
+            // unspent skill points are a reminder to open the skill tree
+            let mut level = if pd.class.is_empty() { format!("{}", pd.level) } else { format!("{} {}", pd.level, pd.class) };
+            if pd.skill_points > 0 {
+                level.push_str(format!(" ({} pts)", pd.skill_points).as_str());
+            }
+            Row::new(vec![
+                Span::raw(name),
+                Span::raw(level),
+                Span::raw(format!("{} / {}", pd.exp, pd.next_exp)),
+                Span::styled(format!("{} / {}", pd.health, pd.max_health), Style::default().fg(health_color)),
+                Span::raw(pd.region.clone()),
+                Span::raw(format!("{}", pd.steps)),
+            ])
+        }
+        None => Row::new(vec![
+            Span::raw(username.to_string()),
+            Span::raw(format!("{}", "?")),
+            Span::raw(format!("{} / {}", "?", "?")),
+            Span::raw(format!("{} / {}", "?", "?")),
+            Span::raw(format!("{}", "?")),
+            Span::raw(format!("{}", "?")),
+        ]),
+    };
+    let home_details = Table::new(vec![row])
         .header(Row::new(vec![
             Span::styled(
                 "Username",
@@ -95,15 +935,15 @@ pub(crate) fn draw_home(rect: &mut Frame<CrosstermBackend<Stdout>>, chunks: &Vec
                 .border_type(BorderType::Plain),
         )
         .widths(&[
+            Constraint::Percentage(18),
             Constraint::Percentage(10),
-            Constraint::Percentage(10),
-            Constraint::Percentage(10),
-            Constraint::Percentage(10),
-            Constraint::Percentage(10),
-            Constraint::Percentage(10),
-            Constraint::Percentage(10),
-            Constraint::Percentage(10),
+            Constraint::Percentage(15),
+            Constraint::Percentage(15),
+            Constraint::Percentage(27),
+            Constraint::Percentage(15),
         ]);
-    rect.render_widget(home, home_chunks[0]);
-    rect.render_widget(home_details, home_chunks[1]);
+    rect.render_widget(home, top_chunks[0]);
+    draw_chat(rect, top_chunks[1], chat);
+    rect.render_widget(home_details, bottom_chunks[0]);
+    draw_party(rect, bottom_chunks[1], party);
 }
\ No newline at end of file
@@ -0,0 +1,9 @@
+#[derive(Clone, Debug)]
+pub struct AchievementData {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub unlocked: bool,
+    /// when the achievement was unlocked in seconds since the unix epoch, 0 if it is locked
+    pub time: u64,
+}
@@ -0,0 +1,97 @@
+use crate::item_data::ItemData;
+
+/// What a player wants to do in the auction house
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum AuctionActionKind {
+    Search, Sell, Bid, Buyout, Cancel,
+}
+
+impl Into<u32> for AuctionActionKind {
+    fn into(self) -> u32 {
+        match self {
+            Self::Search => 0,
+            Self::Sell   => 1,
+            Self::Bid    => 2,
+            Self::Buyout => 3,
+            Self::Cancel => 4,
+        }
+    }
+}
+
+impl From<u32> for AuctionActionKind {
+    fn from(x: u32) -> Self {
+        match x {
+            1 => Self::Sell,
+            2 => Self::Bid,
+            3 => Self::Buyout,
+            4 => Self::Cancel,
+            _ => Self::Search,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct AuctionSearchData {
+    /// only show items of this type
+    pub itype: Option<u32>,
+    /// only show items of this rarity
+    pub rarity: Option<u32>,
+    pub min_level: Option<u32>,
+    pub max_level: Option<u32>,
+    /// only show items with this in their name, empty for any name
+    pub name: String,
+    /// only show the player's own listings and the ones they have bid on
+    pub mine: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct AuctionActionData {
+    pub kind: AuctionActionKind,
+    /// the listings to show the player afterwards
+    pub search: AuctionSearchData,
+    /// the number of the listing to bid on, buy out or cancel
+    pub listing: u32,
+    /// the name of the item to sell
+    pub item: String,
+    /// the bid, or the buyout price when selling
+    pub price: u32,
+    /// the lowest first bid when selling, 0 to only allow buying out
+    pub start_bid: u32,
+    /// how long to list the item for when selling, 0 for the default
+    pub hours: u32,
+}
+
+impl AuctionActionData {
+    /// an action that only looks through the listings
+    pub fn search(search: AuctionSearchData) -> Self {
+        Self { kind: AuctionActionKind::Search, search, listing: 0, item: String::new(), price: 0, start_bid: 0, hours: 0 }
+    }
+}
+
+/// An item for sale in the auction house
+#[derive(Clone, Debug)]
+pub struct ListingData {
+    /// the listing's number, used to bid on or buy it
+    pub number: u32,
+    pub item: ItemData,
+    pub seller: String,
+    /// the highest bid, 0 if nobody has bid yet
+    pub bid: u32,
+    /// the least the next bid can be, 0 if the item can only be bought out
+    pub min_bid: u32,
+    pub buyout: u32,
+    /// seconds until the listing ends
+    pub time_left: u64,
+    /// if the player is the seller
+    pub yours: bool,
+    /// if the player has the highest bid
+    pub winning: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct AuctionListData {
+    /// the listings ending soonest first
+    pub listings: Vec<ListingData>,
+    /// how many listings matched, only the first ones are sent
+    pub total: u32,
+}
@@ -0,0 +1,16 @@
+/// An enemy in the player's bestiary, everything but `unlocked` is left empty until they defeat one
+#[derive(Clone, Debug)]
+pub struct BestiaryEntryData {
+    pub race: String,
+    pub description: String,
+    pub unlocked: bool,
+    /// how many the player has defeated
+    pub kills: u32,
+    /// the levels it appears at, the max is 0 if there is no limit
+    pub min_level: u32,
+    pub max_level: u32,
+    /// what its ability does, empty if it has none
+    pub ability: String,
+    /// what it can drop on top of the usual loot, e.g. "Leather (50%)"
+    pub loot: Vec<String>,
+}
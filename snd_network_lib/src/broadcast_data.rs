@@ -0,0 +1,33 @@
+/// What a server wide broadcast is about, so clients can show each kind differently
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum BroadcastKind {
+    Announcement, Shutdown, WorldBoss, Loot,
+}
+
+impl Into<u32> for BroadcastKind {
+    fn into(self) -> u32 {
+        match self {
+            Self::Announcement => 0,
+            Self::Shutdown     => 1,
+            Self::WorldBoss    => 2,
+            Self::Loot         => 3,
+        }
+    }
+}
+
+impl From<u32> for BroadcastKind {
+    fn from(x: u32) -> Self {
+        match x {
+            1 => Self::Shutdown,
+            2 => Self::WorldBoss,
+            3 => Self::Loot,
+            _ => Self::Announcement,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BroadcastData {
+    pub kind: BroadcastKind,
+    pub message: String,
+}
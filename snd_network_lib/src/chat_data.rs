@@ -0,0 +1,37 @@
+/// The channel a chat message is sent in
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ChatChannel {
+    Global, Region, Whisper, System, Guild,
+}
+
+impl Into<u32> for ChatChannel {
+    fn into(self) -> u32 {
+        match self {
+            Self::Global  => 0,
+            Self::Region  => 1,
+            Self::Whisper => 2,
+            Self::System  => 3,
+            Self::Guild   => 4,
+        }
+    }
+}
+
+impl From<u32> for ChatChannel {
+    fn from(x: u32) -> Self {
+        match x {
+            1 => Self::Region,
+            2 => Self::Whisper,
+            3 => Self::System,
+            4 => Self::Guild,
+            _ => Self::Global,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ChatData {
+    pub channel: ChatChannel,
+    pub sender: String,
+    pub target: String,
+    pub message: String,
+}
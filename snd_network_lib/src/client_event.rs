@@ -1,7 +1,18 @@
 use std::net::TcpStream;
 use capnp::message::Builder;
 use capnp::serialize;
+use crate::auction_data::{AuctionActionData, AuctionActionKind, AuctionSearchData};
+use crate::chat_data::{ChatChannel, ChatData};
+use crate::consumable_data::{ShopActionData, ShopActionKind};
 use crate::error_data::ErrorData;
+use crate::inventory_data::{BankActionData, BankActionKind, InventoryRequestData, InventorySort};
+use crate::duel_data::{DuelActionData, DuelActionKind};
+use crate::mail_data::{MailActionData, MailActionKind};
+use crate::guild_data::{GuildActionData, GuildActionKind};
+use crate::leaderboard_data::{LeaderboardCategory, LeaderboardRequestData};
+use crate::party_data::{PartyActionData, PartyActionKind};
+use crate::quest_data::{QuestActionData, QuestActionKind};
+use crate::skill_data::{SkillActionData, SkillActionKind};
 use crate::packet_capnp::c_event;
 use crate::systime;
 
@@ -10,13 +21,35 @@ pub enum ClientEvent {
     Disconnect,
     KeepAlive(u64),
     Step,
-    OpenInv,
+    /// None for clients from before inventory requests, they are sent every item as a plain list
+    OpenInv(Option<InventoryRequestData>),
     RqstUpdate,
     DropItem(String),
     InspectItem(String),
     Attack,
     TryFlee,
     Error(ErrorData),
+    Command(String),
+    Chat(ChatData),
+    Party(PartyActionData),
+    Duel(DuelActionData),
+    Leaderboard(LeaderboardRequestData),
+    Achievements,
+    Quest(QuestActionData),
+    UseItem(String),
+    Consumables,
+    Shop(ShopActionData),
+    Salvage(String),
+    /// empty to see the recipes
+    Craft(String),
+    Upgrade(String),
+    ExpandInv,
+    Bank(BankActionData),
+    Auction(AuctionActionData),
+    Mail(MailActionData),
+    Guild(GuildActionData),
+    Skill(SkillActionData),
+    Bestiary,
 }
 
 pub fn write_client_disconnect(mut stream: &TcpStream) -> ::capnp::Result<()> {
@@ -55,11 +88,15 @@ pub fn write_client_request_update(mut stream: &TcpStream) -> ::capnp::Result<()
     serialize::write_message(&mut stream, &message)
 }
 
-pub fn write_client_open_inv(mut stream: &TcpStream) -> ::capnp::Result<()> {
+pub fn write_client_open_inv(mut stream: &TcpStream, request: InventoryRequestData) -> ::capnp::Result<()> {
     let mut message = Builder::new_default();
     {
-        let mut er = message.init_root::<c_event::Builder>();
-        er.set_open_inv(true);
+        let er = message.init_root::<c_event::Builder>();
+        let mut inv_builder = er.init_open_inv_request();
+        inv_builder.set_sort(request.sort.into());
+        inv_builder.set_itype(request.itype.map_or(-1, |t| t as i32));
+        inv_builder.set_rarity(request.rarity.map_or(-1, |r| r as i32));
+        inv_builder.set_equipped(request.equipped);
     }
     serialize::write_message(&mut stream, &message)
 }
@@ -82,6 +119,252 @@ pub fn write_client_inspect_item(mut stream: &TcpStream, item: String) -> ::capn
     serialize::write_message(&mut stream, &message)
 }
 
+pub fn write_client_attack(mut stream: &TcpStream) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let mut er = message.init_root::<c_event::Builder>();
+        er.set_attack(true);
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_client_try_flee(mut stream: &TcpStream) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let mut er = message.init_root::<c_event::Builder>();
+        er.set_try_flee(true);
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_client_party(mut stream: &TcpStream, action: PartyActionData) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<c_event::Builder>();
+        let mut party_builder = er.init_party();
+        party_builder.set_kind(action.kind.into());
+        party_builder.set_target(action.target.as_str());
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_client_duel(mut stream: &TcpStream, action: DuelActionData) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<c_event::Builder>();
+        let mut duel_builder = er.init_duel();
+        duel_builder.set_kind(action.kind.into());
+        duel_builder.set_target(action.target.as_str());
+        duel_builder.set_gold(action.gold);
+        let mut items_builder = duel_builder.init_items(action.items.len() as u32);
+        for (x, item) in action.items.iter().enumerate() {
+            items_builder.set(x as u32, item.as_str());
+        }
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_client_leaderboard(mut stream: &TcpStream, request: LeaderboardRequestData) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<c_event::Builder>();
+        let mut lb_builder = er.init_leaderboard();
+        lb_builder.set_category(request.category.into());
+        lb_builder.set_page(request.page);
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_client_achievements(mut stream: &TcpStream) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let mut er = message.init_root::<c_event::Builder>();
+        er.set_achievements(());
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_client_quest(mut stream: &TcpStream, action: QuestActionData) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<c_event::Builder>();
+        let mut quest_builder = er.init_quest();
+        quest_builder.set_kind(action.kind.into());
+        quest_builder.set_quest(action.quest.as_str());
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_client_use_item(mut stream: &TcpStream, item: String) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let mut er = message.init_root::<c_event::Builder>();
+        er.set_use_item(item.as_str());
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_client_consumables(mut stream: &TcpStream) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let mut er = message.init_root::<c_event::Builder>();
+        er.set_consumables(());
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_client_shop(mut stream: &TcpStream, action: ShopActionData) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<c_event::Builder>();
+        let mut shop_builder = er.init_shop();
+        shop_builder.set_kind(action.kind.into());
+        shop_builder.set_item(action.item.as_str());
+        shop_builder.set_quantity(action.quantity);
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_client_salvage(mut stream: &TcpStream, item: String) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let mut er = message.init_root::<c_event::Builder>();
+        er.set_salvage(item.as_str());
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_client_craft(mut stream: &TcpStream, recipe: String) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let mut er = message.init_root::<c_event::Builder>();
+        er.set_craft(recipe.as_str());
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_client_upgrade(mut stream: &TcpStream, item: String) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let mut er = message.init_root::<c_event::Builder>();
+        er.set_upgrade(item.as_str());
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_client_expand_inv(mut stream: &TcpStream) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let mut er = message.init_root::<c_event::Builder>();
+        er.set_expand_inv(());
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_client_bank(mut stream: &TcpStream, action: BankActionData) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<c_event::Builder>();
+        let mut bank_builder = er.init_bank();
+        bank_builder.set_kind(action.kind.into());
+        bank_builder.set_item(action.item.as_str());
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_client_auction(mut stream: &TcpStream, action: AuctionActionData) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<c_event::Builder>();
+        let mut auction_builder = er.init_auction();
+        auction_builder.set_kind(action.kind.into());
+        auction_builder.set_listing(action.listing);
+        auction_builder.set_item(action.item.as_str());
+        auction_builder.set_price(action.price);
+        auction_builder.set_start_bid(action.start_bid);
+        auction_builder.set_hours(action.hours);
+        let mut search_builder = auction_builder.init_search();
+        search_builder.set_itype(action.search.itype.map_or(-1, |t| t as i32));
+        search_builder.set_rarity(action.search.rarity.map_or(-1, |r| r as i32));
+        search_builder.set_min_level(action.search.min_level.unwrap_or(0));
+        search_builder.set_max_level(action.search.max_level.unwrap_or(0));
+        search_builder.set_name(action.search.name.as_str());
+        search_builder.set_mine(action.search.mine);
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_client_mail(mut stream: &TcpStream, action: MailActionData) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<c_event::Builder>();
+        let mut mail_builder = er.init_mail();
+        mail_builder.set_kind(action.kind.into());
+        mail_builder.set_number(action.number);
+        mail_builder.set_to(action.to.as_str());
+        mail_builder.set_message(action.message.as_str());
+        mail_builder.set_gold(action.gold);
+        let mut items_builder = mail_builder.init_items(action.items.len() as u32);
+        for (x, item) in action.items.iter().enumerate() {
+            items_builder.set(x as u32, item.as_str());
+        }
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_client_guild(mut stream: &TcpStream, action: GuildActionData) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<c_event::Builder>();
+        let mut guild_builder = er.init_guild();
+        guild_builder.set_kind(action.kind.into());
+        guild_builder.set_target(action.target.as_str());
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_client_skill(mut stream: &TcpStream, action: SkillActionData) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<c_event::Builder>();
+        let mut skill_builder = er.init_skill();
+        skill_builder.set_kind(action.kind.into());
+        skill_builder.set_skill(action.skill.as_str());
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_client_bestiary(mut stream: &TcpStream) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let mut er = message.init_root::<c_event::Builder>();
+        er.set_bestiary(());
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_client_command(mut stream: &TcpStream, command: String) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let mut er = message.init_root::<c_event::Builder>();
+        er.set_command(command.as_str());
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_client_chat(mut stream: &TcpStream, chat: ChatData) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<c_event::Builder>();
+        let mut chat_builder = er.init_chat();
+        chat_builder.set_channel(chat.channel.into());
+        chat_builder.set_sender(chat.sender.as_str());
+        chat_builder.set_target(chat.target.as_str());
+        chat_builder.set_message(chat.message.as_str());
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
 pub fn write_client_error(mut stream: &TcpStream, error: ErrorData) -> ::capnp::Result<()> {
     let mut message = Builder::new_default();
     {
@@ -120,11 +403,128 @@ pub fn read_client_event(mut stream: &TcpStream) -> ClientEvent {
         c_event::Keepalive(a) => ClientEvent::KeepAlive(a),
         c_event::RqstUpdate(_) => ClientEvent::RqstUpdate,
         c_event::Step(_) => ClientEvent::Step,
-        c_event::OpenInv(_) => ClientEvent::OpenInv,
+        c_event::OpenInv(_) => ClientEvent::OpenInv(None),
+        c_event::OpenInvRequest(inv_reader) => {
+            let inv = inv_reader.unwrap();
+            ClientEvent::OpenInv(Some(InventoryRequestData {
+                sort: InventorySort::from(inv.get_sort()),
+                itype: u32::try_from(inv.get_itype()).ok(),
+                rarity: u32::try_from(inv.get_rarity()).ok(),
+                equipped: inv.get_equipped(),
+            }))
+        }
         c_event::DropItm(name) => ClientEvent::DropItem(name.unwrap().to_string()),
         c_event::InspectItm(name) => ClientEvent::InspectItem(name.unwrap().to_string()),
         c_event::Attack(_) => ClientEvent::Attack,
         c_event::TryFlee(_) => ClientEvent::TryFlee,
+        c_event::Command(cmd) => ClientEvent::Command(cmd.unwrap().to_string()),
+        c_event::Chat(chat_reader) => {
+            let chat = chat_reader.unwrap();
+            ClientEvent::Chat(ChatData {
+                channel: ChatChannel::from(chat.get_channel()),
+                sender: chat.get_sender().unwrap().to_string(),
+                target: chat.get_target().unwrap().to_string(),
+                message: chat.get_message().unwrap().to_string(),
+            })
+        }
+        c_event::Duel(duel_reader) => {
+            let duel = duel_reader.unwrap();
+            ClientEvent::Duel(DuelActionData {
+                kind: DuelActionKind::from(duel.get_kind()),
+                target: duel.get_target().unwrap().to_string(),
+                gold: duel.get_gold(),
+                items: duel.get_items().unwrap().iter().map(|i| i.unwrap().to_string()).collect(),
+            })
+        }
+        c_event::Achievements(_) => ClientEvent::Achievements,
+        c_event::Bestiary(_) => ClientEvent::Bestiary,
+        c_event::Quest(quest_reader) => {
+            let quest = quest_reader.unwrap();
+            ClientEvent::Quest(QuestActionData {
+                kind: QuestActionKind::from(quest.get_kind()),
+                quest: quest.get_quest().unwrap().to_string(),
+            })
+        }
+        c_event::UseItem(item) => ClientEvent::UseItem(item.unwrap().to_string()),
+        c_event::Consumables(_) => ClientEvent::Consumables,
+        c_event::Salvage(item) => ClientEvent::Salvage(item.unwrap().to_string()),
+        c_event::Craft(recipe) => ClientEvent::Craft(recipe.unwrap().to_string()),
+        c_event::Upgrade(item) => ClientEvent::Upgrade(item.unwrap().to_string()),
+        c_event::ExpandInv(_) => ClientEvent::ExpandInv,
+        c_event::Bank(bank_reader) => {
+            let bank = bank_reader.unwrap();
+            ClientEvent::Bank(BankActionData {
+                kind: BankActionKind::from(bank.get_kind()),
+                item: bank.get_item().unwrap().to_string(),
+            })
+        }
+        c_event::Auction(auction_reader) => {
+            let auction = auction_reader.unwrap();
+            let search = auction.get_search().unwrap();
+            ClientEvent::Auction(AuctionActionData {
+                kind: AuctionActionKind::from(auction.get_kind()),
+                search: AuctionSearchData {
+                    itype: u32::try_from(search.get_itype()).ok(),
+                    rarity: u32::try_from(search.get_rarity()).ok(),
+                    min_level: Some(search.get_min_level()).filter(|l| *l > 0),
+                    max_level: Some(search.get_max_level()).filter(|l| *l > 0),
+                    name: search.get_name().unwrap().to_string(),
+                    mine: search.get_mine(),
+                },
+                listing: auction.get_listing(),
+                item: auction.get_item().unwrap().to_string(),
+                price: auction.get_price(),
+                start_bid: auction.get_start_bid(),
+                hours: auction.get_hours(),
+            })
+        }
+        c_event::Guild(guild_reader) => {
+            let guild = guild_reader.unwrap();
+            ClientEvent::Guild(GuildActionData {
+                kind: GuildActionKind::from(guild.get_kind()),
+                target: guild.get_target().unwrap().to_string(),
+            })
+        }
+        c_event::Skill(skill_reader) => {
+            let skill = skill_reader.unwrap();
+            ClientEvent::Skill(SkillActionData {
+                kind: SkillActionKind::from(skill.get_kind()),
+                skill: skill.get_skill().unwrap().to_string(),
+            })
+        }
+        c_event::Mail(mail_reader) => {
+            let mail = mail_reader.unwrap();
+            ClientEvent::Mail(MailActionData {
+                kind: MailActionKind::from(mail.get_kind()),
+                number: mail.get_number(),
+                to: mail.get_to().unwrap().to_string(),
+                message: mail.get_message().unwrap().to_string(),
+                gold: mail.get_gold(),
+                items: mail.get_items().unwrap().iter().map(|i| i.unwrap().to_string()).collect(),
+            })
+        }
+        c_event::Shop(shop_reader) => {
+            let shop = shop_reader.unwrap();
+            ClientEvent::Shop(ShopActionData {
+                kind: ShopActionKind::from(shop.get_kind()),
+                item: shop.get_item().unwrap().to_string(),
+                quantity: shop.get_quantity(),
+            })
+        }
+        c_event::Leaderboard(lb_reader) => {
+            let lb = lb_reader.unwrap();
+            ClientEvent::Leaderboard(LeaderboardRequestData {
+                category: LeaderboardCategory::from(lb.get_category()),
+                page: lb.get_page(),
+            })
+        }
+        c_event::Party(party_reader) => {
+            let party = party_reader.unwrap();
+            ClientEvent::Party(PartyActionData {
+                kind: PartyActionKind::from(party.get_kind()),
+                target: party.get_target().unwrap().to_string(),
+            })
+        }
         c_event::Error(err_reader) => {
             let err = err_reader.unwrap();
             ClientEvent::Error(ErrorData { msg: err.get_error().unwrap().to_string(), disconnect: err.get_disconnect() })
@@ -0,0 +1,46 @@
+/// A stack of consumables a player has, or one a merchant sells
+#[derive(Clone, Debug)]
+pub struct ConsumableData {
+    pub id: String,
+    pub name: String,
+    /// what using it does
+    pub description: String,
+    /// potion, food or scroll
+    pub kind: String,
+    /// how many the player has, 0 for a merchant's stock
+    pub quantity: u32,
+    /// the gold a merchant sells one for
+    pub price: u32,
+}
+
+/// What a player wants to do at the merchant
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ShopActionKind {
+    List, Buy,
+}
+
+impl Into<u32> for ShopActionKind {
+    fn into(self) -> u32 {
+        match self {
+            Self::List => 0,
+            Self::Buy  => 1,
+        }
+    }
+}
+
+impl From<u32> for ShopActionKind {
+    fn from(x: u32) -> Self {
+        match x {
+            1 => Self::Buy,
+            _ => Self::List,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ShopActionData {
+    pub kind: ShopActionKind,
+    /// the name of the consumable to buy, empty when listing the stock
+    pub item: String,
+    pub quantity: u32,
+}
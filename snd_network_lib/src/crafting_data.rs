@@ -0,0 +1,24 @@
+/// A recipe as shown to the player
+#[derive(Clone, Debug)]
+pub struct RecipeData {
+    pub name: String,
+    /// the item it makes, e.g. "rare sword or better"
+    pub result: String,
+    /// the materials and gold it takes
+    pub cost: String,
+    /// if the player has everything it takes
+    pub craftable: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct MaterialData {
+    pub name: String,
+    pub quantity: u32,
+}
+
+/// The recipes and the materials the player has
+#[derive(Clone, Debug)]
+pub struct CraftingData {
+    pub recipes: Vec<RecipeData>,
+    pub materials: Vec<MaterialData>,
+}
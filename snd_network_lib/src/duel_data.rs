@@ -0,0 +1,38 @@
+/// What a player wants to do about a duel
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum DuelActionKind {
+    Challenge, Accept, Decline, Forfeit,
+}
+
+impl Into<u32> for DuelActionKind {
+    fn into(self) -> u32 {
+        match self {
+            Self::Challenge => 0,
+            Self::Accept    => 1,
+            Self::Decline   => 2,
+            Self::Forfeit   => 3,
+        }
+    }
+}
+
+impl From<u32> for DuelActionKind {
+    fn from(x: u32) -> Self {
+        match x {
+            1 => Self::Accept,
+            2 => Self::Decline,
+            3 => Self::Forfeit,
+            _ => Self::Challenge,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DuelActionData {
+    pub kind: DuelActionKind,
+    /// the username to challenge, empty otherwise
+    pub target: String,
+    /// the gold both players wager, only used when challenging
+    pub gold: u32,
+    /// the names of the items the player wagers when challenging or accepting
+    pub items: Vec<String>,
+}
@@ -1,5 +1,6 @@
 use crate::enemy_data::EnemyData;
 use crate::loot_data::LootData;
+use crate::status_data::StatusEffectData;
 
 #[derive(Clone, Debug)]
 pub struct EncounterData {
@@ -8,4 +9,12 @@ pub struct EncounterData {
     pub flee: Option<bool>,
     pub win: Option<LootData>,
     pub lost: Option<bool>,
+    pub start: bool,
+    pub hit: Option<u32>,
+    /// what happened, to show to the player
+    pub message: String,
+    /// the username of the party member whose turn it is, empty once the encounter is over
+    pub turn: String,
+    /// the status effects on the enemy and the players
+    pub effects: Vec<StatusEffectData>,
 }
\ No newline at end of file
@@ -4,4 +4,5 @@ pub struct EnemyData {
     pub race: String,
     pub level: u32,
     pub health: u32,
+    pub max_health: u32,
 }
\ No newline at end of file
@@ -21,6 +21,7 @@ pub fn write_entry_login_attempt(mut stream: &TcpStream, login_data: LoginData)
         login.set_username(login_data.username.as_str());
         login.set_password(login_data.passwd.as_str());
         login.set_signup(login_data.signup);
+        login.set_class(login_data.class.as_str());
     }
     serialize::write_message(&mut stream, &message)
 }
@@ -46,6 +47,7 @@ pub fn read_entry_point(mut stream: &TcpStream) -> (Option<LoginData>, Option<St
                 passwd: raw_ld.get_password().unwrap().to_string(),
                 signup: raw_ld.get_signup(),
                 client_ver: raw_ld.get_client_ver().unwrap().to_string(),
+                class: raw_ld.get_class().unwrap().to_string(),
             };
             (Some(ld), None, None)
         }
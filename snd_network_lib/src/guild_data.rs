@@ -0,0 +1,108 @@
+/// What a player wants to do with their guild
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum GuildActionKind {
+    Info, Create, Invite, Accept, Decline, Leave, Kick, Promote, Demote, Disband, Bank, Deposit, Withdraw,
+}
+
+impl Into<u32> for GuildActionKind {
+    fn into(self) -> u32 {
+        match self {
+            Self::Info     => 0,
+            Self::Create   => 1,
+            Self::Invite   => 2,
+            Self::Accept   => 3,
+            Self::Decline  => 4,
+            Self::Leave    => 5,
+            Self::Kick     => 6,
+            Self::Promote  => 7,
+            Self::Demote   => 8,
+            Self::Disband  => 9,
+            Self::Bank     => 10,
+            Self::Deposit  => 11,
+            Self::Withdraw => 12,
+        }
+    }
+}
+
+impl From<u32> for GuildActionKind {
+    fn from(x: u32) -> Self {
+        match x {
+            1 => Self::Create,
+            2 => Self::Invite,
+            3 => Self::Accept,
+            4 => Self::Decline,
+            5 => Self::Leave,
+            6 => Self::Kick,
+            7 => Self::Promote,
+            8 => Self::Demote,
+            9 => Self::Disband,
+            10 => Self::Bank,
+            11 => Self::Deposit,
+            12 => Self::Withdraw,
+            _ => Self::Info,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GuildActionData {
+    pub kind: GuildActionKind,
+    /// the guild name, username or item name the action is about, empty otherwise
+    pub target: String,
+}
+
+/// A member's standing in their guild, higher ranks can do more
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
+pub enum GuildRank {
+    Member, Officer, Leader,
+}
+
+impl Into<u32> for GuildRank {
+    fn into(self) -> u32 {
+        match self {
+            Self::Member  => 0,
+            Self::Officer => 1,
+            Self::Leader  => 2,
+        }
+    }
+}
+
+impl From<u32> for GuildRank {
+    fn from(x: u32) -> Self {
+        match x {
+            1 => Self::Officer,
+            2 => Self::Leader,
+            _ => Self::Member,
+        }
+    }
+}
+
+impl ToString for GuildRank {
+    fn to_string(&self) -> String {
+        match self {
+            Self::Member => "Member",
+            Self::Officer => "Officer",
+            Self::Leader => "Leader",
+        }.to_string()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GuildMemberData {
+    pub name: String,
+    pub rank: GuildRank,
+    pub level: u32,
+    pub online: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct GuildData {
+    /// the guild's name, empty if the player is not in a guild
+    pub name: String,
+    /// every member, highest rank first
+    pub members: Vec<GuildMemberData>,
+    /// the player's own rank, if they are a member
+    pub rank: GuildRank,
+    /// the guilds that invited the player
+    pub invites: Vec<String>,
+}
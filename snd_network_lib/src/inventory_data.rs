@@ -0,0 +1,95 @@
+use crate::item_data::ItemData;
+
+/// How a player wants their inventory sorted
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum InventorySort {
+    /// in the order the items were found
+    Found,
+    Type,
+    /// best rarity first
+    Rarity,
+    /// highest level first
+    Level,
+}
+
+impl Into<u32> for InventorySort {
+    fn into(self) -> u32 {
+        match self {
+            Self::Found  => 0,
+            Self::Type   => 1,
+            Self::Rarity => 2,
+            Self::Level  => 3,
+        }
+    }
+}
+
+impl From<u32> for InventorySort {
+    fn from(x: u32) -> Self {
+        match x {
+            1 => Self::Type,
+            2 => Self::Rarity,
+            3 => Self::Level,
+            _ => Self::Found,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct InventoryRequestData {
+    pub sort: InventorySort,
+    /// only show items of this type
+    pub itype: Option<u32>,
+    /// only show items of this rarity
+    pub rarity: Option<u32>,
+    /// only show the items the player fights with
+    pub equipped: bool,
+}
+
+impl Default for InventoryRequestData {
+    fn default() -> Self {
+        Self { sort: InventorySort::Found, itype: None, rarity: None, equipped: false }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct InventoryData {
+    /// the items that match the request, sorted
+    pub items: Vec<ItemData>,
+    /// how many items the player has in total
+    pub count: u32,
+    /// the most items the player can carry
+    pub capacity: u32,
+}
+
+/// What a player wants to do at their bank
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum BankActionKind {
+    List, Deposit, Withdraw,
+}
+
+impl Into<u32> for BankActionKind {
+    fn into(self) -> u32 {
+        match self {
+            Self::List     => 0,
+            Self::Deposit  => 1,
+            Self::Withdraw => 2,
+        }
+    }
+}
+
+impl From<u32> for BankActionKind {
+    fn from(x: u32) -> Self {
+        match x {
+            1 => Self::Deposit,
+            2 => Self::Withdraw,
+            _ => Self::List,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BankActionData {
+    pub kind: BankActionKind,
+    /// the name of the item to deposit or withdraw, empty when listing the bank
+    pub item: String,
+}
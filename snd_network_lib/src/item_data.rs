@@ -5,6 +5,12 @@ pub struct ItemData {
     pub level: u32,
     pub itype: u32,
     pub rarity: u32,
+    /// what the item's special ability does, empty if it has none
+    pub ability: String,
+    /// how many times the item has been upgraded
+    pub upgrades: u32,
+    /// if the player fights with it, only set in inventories
+    pub equipped: bool,
     pub damage: Option<u32>,
     pub defense: Option<u32>,
 }
\ No newline at end of file
@@ -0,0 +1,70 @@
+/// What a leaderboard ranks players by
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum LeaderboardCategory {
+    Level, Steps, Kills, Wealth, Duels,
+}
+
+impl LeaderboardCategory {
+    pub const ALL: [LeaderboardCategory; 5] = [Self::Level, Self::Steps, Self::Kills, Self::Wealth, Self::Duels];
+}
+
+impl ToString for LeaderboardCategory {
+    fn to_string(&self) -> String {
+        match self {
+            Self::Level => "level",
+            Self::Steps => "steps",
+            Self::Kills => "kills",
+            Self::Wealth => "wealth",
+            Self::Duels => "duels",
+        }.to_string()
+    }
+}
+
+impl Into<u32> for LeaderboardCategory {
+    fn into(self) -> u32 {
+        match self {
+            Self::Level  => 0,
+            Self::Steps  => 1,
+            Self::Kills  => 2,
+            Self::Wealth => 3,
+            Self::Duels  => 4,
+        }
+    }
+}
+
+impl From<u32> for LeaderboardCategory {
+    fn from(x: u32) -> Self {
+        match x {
+            1 => Self::Steps,
+            2 => Self::Kills,
+            3 => Self::Wealth,
+            4 => Self::Duels,
+            _ => Self::Level,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LeaderboardRequestData {
+    pub category: LeaderboardCategory,
+    /// the page to view, starting at 0
+    pub page: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct LeaderboardEntryData {
+    /// the player's place, starting at 1
+    pub rank: u32,
+    pub name: String,
+    /// the level, steps, kills, gold or duel wins the player is ranked by
+    pub value: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct LeaderboardData {
+    pub category: LeaderboardCategory,
+    pub page: u32,
+    /// how many pages the leaderboard has
+    pub pages: u32,
+    pub entries: Vec<LeaderboardEntryData>,
+}
@@ -12,6 +12,22 @@ pub mod enemy_data;
 pub mod error_data;
 pub mod encounter_data;
 pub mod player_data;
+pub mod chat_data;
+pub mod broadcast_data;
+pub mod party_data;
+pub mod duel_data;
+pub mod leaderboard_data;
+pub mod achievement_data;
+pub mod quest_data;
+pub mod consumable_data;
+pub mod crafting_data;
+pub mod inventory_data;
+pub mod auction_data;
+pub mod mail_data;
+pub mod guild_data;
+pub mod skill_data;
+pub mod status_data;
+pub mod bestiary_data;
 
 pub mod client_event;
 pub mod server_event;
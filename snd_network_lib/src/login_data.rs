@@ -5,4 +5,6 @@ pub struct LoginData {
     pub passwd: String,
     pub signup: bool,
     pub client_ver: String,
+    /// the class picked when signing up, empty for the default class
+    pub class: String,
 }
\ No newline at end of file
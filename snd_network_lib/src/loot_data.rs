@@ -4,4 +4,9 @@ use crate::item_data::ItemData;
 pub struct LootData {
     pub items: Vec<ItemData>,
     pub exp: u32,
+    pub gold: u32,
+    /// the names of the consumables gained, one for each
+    pub consumables: Vec<String>,
+    /// the crafting materials gained with how many, e.g. "2 Leather"
+    pub materials: Vec<String>,
 }
\ No newline at end of file
@@ -0,0 +1,71 @@
+use crate::item_data::ItemData;
+
+/// A letter in a player's mailbox
+#[derive(Clone, Debug)]
+pub struct MailData {
+    /// the mail's number, used to read, claim or delete it
+    pub number: u32,
+    /// the username of the sender, 'System' for mail from the server
+    pub sender: String,
+    pub message: String,
+    /// the gold sent with it
+    pub gold: u32,
+    /// the items sent with it
+    pub items: Vec<ItemData>,
+    /// seconds since it was sent
+    pub age: u64,
+    pub read: bool,
+    /// if the player has taken the gold and items
+    pub claimed: bool,
+}
+
+/// What a player wants to do with their mail
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum MailActionKind {
+    List, Read, Claim, Delete, Send,
+}
+
+impl Into<u32> for MailActionKind {
+    fn into(self) -> u32 {
+        match self {
+            Self::List   => 0,
+            Self::Read   => 1,
+            Self::Claim  => 2,
+            Self::Delete => 3,
+            Self::Send   => 4,
+        }
+    }
+}
+
+impl From<u32> for MailActionKind {
+    fn from(x: u32) -> Self {
+        match x {
+            1 => Self::Read,
+            2 => Self::Claim,
+            3 => Self::Delete,
+            4 => Self::Send,
+            _ => Self::List,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MailActionData {
+    pub kind: MailActionKind,
+    /// the mail to read, claim or delete
+    pub number: u32,
+    /// the username to send mail to, empty otherwise
+    pub to: String,
+    pub message: String,
+    /// the gold to send with the mail
+    pub gold: u32,
+    /// the names of the items to send with the mail
+    pub items: Vec<String>,
+}
+
+impl MailActionData {
+    /// an action on mail already in the player's mailbox
+    pub fn new(kind: MailActionKind, number: u32) -> Self {
+        Self { kind, number, to: String::new(), message: String::new(), gold: 0, items: Vec::new() }
+    }
+}
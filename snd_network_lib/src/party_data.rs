@@ -0,0 +1,47 @@
+/// What a player wants to do with their party
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum PartyActionKind {
+    Invite, Accept, Decline, Leave, Kick, Status,
+}
+
+impl Into<u32> for PartyActionKind {
+    fn into(self) -> u32 {
+        match self {
+            Self::Invite  => 0,
+            Self::Accept  => 1,
+            Self::Decline => 2,
+            Self::Leave   => 3,
+            Self::Kick    => 4,
+            Self::Status  => 5,
+        }
+    }
+}
+
+impl From<u32> for PartyActionKind {
+    fn from(x: u32) -> Self {
+        match x {
+            0 => Self::Invite,
+            1 => Self::Accept,
+            2 => Self::Decline,
+            3 => Self::Leave,
+            4 => Self::Kick,
+            _ => Self::Status,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PartyActionData {
+    pub kind: PartyActionKind,
+    /// the username to invite or kick, empty otherwise
+    pub target: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct PartyMemberData {
+    pub name: String,
+    pub level: u32,
+    pub health: u32,
+    pub max_health: u32,
+    pub leader: bool,
+}
\ No newline at end of file
@@ -1,8 +1,19 @@
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct PlayerData {
     pub level: u32,
     pub exp: u32,
     pub region: String,
     pub steps: u32,
     pub health: u32,
+    pub max_health: u32,
+    /// the exp needed to reach the next level
+    pub next_exp: u32,
+    /// how many letters in the player's mailbox they have not read
+    pub unread_mail: u32,
+    /// the name of the player's guild, empty if they are not in one
+    pub guild: String,
+    /// the name of the player's class, empty if they have not picked one
+    pub class: String,
+    /// the skill points the player has not spent yet
+    pub skill_points: u32,
 }
\ No newline at end of file
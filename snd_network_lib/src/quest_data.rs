@@ -0,0 +1,84 @@
+/// Where a quest is for a player
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum QuestState {
+    /// a quest giver is offering the quest
+    Offered,
+    /// the player is working on the quest
+    Active,
+    /// the objective is done and the quest can be turned in
+    Complete,
+}
+
+impl Into<u32> for QuestState {
+    fn into(self) -> u32 {
+        match self {
+            Self::Offered  => 0,
+            Self::Active   => 1,
+            Self::Complete => 2,
+        }
+    }
+}
+
+impl From<u32> for QuestState {
+    fn from(x: u32) -> Self {
+        match x {
+            1 => Self::Active,
+            2 => Self::Complete,
+            _ => Self::Offered,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct QuestData {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub giver: String,
+    /// what the player has to do, e.g. "Defeat 3 Goblins"
+    pub objective: String,
+    pub progress: u32,
+    pub goal: u32,
+    pub reward_gold: u32,
+    pub reward_exp: u32,
+    /// the kind of item given as a reward, empty if there is none
+    pub reward_item: String,
+    pub state: QuestState,
+}
+
+/// What a player wants to do with their quests
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum QuestActionKind {
+    Accept, Decline, Abandon, TurnIn, Log,
+}
+
+impl Into<u32> for QuestActionKind {
+    fn into(self) -> u32 {
+        match self {
+            Self::Accept  => 0,
+            Self::Decline => 1,
+            Self::Abandon => 2,
+            Self::TurnIn  => 3,
+            Self::Log     => 4,
+        }
+    }
+}
+
+impl From<u32> for QuestActionKind {
+    fn from(x: u32) -> Self {
+        match x {
+            0 => Self::Accept,
+            1 => Self::Decline,
+            2 => Self::Abandon,
+            3 => Self::TurnIn,
+            _ => Self::Log,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct QuestActionData {
+    pub kind: QuestActionKind,
+    /// the name of the quest to abandon or turn in, empty otherwise
+    pub quest: String,
+}
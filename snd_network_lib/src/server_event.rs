@@ -1,14 +1,29 @@
 use std::net::TcpStream;
 use capnp::message::Builder;
 use capnp::serialize;
+use crate::achievement_data::AchievementData;
+use crate::auction_data::{AuctionListData, ListingData};
+use crate::bestiary_data::BestiaryEntryData;
+use crate::broadcast_data::{BroadcastData, BroadcastKind};
+use crate::chat_data::{ChatChannel, ChatData};
 use crate::enemy_data::EnemyData;
 use crate::error_data::ErrorData;
 use crate::item_data::ItemData;
 use crate::{packet_capnp, systime};
 use crate::encounter_data::EncounterData;
+use crate::guild_data::{GuildData, GuildMemberData, GuildRank};
+use crate::mail_data::MailData;
+use crate::leaderboard_data::{LeaderboardCategory, LeaderboardData, LeaderboardEntryData};
 use crate::loot_data::LootData;
-use crate::packet_capnp::{encounter, s_event};
+use crate::packet_capnp::{achievement, consumable, encounter, inventory, item, quest, s_event};
+use crate::party_data::PartyMemberData;
 use crate::player_data::PlayerData;
+use crate::quest_data::{QuestData, QuestState};
+use crate::skill_data::{SkillData, SkillTreeData};
+use crate::status_data::{StatusEffectData, StatusKind};
+use crate::consumable_data::ConsumableData;
+use crate::crafting_data::{CraftingData, MaterialData, RecipeData};
+use crate::inventory_data::InventoryData;
 
 #[derive(Clone, Debug)]
 pub enum ServerEvent {
@@ -19,9 +34,33 @@ pub enum ServerEvent {
     FindItem(ItemData),
     Encounter(EncounterData),
     Update(PlayerData),
-    Inventory(Vec<ItemData>),
+    Inventory(InventoryData),
+    /// the items in the player's bank
+    Bank(InventoryData),
     ItemView(ItemData),
     Error(ErrorData),
+    Chat(ChatData),
+    Broadcast(BroadcastData),
+    Party(Vec<PartyMemberData>),
+    Leaderboard(LeaderboardData),
+    Achievement(AchievementData),
+    Achievements(Vec<AchievementData>),
+    QuestOffer(QuestData),
+    Quests(Vec<QuestData>),
+    Consumables(Vec<ConsumableData>),
+    Shop(Vec<ConsumableData>),
+    Crafting(CraftingData),
+    Auctions(AuctionListData),
+    /// the player's mailbox, newest first
+    Mail(Vec<MailData>),
+    /// the player's guild, or another one they looked up
+    Guild(GuildData),
+    /// the items in the guild's bank
+    GuildBank(InventoryData),
+    /// the player's class and skill tree
+    Skills(SkillTreeData),
+    /// every enemy in the player's bestiary
+    Bestiary(Vec<BestiaryEntryData>),
 }
 
 pub fn write_server_disconnect(mut stream: &TcpStream) -> ::capnp::Result<()> {
@@ -70,6 +109,9 @@ pub fn write_server_find_item(mut stream: &TcpStream, item_data: ItemData) -> ::
         found_item_reader.set_itype(item_data.itype);
         found_item_reader.set_level(item_data.level);
         found_item_reader.set_rarity(item_data.rarity);
+        found_item_reader.set_ability(item_data.ability.as_str());
+        found_item_reader.set_upgrades(item_data.upgrades);
+        found_item_reader.set_equipped(item_data.equipped);
         found_item_reader.set_damage(item_data.damage.unwrap_or(0));
         found_item_reader.set_defense(item_data.defense.unwrap_or(0));
     }
@@ -89,6 +131,7 @@ pub fn write_server_encounter_attack(mut stream: &TcpStream, enemy: EnemyData, d
         enemy_builder.set_race(enemy.race.as_str());
         enemy_builder.set_health(enemy.health);
         enemy_builder.set_level(enemy.level);
+        enemy_builder.set_max_health(enemy.max_health);
     }
     serialize::write_message(&mut stream, &message)
 }
@@ -106,6 +149,7 @@ pub fn write_server_encounter_flee(mut stream: &TcpStream, enemy: EnemyData, suc
         enemy_builder.set_race(enemy.race.as_str());
         enemy_builder.set_health(enemy.health);
         enemy_builder.set_level(enemy.level);
+        enemy_builder.set_max_health(enemy.max_health);
     }
     serialize::write_message(&mut stream, &message)
 }
@@ -123,6 +167,7 @@ pub fn write_server_encounter_lost(mut stream: &TcpStream, enemy: EnemyData) ->
         enemy_builder.set_race(enemy.race.as_str());
         enemy_builder.set_health(enemy.health);
         enemy_builder.set_level(enemy.level);
+        enemy_builder.set_max_health(enemy.max_health);
     }
     serialize::write_message(&mut stream, &message)
 }
@@ -135,6 +180,7 @@ pub fn write_server_encounter_win(mut stream: &TcpStream, enemy: EnemyData, loot
 
         let mut win_builder = encounter_builder.reborrow().init_win();
         win_builder.set_exp(loot.exp);
+        win_builder.set_gold(loot.gold);
 
         for x in 0..loot.items.len() {
             let item_data = loot.items.get(x).unwrap();
@@ -144,6 +190,9 @@ pub fn write_server_encounter_win(mut stream: &TcpStream, enemy: EnemyData, loot
             ib.reborrow().set_itype(item_data.itype);
             ib.reborrow().set_level(item_data.level);
             ib.reborrow().set_rarity(item_data.rarity);
+            ib.reborrow().set_ability(item_data.ability.as_str());
+            ib.reborrow().set_upgrades(item_data.upgrades);
+            ib.reborrow().set_equipped(item_data.equipped);
             ib.reborrow().set_damage(item_data.damage.unwrap_or(0));
             ib.reborrow().set_defense(item_data.defense.unwrap_or(0));
             //item_builder.set_with_caveats(index, ib);
@@ -154,29 +203,294 @@ pub fn write_server_encounter_win(mut stream: &TcpStream, enemy: EnemyData, loot
         enemy_builder.set_race(enemy.race.as_str());
         enemy_builder.set_health(enemy.health);
         enemy_builder.set_level(enemy.level);
+        enemy_builder.set_max_health(enemy.max_health);
     }
     serialize::write_message(&mut stream, &message)
 }
 
-pub fn write_server_inventory(mut stream: &TcpStream, inventory: Vec<ItemData>) -> ::capnp::Result<()> {
+/// writes any kind of encounter update, including the message and whose turn it is
+pub fn write_server_encounter(mut stream: &TcpStream, data: EncounterData) -> ::capnp::Result<()> {
     let mut message = Builder::new_default();
     {
         let er = message.init_root::<s_event::Builder>();
-        let mut inv_builder = er.init_inventory(inventory.len() as u32);
-        for x in 0..inventory.len() {
-            let item_data = inventory.get(x).unwrap();
-            let index = x as u32;
-            inv_builder.reborrow().get(index).set_name(item_data.name.as_str());
-            inv_builder.reborrow().get(index).set_itype(item_data.itype);
-            inv_builder.reborrow().get(index).set_level(item_data.level);
-            inv_builder.reborrow().get(index).set_rarity(item_data.rarity);
-            inv_builder.reborrow().get(index).set_damage(item_data.damage.unwrap_or(0));
-            inv_builder.reborrow().get(index).set_defense(item_data.defense.unwrap_or(0));
+        let mut encounter_builder = er.init_encounter();
+
+        if let Some(damage) = data.attk {
+            encounter_builder.set_attk(damage);
+        } else if let Some(success) = data.flee {
+            encounter_builder.set_flee(success);
+        } else if let Some(loot) = data.win {
+            let mut win_builder = encounter_builder.reborrow().init_win();
+            win_builder.set_exp(loot.exp);
+        win_builder.set_gold(loot.gold);
+            let mut consumables_builder = win_builder.reborrow().init_consumables(loot.consumables.len() as u32);
+            for (x, name) in loot.consumables.iter().enumerate() {
+                consumables_builder.set(x as u32, name.as_str());
+            }
+            let mut materials_builder = win_builder.reborrow().init_materials(loot.materials.len() as u32);
+            for (x, name) in loot.materials.iter().enumerate() {
+                materials_builder.set(x as u32, name.as_str());
+            }
+            let mut items_builder = win_builder.init_items(loot.items.len() as u32);
+            for x in 0..loot.items.len() {
+                let item_data = loot.items.get(x).unwrap();
+                let mut ib = items_builder.reborrow().get(x as u32);
+                ib.set_name(item_data.name.as_str());
+                ib.set_itype(item_data.itype);
+                ib.set_level(item_data.level);
+                ib.set_rarity(item_data.rarity);
+                ib.set_ability(item_data.ability.as_str());
+                ib.set_upgrades(item_data.upgrades);
+                ib.set_equipped(item_data.equipped);
+                // only swords deal damage, everything else gives defense
+                if item_data.damage.unwrap_or(0) > 0 {
+                    ib.set_damage(item_data.damage.unwrap());
+                } else {
+                    ib.set_defense(item_data.defense.unwrap_or(0));
+                }
+            }
+        } else if data.lost.is_some() {
+            encounter_builder.set_lost(true);
+        } else if let Some(damage) = data.hit {
+            encounter_builder.set_hit(damage);
+        } else {
+            encounter_builder.set_start(());
+        }
+
+        encounter_builder.set_message(data.message.as_str());
+        encounter_builder.set_turn(data.turn.as_str());
+        let mut effects_builder = encounter_builder.reborrow().init_effects(data.effects.len() as u32);
+        for (x, effect) in data.effects.iter().enumerate() {
+            let mut eb = effects_builder.reborrow().get(x as u32);
+            eb.set_target(effect.target.as_str());
+            eb.set_kind(effect.kind.into());
+            eb.set_amount(effect.amount);
+            eb.set_turns(effect.turns);
         }
+
+        let mut enemy_builder = encounter_builder.init_enemy();
+        enemy_builder.set_name(data.enemy.name.as_str());
+        enemy_builder.set_race(data.enemy.race.as_str());
+        enemy_builder.set_health(data.enemy.health);
+        enemy_builder.set_level(data.enemy.level);
+        enemy_builder.set_max_health(data.enemy.max_health);
     }
     serialize::write_message(&mut stream, &message)
 }
 
+pub fn write_server_inventory(mut stream: &TcpStream, inventory: InventoryData) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<s_event::Builder>();
+        build_inventory(er.init_inventory_page(), &inventory);
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+/// sends the items as a plain list for clients from before inventory requests
+pub fn write_server_inventory_list(mut stream: &TcpStream, items: Vec<ItemData>) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<s_event::Builder>();
+        let mut inv_builder = er.init_inventory(items.len() as u32);
+        for x in 0..items.len() {
+            build_item(inv_builder.reborrow().get(x as u32), items.get(x).unwrap());
+        }
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_server_bank(mut stream: &TcpStream, bank: InventoryData) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<s_event::Builder>();
+        build_inventory(er.init_bank(), &bank);
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_server_guild_bank(mut stream: &TcpStream, bank: InventoryData) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<s_event::Builder>();
+        build_inventory(er.init_guild_bank(), &bank);
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_server_guild(mut stream: &TcpStream, guild: GuildData) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<s_event::Builder>();
+        let mut guild_builder = er.init_guild();
+        guild_builder.set_name(guild.name.as_str());
+        guild_builder.set_rank(guild.rank.into());
+        let mut members_builder = guild_builder.reborrow().init_members(guild.members.len() as u32);
+        for (x, member) in guild.members.iter().enumerate() {
+            let mut mb = members_builder.reborrow().get(x as u32);
+            mb.set_name(member.name.as_str());
+            mb.set_rank(member.rank.into());
+            mb.set_level(member.level);
+            mb.set_online(member.online);
+        }
+        let mut invites_builder = guild_builder.init_invites(guild.invites.len() as u32);
+        for (x, invite) in guild.invites.iter().enumerate() {
+            invites_builder.set(x as u32, invite.as_str());
+        }
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_server_skills(mut stream: &TcpStream, tree: SkillTreeData) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<s_event::Builder>();
+        let mut tree_builder = er.init_skills();
+        tree_builder.set_class(tree.class.as_str());
+        tree_builder.set_points(tree.points);
+        let mut skills_builder = tree_builder.reborrow().init_skills(tree.skills.len() as u32);
+        for (x, skill) in tree.skills.iter().enumerate() {
+            let mut sb = skills_builder.reborrow().get(x as u32);
+            sb.set_name(skill.name.as_str());
+            sb.set_description(skill.description.as_str());
+            sb.set_active(skill.active);
+            sb.set_rank(skill.rank);
+            sb.set_max_rank(skill.max_rank);
+            sb.set_level(skill.level);
+            sb.set_requires(skill.requires.as_str());
+            sb.set_cooldown(skill.cooldown);
+        }
+        let mut classes_builder = tree_builder.init_classes(tree.classes.len() as u32);
+        for (x, class) in tree.classes.iter().enumerate() {
+            classes_builder.set(x as u32, class.as_str());
+        }
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_server_bestiary(mut stream: &TcpStream, entries: Vec<BestiaryEntryData>) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<s_event::Builder>();
+        let mut list_builder = er.init_bestiary(entries.len() as u32);
+        for (x, entry) in entries.iter().enumerate() {
+            let mut eb = list_builder.reborrow().get(x as u32);
+            eb.set_race(entry.race.as_str());
+            eb.set_description(entry.description.as_str());
+            eb.set_unlocked(entry.unlocked);
+            eb.set_kills(entry.kills);
+            eb.set_min_level(entry.min_level);
+            eb.set_max_level(entry.max_level);
+            eb.set_ability(entry.ability.as_str());
+            let mut loot_builder = eb.init_loot(entry.loot.len() as u32);
+            for (y, loot) in entry.loot.iter().enumerate() {
+                loot_builder.set(y as u32, loot.as_str());
+            }
+        }
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+fn build_inventory(mut builder: inventory::Builder, inventory: &InventoryData) {
+    builder.set_count(inventory.count);
+    builder.set_capacity(inventory.capacity);
+    let mut inv_builder = builder.init_items(inventory.items.len() as u32);
+    for x in 0..inventory.items.len() {
+        build_item(inv_builder.reborrow().get(x as u32), inventory.items.get(x).unwrap());
+    }
+}
+
+fn build_item(mut builder: item::Builder, item_data: &ItemData) {
+    builder.set_name(item_data.name.as_str());
+    builder.set_itype(item_data.itype);
+    builder.set_level(item_data.level);
+    builder.set_rarity(item_data.rarity);
+    builder.set_ability(item_data.ability.as_str());
+    builder.set_upgrades(item_data.upgrades);
+    builder.set_equipped(item_data.equipped);
+    // only swords deal damage, everything else gives defense
+    if item_data.damage.unwrap_or(0) > 0 {
+        builder.set_damage(item_data.damage.unwrap());
+    } else {
+        builder.set_defense(item_data.defense.unwrap_or(0));
+    }
+}
+
+fn read_inventory(reader: inventory::Reader) -> Result<InventoryData, capnp::NotInSchema> {
+    let mut items = Vec::new();
+    for item in reader.get_items().unwrap().into_iter() {
+        items.push(read_item(item)?);
+    }
+
+    Ok(InventoryData {
+        items,
+        count: reader.get_count(),
+        capacity: reader.get_capacity(),
+    })
+}
+
+fn read_item(item: item::Reader) -> Result<ItemData, capnp::NotInSchema> {
+    let mut damage: Option<u32> = None;
+    let mut defense: Option<u32> = None;
+
+    match item.which()? {
+        packet_capnp::item::Which::Damage(i) => damage = Some(i),
+        packet_capnp::item::Which::Defense(i) => defense = Some(i),
+    }
+
+    Ok(ItemData {
+        name: item.get_name().unwrap().to_string(),
+        level: item.get_level(),
+        itype: item.get_itype(),
+        rarity: item.get_rarity(),
+        ability: item.get_ability().unwrap().to_string(),
+        upgrades: item.get_upgrades(),
+        equipped: item.get_equipped(),
+        damage, defense
+    })
+}
+
+pub fn write_server_auctions(mut stream: &TcpStream, auctions: AuctionListData) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<s_event::Builder>();
+        let mut auctions_builder = er.init_auctions();
+        auctions_builder.set_total(auctions.total);
+        let mut list_builder = auctions_builder.init_listings(auctions.listings.len() as u32);
+        for (x, listing) in auctions.listings.iter().enumerate() {
+            let mut listing_builder = list_builder.reborrow().get(x as u32);
+            listing_builder.set_number(listing.number);
+            listing_builder.set_seller(listing.seller.as_str());
+            listing_builder.set_bid(listing.bid);
+            listing_builder.set_min_bid(listing.min_bid);
+            listing_builder.set_buyout(listing.buyout);
+            listing_builder.set_time_left(listing.time_left);
+            listing_builder.set_yours(listing.yours);
+            listing_builder.set_winning(listing.winning);
+            build_item(listing_builder.init_item(), &listing.item);
+        }
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+fn read_auctions(reader: packet_capnp::auctions::Reader) -> Result<AuctionListData, capnp::NotInSchema> {
+    let mut listings = Vec::new();
+    for listing in reader.get_listings().unwrap().into_iter() {
+        listings.push(ListingData {
+            number: listing.get_number(),
+            item: read_item(listing.get_item().unwrap())?,
+            seller: listing.get_seller().unwrap().to_string(),
+            bid: listing.get_bid(),
+            min_bid: listing.get_min_bid(),
+            buyout: listing.get_buyout(),
+            time_left: listing.get_time_left(),
+            yours: listing.get_yours(),
+            winning: listing.get_winning(),
+        });
+    }
+    Ok(AuctionListData { listings, total: reader.get_total() })
+}
+
 pub fn write_server_update(mut stream: &TcpStream, data: PlayerData) -> ::capnp::Result<()> {
     let mut message = Builder::new_default();
     {
@@ -187,6 +501,12 @@ pub fn write_server_update(mut stream: &TcpStream, data: PlayerData) -> ::capnp:
         pd.set_region(data.region.as_str());
         pd.set_steps(data.steps);
         pd.set_health(data.health);
+        pd.set_max_health(data.max_health);
+        pd.set_next_exp(data.next_exp);
+        pd.set_unread_mail(data.unread_mail);
+        pd.set_guild(data.guild.as_str());
+        pd.set_class(data.class.as_str());
+        pd.set_skill_points(data.skill_points);
     }
     serialize::write_message(&mut stream, &message)
 }
@@ -200,6 +520,9 @@ pub fn write_server_item_view(mut stream: &TcpStream, item_data: ItemData) -> ::
         item_view_reader.set_itype(item_data.itype);
         item_view_reader.set_level(item_data.level);
         item_view_reader.set_rarity(item_data.rarity);
+        item_view_reader.set_ability(item_data.ability.as_str());
+        item_view_reader.set_upgrades(item_data.upgrades);
+        item_view_reader.set_equipped(item_data.equipped);
         item_view_reader.set_damage(item_data.damage.unwrap_or(0));
         item_view_reader.set_defense(item_data.defense.unwrap_or(0));
     }
@@ -217,6 +540,260 @@ pub fn write_server_error(mut stream: &TcpStream, error: ErrorData) -> ::capnp::
     serialize::write_message(&mut stream, &message)
 }
 
+pub fn write_server_chat(mut stream: &TcpStream, chat: ChatData) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<s_event::Builder>();
+        let mut chat_builder = er.init_chat();
+        chat_builder.set_channel(chat.channel.into());
+        chat_builder.set_sender(chat.sender.as_str());
+        chat_builder.set_target(chat.target.as_str());
+        chat_builder.set_message(chat.message.as_str());
+    }
+    serialize::write_message(&mut stream, &message)
+}
+pub fn write_server_broadcast(mut stream: &TcpStream, broadcast: BroadcastData) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<s_event::Builder>();
+        let mut broadcast_builder = er.init_broadcast();
+        broadcast_builder.set_kind(broadcast.kind.into());
+        broadcast_builder.set_message(broadcast.message.as_str());
+    }
+    serialize::write_message(&mut stream, &message)
+}
+pub fn write_server_party(mut stream: &TcpStream, members: Vec<PartyMemberData>) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<s_event::Builder>();
+        let mut party_builder = er.init_party(members.len() as u32);
+        for x in 0..members.len() {
+            let member = members.get(x).unwrap();
+            let mut mb = party_builder.reborrow().get(x as u32);
+            mb.set_name(member.name.as_str());
+            mb.set_level(member.level);
+            mb.set_health(member.health);
+            mb.set_max_health(member.max_health);
+            mb.set_leader(member.leader);
+        }
+    }
+    serialize::write_message(&mut stream, &message)
+}
+pub fn write_server_leaderboard(mut stream: &TcpStream, leaderboard: LeaderboardData) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<s_event::Builder>();
+        let mut lb_builder = er.init_leaderboard();
+        lb_builder.set_category(leaderboard.category.into());
+        lb_builder.set_page(leaderboard.page);
+        lb_builder.set_pages(leaderboard.pages);
+        let mut entries_builder = lb_builder.init_entries(leaderboard.entries.len() as u32);
+        for (x, entry) in leaderboard.entries.iter().enumerate() {
+            let mut eb = entries_builder.reborrow().get(x as u32);
+            eb.set_rank(entry.rank);
+            eb.set_name(entry.name.as_str());
+            eb.set_value(entry.value);
+        }
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+fn build_achievement(mut builder: achievement::Builder, achievement: &AchievementData) {
+    builder.set_id(achievement.id.as_str());
+    builder.set_name(achievement.name.as_str());
+    builder.set_description(achievement.description.as_str());
+    builder.set_unlocked(achievement.unlocked);
+    builder.set_time(achievement.time);
+}
+
+fn read_achievement(reader: achievement::Reader) -> AchievementData {
+    AchievementData {
+        id: reader.get_id().unwrap().to_string(),
+        name: reader.get_name().unwrap().to_string(),
+        description: reader.get_description().unwrap().to_string(),
+        unlocked: reader.get_unlocked(),
+        time: reader.get_time(),
+    }
+}
+
+pub fn write_server_achievement(mut stream: &TcpStream, achievement: AchievementData) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<s_event::Builder>();
+        build_achievement(er.init_achievement(), &achievement);
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_server_achievements(mut stream: &TcpStream, achievements: Vec<AchievementData>) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<s_event::Builder>();
+        let mut list_builder = er.init_achievements(achievements.len() as u32);
+        for (x, achievement) in achievements.iter().enumerate() {
+            build_achievement(list_builder.reborrow().get(x as u32), achievement);
+        }
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+fn build_quest(mut builder: quest::Builder, quest: &QuestData) {
+    builder.set_id(quest.id.as_str());
+    builder.set_name(quest.name.as_str());
+    builder.set_description(quest.description.as_str());
+    builder.set_giver(quest.giver.as_str());
+    builder.set_objective(quest.objective.as_str());
+    builder.set_progress(quest.progress);
+    builder.set_goal(quest.goal);
+    builder.set_reward_gold(quest.reward_gold);
+    builder.set_reward_exp(quest.reward_exp);
+    builder.set_reward_item(quest.reward_item.as_str());
+    builder.set_state(quest.state.into());
+}
+
+fn read_quest(reader: quest::Reader) -> QuestData {
+    QuestData {
+        id: reader.get_id().unwrap().to_string(),
+        name: reader.get_name().unwrap().to_string(),
+        description: reader.get_description().unwrap().to_string(),
+        giver: reader.get_giver().unwrap().to_string(),
+        objective: reader.get_objective().unwrap().to_string(),
+        progress: reader.get_progress(),
+        goal: reader.get_goal(),
+        reward_gold: reader.get_reward_gold(),
+        reward_exp: reader.get_reward_exp(),
+        reward_item: reader.get_reward_item().unwrap().to_string(),
+        state: QuestState::from(reader.get_state()),
+    }
+}
+
+pub fn write_server_quest_offer(mut stream: &TcpStream, quest: QuestData) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<s_event::Builder>();
+        build_quest(er.init_quest_offer(), &quest);
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_server_quests(mut stream: &TcpStream, quests: Vec<QuestData>) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<s_event::Builder>();
+        let mut list_builder = er.init_quests(quests.len() as u32);
+        for (x, quest) in quests.iter().enumerate() {
+            build_quest(list_builder.reborrow().get(x as u32), quest);
+        }
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+fn build_consumable(mut builder: consumable::Builder, consumable: &ConsumableData) {
+    builder.set_id(consumable.id.as_str());
+    builder.set_name(consumable.name.as_str());
+    builder.set_description(consumable.description.as_str());
+    builder.set_kind(consumable.kind.as_str());
+    builder.set_quantity(consumable.quantity);
+    builder.set_price(consumable.price);
+}
+
+fn read_consumable(reader: consumable::Reader) -> ConsumableData {
+    ConsumableData {
+        id: reader.get_id().unwrap().to_string(),
+        name: reader.get_name().unwrap().to_string(),
+        description: reader.get_description().unwrap().to_string(),
+        kind: reader.get_kind().unwrap().to_string(),
+        quantity: reader.get_quantity(),
+        price: reader.get_price(),
+    }
+}
+
+pub fn write_server_consumables(mut stream: &TcpStream, consumables: Vec<ConsumableData>) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<s_event::Builder>();
+        let mut list_builder = er.init_consumables(consumables.len() as u32);
+        for (x, consumable) in consumables.iter().enumerate() {
+            build_consumable(list_builder.reborrow().get(x as u32), consumable);
+        }
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_server_shop(mut stream: &TcpStream, stock: Vec<ConsumableData>) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<s_event::Builder>();
+        let mut list_builder = er.init_shop(stock.len() as u32);
+        for (x, consumable) in stock.iter().enumerate() {
+            build_consumable(list_builder.reborrow().get(x as u32), consumable);
+        }
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+pub fn write_server_mail(mut stream: &TcpStream, mailbox: Vec<MailData>) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<s_event::Builder>();
+        let mut list_builder = er.init_mail(mailbox.len() as u32);
+        for (x, mail) in mailbox.iter().enumerate() {
+            let mut mail_builder = list_builder.reborrow().get(x as u32);
+            mail_builder.set_number(mail.number);
+            mail_builder.set_sender(mail.sender.as_str());
+            mail_builder.set_message(mail.message.as_str());
+            mail_builder.set_gold(mail.gold);
+            mail_builder.set_age(mail.age);
+            mail_builder.set_read(mail.read);
+            mail_builder.set_claimed(mail.claimed);
+            let mut items_builder = mail_builder.init_items(mail.items.len() as u32);
+            for (i, item) in mail.items.iter().enumerate() {
+                build_item(items_builder.reborrow().get(i as u32), item);
+            }
+        }
+    }
+    serialize::write_message(&mut stream, &message)
+}
+
+fn read_mail(reader: packet_capnp::mail::Reader) -> Result<MailData, capnp::NotInSchema> {
+    let mut items = Vec::new();
+    for item in reader.get_items().unwrap().into_iter() {
+        items.push(read_item(item)?);
+    }
+    Ok(MailData {
+        number: reader.get_number(),
+        sender: reader.get_sender().unwrap().to_string(),
+        message: reader.get_message().unwrap().to_string(),
+        gold: reader.get_gold(),
+        items,
+        age: reader.get_age(),
+        read: reader.get_read(),
+        claimed: reader.get_claimed(),
+    })
+}
+
+pub fn write_server_crafting(mut stream: &TcpStream, crafting: CraftingData) -> ::capnp::Result<()> {
+    let mut message = Builder::new_default();
+    {
+        let er = message.init_root::<s_event::Builder>();
+        let mut crafting_builder = er.init_crafting();
+        let mut recipes_builder = crafting_builder.reborrow().init_recipes(crafting.recipes.len() as u32);
+        for (x, recipe) in crafting.recipes.iter().enumerate() {
+            let mut rb = recipes_builder.reborrow().get(x as u32);
+            rb.set_name(recipe.name.as_str());
+            rb.set_result(recipe.result.as_str());
+            rb.set_cost(recipe.cost.as_str());
+            rb.set_craftable(recipe.craftable);
+        }
+        let mut materials_builder = crafting_builder.init_materials(crafting.materials.len() as u32);
+        for (x, material) in crafting.materials.iter().enumerate() {
+            let mut mb = materials_builder.reborrow().get(x as u32);
+            mb.set_name(material.name.as_str());
+            mb.set_quantity(material.quantity);
+        }
+    }
+    serialize::write_message(&mut stream, &message)
+}
 
 // a method for the client to expect messages from the server
 pub fn read_server_event(mut stream: &TcpStream) -> ServerEvent {
@@ -248,6 +825,12 @@ pub fn read_server_event(mut stream: &TcpStream) -> ServerEvent {
                 level: raw_pdata.get_level(),
                 exp: raw_pdata.get_exp(),
                 health: raw_pdata.get_health(),
+                max_health: raw_pdata.get_max_health(),
+                next_exp: raw_pdata.get_next_exp(),
+                unread_mail: raw_pdata.get_unread_mail(),
+                guild: raw_pdata.get_guild().unwrap().to_string(),
+                class: raw_pdata.get_class().unwrap().to_string(),
+                skill_points: raw_pdata.get_skill_points(),
                 steps: raw_pdata.get_steps(),
                 region: raw_pdata.get_region().unwrap().to_string()
             })
@@ -270,6 +853,9 @@ pub fn read_server_event(mut stream: &TcpStream) -> ServerEvent {
                 level: raw_id.get_level(),
                 itype: raw_id.get_itype(),
                 rarity: raw_id.get_rarity(),
+                ability: raw_id.get_ability().unwrap().to_string(),
+                upgrades: raw_id.get_upgrades(),
+                equipped: raw_id.get_equipped(),
                 defense, damage
             };
 
@@ -287,29 +873,31 @@ pub fn read_server_event(mut stream: &TcpStream) -> ServerEvent {
                 race: enemy.get_race().unwrap().to_string(),
                 level: enemy.get_level(),
                 health: enemy.get_health(),
+                max_health: enemy.get_max_health(),
             };
-            let edata = match which.unwrap() {
-                 encounter::Attk(damage) => EncounterData {
-                     enemy: emydata,
-                     attk: Some(damage),
-                     flee: None,
-                     win: None,
-                     lost: None
-                 },
-                encounter::Flee(b) => EncounterData {
-                    enemy: emydata,
-                    attk: None,
-                    flee: Some(b),
-                    win: None,
-                    lost: None
-                },
-                encounter::Lost(_) => EncounterData {
-                    enemy: emydata,
-                    attk: None,
-                    flee: None,
-                    win: None,
-                    lost: Some(true)
-                },
+            let mut edata = EncounterData {
+                enemy: emydata,
+                attk: None,
+                flee: None,
+                win: None,
+                lost: None,
+                start: false,
+                hit: None,
+                message: emy.get_message().unwrap().to_string(),
+                turn: emy.get_turn().unwrap().to_string(),
+                effects: emy.get_effects().unwrap().iter().map(|e| StatusEffectData {
+                    target: e.get_target().unwrap().to_string(),
+                    kind: StatusKind::from(e.get_kind()),
+                    amount: e.get_amount(),
+                    turns: e.get_turns(),
+                }).collect(),
+            };
+            match which.unwrap() {
+                encounter::Attk(damage) => edata.attk = Some(damage),
+                encounter::Flee(b) => edata.flee = Some(b),
+                encounter::Lost(_) => edata.lost = Some(true),
+                encounter::Start(_) => edata.start = true,
+                encounter::Hit(damage) => edata.hit = Some(damage),
                 encounter::Win(loot) => {
                     let win = loot.unwrap();
                     let mut items = Vec::new();
@@ -332,51 +920,52 @@ pub fn read_server_event(mut stream: &TcpStream) -> ServerEvent {
                             level: i.get_level(),
                             itype: i.get_itype(),
                             rarity: i.get_rarity(),
+                            ability: i.get_ability().unwrap().to_string(),
+                            upgrades: i.get_upgrades(),
+                            equipped: i.get_equipped(),
                             damage, defense
                         });
                     }
-                    let loot_data = LootData {
+                    edata.win = Some(LootData {
                         items,
                         exp: win.get_exp(),
-                    };
-                    EncounterData {
-                        enemy: emydata,
-                        attk: None,
-                        flee: None,
-                        win: Some(loot_data),
-                        lost: None
-                    }
+                        gold: win.get_gold(),
+                        consumables: win.get_consumables().unwrap().iter().map(|c| c.unwrap().to_string()).collect(),
+                        materials: win.get_materials().unwrap().iter().map(|m| m.unwrap().to_string()).collect(),
+                    });
                 }
-            };
+            }
             ServerEvent::Encounter(edata)
         }
         s_event::Inventory(inv_reader) => {
-            let inv = inv_reader.unwrap();
             let mut items = Vec::new();
-            for item in inv.into_iter() {
-                let iwhich = item.which();
-                if let Err(err) = iwhich {
-                    return ServerEvent::Error(ErrorData { msg: format!("Read invalid Server Event packet! Error: {}", err), disconnect: true });
+            for item in inv_reader.unwrap().into_iter() {
+                match read_item(item) {
+                    Ok(item) => items.push(item),
+                    Err(err) => return ServerEvent::Error(ErrorData { msg: format!("Read invalid Server Event packet! Error: {}", err), disconnect: true }),
                 }
-                let w = iwhich.unwrap();
-                let mut damage: Option<u32> = None;
-                let mut defense: Option<u32> = None;
-
-                match w {
-                    packet_capnp::item::Which::Damage(i) => damage = Some(i),
-                    packet_capnp::item::Which::Defense(i) => defense = Some(i),
-                }
-
-                items.push(ItemData {
-                    name: item.get_name().unwrap().to_string(),
-                    level: item.get_level(),
-                    itype: item.get_itype(),
-                    rarity: item.get_rarity(),
-                    damage, defense
-                });
             }
-
-            ServerEvent::Inventory(items)
+            // servers from before inventory requests do not send the count or capacity
+            let count = items.len() as u32;
+            ServerEvent::Inventory(InventoryData { items, count, capacity: count })
+        }
+        s_event::InventoryPage(inv_reader) => {
+            match read_inventory(inv_reader.unwrap()) {
+                Ok(inventory) => ServerEvent::Inventory(inventory),
+                Err(err) => ServerEvent::Error(ErrorData { msg: format!("Read invalid Server Event packet! Error: {}", err), disconnect: true }),
+            }
+        }
+        s_event::Auctions(auctions_reader) => {
+            match read_auctions(auctions_reader.unwrap()) {
+                Ok(auctions) => ServerEvent::Auctions(auctions),
+                Err(err) => ServerEvent::Error(ErrorData { msg: format!("Read invalid Server Event packet! Error: {}", err), disconnect: true }),
+            }
+        }
+        s_event::Bank(bank_reader) => {
+            match read_inventory(bank_reader.unwrap()) {
+                Ok(bank) => ServerEvent::Bank(bank),
+                Err(err) => ServerEvent::Error(ErrorData { msg: format!("Read invalid Server Event packet! Error: {}", err), disconnect: true }),
+            }
         }
         s_event::ItemView(item_reader) => {
             let raw_id = item_reader.unwrap();
@@ -396,6 +985,9 @@ pub fn read_server_event(mut stream: &TcpStream) -> ServerEvent {
                 level: raw_id.get_level(),
                 itype: raw_id.get_itype(),
                 rarity: raw_id.get_rarity(),
+                ability: raw_id.get_ability().unwrap().to_string(),
+                upgrades: raw_id.get_upgrades(),
+                equipped: raw_id.get_equipped(),
                 defense, damage
             };
 
@@ -408,5 +1000,137 @@ pub fn read_server_event(mut stream: &TcpStream) -> ServerEvent {
                     disconnect: err.get_disconnect()
                 })
         }
+        s_event::Chat(chat_reader) => {
+            let chat = chat_reader.unwrap();
+            ServerEvent::Chat(ChatData {
+                channel: ChatChannel::from(chat.get_channel()),
+                sender: chat.get_sender().unwrap().to_string(),
+                target: chat.get_target().unwrap().to_string(),
+                message: chat.get_message().unwrap().to_string(),
+            })
+        }
+        s_event::Broadcast(broadcast_reader) => {
+            let broadcast = broadcast_reader.unwrap();
+            ServerEvent::Broadcast(BroadcastData {
+                kind: BroadcastKind::from(broadcast.get_kind()),
+                message: broadcast.get_message().unwrap().to_string(),
+            })
+        }
+        s_event::Party(party_reader) => {
+            let mut members = Vec::new();
+            for m in party_reader.unwrap().into_iter() {
+                members.push(PartyMemberData {
+                    name: m.get_name().unwrap().to_string(),
+                    level: m.get_level(),
+                    health: m.get_health(),
+                    max_health: m.get_max_health(),
+                    leader: m.get_leader(),
+                });
+            }
+            ServerEvent::Party(members)
+        }
+        s_event::Achievement(achievement_reader) => {
+            ServerEvent::Achievement(read_achievement(achievement_reader.unwrap()))
+        }
+        s_event::Achievements(list_reader) => {
+            ServerEvent::Achievements(list_reader.unwrap().iter().map(read_achievement).collect())
+        }
+        s_event::QuestOffer(quest_reader) => {
+            ServerEvent::QuestOffer(read_quest(quest_reader.unwrap()))
+        }
+        s_event::Quests(list_reader) => {
+            ServerEvent::Quests(list_reader.unwrap().iter().map(read_quest).collect())
+        }
+        s_event::Consumables(list_reader) => {
+            ServerEvent::Consumables(list_reader.unwrap().iter().map(read_consumable).collect())
+        }
+        s_event::Mail(list_reader) => {
+            match list_reader.unwrap().iter().map(read_mail).collect() {
+                Ok(mailbox) => ServerEvent::Mail(mailbox),
+                Err(err) => ServerEvent::Error(ErrorData { msg: format!("Read invalid Server Event packet! Error: {}", err), disconnect: true }),
+            }
+        }
+        s_event::Guild(guild_reader) => {
+            let guild = guild_reader.unwrap();
+            ServerEvent::Guild(GuildData {
+                name: guild.get_name().unwrap().to_string(),
+                members: guild.get_members().unwrap().iter().map(|m| GuildMemberData {
+                    name: m.get_name().unwrap().to_string(),
+                    rank: GuildRank::from(m.get_rank()),
+                    level: m.get_level(),
+                    online: m.get_online(),
+                }).collect(),
+                rank: GuildRank::from(guild.get_rank()),
+                invites: guild.get_invites().unwrap().iter().map(|i| i.unwrap().to_string()).collect(),
+            })
+        }
+        s_event::Skills(tree_reader) => {
+            let tree = tree_reader.unwrap();
+            ServerEvent::Skills(SkillTreeData {
+                class: tree.get_class().unwrap().to_string(),
+                points: tree.get_points(),
+                skills: tree.get_skills().unwrap().iter().map(|s| SkillData {
+                    name: s.get_name().unwrap().to_string(),
+                    description: s.get_description().unwrap().to_string(),
+                    active: s.get_active(),
+                    rank: s.get_rank(),
+                    max_rank: s.get_max_rank(),
+                    level: s.get_level(),
+                    requires: s.get_requires().unwrap().to_string(),
+                    cooldown: s.get_cooldown(),
+                }).collect(),
+                classes: tree.get_classes().unwrap().iter().map(|c| c.unwrap().to_string()).collect(),
+            })
+        }
+        s_event::Bestiary(list_reader) => {
+            ServerEvent::Bestiary(list_reader.unwrap().iter().map(|e| BestiaryEntryData {
+                race: e.get_race().unwrap().to_string(),
+                description: e.get_description().unwrap().to_string(),
+                unlocked: e.get_unlocked(),
+                kills: e.get_kills(),
+                min_level: e.get_min_level(),
+                max_level: e.get_max_level(),
+                ability: e.get_ability().unwrap().to_string(),
+                loot: e.get_loot().unwrap().iter().map(|l| l.unwrap().to_string()).collect(),
+            }).collect())
+        }
+        s_event::GuildBank(bank_reader) => {
+            match read_inventory(bank_reader.unwrap()) {
+                Ok(bank) => ServerEvent::GuildBank(bank),
+                Err(err) => ServerEvent::Error(ErrorData { msg: format!("Read invalid Server Event packet! Error: {}", err), disconnect: true }),
+            }
+        }
+        s_event::Shop(list_reader) => {
+            ServerEvent::Shop(list_reader.unwrap().iter().map(read_consumable).collect())
+        }
+        s_event::Crafting(crafting_reader) => {
+            let crafting = crafting_reader.unwrap();
+            ServerEvent::Crafting(CraftingData {
+                recipes: crafting.get_recipes().unwrap().iter().map(|r| RecipeData {
+                    name: r.get_name().unwrap().to_string(),
+                    result: r.get_result().unwrap().to_string(),
+                    cost: r.get_cost().unwrap().to_string(),
+                    craftable: r.get_craftable(),
+                }).collect(),
+                materials: crafting.get_materials().unwrap().iter().map(|m| MaterialData {
+                    name: m.get_name().unwrap().to_string(),
+                    quantity: m.get_quantity(),
+                }).collect(),
+            })
+        }
+        s_event::Leaderboard(lb_reader) => {
+            let lb = lb_reader.unwrap();
+            let entries = lb.get_entries().unwrap().iter().map(|e| LeaderboardEntryData {
+                rank: e.get_rank(),
+                name: e.get_name().unwrap().to_string(),
+                value: e.get_value(),
+            }).collect();
+            ServerEvent::Leaderboard(LeaderboardData {
+                category: LeaderboardCategory::from(lb.get_category()),
+                page: lb.get_page(),
+                pages: lb.get_pages(),
+                entries,
+            })
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,66 @@
+/// What a player wants to do with their skills
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SkillActionKind {
+    List, Learn, Use, Reset, Class,
+}
+
+impl Into<u32> for SkillActionKind {
+    fn into(self) -> u32 {
+        match self {
+            Self::List  => 0,
+            Self::Learn => 1,
+            Self::Use   => 2,
+            Self::Reset => 3,
+            Self::Class => 4,
+        }
+    }
+}
+
+impl From<u32> for SkillActionKind {
+    fn from(x: u32) -> Self {
+        match x {
+            1 => Self::Learn,
+            2 => Self::Use,
+            3 => Self::Reset,
+            4 => Self::Class,
+            _ => Self::List,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SkillActionData {
+    pub kind: SkillActionKind,
+    /// the skill to learn or use, or the class to pick, empty otherwise
+    pub skill: String,
+}
+
+/// A skill in the player's skill tree
+#[derive(Clone, Debug)]
+pub struct SkillData {
+    pub name: String,
+    pub description: String,
+    /// if it is used in fights, otherwise it is always on
+    pub active: bool,
+    /// how many points the player spent on it
+    pub rank: u32,
+    pub max_rank: u32,
+    /// the level needed to learn it
+    pub level: u32,
+    /// the name of the skill that needs a point in it first, empty if none
+    pub requires: String,
+    /// the rounds before an active skill can be used again
+    pub cooldown: u32,
+}
+
+/// The player's class and every skill they can learn
+#[derive(Clone, Debug)]
+pub struct SkillTreeData {
+    /// empty if the player has not picked a class
+    pub class: String,
+    /// the skill points the player has not spent yet
+    pub points: u32,
+    pub skills: Vec<SkillData>,
+    /// the names of every class
+    pub classes: Vec<String>,
+}
@@ -0,0 +1,66 @@
+/// A timed effect on someone in a fight
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum StatusKind {
+    /// loses a set amount of health every round
+    Poison,
+    /// loses a percent of their max health every round
+    Bleed,
+    /// loses their turns
+    Stun,
+    /// deals a percent less damage
+    Weaken,
+    /// deals a percent more damage
+    Strength,
+    /// takes a percent less damage
+    Shield,
+}
+
+impl Into<u32> for StatusKind {
+    fn into(self) -> u32 {
+        match self {
+            Self::Poison   => 0,
+            Self::Bleed    => 1,
+            Self::Stun     => 2,
+            Self::Weaken   => 3,
+            Self::Strength => 4,
+            Self::Shield   => 5,
+        }
+    }
+}
+
+impl From<u32> for StatusKind {
+    fn from(x: u32) -> Self {
+        match x {
+            1 => Self::Bleed,
+            2 => Self::Stun,
+            3 => Self::Weaken,
+            4 => Self::Strength,
+            5 => Self::Shield,
+            _ => Self::Poison,
+        }
+    }
+}
+
+impl ToString for StatusKind {
+    fn to_string(&self) -> String {
+        match self {
+            Self::Poison => "Poison",
+            Self::Bleed => "Bleed",
+            Self::Stun => "Stun",
+            Self::Weaken => "Weaken",
+            Self::Strength => "Strength",
+            Self::Shield => "Shield",
+        }.to_string()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct StatusEffectData {
+    /// the username of the player it is on, empty if it is on the enemy
+    pub target: String,
+    pub kind: StatusKind,
+    /// the damage or percent of the effect, unused for stuns
+    pub amount: u32,
+    /// the rounds it lasts for
+    pub turns: u32,
+}
@@ -0,0 +1,280 @@
+use std::str::FromStr;
+use log::{error, info, warn};
+use serde::Deserialize;
+use uuid::Uuid;
+use snd_network_lib::achievement_data::AchievementData;
+use snd_network_lib::systime;
+use crate::config::ServerConfig;
+use crate::data::read_data_file;
+use crate::database::{Database, PlayerValueDB};
+use crate::game_event::GameEvent;
+use crate::item::ItemRarity;
+use crate::session::SessionMessage;
+use crate::state::ServerState;
+
+const LOG_TARGET: &str = "achievement";
+
+/// The name of the achievements file in the data folder
+const ACHIEVEMENTS_FILE: &str = "achievements.toml";
+
+/// The contents written to a new achievements file when one does not exist yet
+pub const DEFAULT_ACHIEVEMENTS: &str = r#"# Every achievement needs a unique id, a name and a description, and one of these conditions:
+#   stat and at_least: unlocks once the player's stat reaches the value.
+#     stat is one of steps, level, kills, gold or duel_wins
+#   event: unlocks the first time it happens, one of find_item, flee, defeat_boss or win_duel.
+#     find_item can also have a rarity (common, rare, epic or legendary) to only count items of that rarity
+# Changing the id of an achievement will lock it again for every player who unlocked it
+
+[[achievement]]
+id = "first_kill"
+name = "First Blood"
+description = "Defeat your first enemy"
+stat = "kills"
+at_least = 1
+
+[[achievement]]
+id = "first_flee"
+name = "Live to Fight Another Day"
+description = "Flee from a fight"
+event = "flee"
+
+[[achievement]]
+id = "first_legendary"
+name = "Legendary"
+description = "Find your first Legendary item"
+event = "find_item"
+rarity = "legendary"
+
+[[achievement]]
+id = "boss_slayer"
+name = "Boss Slayer"
+description = "Defeat a boss"
+event = "defeat_boss"
+
+[[achievement]]
+id = "first_duel_win"
+name = "Duelist"
+description = "Win a duel against another player"
+event = "win_duel"
+
+[[achievement]]
+id = "level_25"
+name = "Veteran"
+description = "Reach level 25"
+stat = "level"
+at_least = 25
+
+[[achievement]]
+id = "steps_10000"
+name = "Wanderer"
+description = "Take 10,000 steps"
+stat = "steps"
+at_least = 10000
+"#;
+
+/// A value kept for every player that an achievement can require
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Stat {
+    Steps, Level, Kills, Gold, DuelWins,
+}
+
+impl Stat {
+    fn column(&self) -> PlayerValueDB {
+        match self {
+            Self::Steps => PlayerValueDB::Steps,
+            Self::Level => PlayerValueDB::Level,
+            Self::Kills => PlayerValueDB::Kills,
+            Self::Gold => PlayerValueDB::Gold,
+            Self::DuelWins => PlayerValueDB::DuelWins,
+        }
+    }
+
+    /// if the event can change the stat, so stats are only read when they could have changed
+    fn changed_by(&self, event: &GameEvent) -> bool {
+        match self {
+            Self::Steps => *event == GameEvent::Step,
+            Self::Level => matches!(event, GameEvent::Step | GameEvent::Kill { .. }),
+            Self::Kills => matches!(event, GameEvent::Kill { .. }),
+            Self::Gold => matches!(event, GameEvent::Kill { .. } | GameEvent::DuelWin),
+            Self::DuelWins => *event == GameEvent::DuelWin,
+        }
+    }
+}
+
+impl FromStr for Stat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "steps" => Ok(Self::Steps),
+            "level" => Ok(Self::Level),
+            "kills" => Ok(Self::Kills),
+            "gold" => Ok(Self::Gold),
+            "duel_wins" => Ok(Self::DuelWins),
+            _ => Err(()),
+        }
+    }
+}
+
+/// What has to happen for an achievement to unlock
+#[derive(Copy, Clone, Debug)]
+enum Condition {
+    /// a stat reaches at least the value
+    Stat(Stat, u64),
+    /// finding an item, only of the rarity if there is one
+    FindItem(Option<ItemRarity>),
+    Flee,
+    DefeatBoss,
+    WinDuel,
+}
+
+impl Condition {
+    /// if the event could unlock an achievement with this condition
+    fn triggered_by(&self, event: &GameEvent) -> bool {
+        match self {
+            Self::Stat(stat, _) => stat.changed_by(event),
+            Self::FindItem(None) => matches!(event, GameEvent::FindItem(..)),
+            Self::FindItem(Some(rarity)) => matches!(event, GameEvent::FindItem(_, r) if r == rarity),
+            Self::Flee => *event == GameEvent::Flee,
+            Self::DefeatBoss => matches!(event, GameEvent::Kill { boss: true, .. }),
+            Self::WinDuel => *event == GameEvent::DuelWin,
+        }
+    }
+
+    /// if the condition is met, only called for events that trigger it
+    fn met(&self, db: &Database, uuid: &Uuid) -> bool {
+        match self {
+            Self::Stat(stat, at_least) => db.get_player_value(uuid, stat.column())
+                .and_then(|v| v.parse::<u64>().ok())
+                .map_or(false, |v| v >= *at_least),
+            _ => true,
+        }
+    }
+}
+
+pub struct Achievement {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    condition: Condition,
+}
+
+impl Achievement {
+    /// the achievement as sent to a player, unlocked at the time if there is one
+    fn as_data(&self, unlocked: Option<u64>) -> AchievementData {
+        AchievementData {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            description: self.description.clone(),
+            unlocked: unlocked.is_some(),
+            time: unlocked.unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct AchievementFile {
+    achievement: Option<Vec<RawAchievement>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawAchievement {
+    id: String,
+    name: String,
+    description: String,
+    stat: Option<String>,
+    at_least: Option<u64>,
+    event: Option<String>,
+    rarity: Option<String>,
+}
+
+impl RawAchievement {
+    fn condition(&self) -> Result<Condition, String> {
+        match (&self.stat, &self.event) {
+            (Some(stat), None) => {
+                let stat = Stat::from_str(stat.as_str()).map_err(|_| format!("unknown stat '{}'", stat))?;
+                let at_least = self.at_least.ok_or(format!("a stat needs an at_least value"))?;
+                Ok(Condition::Stat(stat, at_least))
+            }
+            (None, Some(event)) => match event.to_ascii_lowercase().as_str() {
+                "find_item" => match &self.rarity {
+                    Some(rarity) => ItemRarity::from_str(rarity.as_str())
+                        .map(|r| Condition::FindItem(Some(r)))
+                        .map_err(|_| format!("unknown rarity '{}'", rarity)),
+                    None => Ok(Condition::FindItem(None)),
+                },
+                "flee" => Ok(Condition::Flee),
+                "defeat_boss" => Ok(Condition::DefeatBoss),
+                "win_duel" => Ok(Condition::WinDuel),
+                _ => Err(format!("unknown event '{}'", event)),
+            },
+            _ => Err(format!("it needs either a stat or an event")),
+        }
+    }
+}
+
+/// Every achievement players can unlock, read from the data folder when the server starts
+pub struct AchievementList {
+    achievements: Vec<Achievement>,
+}
+
+impl AchievementList {
+    pub fn load(config: &ServerConfig) -> Self {
+        let raw = read_data_file(config, ACHIEVEMENTS_FILE, DEFAULT_ACHIEVEMENTS);
+        let file = toml::from_str::<AchievementFile>(raw.as_str()).unwrap_or_else(|e| {
+            error!(target:LOG_TARGET, "Invalid {}, using the default achievements: {}", ACHIEVEMENTS_FILE, e);
+            toml::from_str(DEFAULT_ACHIEVEMENTS).expect("The default achievements are invalid")
+        });
+
+        let mut achievements: Vec<Achievement> = Vec::new();
+        for raw in file.achievement.unwrap_or_default() {
+            if achievements.iter().any(|a| a.id == raw.id) {
+                warn!(target:LOG_TARGET, "Skipping achievement '{}': the id is used more than once", raw.id);
+                continue;
+            }
+            match raw.condition() {
+                Ok(condition) => achievements.push(Achievement {
+                    id: raw.id, name: raw.name, description: raw.description, condition,
+                }),
+                Err(e) => warn!(target:LOG_TARGET, "Skipping achievement '{}': {}", raw.id, e),
+            }
+        }
+        info!(target:LOG_TARGET, "Loaded {} achievements", achievements.len());
+        Self { achievements }
+    }
+
+    /// every achievement and whether the player has unlocked it
+    pub fn list(&self, db: &Database, uuid: &Uuid) -> Vec<AchievementData> {
+        let unlocked = db.get_player_achievements(uuid);
+        self.achievements.iter()
+            .map(|a| a.as_data(unlocked.iter().find(|(id, _)| *id == a.id).map(|(_, time)| *time)))
+            .collect()
+    }
+}
+
+/// Unlocks any achievements the event earned the player and tells them about it
+pub fn trigger(state: &ServerState, uuid: &Uuid, event: &GameEvent) {
+    let candidates = state.achievements.achievements.iter()
+        .filter(|a| a.condition.triggered_by(event))
+        .collect::<Vec<&Achievement>>();
+    if candidates.is_empty() {
+        return;
+    }
+
+    let (unlocked, username) = {
+        let db = state.db.lock().unwrap();
+        let have = db.get_player_achievements(uuid);
+        let unlocked = candidates.into_iter()
+            .filter(|a| !have.iter().any(|(id, _)| *id == a.id))
+            .filter(|a| a.condition.met(&db, uuid) && db.unlock_achievement(uuid, a.id.as_str()))
+            .collect::<Vec<&Achievement>>();
+        (unlocked, db.get_player_value(uuid, PlayerValueDB::Username).unwrap_or_default())
+    };
+
+    let now = systime().as_secs();
+    let sessions = state.sessions.lock().unwrap();
+    for a in unlocked {
+        info!(target:LOG_TARGET, "{} unlocked the achievement '{}'", username, a.id);
+        sessions.send(uuid, SessionMessage::Achievement(a.as_data(Some(now))));
+    }
+}
@@ -0,0 +1,353 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use log::{error, info};
+use uuid::Uuid;
+use snd_network_lib::auction_data::{AuctionActionData, AuctionListData, AuctionSearchData, ListingData};
+use snd_network_lib::systime;
+use crate::config::ServerConfig;
+use crate::database::{Database, PlayerValueDB};
+use crate::inventory;
+use crate::mail;
+use crate::session::SessionMessage;
+use crate::state::ServerState;
+
+const LOG_TARGET: &str = "auction";
+/// the most listings sent for one search
+const MAX_RESULTS: usize = 50;
+
+/// An item for sale in the auction house, the item and the highest bid are held in an escrow until it ends
+#[derive(Clone, Debug)]
+pub struct Listing {
+    /// the number players use to pick the listing
+    pub number: u32,
+    /// the escrow holding the item and the highest bid
+    pub id: Uuid,
+    pub seller: Uuid,
+    pub item: Uuid,
+    /// the lowest first bid, 0 if the item can only be bought out
+    pub start_bid: u32,
+    /// the highest bid, 0 if nobody has bid yet
+    pub bid: u32,
+    pub bidder: Option<Uuid>,
+    pub buyout: u32,
+    /// when the listing ends, in seconds since the unix epoch
+    pub expires: u64,
+}
+
+impl Listing {
+    /// the least the next bid can be, 0 if the item can only be bought out
+    pub fn min_bid(&self, config: &ServerConfig) -> u32 {
+        if self.start_bid == 0 {
+            return 0;
+        }
+        match self.bidder {
+            Some(_) => {
+                let raise = (self.bid as u64 * config.auction_min_raise_percent as u64 / 100).max(1);
+                (self.bid as u64 + raise).min(u32::MAX as u64) as u32
+            }
+            None => self.start_bid,
+        }
+    }
+
+    fn ended(&self) -> bool {
+        self.expires <= systime().as_secs()
+    }
+}
+
+/// the gold a seller gets for a sale after the fee
+fn payout(config: &ServerConfig, price: u32) -> u32 {
+    let fee = price as u64 * config.auction_fee_percent.min(100) as u64 / 100;
+    price - fee as u32
+}
+
+fn item_name(db: &Database, listing: &Listing) -> String {
+    db.get_item(&listing.item).map(|i| i.name).unwrap_or(format!("an item"))
+}
+
+/// Players can only use the auction house outside of fights.
+/// Returns an error to show the player if they can not reach it
+pub fn check_access(state: &ServerState, uuid: &Uuid) -> Result<(), String> {
+    if state.encounters.lock().unwrap().in_fight(uuid) || state.duels.lock().unwrap().in_duel(uuid) {
+        return Err(format!("You can not visit the auction house in the middle of a fight"));
+    }
+    Ok(())
+}
+
+/// the listings that match a search, as the player searching sees them
+pub fn search(db: &Database, config: &ServerConfig, uuid: &Uuid, search: &AuctionSearchData) -> AuctionListData {
+    let listings = db.search_auctions(search, uuid);
+    let now = systime().as_secs();
+    AuctionListData {
+        total: listings.len() as u32,
+        listings: listings.iter().take(MAX_RESULTS).filter_map(|l| {
+            Some(ListingData {
+                number: l.number,
+                item: db.get_item(&l.item)?.as_data(),
+                seller: db.get_player_value(&l.seller, PlayerValueDB::Username).unwrap_or_default(),
+                bid: l.bid,
+                min_bid: l.min_bid(config),
+                buyout: l.buyout,
+                time_left: l.expires.saturating_sub(now),
+                yours: l.seller == *uuid,
+                winning: l.bidder == Some(*uuid),
+            })
+        }).collect(),
+    }
+}
+
+/// Puts one of the player's items up for sale.
+/// Returns what happened to show the player, or an error if they can not sell it
+pub fn sell(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str, action: &AuctionActionData) -> Result<String, String> {
+    check_access(state, uuid)?;
+    let (item_name, buyout, start_bid, hours) = (action.item.as_str(), action.price, action.start_bid, action.hours);
+    if buyout == 0 {
+        return Err(format!("You must set a buyout price"));
+    }
+    if start_bid >= buyout {
+        return Err(format!("The starting bid must be lower than the buyout price"));
+    }
+    if hours > config.auction_max_hours {
+        return Err(format!("Listings can last at most {} hours", config.auction_max_hours));
+    }
+    let hours = if hours == 0 { config.auction_default_hours } else { hours };
+
+    let db = state.db.lock().unwrap();
+    let item = db.item_uuid_from_name(item_name.replace("'", ""), uuid)
+        .ok_or(format!("You do not have an item called '{}'", item_name))?;
+    if db.count_player_auctions(uuid) >= config.auction_max_listings {
+        return Err(format!("You can only have {} items for sale at once", config.auction_max_listings));
+    }
+    let listing = Listing {
+        number: 0,
+        id: Uuid::new_v4(),
+        seller: uuid.clone(),
+        item, start_bid, buyout,
+        bid: 0,
+        bidder: None,
+        expires: systime().as_secs() + hours as u64 * 60 * 60,
+    };
+    if !db.list_auction(&listing) {
+        return Err(format!("Failed to list '{}'", item_name));
+    }
+    info!(target:LOG_TARGET, "{} listed '{}' for {} gold (bids from {}) for {} hours", username, item_name, buyout, start_bid, hours);
+    Ok(format!("You put '{}' up for sale for {} gold for {} hours", item_name, buyout, hours))
+}
+
+/// Bids on a listing, holding the gold until the player is outbid or the listing ends.
+/// A bid of at least the buyout price buys the item right away.
+/// Returns what happened to show the player, or an error if they can not bid
+pub fn bid(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str, number: u32, amount: u32) -> Result<String, String> {
+    check_access(state, uuid)?;
+    let db = state.db.lock().unwrap();
+    let listing = db.get_auction(number).filter(|l| !l.ended())
+        .ok_or(format!("There is no listing #{}", number))?;
+    if listing.seller == *uuid {
+        return Err(format!("You can not bid on your own listing"));
+    }
+    if listing.start_bid == 0 {
+        return Err(format!("Listing #{} can only be bought out, for {} gold", number, listing.buyout));
+    }
+    if amount >= listing.buyout {
+        drop(db);
+        return buyout(state, config, uuid, username, number);
+    }
+    if listing.bidder == Some(*uuid) {
+        return Err(format!("You already have the highest bid on listing #{}", number));
+    }
+    let min = listing.min_bid(config);
+    if amount < min {
+        return Err(format!("You must bid at least {} gold on listing #{}", min, number));
+    }
+    let gold = db.get_player_gold(uuid).unwrap_or(0);
+    if gold < amount {
+        return Err(format!("You only have {} gold", gold));
+    }
+    if !db.bid_auction(&listing, uuid, amount) {
+        return Err(format!("Failed to bid on listing #{}", number));
+    }
+
+    let name = item_name(&db, &listing);
+    if let Some(outbid) = listing.bidder {
+        let sessions = state.sessions.lock().unwrap();
+        mail::notify(&db, &sessions, &outbid,
+                     format!("You were outbid on '{}' (#{}), your {} gold was returned", name, number, listing.bid));
+        sessions.send(&outbid, SessionMessage::StatsChanged);
+    }
+    info!(target:LOG_TARGET, "{} bid {} gold on listing #{}", username, amount, number);
+    Ok(format!("You bid {} gold on '{}', it is held until you are outbid or the listing ends", amount, name))
+}
+
+/// Buys a listing right away for its buyout price.
+/// Returns what happened to show the player, or an error if they can not buy it
+pub fn buyout(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str, number: u32) -> Result<String, String> {
+    check_access(state, uuid)?;
+    let db = state.db.lock().unwrap();
+    let listing = db.get_auction(number).filter(|l| !l.ended())
+        .ok_or(format!("There is no listing #{}", number))?;
+    if listing.seller == *uuid {
+        return Err(format!("You can not buy your own listing, use 'ah cancel {}' to take it back", number));
+    }
+    let gold = db.get_player_gold(uuid).unwrap_or(0);
+    // a player who has the highest bid gets it back, so it counts towards the price
+    let held = if listing.bidder == Some(*uuid) { listing.bid } else { 0 };
+    if gold.saturating_add(held) < listing.buyout {
+        return Err(format!("Listing #{} costs {} gold but you only have {}", number, listing.buyout, gold));
+    }
+    if inventory::is_full(&db, config, uuid) {
+        return Err(format!("Your inventory is full, make room before buying anything"));
+    }
+    let name = item_name(&db, &listing);
+    let paid = payout(config, listing.buyout);
+    if !db.buyout_auction(&listing, uuid, paid) {
+        return Err(format!("Failed to buy listing #{}", number));
+    }
+
+    let sessions = state.sessions.lock().unwrap();
+    if let Some(outbid) = listing.bidder.filter(|b| b != uuid) {
+        mail::notify(&db, &sessions, &outbid,
+                     format!("'{}' (#{}) was bought out, your {} gold was returned", name, number, listing.bid));
+        sessions.send(&outbid, SessionMessage::StatsChanged);
+    }
+    mail::notify(&db, &sessions, &listing.seller,
+                 format!("{} bought your '{}' for {} gold, you got {} after the fee", username, name, listing.buyout, paid));
+    sessions.send(&listing.seller, SessionMessage::StatsChanged);
+    info!(target:LOG_TARGET, "{} bought out listing #{} for {} gold", username, number, listing.buyout);
+    Ok(format!("You bought '{}' for {} gold", name, listing.buyout))
+}
+
+/// Takes one of the player's listings back, only before anyone has bid on it.
+/// Returns what happened to show the player, or an error if they can not cancel it
+pub fn cancel(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str, number: u32) -> Result<String, String> {
+    check_access(state, uuid)?;
+    let db = state.db.lock().unwrap();
+    let listing = db.get_auction(number).filter(|l| l.seller == *uuid && !l.ended())
+        .ok_or(format!("You do not have a listing #{}", number))?;
+    if listing.bidder.is_some() {
+        return Err(format!("Someone has already bid on listing #{}", number));
+    }
+    if inventory::is_full(&db, config, uuid) {
+        return Err(format!("Your inventory is full, make room before taking listing #{} back", number));
+    }
+    let name = item_name(&db, &listing);
+    if !db.return_auction(&listing) {
+        return Err(format!("Failed to cancel listing #{}", number));
+    }
+    info!(target:LOG_TARGET, "{} cancelled listing #{}", username, number);
+    Ok(format!("You took '{}' off the auction house", name))
+}
+
+/// Settles every listing that ran out of time, selling it to the highest bidder or giving it back to the seller.
+/// Items are delivered even if the player is offline or their inventory is full, offline players are told by mail
+pub fn settle_ended(state: &ServerState, config: &ServerConfig) {
+    let db = state.db.lock().unwrap();
+    let sessions = state.sessions.lock().unwrap();
+    for listing in db.get_ended_auctions() {
+        let name = item_name(&db, &listing);
+        match listing.bidder {
+            Some(bidder) => {
+                let paid = payout(config, listing.bid);
+                if !db.settle_auction(&listing, paid) {
+                    error!(target:LOG_TARGET, "Failed to settle listing #{}", listing.number);
+                    continue;
+                }
+                mail::notify(&db, &sessions, &bidder, format!("You won '{}' (#{}) for {} gold", name, listing.number, listing.bid));
+                mail::notify(&db, &sessions, &listing.seller,
+                             format!("Your '{}' sold for {} gold, you got {} after the fee", name, listing.bid, paid));
+                sessions.send(&bidder, SessionMessage::StatsChanged);
+                sessions.send(&listing.seller, SessionMessage::StatsChanged);
+                info!(target:LOG_TARGET, "Listing #{} sold for {} gold", listing.number, listing.bid);
+            }
+            None => {
+                if !db.return_auction(&listing) {
+                    error!(target:LOG_TARGET, "Failed to return listing #{}", listing.number);
+                    continue;
+                }
+                mail::notify(&db, &sessions, &listing.seller, format!("Nobody bought your '{}', it was returned to you", name));
+                info!(target:LOG_TARGET, "Listing #{} ended without a sale", listing.number);
+            }
+        }
+    }
+}
+
+/// Starts settling listings that ended every `check_seconds` on a new thread, until the server shuts down
+pub fn start_expiry_task(state: ServerState, config: Arc<ServerConfig>, terminate: Arc<AtomicBool>) {
+    let spawned = thread::Builder::new()
+        .name(format!("auction"))
+        .spawn(move || {
+            let mut last = Instant::now();
+            while !terminate.load(Ordering::SeqCst) {
+                // wake up often so shutting down is not held up
+                thread::sleep(Duration::from_millis(250));
+                if last.elapsed() >= Duration::from_secs(config.auction_check_seconds) {
+                    settle_ended(&state, &config);
+                    last = Instant::now();
+                }
+            }
+        });
+
+    if let Err(e) = spawned {
+        error!(target:LOG_TARGET, "Failed to start the auction expiry task: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing(start_bid: u32, bid: u32, bidder: Option<Uuid>) -> Listing {
+        Listing {
+            number: 1,
+            id: Uuid::new_v4(),
+            seller: Uuid::new_v4(),
+            item: Uuid::new_v4(),
+            start_bid, bid, bidder,
+            buyout: u32::MAX,
+            expires: 0,
+        }
+    }
+
+    #[test]
+    fn buyout_only_listings_have_no_min_bid() {
+        let config = ServerConfig::default();
+        assert_eq!(listing(0, 0, None).min_bid(&config), 0);
+    }
+
+    #[test]
+    fn first_bid_is_the_start_bid() {
+        let config = ServerConfig::default();
+        assert_eq!(listing(40, 0, None).min_bid(&config), 40);
+    }
+
+    #[test]
+    fn next_bid_raises_by_the_min_raise() {
+        let config = ServerConfig { auction_min_raise_percent: 10, ..ServerConfig::default() };
+        assert_eq!(listing(40, 200, Some(Uuid::new_v4())).min_bid(&config), 220);
+        // small bids still have to go up by at least 1
+        assert_eq!(listing(1, 5, Some(Uuid::new_v4())).min_bid(&config), 6);
+    }
+
+    #[test]
+    fn min_bid_does_not_overflow() {
+        let config = ServerConfig { auction_min_raise_percent: 1000, ..ServerConfig::default() };
+        assert_eq!(listing(1, u32::MAX - 10, Some(Uuid::new_v4())).min_bid(&config), u32::MAX);
+        assert_eq!(listing(1, u32::MAX, Some(Uuid::new_v4())).min_bid(&config), u32::MAX);
+    }
+
+    #[test]
+    fn payout_takes_the_fee() {
+        let config = ServerConfig { auction_fee_percent: 5, ..ServerConfig::default() };
+        assert_eq!(payout(&config, 1000), 950);
+        assert_eq!(payout(&config, 10), 10);
+        assert_eq!(payout(&config, 0), 0);
+    }
+
+    #[test]
+    fn payout_does_not_overflow() {
+        let config = ServerConfig { auction_fee_percent: 5, ..ServerConfig::default() };
+        assert_eq!(payout(&config, u32::MAX), u32::MAX - (u32::MAX as u64 * 5 / 100) as u32);
+        let config = ServerConfig { auction_fee_percent: 100, ..ServerConfig::default() };
+        assert_eq!(payout(&config, u32::MAX), 0);
+    }
+}
@@ -0,0 +1,72 @@
+use log::info;
+use uuid::Uuid;
+use snd_network_lib::inventory_data::InventoryData;
+use crate::config::ServerConfig;
+use crate::database::Database;
+use crate::inventory;
+use crate::state::ServerState;
+
+const LOG_TARGET: &str = "bank";
+
+/// Players can only use their bank outside of fights, in a town or at the merchant if the config allows it.
+/// Returns an error to show the player if they can not reach it
+pub fn check_access(state: &ServerState, config: &ServerConfig, uuid: &Uuid) -> Result<(), String> {
+    if state.encounters.lock().unwrap().in_fight(uuid) || state.duels.lock().unwrap().in_duel(uuid) {
+        return Err(format!("You can not reach your bank in the middle of a fight"));
+    }
+    if config.bank_at_merchant {
+        return Ok(());
+    }
+    let region = state.db.lock().unwrap().get_player_region(uuid).unwrap_or_default();
+    if !config.bank_towns.iter().any(|town| town.eq_ignore_ascii_case(region.as_str())) {
+        if config.bank_towns.is_empty() {
+            return Err(format!("There are no banks in this world"));
+        }
+        return Err(format!("There is no bank in {}, you can find one in {}", region, config.bank_towns.join(", ")));
+    }
+    Ok(())
+}
+
+/// the items in the player's bank
+pub fn view(db: &Database, config: &ServerConfig, uuid: &Uuid) -> InventoryData {
+    let items = db.get_bank_items(uuid);
+    InventoryData {
+        count: items.len() as u32,
+        items: items.iter().map(|i| i.as_data()).collect(),
+        capacity: config.bank_capacity,
+    }
+}
+
+/// Moves one of the player's items into their bank.
+/// Returns what happened to show the player, or an error if they can not deposit it
+pub fn deposit(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str, item_name: &str) -> Result<String, String> {
+    check_access(state, config, uuid)?;
+    let db = state.db.lock().unwrap();
+    let item = db.item_uuid_from_name(item_name.replace("'", ""), uuid)
+        .ok_or(format!("You do not have an item called '{}'", item_name))?;
+    if db.count_bank_items(uuid) >= config.bank_capacity {
+        return Err(format!("Your bank is full, it can only hold {} items", config.bank_capacity));
+    }
+    if !db.deposit_item(uuid, &item) {
+        return Err(format!("Failed to deposit '{}'", item_name));
+    }
+    info!(target:LOG_TARGET, "{} deposited '{}'", username, item_name);
+    Ok(format!("You put '{}' in your bank", item_name))
+}
+
+/// Moves an item from the player's bank back into their inventory.
+/// Returns what happened to show the player, or an error if they can not withdraw it
+pub fn withdraw(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str, item_name: &str) -> Result<String, String> {
+    check_access(state, config, uuid)?;
+    let db = state.db.lock().unwrap();
+    let item = db.bank_item_uuid_from_name(item_name.replace("'", ""), uuid)
+        .ok_or(format!("There is no item called '{}' in your bank", item_name))?;
+    if inventory::is_full(&db, config, uuid) {
+        return Err(format!("Your inventory is full, make room before taking '{}' out of your bank", item_name));
+    }
+    if !db.withdraw_item(uuid, &item) {
+        return Err(format!("Failed to withdraw '{}'", item_name));
+    }
+    info!(target:LOG_TARGET, "{} withdrew '{}'", username, item_name);
+    Ok(format!("You took '{}' out of your bank", item_name))
+}
@@ -0,0 +1,588 @@
+use std::collections::HashMap;
+use log::{error, info, warn};
+use rand::{Rng, thread_rng};
+use serde::Deserialize;
+use uuid::Uuid;
+use snd_network_lib::bestiary_data::BestiaryEntryData;
+use snd_network_lib::status_data::StatusKind;
+use crate::config::ServerConfig;
+use crate::consumable::ConsumableList;
+use crate::crafting::CraftingList;
+use crate::data::read_data_file;
+use crate::database::Database;
+use crate::enemy::EnemyAbility;
+
+const LOG_TARGET: &str = "bestiary";
+
+/// The name of the bestiary file in the data folder
+const BESTIARY_FILE: &str = "bestiary.toml";
+
+/// The contents written to a new bestiary file when one does not exist yet
+pub const DEFAULT_BESTIARY: &str = r#"# Every enemy needs a unique race, a description, the names it can have and its health and damage at level 1.
+# Optional settings:
+#   health_per_level / damage_per_level: what it gains for each level past 1, 10 and 2 if missing
+#   scaling: how fast the gains add up, one of:
+#     linear: the same every level, used if missing
+#     steep: each level adds 5% more than the last, for enemies that get much tougher later on
+#     gentle: each level adds a little less than the last
+#   min_level / max_level: the levels it is met at, it is only met outside of them if no other enemy can be
+#   weight: how likely it is to be met compared to the others, 10 if missing
+#   regions: the weight in some regions instead, e.g. { "Plains of Arenlok" = 20 }, 0 means it is never met there
+#   pattern: what it does each round in order, starting over at the end, every round is an attack if missing:
+#     attack: hits a random player
+#     heavy: hits for 50% more damage
+#     ability: hits and always uses its ability
+#     guard: does not attack but takes half damage until its next turn
+#   ability: used with some attacks, putting a status effect on the player it hits, or on itself for strength and shield.
+#     It needs a name, an effect (poison, bleed, stun, weaken, strength or shield), the rounds it lasts and the percent chance it is used.
+#     The amount is the percent of its damage poison deals each round and the percent for the other effects, stuns do not need one
+#   loot: drops on top of the usual loot, each has a consumable or material id, the percent chance and the amount, 1 if missing.
+#     Every player who helped defeat it rolls for each one
+# Bosses are a few levels higher with three times the health and use their ability twice as often.
+# Changing the race of an enemy hides it from every player's bestiary and breaks quests that ask for it
+
+[[enemy]]
+race = "Goblin"
+description = "A small and sneaky raider that coats its rusty blade in poison."
+names = ["Grubnak", "Snikkit", "Vex", "Dreg"]
+health = 25
+health_per_level = 9
+damage = 4
+max_level = 25
+weight = 12
+regions = { "Plains of Arenlok" = 16 }
+pattern = ["attack", "attack", "ability"]
+ability = { name = "Poisoned Blade", effect = "poison", amount = 40, turns = 3, chance = 30 }
+loot = [{ consumable = "minor_health_potion", chance = 15 }, { material = "leather", chance = 25 }]
+
+[[enemy]]
+race = "Wolf"
+description = "A hungry hunter of the plains whose bite leaves deep wounds."
+names = ["Fang", "Ash", "Howl", "Grey Maw"]
+health = 20
+health_per_level = 8
+damage = 6
+max_level = 20
+regions = { "Plains of Arenlok" = 14 }
+pattern = ["attack", "heavy", "attack"]
+ability = { name = "Savage Bite", effect = "bleed", amount = 5, turns = 3, chance = 30 }
+loot = [{ material = "leather", chance = 50, amount = 2 }]
+
+[[enemy]]
+race = "Bandit"
+description = "An outlaw who robs travellers on the roads and is not above a dirty trick."
+names = ["Morzul", "Karn", "Thassa", "Rook"]
+health = 30
+damage = 5
+min_level = 3
+pattern = ["attack", "attack", "ability"]
+ability = { name = "Dirty Trick", effect = "weaken", amount = 30, turns = 2, chance = 20 }
+loot = [{ consumable = "bread", chance = 20 }, { material = "scrap_metal", chance = 30 }]
+
+[[enemy]]
+race = "Skeleton"
+description = "The restless bones of a fallen soldier, its touch chills the blood."
+names = ["Ulgrim", "Rattle", "Osric", "Marrow"]
+health = 25
+health_per_level = 9
+damage = 6
+min_level = 5
+weight = 8
+pattern = ["attack", "guard", "heavy"]
+ability = { name = "Bone Chill", effect = "weaken", amount = 25, turns = 2, chance = 25 }
+loot = [{ material = "arcane_dust", chance = 30 }, { material = "scrap_metal", chance = 20 }]
+
+[[enemy]]
+race = "Orc"
+description = "A hulking warrior whose crushing blows can knock a fighter senseless."
+names = ["Skarr", "Grom", "Urzog", "Bruk"]
+health = 35
+health_per_level = 11
+damage = 6
+min_level = 8
+weight = 8
+pattern = ["attack", "attack", "heavy", "ability"]
+ability = { name = "Crushing Blow", effect = "stun", turns = 1, chance = 15 }
+loot = [{ consumable = "strength_elixir", chance = 10 }, { material = "scrap_metal", chance = 40, amount = 2 }]
+
+[[enemy]]
+race = "Troll"
+description = "A huge and slow brute that flies into a rage the longer a fight goes on."
+names = ["Thrak", "Gorm", "Mossback", "Old Knuckles"]
+health = 50
+health_per_level = 14
+damage = 5
+scaling = "steep"
+min_level = 12
+weight = 5
+pattern = ["attack", "guard", "heavy"]
+ability = { name = "Rage", effect = "strength", amount = 30, turns = 3, chance = 20 }
+loot = [{ consumable = "iron_skin_tonic", chance = 15 }, { material = "starlight_shard", chance = 10 }]
+"#;
+
+/// How an enemy's health and damage grow with its level
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Scaling {
+    Linear, Steep, Gentle,
+}
+
+impl Scaling {
+    /// how many levels of gains an enemy of a level gets
+    fn levels(&self, level: u32) -> u32 {
+        let n = level.saturating_sub(1);
+        match self {
+            Self::Linear => n,
+            Self::Steep => n.saturating_mul(20 + n) / 20,
+            Self::Gentle => (n as u64 * 40 / (40 + n as u64)) as u32,
+        }
+    }
+}
+
+/// What an enemy does on its turn, it goes through its pattern in order
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Move {
+    Attack, Heavy, Ability, Guard,
+}
+
+/// Something extra an enemy can drop, by the id and name of a consumable or material
+#[derive(Clone, Debug)]
+pub enum LootItem {
+    Consumable { id: String, name: String },
+    Material { id: String, name: String },
+}
+
+#[derive(Clone, Debug)]
+pub struct Loot {
+    pub item: LootItem,
+    /// the percent chance each player gets it
+    pub chance: u32,
+    pub amount: u32,
+}
+
+impl Loot {
+    /// e.g. `2 Leather (50%)`
+    fn describe(&self) -> String {
+        let name = match &self.item {
+            LootItem::Consumable { name, .. } | LootItem::Material { name, .. } => name,
+        };
+        match self.amount {
+            1 => format!("{} ({}%)", name, self.chance),
+            amount => format!("{} {} ({}%)", amount, name, self.chance),
+        }
+    }
+}
+
+/// A kind of enemy, every enemy that is met is made from one of these
+pub struct EnemyTemplate {
+    pub race: String,
+    pub description: String,
+    pub names: Vec<String>,
+    health: u32,
+    health_per_level: u32,
+    damage: u32,
+    damage_per_level: u32,
+    scaling: Scaling,
+    pub min_level: u32,
+    /// 0 if there is no limit
+    pub max_level: u32,
+    weight: u32,
+    /// the weight in some regions instead of the normal one, by the lowercase region name
+    regions: HashMap<String, u32>,
+    pub pattern: Vec<Move>,
+    pub ability: Option<EnemyAbility>,
+    pub loot: Vec<Loot>,
+}
+
+impl EnemyTemplate {
+    pub fn health_at(&self, level: u32) -> u32 {
+        self.health.saturating_add(self.health_per_level.saturating_mul(self.scaling.levels(level)))
+    }
+
+    pub fn damage_at(&self, level: u32) -> u32 {
+        self.damage.saturating_add(self.damage_per_level.saturating_mul(self.scaling.levels(level)))
+    }
+
+    /// moves a level into the levels the enemy is met at
+    pub fn clamp_level(&self, level: u32) -> u32 {
+        match self.max_level {
+            0 => level.max(self.min_level),
+            max => level.clamp(self.min_level, max),
+        }
+    }
+
+    fn weight_in(&self, region: &str) -> u32 {
+        *self.regions.get(region.to_ascii_lowercase().as_str()).unwrap_or(&self.weight)
+    }
+
+    fn fits(&self, level: u32) -> bool {
+        self.clamp_level(level) == level
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct BestiaryFile {
+    enemy: Option<Vec<RawEnemy>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawAbility {
+    name: String,
+    effect: String,
+    amount: Option<u32>,
+    turns: u32,
+    chance: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawLoot {
+    consumable: Option<String>,
+    material: Option<String>,
+    chance: u32,
+    amount: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawEnemy {
+    race: String,
+    description: String,
+    names: Vec<String>,
+    health: u32,
+    damage: u32,
+    health_per_level: Option<u32>,
+    damage_per_level: Option<u32>,
+    scaling: Option<String>,
+    min_level: Option<u32>,
+    max_level: Option<u32>,
+    weight: Option<u32>,
+    regions: Option<HashMap<String, u32>>,
+    pattern: Option<Vec<String>>,
+    ability: Option<RawAbility>,
+    loot: Option<Vec<RawLoot>>,
+}
+
+impl RawAbility {
+    fn parse(self) -> Result<EnemyAbility, String> {
+        let kind = match self.effect.to_ascii_lowercase().as_str() {
+            "poison" => StatusKind::Poison,
+            "bleed" => StatusKind::Bleed,
+            "stun" => StatusKind::Stun,
+            "weaken" => StatusKind::Weaken,
+            "strength" => StatusKind::Strength,
+            "shield" => StatusKind::Shield,
+            other => return Err(format!("unknown ability effect '{}'", other)),
+        };
+        let amount = match (kind, self.amount) {
+            (StatusKind::Stun, _) => 0,
+            (_, Some(amount)) if amount > 0 => amount,
+            _ => return Err(format!("the {} ability needs an amount of at least 1", self.effect)),
+        };
+        if self.turns == 0 {
+            return Err(format!("the {} ability needs at least 1 turn", self.effect));
+        }
+        Ok(EnemyAbility {
+            name: self.name,
+            kind,
+            // nobody can take less than no damage
+            amount: if kind == StatusKind::Shield { amount.min(90) } else { amount },
+            turns: self.turns,
+            chance: self.chance.min(100),
+        })
+    }
+}
+
+impl RawLoot {
+    fn parse(self, consumables: &ConsumableList, crafting: &CraftingList) -> Result<Loot, String> {
+        let item = match (self.consumable, self.material) {
+            (Some(id), None) => match consumables.by_name(id.as_str()) {
+                Some(c) => LootItem::Consumable { id: c.id.clone(), name: c.name.clone() },
+                None => return Err(format!("unknown consumable '{}' in its loot", id)),
+            },
+            (None, Some(id)) if crafting.has_material(id.as_str()) => LootItem::Material { name: crafting.material_name(id.as_str()), id },
+            (None, Some(id)) => return Err(format!("unknown material '{}' in its loot", id)),
+            _ => return Err(format!("every loot drop needs either a consumable or a material")),
+        };
+        let amount = self.amount.unwrap_or(1);
+        if amount == 0 || self.chance == 0 {
+            return Err(format!("every loot drop needs a chance and an amount of at least 1"));
+        }
+        Ok(Loot { item, chance: self.chance.min(100), amount })
+    }
+}
+
+impl RawEnemy {
+    fn parse(self, consumables: &ConsumableList, crafting: &CraftingList) -> Result<EnemyTemplate, String> {
+        if self.names.is_empty() {
+            return Err(format!("it needs at least one name"));
+        }
+        if self.health == 0 || self.damage == 0 {
+            return Err(format!("health and damage must be at least 1"));
+        }
+        let scaling = match self.scaling.as_deref().map(|s| s.to_ascii_lowercase()).as_deref() {
+            None | Some("linear") => Scaling::Linear,
+            Some("steep") => Scaling::Steep,
+            Some("gentle") => Scaling::Gentle,
+            Some(other) => return Err(format!("unknown scaling '{}'", other)),
+        };
+        let min_level = self.min_level.unwrap_or(1).max(1);
+        let max_level = self.max_level.unwrap_or(0);
+        if max_level != 0 && max_level < min_level {
+            return Err(format!("max_level is below min_level"));
+        }
+        let ability = self.ability.map(RawAbility::parse).transpose()?;
+        let mut pattern = Vec::new();
+        for step in self.pattern.unwrap_or_default() {
+            pattern.push(match step.to_ascii_lowercase().as_str() {
+                "attack" => Move::Attack,
+                "heavy" => Move::Heavy,
+                "ability" if ability.is_some() => Move::Ability,
+                "ability" => return Err(format!("its pattern uses an ability but it does not have one")),
+                "guard" => Move::Guard,
+                other => return Err(format!("unknown move '{}' in its pattern", other)),
+            });
+        }
+        // an enemy that only guards would never fight back
+        if pattern.iter().all(|m| *m == Move::Guard) {
+            pattern = vec![Move::Attack];
+        }
+        let loot = self.loot.unwrap_or_default().into_iter()
+            .map(|l| l.parse(consumables, crafting))
+            .collect::<Result<Vec<Loot>, String>>()?;
+
+        Ok(EnemyTemplate {
+            race: self.race,
+            description: self.description,
+            names: self.names,
+            health: self.health,
+            health_per_level: self.health_per_level.unwrap_or(10),
+            damage: self.damage,
+            damage_per_level: self.damage_per_level.unwrap_or(2),
+            scaling,
+            min_level,
+            max_level,
+            weight: self.weight.unwrap_or(10),
+            regions: self.regions.unwrap_or_default().into_iter().map(|(r, w)| (r.to_ascii_lowercase(), w)).collect(),
+            pattern,
+            ability,
+            loot,
+        })
+    }
+}
+
+/// Every kind of enemy in the game, read from the data folder when the server starts
+pub struct Bestiary {
+    enemies: Vec<EnemyTemplate>,
+}
+
+impl Bestiary {
+    /// the loot drops are checked against the consumables and crafting materials, so those are loaded first
+    pub fn load(config: &ServerConfig, consumables: &ConsumableList, crafting: &CraftingList) -> Self {
+        let raw = read_data_file(config, BESTIARY_FILE, DEFAULT_BESTIARY);
+        let enemies = Self::parse(raw.as_str(), consumables, crafting).unwrap_or_else(|e| {
+            error!(target:LOG_TARGET, "Invalid {}, using the default enemies: {}", BESTIARY_FILE, e);
+            Self::parse(DEFAULT_BESTIARY, consumables, crafting).expect("The default enemies are invalid")
+        });
+        info!(target:LOG_TARGET, "Loaded {} enemies", enemies.len());
+        Self { enemies }
+    }
+
+    /// reads every enemy in a bestiary file, skipping the invalid ones. Fails if none are left since fights need one
+    fn parse(raw: &str, consumables: &ConsumableList, crafting: &CraftingList) -> Result<Vec<EnemyTemplate>, String> {
+        let file = toml::from_str::<BestiaryFile>(raw).map_err(|e| e.to_string())?;
+        let mut enemies: Vec<EnemyTemplate> = Vec::new();
+        for raw in file.enemy.unwrap_or_default() {
+            let race = raw.race.clone();
+            if enemies.iter().any(|e| e.race.eq_ignore_ascii_case(race.as_str())) {
+                warn!(target:LOG_TARGET, "Skipping enemy '{}': the race is used more than once", race);
+                continue;
+            }
+            match raw.parse(consumables, crafting) {
+                Ok(enemy) => enemies.push(enemy),
+                Err(e) => warn!(target:LOG_TARGET, "Skipping enemy '{}': {}", race, e),
+            }
+        }
+        match enemies.is_empty() {
+            true => Err(format!("there are no valid enemies")),
+            false => Ok(enemies),
+        }
+    }
+
+    /// picks the kind of enemy met in a region around a level by their weights.
+    /// If nothing is met at the level the level is ignored, and then the region, so there is always an enemy
+    pub fn pick(&self, region: &str, level: u32) -> &EnemyTemplate {
+        let choices = [
+            self.weighted(|e| if e.fits(level) { e.weight_in(region) } else { 0 }),
+            self.weighted(|e| e.weight_in(region)),
+            self.weighted(|e| e.weight),
+        ];
+        choices.into_iter().flatten().next()
+            .unwrap_or_else(|| &self.enemies[thread_rng().gen_range(0..self.enemies.len())])
+    }
+
+    fn weighted<F: Fn(&EnemyTemplate) -> u32>(&self, weight: F) -> Option<&EnemyTemplate> {
+        // the weights come from the data file, so they are added up as u64 to never overflow
+        let total = self.enemies.iter().map(|e| weight(e) as u64).sum::<u64>();
+        if total == 0 {
+            return None;
+        }
+        let mut roll = thread_rng().gen_range(0..total);
+        for e in &self.enemies {
+            if roll < weight(e) as u64 {
+                return Some(e);
+            }
+            roll -= weight(e) as u64;
+        }
+        None
+    }
+
+    /// every enemy in the player's bestiary, the ones they have not defeated yet are hidden
+    pub fn view(&self, db: &Database, uuid: &Uuid) -> Vec<BestiaryEntryData> {
+        let kills = db.get_bestiary(uuid);
+        self.enemies.iter().map(|e| {
+            match kills.iter().find(|(race, _)| race.eq_ignore_ascii_case(e.race.as_str())) {
+                Some((_, kills)) => BestiaryEntryData {
+                    race: e.race.clone(),
+                    description: e.description.clone(),
+                    unlocked: true,
+                    kills: *kills,
+                    min_level: e.min_level,
+                    max_level: e.max_level,
+                    ability: e.ability.as_ref().map(EnemyAbility::describe).unwrap_or_default(),
+                    loot: e.loot.iter().map(Loot::describe).collect(),
+                },
+                None => BestiaryEntryData {
+                    race: String::new(),
+                    description: String::new(),
+                    unlocked: false,
+                    kills: 0,
+                    min_level: 0,
+                    max_level: 0,
+                    ability: String::new(),
+                    loot: Vec::new(),
+                },
+            }
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::consumable::DEFAULT_CONSUMABLES;
+    use crate::crafting::DEFAULT_CRAFTING;
+    use super::*;
+
+    fn enemy(race: &str, extra: &str) -> String {
+        format!("[[enemy]]\nrace = \"{}\"\ndescription = \"A test enemy\"\nnames = [\"Test\"]\nhealth = 10\ndamage = 2\n{}\n", race, extra)
+    }
+
+    fn parse(raw: &str) -> Result<Vec<EnemyTemplate>, String> {
+        Bestiary::parse(raw, &ConsumableList::parse(DEFAULT_CONSUMABLES), &CraftingList::parse(DEFAULT_CRAFTING))
+    }
+
+    fn load(raw: &str) -> Bestiary {
+        Bestiary { enemies: parse(raw).unwrap() }
+    }
+
+    fn races(enemies: &[EnemyTemplate]) -> Vec<&str> {
+        enemies.iter().map(|e| e.race.as_str()).collect()
+    }
+
+    #[test]
+    fn default_bestiary_is_valid() {
+        assert_eq!(parse(DEFAULT_BESTIARY).unwrap().len(), 6);
+    }
+
+    #[test]
+    fn skips_duplicate_races() {
+        let raw = enemy("Goblin", "weight = 1") + &enemy("goblin", "weight = 2") + &enemy("Wolf", "");
+        let enemies = parse(raw.as_str()).unwrap();
+        assert_eq!(races(&enemies), vec!["Goblin", "Wolf"]);
+        assert_eq!(enemies[0].weight, 1);
+    }
+
+    #[test]
+    fn skips_patterns_using_a_missing_ability() {
+        let raw = enemy("Goblin", "pattern = [\"attack\", \"ability\"]")
+            + &enemy("Wolf", "pattern = [\"attack\", \"ability\"]\nability = { name = \"Bite\", effect = \"bleed\", amount = 5, turns = 2, chance = 50 }");
+        let enemies = parse(raw.as_str()).unwrap();
+        assert_eq!(races(&enemies), vec!["Wolf"]);
+        assert_eq!(enemies[0].pattern, vec![Move::Attack, Move::Ability]);
+    }
+
+    #[test]
+    fn patterns_that_only_guard_attack_instead() {
+        let enemies = parse((enemy("Turtle", "pattern = [\"guard\", \"guard\"]") + &enemy("Goblin", "")).as_str()).unwrap();
+        assert_eq!(enemies[0].pattern, vec![Move::Attack]);
+        assert_eq!(enemies[1].pattern, vec![Move::Attack]);
+    }
+
+    #[test]
+    fn skips_unknown_loot() {
+        let raw = enemy("Goblin", "loot = [{ consumable = \"golden_apple\", chance = 10 }]")
+            + &enemy("Wolf", "loot = [{ material = \"dragon_scale\", chance = 10 }]")
+            + &enemy("Bandit", "loot = [{ consumable = \"bread\", material = \"leather\", chance = 10 }]")
+            + &enemy("Orc", "loot = [{ consumable = \"bread\", chance = 10 }, { material = \"leather\", chance = 50, amount = 2 }]");
+        let enemies = parse(raw.as_str()).unwrap();
+        assert_eq!(races(&enemies), vec!["Orc"]);
+        assert_eq!(enemies[0].loot.iter().map(Loot::describe).collect::<Vec<String>>(), vec!["Bread (10%)", "2 Leather (50%)"]);
+    }
+
+    #[test]
+    fn skips_max_levels_below_min_levels() {
+        let raw = enemy("Goblin", "min_level = 10\nmax_level = 5") + &enemy("Wolf", "min_level = 10\nmax_level = 10") + &enemy("Bandit", "min_level = 10");
+        let enemies = parse(raw.as_str()).unwrap();
+        assert_eq!(races(&enemies), vec!["Wolf", "Bandit"]);
+        assert_eq!(enemies[1].max_level, 0);
+    }
+
+    #[test]
+    fn fails_without_any_valid_enemies() {
+        assert!(parse("").is_err());
+        assert!(parse("not = [valid").is_err());
+        assert!(parse(enemy("Goblin", "scaling = \"sideways\"").as_str()).is_err());
+    }
+
+    #[test]
+    fn picks_enemies_met_at_the_level() {
+        let bestiary = load((enemy("Goblin", "max_level = 5") + &enemy("Troll", "min_level = 10")).as_str());
+        for _ in 0..50 {
+            assert_eq!(bestiary.pick("Plains of Arenlok", 3).race, "Goblin");
+            assert_eq!(bestiary.pick("Plains of Arenlok", 12).race, "Troll");
+        }
+    }
+
+    #[test]
+    fn pick_ignores_the_level_when_nothing_is_met_at_it() {
+        let bestiary = load((enemy("Goblin", "max_level = 5") + &enemy("Troll", "min_level = 10\nweight = 0\nregions = { Cave = 10 }")).as_str());
+        for _ in 0..50 {
+            assert_eq!(bestiary.pick("Plains of Arenlok", 7).race, "Goblin");
+            assert_eq!(bestiary.pick("cave", 3).race, "Goblin");
+        }
+    }
+
+    #[test]
+    fn pick_ignores_the_region_when_nothing_is_met_in_it() {
+        let bestiary = load((enemy("Goblin", "regions = { Cave = 0 }") + &enemy("Troll", "weight = 0")).as_str());
+        for _ in 0..50 {
+            assert_eq!(bestiary.pick("Cave", 3).race, "Goblin");
+        }
+        // even without any weights there is always an enemy
+        let bestiary = load(enemy("Goblin", "weight = 0").as_str());
+        assert_eq!(bestiary.pick("Cave", 3).race, "Goblin");
+    }
+
+    #[test]
+    fn weights_do_not_overflow() {
+        let bestiary = load((enemy("Goblin", "weight = 4294967295") + &enemy("Troll", "weight = 4294967295")).as_str());
+        let picked = (0..50).map(|_| bestiary.pick("Cave", 3).race.clone()).collect::<Vec<String>>();
+        assert!(picked.iter().all(|r| r == "Goblin" || r == "Troll"));
+    }
+
+    #[test]
+    fn scales_with_level() {
+        let enemies = parse((enemy("Goblin", "health_per_level = 5\ndamage_per_level = 1") + &enemy("Troll", "scaling = \"steep\"")).as_str()).unwrap();
+        assert_eq!(enemies[0].health_at(1), 10);
+        assert_eq!(enemies[0].health_at(11), 60);
+        assert_eq!(enemies[0].damage_at(11), 12);
+        assert!(enemies[1].health_at(21) > 10 + 10 * 20);
+    }
+}
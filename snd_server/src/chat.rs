@@ -0,0 +1,131 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use log::info;
+use uuid::Uuid;
+use snd_network_lib::chat_data::{ChatChannel, ChatData};
+use crate::config::ServerConfig;
+use crate::database::Database;
+use crate::session::{SessionMessage, SessionRegistry};
+
+const LOG_TARGET: &str = "chat";
+
+/// Limits how many messages a player can send in a window of time.
+/// Each connection has its own limiter as chat is sent from the player's handler thread
+pub struct RateLimiter {
+    max_messages: usize,
+    window: Duration,
+    sent: VecDeque<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(max_messages: usize, window_secs: u64) -> Self {
+        Self {
+            max_messages,
+            window: Duration::from_secs(window_secs),
+            sent: VecDeque::new(),
+        }
+    }
+
+    /// records a message being sent, returns false if the player is sending too fast
+    pub fn try_send(&mut self) -> bool {
+        let now = Instant::now();
+        while let Some(first) = self.sent.front() {
+            if now.duration_since(*first) < self.window {
+                break;
+            }
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= self.max_messages {
+            return false;
+        }
+        self.sent.push_back(now);
+        true
+    }
+}
+
+/// Delivers a chat message from a player to everyone who should see it.
+/// Returns an error to show the sender if the message could not be sent
+pub fn route(db: &Arc<Mutex<Database>>, sessions: &Arc<Mutex<SessionRegistry>>, config: &ServerConfig,
+             sender: &Uuid, username: &str, chat: ChatData) -> Result<(), String> {
+    let message = chat.message.trim().to_string();
+    if message.is_empty() {
+        return Err(format!("You can not send an empty message"));
+    }
+    if message.chars().count() > config.chat_max_message_length {
+        return Err(format!("Your message is too long, the limit is {} characters", config.chat_max_message_length));
+    }
+    if message.chars().any(|c| c.is_control()) {
+        return Err(format!("Your message contains invalid characters"));
+    }
+
+    let (mute, ignoring) = {
+        let db = db.lock().unwrap();
+        (db.get_player_mute(sender), db.get_ignored_by(sender))
+    };
+    if let Some(m) = mute {
+        return Err(m.message());
+    }
+
+    let mut out = ChatData {
+        channel: chat.channel,
+        sender: username.to_string(),
+        target: String::new(),
+        message,
+    };
+
+    // find who is in the sender's region or guild before locking the sessions so both are never held at once
+    let listeners = match chat.channel {
+        ChatChannel::Region => {
+            let db = db.lock().unwrap();
+            out.target = db.get_player_region(sender).unwrap_or_default();
+            db.get_players_in_region(out.target.as_str())
+        }
+        ChatChannel::Guild => {
+            let db = db.lock().unwrap();
+            let (guild, _) = db.get_player_guild(sender).ok_or(format!("You are not in a guild"))?;
+            out.target = guild.name.clone();
+            db.get_guild_members(&guild).into_iter().map(|(m, _)| m).collect()
+        }
+        _ => Vec::new(),
+    };
+
+    let sessions = sessions.lock().unwrap();
+    match chat.channel {
+        ChatChannel::Global => {
+            info!(target:LOG_TARGET, "[Global] {}: {}", username, out.message);
+            for s in sessions.all() {
+                if !ignoring.contains(&s.uuid) {
+                    sessions.send(&s.uuid, SessionMessage::Chat(out.clone()));
+                }
+            }
+        }
+        ChatChannel::Region | ChatChannel::Guild => {
+            info!(target:LOG_TARGET, "[{}] {}: {}", out.target, username, out.message);
+            for uuid in listeners {
+                if sessions.is_online(&uuid) && !ignoring.contains(&uuid) {
+                    sessions.send(&uuid, SessionMessage::Chat(out.clone()));
+                }
+            }
+        }
+        ChatChannel::Whisper => {
+            let target = sessions.find_by_name(chat.target.as_str())
+                .ok_or(format!("{} is not online", chat.target))?;
+            if target == *sender {
+                return Err(format!("You can not whisper to yourself"));
+            }
+            if ignoring.contains(&target) {
+                return Err(format!("{} is not accepting your messages", chat.target));
+            }
+            out.target = sessions.get(&target).unwrap().username.clone();
+            info!(target:LOG_TARGET, "[Whisper] {} -> {}: {}", username, out.target, out.message);
+            sessions.send(&target, SessionMessage::Chat(out.clone()));
+            // echo the whisper back so the sender can see it in their chat
+            sessions.send(sender, SessionMessage::Chat(out));
+        }
+        ChatChannel::System => {
+            return Err(format!("Only the server can send system messages"));
+        }
+    }
+    Ok(())
+}
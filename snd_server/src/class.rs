@@ -0,0 +1,626 @@
+use log::{error, info, warn};
+use serde::Deserialize;
+use uuid::Uuid;
+use snd_network_lib::skill_data::{SkillActionData, SkillActionKind, SkillData, SkillTreeData};
+use crate::config::ServerConfig;
+use crate::data::read_data_file;
+use crate::database::Database;
+use crate::encounter;
+use crate::player::SKILL_POINTS_PER_LEVEL;
+use crate::session::SessionMessage;
+use crate::state::ServerState;
+
+const LOG_TARGET: &str = "class";
+
+/// The name of the classes file in the data folder
+const CLASSES_FILE: &str = "classes.toml";
+
+/// The contents written to a new classes file when one does not exist yet
+pub const DEFAULT_CLASSES: &str = r#"# Every class needs a unique id, a name and a description.
+# The first class is given to players who sign up without picking one.
+# Optional settings:
+#   health: the percent of the normal max health the class has, 100 if missing
+#   attack: the percent of the normal damage the class deals, 100 if missing
+#   defense: the percent of the armor's defense the class gets, 100 if missing
+#   flee: added to the chance of fleeing from a fight, it can be below 0
+# Changing the id of a class takes it away from every player who has it
+
+[[class]]
+id = "warrior"
+name = "Warrior"
+description = "A sturdy fighter in heavy armor who is hard to bring down but slow to run."
+health = 120
+defense = 120
+flee = -10
+
+[[class]]
+id = "rogue"
+name = "Rogue"
+description = "A quick fighter who lands critical hits and slips away from danger."
+health = 90
+attack = 110
+defense = 90
+flee = 20
+
+[[class]]
+id = "mage"
+name = "Mage"
+description = "A frail spellcaster who hits the hardest and can mend their own wounds."
+health = 80
+attack = 125
+defense = 80
+
+# Every skill needs a unique id, the id of its class, a name, a description and an effect.
+# Passive effects are always on and give amount for every rank:
+#   health: percent more max health
+#   attack: percent more damage
+#   defense: percent less damage taken
+#   crit: percent chance of a critical hit that deals double damage
+#   flee: percent more chance to flee from a fight
+#   lifesteal: percent of the damage dealt healed
+# Active effects are used in a fight with 'skill <name>' on the player's turn, which ends it:
+#   strike: attacks for amount percent more damage for every rank
+#   heal: heals amount percent of the player's max health for every rank
+#   attack_buff: deals amount percent more damage for every rank for the next turns rounds
+#   defense_buff: takes amount percent less damage for every rank for the next turns rounds
+# Optional settings:
+#   max_rank: how many skill points can be spent on it, 1 if missing
+#   level: the level needed to learn it, 1 if missing
+#   requires: the id of a skill of the same class that needs a point in it first
+#   cooldown: the rounds before an active skill can be used again, 0 if missing
+# Changing the id of a skill takes it away from every player who learned it without giving back the points
+
+[[skill]]
+id = "toughness"
+class = "warrior"
+name = "Toughness"
+description = "5% more max health for every rank."
+effect = "health"
+amount = 5
+max_rank = 5
+
+[[skill]]
+id = "heavy_strike"
+class = "warrior"
+name = "Heavy Strike"
+description = "An attack that deals 40% more damage for every rank."
+effect = "strike"
+amount = 40
+max_rank = 3
+cooldown = 2
+
+[[skill]]
+id = "battle_cry"
+class = "warrior"
+name = "Battle Cry"
+description = "Deal 15% more damage for every rank for 3 rounds."
+effect = "attack_buff"
+amount = 15
+turns = 3
+max_rank = 3
+level = 5
+requires = "heavy_strike"
+cooldown = 5
+
+[[skill]]
+id = "shield_wall"
+class = "warrior"
+name = "Shield Wall"
+description = "Take 20% less damage for every rank for 2 rounds."
+effect = "defense_buff"
+amount = 20
+turns = 2
+max_rank = 3
+level = 10
+requires = "toughness"
+cooldown = 5
+
+[[skill]]
+id = "precision"
+class = "rogue"
+name = "Precision"
+description = "4% more chance of a critical hit for every rank."
+effect = "crit"
+amount = 4
+max_rank = 5
+
+[[skill]]
+id = "backstab"
+class = "rogue"
+name = "Backstab"
+description = "An attack that deals 60% more damage for every rank."
+effect = "strike"
+amount = 60
+max_rank = 3
+cooldown = 3
+
+[[skill]]
+id = "evasion"
+class = "rogue"
+name = "Evasion"
+description = "5% more chance to flee from a fight for every rank."
+effect = "flee"
+amount = 5
+max_rank = 3
+level = 5
+requires = "precision"
+
+[[skill]]
+id = "leeching_blade"
+class = "rogue"
+name = "Leeching Blade"
+description = "Heal 5% of the damage you deal for every rank."
+effect = "lifesteal"
+amount = 5
+max_rank = 3
+level = 10
+requires = "backstab"
+
+[[skill]]
+id = "arcane_power"
+class = "mage"
+name = "Arcane Power"
+description = "4% more damage for every rank."
+effect = "attack"
+amount = 4
+max_rank = 5
+
+[[skill]]
+id = "fireball"
+class = "mage"
+name = "Fireball"
+description = "A spell that deals 80% more damage for every rank."
+effect = "strike"
+amount = 80
+max_rank = 3
+cooldown = 3
+
+[[skill]]
+id = "mend"
+class = "mage"
+name = "Mend"
+description = "Heal 10% of your max health for every rank."
+effect = "heal"
+amount = 10
+max_rank = 3
+level = 5
+cooldown = 4
+
+[[skill]]
+id = "mana_shield"
+class = "mage"
+name = "Mana Shield"
+description = "Take 25% less damage for every rank for 2 rounds."
+effect = "defense_buff"
+amount = 25
+turns = 2
+max_rank = 2
+level = 10
+requires = "mend"
+cooldown = 6
+"#;
+
+pub struct Class {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    /// the percent of the normal max health the class has
+    pub health: u32,
+    /// the percent of the normal damage the class deals
+    attack: u32,
+    /// the percent of the armor's defense the class gets
+    defense: u32,
+    /// added to the chance of fleeing
+    flee: i32,
+}
+
+/// What a skill does, the amounts are for each rank the player has in it
+#[derive(Copy, Clone, Debug)]
+pub enum SkillEffect {
+    Health(u32),
+    Attack(u32),
+    Defense(u32),
+    Crit(u32),
+    Flee(u32),
+    Lifesteal(u32),
+    Strike(u32),
+    Heal(u32),
+    AttackBuff { percent: u32, turns: u32 },
+    DefenseBuff { percent: u32, turns: u32 },
+}
+
+impl SkillEffect {
+    /// if the skill is used in fights, otherwise it is always on
+    pub fn is_active(&self) -> bool {
+        matches!(self, Self::Strike(_) | Self::Heal(_) | Self::AttackBuff { .. } | Self::DefenseBuff { .. })
+    }
+}
+
+pub struct Skill {
+    pub id: String,
+    pub class: String,
+    pub name: String,
+    pub description: String,
+    pub effect: SkillEffect,
+    pub max_rank: u32,
+    /// the level needed to learn it
+    pub level: u32,
+    /// the id of the skill that needs a point in it first
+    requires: Option<String>,
+    /// the rounds before it can be used again
+    pub cooldown: u32,
+}
+
+/// The bonuses a player's class and passive skills give them in fights
+#[derive(Copy, Clone, Debug)]
+pub struct ClassStats {
+    /// the percent of the normal damage the player deals
+    pub attack: u32,
+    /// the percent of their armor's defense the player gets
+    pub armor: u32,
+    /// the percent less damage the player always takes
+    pub guard: u32,
+    pub crit: u32,
+    /// added to the chance of fleeing
+    pub flee: i32,
+    pub lifesteal: u32,
+}
+
+impl Default for ClassStats {
+    fn default() -> Self {
+        Self { attack: 100, armor: 100, guard: 0, crit: 0, flee: 0, lifesteal: 0 }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ClassFile {
+    class: Option<Vec<RawClass>>,
+    skill: Option<Vec<RawSkill>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawClass {
+    id: String,
+    name: String,
+    description: String,
+    health: Option<u32>,
+    attack: Option<u32>,
+    defense: Option<u32>,
+    flee: Option<i32>,
+}
+
+impl RawClass {
+    fn parse(self) -> Result<Class, String> {
+        let health = self.health.unwrap_or(100);
+        if health == 0 {
+            return Err(format!("health must be at least 1"));
+        }
+        Ok(Class {
+            id: self.id,
+            name: self.name,
+            description: self.description,
+            health,
+            attack: self.attack.unwrap_or(100),
+            defense: self.defense.unwrap_or(100),
+            flee: self.flee.unwrap_or(0),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RawSkill {
+    id: String,
+    class: String,
+    name: String,
+    description: String,
+    effect: String,
+    amount: Option<u32>,
+    turns: Option<u32>,
+    max_rank: Option<u32>,
+    level: Option<u32>,
+    requires: Option<String>,
+    cooldown: Option<u32>,
+}
+
+impl RawSkill {
+    fn parse(self) -> Result<Skill, String> {
+        let amount = || match self.amount {
+            Some(amount) if amount > 0 => Ok(amount),
+            _ => Err(format!("the {} effect needs an amount of at least 1", self.effect)),
+        };
+        let turns = || match self.turns {
+            Some(turns) if turns > 0 => Ok(turns),
+            _ => Err(format!("the {} effect needs at least 1 turn", self.effect)),
+        };
+        let effect = match self.effect.to_ascii_lowercase().as_str() {
+            "health" => SkillEffect::Health(amount()?),
+            "attack" => SkillEffect::Attack(amount()?),
+            "defense" => SkillEffect::Defense(amount()?),
+            "crit" => SkillEffect::Crit(amount()?),
+            "flee" => SkillEffect::Flee(amount()?),
+            "lifesteal" => SkillEffect::Lifesteal(amount()?),
+            "strike" => SkillEffect::Strike(amount()?),
+            "heal" => SkillEffect::Heal(amount()?),
+            "attack_buff" => SkillEffect::AttackBuff { percent: amount()?, turns: turns()? },
+            "defense_buff" => SkillEffect::DefenseBuff { percent: amount()?, turns: turns()? },
+            other => return Err(format!("unknown effect '{}'", other)),
+        };
+
+        Ok(Skill {
+            id: self.id,
+            class: self.class,
+            name: self.name,
+            description: self.description,
+            effect,
+            max_rank: self.max_rank.unwrap_or(1).max(1),
+            level: self.level.unwrap_or(1),
+            requires: self.requires,
+            cooldown: if effect.is_active() { self.cooldown.unwrap_or(0) } else { 0 },
+        })
+    }
+}
+
+/// Every class and skill in the game, read from the data folder when the server starts
+pub struct ClassList {
+    classes: Vec<Class>,
+    skills: Vec<Skill>,
+}
+
+impl ClassList {
+    pub fn load(config: &ServerConfig) -> Self {
+        let raw = read_data_file(config, CLASSES_FILE, DEFAULT_CLASSES);
+        let file = toml::from_str::<ClassFile>(raw.as_str()).unwrap_or_else(|e| {
+            error!(target:LOG_TARGET, "Invalid {}, using the default classes: {}", CLASSES_FILE, e);
+            toml::from_str(DEFAULT_CLASSES).expect("The default classes are invalid")
+        });
+
+        let mut classes: Vec<Class> = Vec::new();
+        for raw in file.class.unwrap_or_default() {
+            let id = raw.id.clone();
+            if classes.iter().any(|c| c.id == id) {
+                warn!(target:LOG_TARGET, "Skipping class '{}': the id is used more than once", id);
+                continue;
+            }
+            match raw.parse() {
+                Ok(class) => classes.push(class),
+                Err(e) => warn!(target:LOG_TARGET, "Skipping class '{}': {}", id, e),
+            }
+        }
+
+        let mut skills: Vec<Skill> = Vec::new();
+        for raw in file.skill.unwrap_or_default() {
+            let id = raw.id.clone();
+            if skills.iter().any(|s| s.id == id) {
+                warn!(target:LOG_TARGET, "Skipping skill '{}': the id is used more than once", id);
+                continue;
+            }
+            if !classes.iter().any(|c| c.id == raw.class) {
+                warn!(target:LOG_TARGET, "Skipping skill '{}': there is no class '{}'", id, raw.class);
+                continue;
+            }
+            match raw.parse() {
+                Ok(skill) => skills.push(skill),
+                Err(e) => warn!(target:LOG_TARGET, "Skipping skill '{}': {}", id, e),
+            }
+        }
+        // a skill can only need another skill of the same class, checked once every skill is read
+        let invalid = skills.iter()
+            .filter(|s| s.requires.as_ref().map_or(false, |r| !skills.iter().any(|o| o.id == *r && o.class == s.class && o.id != s.id)))
+            .map(|s| s.id.clone())
+            .collect::<Vec<String>>();
+        for id in &invalid {
+            warn!(target:LOG_TARGET, "Skill '{}' needs a skill that is not in its class, it no longer needs one", id);
+        }
+        skills.iter_mut().filter(|s| invalid.contains(&s.id)).for_each(|s| s.requires = None);
+
+        info!(target:LOG_TARGET, "Loaded {} classes with {} skills", classes.len(), skills.len());
+        Self { classes, skills }
+    }
+
+    fn by_id(&self, id: &str) -> Option<&Class> {
+        self.classes.iter().find(|c| c.id == id)
+    }
+
+    /// finds a class by the name players see, or its id
+    pub fn by_name(&self, name: &str) -> Option<&Class> {
+        self.classes.iter().find(|c| c.name.eq_ignore_ascii_case(name) || c.id.eq_ignore_ascii_case(name))
+    }
+
+    /// the class players get when they sign up without picking one
+    pub fn default_class(&self) -> Option<&Class> {
+        self.classes.first()
+    }
+
+    /// the names of every class, to show players who picked one that does not exist
+    pub fn names(&self) -> String {
+        self.classes.iter().map(|c| c.name.as_str()).collect::<Vec<&str>>().join(", ")
+    }
+
+    /// the class a player has, players from before classes have none until they pick one
+    pub fn of(&self, db: &Database, uuid: &Uuid) -> Option<&Class> {
+        db.get_player_class(uuid).and_then(|id| self.by_id(id.as_str()))
+    }
+
+    /// the skills of a class, in the order they are in the file
+    fn skills_of(&self, class: &Class) -> impl Iterator<Item = &Skill> {
+        let id = class.id.clone();
+        self.skills.iter().filter(move |s| s.class == id)
+    }
+
+    /// finds a skill of a class by the name players see, or its id
+    fn skill_by_name(&self, class: &Class, name: &str) -> Option<&Skill> {
+        self.skills_of(class).find(|s| s.name.eq_ignore_ascii_case(name) || s.id.eq_ignore_ascii_case(name))
+    }
+
+    /// the skills of the player's class they spent points on, with their ranks
+    fn learned(&self, db: &Database, uuid: &Uuid) -> Vec<(&Skill, u32)> {
+        let class = match self.of(db, uuid) {
+            Some(class) => class,
+            None => return Vec::new(),
+        };
+        let ranks = db.get_player_skills(uuid);
+        self.skills_of(class)
+            .filter_map(|s| ranks.iter().find(|(id, _)| *id == s.id).map(|(_, rank)| (s, (*rank).min(s.max_rank))))
+            .collect()
+    }
+
+    /// an active skill the player learned, with their rank in it.
+    /// Returns an error to show the player if they can not use it
+    pub fn active_skill(&self, db: &Database, uuid: &Uuid, name: &str) -> Result<(&Skill, u32), String> {
+        let class = self.of(db, uuid).ok_or(format!("You do not have a class, pick one with 'skills class <class>'"))?;
+        let skill = self.skill_by_name(class, name).ok_or(format!("{}s do not have a skill called '{}'", class.name, name))?;
+        if !skill.effect.is_active() {
+            return Err(format!("{} is always on, it does not need to be used", skill.name));
+        }
+        self.learned(db, uuid).into_iter().find(|(s, _)| s.id == skill.id)
+            .ok_or(format!("You have not learned {}", skill.name))
+    }
+
+    /// the bonuses the player's class and passive skills give them in fights
+    pub fn stats(&self, db: &Database, uuid: &Uuid) -> ClassStats {
+        let mut stats = ClassStats::default();
+        if let Some(class) = self.of(db, uuid) {
+            stats.attack = class.attack;
+            stats.armor = class.defense;
+            stats.flee = class.flee;
+        }
+        for (skill, rank) in self.learned(db, uuid) {
+            match skill.effect {
+                SkillEffect::Attack(amount) => stats.attack += amount * rank,
+                SkillEffect::Defense(amount) => stats.guard += amount * rank,
+                SkillEffect::Crit(amount) => stats.crit += amount * rank,
+                SkillEffect::Flee(amount) => stats.flee += (amount * rank) as i32,
+                SkillEffect::Lifesteal(amount) => stats.lifesteal += amount * rank,
+                _ => {}
+            }
+        }
+        stats.guard = stats.guard.min(75);
+        stats
+    }
+
+    /// the percent of the normal max health the player's class and passive skills give them
+    fn health_percent(&self, db: &Database, uuid: &Uuid) -> u32 {
+        let base = self.of(db, uuid).map_or(100, |c| c.health);
+        base + self.learned(db, uuid).iter()
+            .map(|(s, rank)| if let SkillEffect::Health(amount) = s.effect { amount * rank } else { 0 })
+            .sum::<u32>()
+    }
+
+    /// stores the player's max health after their class or skills changed, or when they log in in case the file changed
+    pub fn refresh(&self, db: &Database, uuid: &Uuid) {
+        db.set_health_percent(uuid, self.health_percent(db, uuid));
+    }
+
+    /// the player's class and every skill they can learn
+    pub fn tree(&self, db: &Database, uuid: &Uuid) -> SkillTreeData {
+        let class = self.of(db, uuid);
+        let learned = self.learned(db, uuid);
+        SkillTreeData {
+            class: class.map(|c| c.name.clone()).unwrap_or_default(),
+            points: db.get_skill_points(uuid),
+            skills: class.map(|class| self.skills_of(class).map(|s| SkillData {
+                name: s.name.clone(),
+                description: s.description.clone(),
+                active: s.effect.is_active(),
+                rank: learned.iter().find(|(l, _)| l.id == s.id).map_or(0, |(_, rank)| *rank),
+                max_rank: s.max_rank,
+                level: s.level,
+                requires: s.requires.as_ref().and_then(|r| self.skills.iter().find(|o| o.id == *r))
+                    .map(|r| r.name.clone()).unwrap_or_default(),
+                cooldown: s.cooldown,
+            }).collect()).unwrap_or_default(),
+            classes: self.classes.iter().map(|c| format!("{}: {}", c.name, c.description)).collect(),
+        }
+    }
+}
+
+/// Learns, uses or resets the player's skills, or picks their class.
+/// Returns what happened to show the player, or an error if they can not do it
+pub fn handle_action(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str,
+                     action: &SkillActionData) -> Result<Option<String>, String> {
+    let name = action.skill.trim();
+    if action.kind == SkillActionKind::Use {
+        return encounter::use_skill(state, config, uuid, name).map(|_| None);
+    }
+    if action.kind == SkillActionKind::List {
+        return Ok(None);
+    }
+    if state.encounters.lock().unwrap().in_fight(uuid) || state.duels.lock().unwrap().in_duel(uuid) {
+        return Err(format!("You can not change your skills in the middle of a fight"));
+    }
+
+    let classes = &state.classes;
+    let db = state.db.lock().unwrap();
+    let message = match action.kind {
+        SkillActionKind::Learn => {
+            let class = classes.of(&db, uuid).ok_or(format!("You do not have a class, pick one with 'skills class <class>'"))?;
+            let skill = classes.skill_by_name(class, name).ok_or(format!("{}s do not have a skill called '{}'", class.name, name))?;
+            let learned = classes.learned(&db, uuid);
+            let rank = learned.iter().find(|(s, _)| s.id == skill.id).map_or(0, |(_, rank)| *rank);
+            if rank >= skill.max_rank {
+                return Err(format!("You already mastered {}", skill.name));
+            }
+            let level = db.get_player_level(uuid).unwrap_or(1);
+            if level < skill.level {
+                return Err(format!("You need to be level {} to learn {}", skill.level, skill.name));
+            }
+            if let Some(requires) = &skill.requires {
+                if !learned.iter().any(|(s, _)| s.id == *requires) {
+                    let needed = classes.skills.iter().find(|s| s.id == *requires).map_or(requires.as_str(), |s| s.name.as_str());
+                    return Err(format!("You need to learn {} before {}", needed, skill.name));
+                }
+            }
+            if db.get_skill_points(uuid) == 0 {
+                return Err(format!("You do not have any skill points, you get {} every level", SKILL_POINTS_PER_LEVEL));
+            }
+            if !db.learn_skill(uuid, skill.id.as_str()) {
+                return Err(format!("Failed to learn {}", skill.name));
+            }
+            info!(target:LOG_TARGET, "{} learned rank {} of {}", username, rank + 1, skill.id);
+            format!("You learned rank {} of {}", rank + 1, skill.name)
+        }
+        SkillActionKind::Class => {
+            if let Some(class) = classes.of(&db, uuid) {
+                return Err(format!("You are already a {}, use 'skills reset <class>' to change class", class.name));
+            }
+            let class = classes.by_name(name).ok_or(format!("There is no class called '{}', pick one of: {}", name, classes.names()))?;
+            if !db.reset_skills(uuid, class.id.as_str(), 0) {
+                return Err(format!("Failed to become a {}", class.name));
+            }
+            info!(target:LOG_TARGET, "{} picked the {} class", username, class.id);
+            format!("You are now a {}! Type 'skills' to see what you can learn", class.name)
+        }
+        SkillActionKind::Reset => {
+            let current = classes.of(&db, uuid);
+            let class = if name.is_empty() {
+                current.ok_or(format!("You do not have a class, pick one with 'skills class <class>'"))?
+            } else {
+                classes.by_name(name).ok_or(format!("There is no class called '{}', pick one of: {}", name, classes.names()))?
+            };
+            let refund = db.get_player_skills(uuid).iter().map(|(_, rank)| rank).sum::<u32>();
+            let changing = current.map_or(true, |c| c.id != class.id);
+            if refund == 0 && !changing {
+                return Err(format!("You have not learned any skills to reset"));
+            }
+            // players from before classes pick their first class for free
+            let cost = if current.is_none() { 0 } else { config.skills_reset_cost };
+            let gold = db.get_player_gold(uuid).unwrap_or(0);
+            if gold < cost {
+                return Err(format!("Resetting your skills costs {} gold but you only have {}", cost, gold));
+            }
+            if !db.reset_skills(uuid, class.id.as_str(), cost) {
+                return Err(format!("Failed to reset your skills"));
+            }
+            info!(target:LOG_TARGET, "{} reset their skills as a {} for {} gold", username, class.id, cost);
+            if changing {
+                format!("You are now a {} and got back {} skill points for {} gold", class.name, refund, cost)
+            } else {
+                format!("You got back {} skill points for {} gold", refund, cost)
+            }
+        }
+        SkillActionKind::List | SkillActionKind::Use => return Ok(None),
+    };
+    classes.refresh(&db, uuid);
+    state.sessions.lock().unwrap().send(uuid, SessionMessage::StatsChanged);
+    Ok(Some(message))
+}
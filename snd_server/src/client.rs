@@ -1,27 +1,121 @@
+use std::io;
 use std::net::TcpStream;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::SystemTime;
+use std::time::{Duration, Instant, SystemTime};
 use log::{error, info, trace, warn};
 use rand::{Rng, thread_rng};
 use rand_distr::{Normal, Distribution};
 use uuid::Uuid;
-use crate::{ACCEPTED_CLIENT_VERSION, KEEPALIVE_INTERVAL, MOTD};
-use crate::database::{Database, LoginFailReason, PlayerValueDB};
+use crate::auction;
+use crate::bank;
+use crate::chat::{self, RateLimiter};
+use crate::class;
+use crate::command::{self, CommandContext, CommandSender};
+use crate::config::ServerConfig;
+use crate::database::{LoginFailReason, PlayerValueDB};
+use crate::duel;
+use crate::encounter;
+use crate::game_event::{self, GameEvent};
+use crate::guild;
 use snd_network_lib::to_epoch;
+use snd_network_lib::broadcast_data::BroadcastKind;
+use snd_network_lib::chat_data::{ChatChannel, ChatData};
 use snd_network_lib::client_event::{ClientEvent, read_client_event};
 use snd_network_lib::entry_point_io::read_entry_point;
 use snd_network_lib::entry_response::{write_invalid_entry_response, write_ping_entry_response, write_valid_entry_response};
+use snd_network_lib::auction_data::AuctionActionKind;
 use snd_network_lib::error_data::ErrorData;
-use snd_network_lib::item_data::ItemData;
+use snd_network_lib::inventory_data::BankActionKind;
+use snd_network_lib::mail_data::MailActionKind;
+use snd_network_lib::guild_data::GuildActionKind;
+use snd_network_lib::skill_data::SkillActionKind;
 use snd_network_lib::player_data::PlayerData;
-use snd_network_lib::server_event::{write_server_disconnect, write_server_error, write_server_event, write_server_find_item, write_server_gain_exp, write_server_inventory, write_server_item_view, write_server_keepalive, write_server_update};
+use snd_network_lib::server_event::{write_server_achievement, write_server_achievements, write_server_auctions, write_server_bank, write_server_bestiary, write_server_broadcast, write_server_chat, write_server_consumables, write_server_crafting, write_server_disconnect, write_server_encounter, write_server_error, write_server_event, write_server_find_item, write_server_gain_exp, write_server_guild, write_server_guild_bank, write_server_inventory, write_server_inventory_list, write_server_item_view, write_server_keepalive, write_server_leaderboard, write_server_mail, write_server_party, write_server_quest_offer, write_server_quests, write_server_shop, write_server_skills, write_server_update};
+use crate::consumable;
+use crate::crafting;
 use crate::item::{Item, ItemRarity, ItemType};
-use crate::player::Player;
+use crate::logging;
+use crate::mail;
+use crate::party;
+use crate::health::{self, RegenMode};
+use crate::inventory;
+use crate::player::{self, Player};
+use crate::quest;
+use crate::session::SessionMessage;
+use crate::state::ServerState;
 
 const LOG_TARGET: &str = "client_handler";
 
-pub fn handle_connection(stream: TcpStream, db: Arc<Mutex<Database>>, tarc: Arc<AtomicBool>) {
+// how long to wait for a packet before checking for messages from the rest of the server
+const PACKET_POLL_INTERVAL_MS: u64 = 100;
+
+/// the name of a client event for structured logging
+fn event_name(event: &ClientEvent) -> &'static str {
+    match event {
+        ClientEvent::Disconnect => "disconnect",
+        ClientEvent::KeepAlive(_) => "keepalive",
+        ClientEvent::Step => "step",
+        ClientEvent::OpenInv(_) => "open_inv",
+        ClientEvent::RqstUpdate => "request_update",
+        ClientEvent::DropItem(_) => "drop_item",
+        ClientEvent::InspectItem(_) => "inspect_item",
+        ClientEvent::Attack => "attack",
+        ClientEvent::TryFlee => "try_flee",
+        ClientEvent::Error(_) => "error",
+        ClientEvent::Command(_) => "command",
+        ClientEvent::Chat(_) => "chat",
+        ClientEvent::Party(_) => "party",
+        ClientEvent::Duel(_) => "duel",
+        ClientEvent::Leaderboard(_) => "leaderboard",
+        ClientEvent::Achievements => "achievements",
+        ClientEvent::Quest(_) => "quest",
+        ClientEvent::UseItem(_) => "use_item",
+        ClientEvent::Consumables => "consumables",
+        ClientEvent::Shop(_) => "shop",
+        ClientEvent::Salvage(_) => "salvage",
+        ClientEvent::Craft(_) => "craft",
+        ClientEvent::Upgrade(_) => "upgrade",
+        ClientEvent::ExpandInv => "expand_inv",
+        ClientEvent::Bank(_) => "bank",
+        ClientEvent::Auction(_) => "auction",
+        ClientEvent::Mail(_) => "mail",
+        ClientEvent::Guild(_) => "guild",
+        ClientEvent::Skill(_) => "skill",
+        ClientEvent::Bestiary => "bestiary",
+    }
+}
+
+/// sends the player's stats to their client if they changed since they were last sent
+fn send_stats(stream: &TcpStream, state: &ServerState, uuid: &Uuid, last: &mut Option<PlayerData>) -> Result<(), String> {
+    let stats = player::stats(&state.db.lock().unwrap(), &state.classes, uuid);
+    if stats.is_none() || stats == *last {
+        return Ok(());
+    }
+    write_server_update(stream, stats.clone().unwrap()).map_err(|e| e.to_string())?;
+    *last = stats;
+    Ok(())
+}
+
+/// Waits a short time for data from the client so the game loop can handle messages from the server in between.
+/// Returns true if there is a packet ready to be read
+fn packet_ready(stream: &TcpStream) -> io::Result<bool> {
+    stream.set_read_timeout(Some(Duration::from_millis(PACKET_POLL_INTERVAL_MS)))?;
+    let mut buf = [0u8; 1];
+    let ready = match stream.peek(&mut buf) {
+        Ok(0) => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the client closed the connection")),
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => Ok(false),
+        Err(e) => Err(e),
+    };
+    // reading the packet itself should block until it is complete
+    stream.set_read_timeout(None)?;
+    ready
+}
+
+pub fn handle_connection(stream: TcpStream, tarc: Arc<AtomicBool>, config: Arc<ServerConfig>, state: ServerState) {
+    let ServerState { db, sessions, .. } = state.clone();
+
     // ensure the stream is blocking as the listener was not
     if let Err(e) = stream.set_nonblocking(false) {
         error!(target:LOG_TARGET, "Failed to set a connected stream to blocking, can not handle this connection properly, dropping.");
@@ -40,6 +134,7 @@ pub fn handle_connection(stream: TcpStream, db: Arc<Mutex<Database>>, tarc: Arc<
     } else {
         ip_res.unwrap().to_string()
     };
+    logging::set_context_ip(ip.clone());
 
     // expect an entrypoint packet
     let (login, version, error) = read_entry_point(&stream);
@@ -50,18 +145,30 @@ pub fn handle_connection(stream: TcpStream, db: Arc<Mutex<Database>>, tarc: Arc<
     }
 
     if let Some(ver) = version {
-        let valid = ver == ACCEPTED_CLIENT_VERSION;
+        let valid = ver == config.accepted_client_version;
         info!(target:LOG_TARGET, "Ping request from {} was {}", ip, match valid.clone() {
             true => "valid",
             false => "invalid"
         });
-        let res = write_ping_entry_response(&stream, valid, ACCEPTED_CLIENT_VERSION.to_string());
+        let res = write_ping_entry_response(&stream, valid, config.accepted_client_version.clone());
         if res.is_err() {
             error!(target:LOG_TARGET, "Failed to send ping entry response to {}", ip);
         }
         return;
     }
 
+    // refuse connections from banned addresses before they can log in or sign up
+    if let Ok(addr) = stream.peer_addr() {
+        let ban = db.lock().unwrap().get_ip_ban(&addr.ip());
+        if let Some(b) = ban {
+            info!(target:LOG_TARGET, "Refused connection from banned address {}", ip);
+            if let Err(e) = write_invalid_entry_response(&stream, b.message()) {
+                error!(target:LOG_TARGET, "Failed to write error to {}: {}", ip, e);
+            }
+            return;
+        }
+    }
+
     info!(target:LOG_TARGET, "Accepted connection from '{}'", ip.clone());
 
     if login.is_none() {
@@ -84,13 +191,13 @@ pub fn handle_connection(stream: TcpStream, db: Arc<Mutex<Database>>, tarc: Arc<
             }
             return;
         }
-        if username.len() < 3 {
+        if username.len() < config.username_min_length {
             if let Err(e) = write_invalid_entry_response(&stream, "Username is too short") {
                 error!(target:LOG_TARGET, "Failed to write error to {}: {}", ip, e);
             }
             return;
         }
-        if username.len() > 16 {
+        if username.len() > config.username_max_length {
             if let Err(e) = write_invalid_entry_response(&stream, "Username is too long") {
                 error!(target:LOG_TARGET, "Failed to write error to {}: {}", ip, e);
             }
@@ -113,24 +220,41 @@ pub fn handle_connection(stream: TcpStream, db: Arc<Mutex<Database>>, tarc: Arc<
             }
             return;
         }
-        if passwd.len() < 4 {
+        if passwd.len() < config.password_min_length {
             if let Err(e) = write_invalid_entry_response(&stream, "Password is too short") {
                 error!(target:LOG_TARGET, "Failed to write error to {}: {}", ip, e);
             }
             return;
         }
-        if passwd.len() > 32 {
+        if passwd.len() > config.password_max_length {
             if let Err(e) = write_invalid_entry_response(&stream, "Password is too long") {
                 error!(target:LOG_TARGET, "Failed to write error to {}: {}", ip, e);
             }
             return;
         }
 
+        // players who do not pick a class get the default one
+        let class = if login_data.class.trim().is_empty() {
+            state.classes.default_class()
+        } else {
+            match state.classes.by_name(login_data.class.trim()) {
+                Some(class) => Some(class),
+                None => {
+                    let message = format!("Unknown class, pick one of: {}", state.classes.names());
+                    if let Err(e) = write_invalid_entry_response(&stream, message.as_str()) {
+                        error!(target:LOG_TARGET, "Failed to write error to {}: {}", ip, e);
+                    }
+                    return;
+                }
+            }
+        };
+
         let player = Player {
             uuid: uuid.clone(), name: login_data.username.clone(),
         };
 
-        if !db.lock().unwrap().new_player(&player, passwd) {
+        let (class_id, health_percent) = class.map_or((String::new(), 100), |c| (c.id.clone(), c.health));
+        if !db.lock().unwrap().new_player(&player, passwd, class_id.as_str(), health_percent) {
             if let Err(e) = write_invalid_entry_response(&stream, "Failed to enter data into the database"){
                 error!(target:LOG_TARGET, "Failed to write error to {}: {}", ip, e);
             }
@@ -147,6 +271,7 @@ pub fn handle_connection(stream: TcpStream, db: Arc<Mutex<Database>>, tarc: Arc<
                 LoginFailReason::Unrecognized => write_invalid_entry_response(&stream, "Invalid User"),
                 LoginFailReason::Unauthorized => write_invalid_entry_response(&stream, "Invalid Password"),
                 LoginFailReason::AlreadyOnline => write_invalid_entry_response(&stream, "Already Online"),
+                LoginFailReason::Banned(ban) => write_invalid_entry_response(&stream, ban.message()),
             };
             if let Err(e) = res {
                 error!(target:LOG_TARGET, "Failed to write invalid login data to {}: {}", ip, e);
@@ -163,35 +288,142 @@ pub fn handle_connection(stream: TcpStream, db: Arc<Mutex<Database>>, tarc: Arc<
         uuid = set_uuid.unwrap();
     };
 
-    if let Err(e) = write_valid_entry_response(&stream, MOTD.to_string()) {
+    if let Err(e) = write_valid_entry_response(&stream, config.motd.clone()) {
         error!(target:LOG_TARGET, "Failed to send entry response to {}: {}", ip, e);
         return;
     }
 
+    logging::set_context_player(&uuid);
     info!(target:LOG_TARGET, "User {} logged in with the uuid {}", username, uuid);
 
     let mut last_keepalive = SystemTime::now();
     let mut expecting_keepalive = false;
     let mut ping = 0;
-
-    db.lock().unwrap().set_player_active(&uuid);
+    // the stats last sent to the client, they are only sent again when they change
+    let mut last_stats: Option<PlayerData> = None;
+    let mut last_regen = Instant::now();
+    let mut chat_limiter = RateLimiter::new(config.chat_rate_limit_messages, config.chat_rate_limit_seconds);
+
+    {
+        let db = db.lock().unwrap();
+        db.set_player_active(&uuid);
+        // the classes file may have changed since the player last logged in
+        state.classes.refresh(&db, &uuid);
+    }
+    let messages = sessions.lock().unwrap().register(&uuid, username.clone(), ip.clone());
+    // the shutdown message is only sent to registered players, so catch a shutdown that started while logging in
+    if tarc.load(Ordering::SeqCst) {
+        let _ = write_server_error(&stream, ErrorData { msg: format!("The server is shutting down!"), disconnect: true });
+        sessions.lock().unwrap().remove(&uuid);
+        db.lock().unwrap().set_player_inactive(&uuid);
+        return;
+    }
+    if let Err(e) = send_stats(&stream, &state, &uuid, &mut last_stats) {
+        error!(target:LOG_TARGET, "Failed to send stats to {}: {}", ip, e);
+    }
+    // players from before classes are asked to pick one
+    if state.classes.default_class().is_some() && state.classes.of(&db.lock().unwrap(), &uuid).is_none() {
+        if let Err(e) = write_server_event(&stream, "You do not have a class yet, type 'skills' to pick one") {
+            error!(target:LOG_TARGET, "Failed to send class notice to {}: {}", ip, e);
+        }
+    }
 
     // game loop
-    loop {
-        // check if the server is being shutdown
-        if tarc.load(Ordering::SeqCst) {
-            if let Err(e) = write_server_error(&stream, ErrorData { msg: format!("The server is shutting down!"), disconnect: true}) {
-                error!(target:LOG_TARGET, "Failed to send shutdown message to {}: {}", ip, e);
-                break;
+    'game: loop {
+        // handle messages sent to this player from the rest of the server, including shutting down
+        while let Ok(msg) = messages.try_recv() {
+            match msg {
+                SessionMessage::Event(s) => {
+                    if let Err(e) = write_server_event(&stream, s) {
+                        error!(target:LOG_TARGET, "Failed to send event to {}: {}", ip, e);
+                        break 'game;
+                    }
+                }
+                SessionMessage::Kick(reason) => {
+                    info!(target:LOG_TARGET, "Kicking {}: {}", username, reason);
+                    let _ = write_server_error(&stream, ErrorData { msg: reason, disconnect: true });
+                    break 'game;
+                }
+                SessionMessage::Chat(chat) => {
+                    if let Err(e) = write_server_chat(&stream, chat) {
+                        error!(target:LOG_TARGET, "Failed to send chat message to {}: {}", ip, e);
+                        break 'game;
+                    }
+                }
+                SessionMessage::Broadcast(broadcast) => {
+                    if let Err(e) = write_server_broadcast(&stream, broadcast) {
+                        error!(target:LOG_TARGET, "Failed to send broadcast to {}: {}", ip, e);
+                        break 'game;
+                    }
+                }
+                SessionMessage::Encounter(data) => {
+                    if let Err(e) = write_server_encounter(&stream, data) {
+                        error!(target:LOG_TARGET, "Failed to send encounter update to {}: {}", ip, e);
+                        break 'game;
+                    }
+                }
+                SessionMessage::Party(members) => {
+                    if let Err(e) = write_server_party(&stream, members) {
+                        error!(target:LOG_TARGET, "Failed to send party status to {}: {}", ip, e);
+                        break 'game;
+                    }
+                }
+                SessionMessage::Achievement(achievement) => {
+                    if let Err(e) = write_server_achievement(&stream, achievement) {
+                        error!(target:LOG_TARGET, "Failed to send achievement to {}: {}", ip, e);
+                        break 'game;
+                    }
+                }
+                SessionMessage::QuestOffer(quest) => {
+                    if let Err(e) = write_server_quest_offer(&stream, quest) {
+                        error!(target:LOG_TARGET, "Failed to send quest offer to {}: {}", ip, e);
+                        break 'game;
+                    }
+                }
+                SessionMessage::Quests(quests) => {
+                    if let Err(e) = write_server_quests(&stream, quests) {
+                        error!(target:LOG_TARGET, "Failed to send quest log to {}: {}", ip, e);
+                        break 'game;
+                    }
+                }
+                SessionMessage::StatsChanged => {
+                    if let Err(e) = send_stats(&stream, &state, &uuid, &mut last_stats) {
+                        error!(target:LOG_TARGET, "Failed to send stats to {}: {}", ip, e);
+                        break 'game;
+                    }
+                }
+                SessionMessage::Shutdown(msg) => {
+                    if let Err(e) = write_server_error(&stream, ErrorData { msg, disconnect: true }) {
+                        error!(target:LOG_TARGET, "Failed to send shutdown message to {}: {}", ip, e);
+                    }
+                    break 'game;
+                }
             }
         }
 
+        // heal over time outside of fights
+        if config.regen_mode == RegenMode::Time && last_regen.elapsed() >= Duration::from_secs(config.regen_interval) {
+            last_regen = Instant::now();
+            let fighting = state.encounters.lock().unwrap().in_fight(&uuid);
+            if !fighting && health::regen(&db.lock().unwrap(), &config, &uuid) {
+                party::refresh(&state, &[uuid.clone()]);
+                if let Err(e) = send_stats(&stream, &state, &uuid, &mut last_stats) {
+                    error!(target:LOG_TARGET, "Failed to send stats to {}: {}", ip, e);
+                    break;
+                }
+            }
+        }
+
+        // skip the player whose turn it is in this player's fight if they are taking too long
+        encounter::check_turn_timeout(&state, &config, &uuid);
+        duel::check_turn_timeout(&state, &config, &uuid);
+
         // check keepalive
         let now = SystemTime::now();
         let duration = now.duration_since(last_keepalive)
             .expect("Fatal error occurred: System time moved backwards! Are you a time traveler?")
             .as_secs();
-        if duration >= KEEPALIVE_INTERVAL {
+        if duration >= config.keepalive_interval {
             if !expecting_keepalive { // if there is not a keepalive expected, send a request
                 if let Err(e) = write_server_keepalive(&stream) {
                     error!(target:LOG_TARGET, "Failed to write keepalive request to {}: {}", ip, e);
@@ -209,7 +441,16 @@ pub fn handle_connection(stream: TcpStream, db: Arc<Mutex<Database>>, tarc: Arc<
         }
 
         // expect a client event from the user
+        match packet_ready(&stream) {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(e) => {
+                info!(target:LOG_TARGET, "Lost connection to {}: {}", ip, e);
+                break;
+            }
+        }
         let event = read_client_event(&stream);
+        let _event_context = logging::EventContext::enter(event_name(&event));
         match event {
             ClientEvent::Disconnect => {
                 // if the user sends that it disconnected, drop the connection properly
@@ -223,35 +464,38 @@ pub fn handle_connection(stream: TcpStream, db: Arc<Mutex<Database>>, tarc: Arc<
                     continue;
                 }
                 // calculate the ping
-                ping = a - (to_epoch(last_keepalive).as_secs() - KEEPALIVE_INTERVAL);
+                ping = a - (to_epoch(last_keepalive).as_secs() - config.keepalive_interval);
                 trace!(target:LOG_TARGET, "Connection with {} has ping {}", ip.clone(), ping.clone());
                 // set flag
                 expecting_keepalive = false;
             }
             ClientEvent::RqstUpdate => {
-                let pd = PlayerData {
-                    level: db.lock().unwrap().get_player_level(&uuid).unwrap(),
-                    exp: db.lock().unwrap().get_player_exp(&uuid).unwrap(),
-                    health: db.lock().unwrap().get_player_health(&uuid).unwrap(),
-                    steps: db.lock().unwrap().get_player_steps(&uuid).unwrap(),
-                    region: db.lock().unwrap().get_player_region(&uuid).unwrap(),
-                };
-
-                if let Err(e) = write_server_update(&stream, pd) {
-                    error!(target:LOG_TARGET, "Failed to write update to {} connected at ip {}: {}", username, ip, e);
-                    break;
-                }
+                // stats are sent after every event when they change, so forgetting them sends them again
+                last_stats = None;
             }
             ClientEvent::Step => {
+                if state.encounters.lock().unwrap().in_fight(&uuid) || state.duels.lock().unwrap().in_duel(&uuid) {
+                    if let Err(e) = write_server_event(&stream, "You can not walk away from a fight! Attack or flee.") {
+                        error!(target:LOG_TARGET, "Failed to send event to {}: {}", ip, e);
+                        break;
+                    }
+                    continue;
+                }
                 // increment the player's total step count
                 if !db.lock().unwrap().inc_player_steps(&uuid) {
                     warn!(target:LOG_TARGET, "Player {} took a step but the database failed to write steps", username);
                 }
+                if health::regen_step(&db.lock().unwrap(), &config, &uuid) {
+                    party::refresh(&state, &[uuid.clone()]);
+                }
 
                 // randomly select between gaining exp, finding an item, or having an encounter
-                // todo(eric): add finding items and encounters
-
-                let rng = thread_rng().gen_range(0..100);
+                let abilities = encounter::abilities(&db.lock().unwrap(), &uuid);
+                let mut rng = thread_rng().gen_range(0..100);
+                // step luck turns some of the steps that would give exp into finding an item
+                if rng < 80 && thread_rng().gen_range(0..100) < abilities.step_luck {
+                    rng = 80;
+                }
 
                 match rng {
                     // 60% - Gain EXP
@@ -264,7 +508,7 @@ pub fn handle_connection(stream: TcpStream, db: Arc<Mutex<Database>>, tarc: Arc<
                             break;
                         }
                         let rnd = normal_res.unwrap().sample(&mut thread_rng()) as u32;
-                        let amt = rnd.min(10).max(2);
+                        let amt = abilities.exp(rnd.min(10).max(2));
                         if let Err(e) = write_server_gain_exp(&stream, amt.clone()) {
                             error!(target:LOG_TARGET, "Failed to send exp gain to client: {}", e);
                             break;
@@ -274,7 +518,23 @@ pub fn handle_connection(stream: TcpStream, db: Arc<Mutex<Database>>, tarc: Arc<
                         // check if the player needs to level up
                         db.lock().unwrap().check_levelup(&uuid);
                     }
-                    // 10% - Find Item
+                    // 10% - Find Item, some of them are consumables
+                    _ if rng < 90 && thread_rng().gen_range(0..100) < config.consumable_find_chance => {
+                        if let Some(found) = state.consumables.rand_drop() {
+                            db.lock().unwrap().add_consumable(&uuid, found.id.as_str(), 1, config.consumable_max_stack);
+                            if let Err(e) = write_server_event(&stream, format!("You found a {}!", found.name)) {
+                                error!(target:LOG_TARGET, "error sending found consumable to {}: {}", ip, e);
+                                break;
+                            }
+                        }
+                    }
+                    _ if rng < 90 && inventory::is_full(&db.lock().unwrap(), &config, &uuid) => {
+                        if let Err(e) = write_server_event(&stream,
+                            format!("You found an item but your inventory is full! 'inv expand' buys more space")) {
+                            error!(target:LOG_TARGET, "error sending full inventory to {}: {}", ip, e);
+                            break;
+                        }
+                    }
                     _ if rng < 90 => {
                         let found_item = Item::new_rand(ItemType::rand(), &uuid,
                                                         db.lock().unwrap().get_player_level(&uuid).unwrap_or(0),
@@ -284,25 +544,67 @@ pub fn handle_connection(stream: TcpStream, db: Arc<Mutex<Database>>, tarc: Arc<
                             error!(target:LOG_TARGET, "error sending found item to {}: {}", ip, e);
                             break;
                         }
+                        if found_item.rarity == ItemRarity::Legendary {
+                            sessions.lock().unwrap().announce(BroadcastKind::Loot,
+                                format!("{} found a Legendary item: {}!", username, found_item.name));
+                        }
+                        game_event::fire(&state, &uuid, GameEvent::FindItem(found_item.item_type, found_item.rarity));
                     }
-                    // 10% - Encounter enemy
+                    // 10% - Encounter enemy, the whole party joins the fight
                     _ if rng < 100 => {
-                        // todo(eric): add encounters
+                        encounter::start(&state, &uuid);
                     }
                     _ => { unreachable!() }
                 }
+                game_event::fire(&state, &uuid, GameEvent::Step);
+                // quest givers are only met outside of fights
+                if thread_rng().gen_range(0..100) < config.quest_giver_chance
+                    && !state.encounters.lock().unwrap().in_fight(&uuid) {
+                    quest::offer(&state, &uuid);
+                }
             }
-            ClientEvent::OpenInv => {
+            ClientEvent::OpenInv(request) => {
                 // get the player's inventory from the database and send it to the client to display
-                let inv = db.lock().unwrap().get_player_items(&uuid);
-                if let Err(e) = write_server_inventory(&stream,
-                                                       inv.unwrap_or(Vec::new())
-                                                           .iter().map(|i| { i.as_data() })
-                                                           .collect::<Vec<ItemData>>()) {
+                // older clients can not read a sorted page, they get every item as a plain list
+                let legacy = request.is_none();
+                let inv = inventory::view(&db.lock().unwrap(), &config, &uuid, &request.unwrap_or_default());
+                let sent = if legacy { write_server_inventory_list(&stream, inv.items) } else { write_server_inventory(&stream, inv) };
+                if let Err(e) = sent {
                     error!(target:LOG_TARGET, "error sending inventory to {}: {}", ip, e);
                     break;
                 }
             }
+            ClientEvent::ExpandInv => {
+                let message = inventory::expand(&state, &config, &uuid, username.as_str()).unwrap_or_else(|e| e);
+                if let Err(e) = write_server_event(&stream, message) {
+                    error!(target:LOG_TARGET, "Failed to send event to {}: {}", ip, e);
+                    break;
+                }
+            }
+            ClientEvent::Achievements => {
+                let achievements = state.achievements.list(&db.lock().unwrap(), &uuid);
+                if let Err(e) = write_server_achievements(&stream, achievements) {
+                    error!(target:LOG_TARGET, "error sending achievements to {}: {}", ip, e);
+                    break;
+                }
+            }
+            ClientEvent::Bestiary => {
+                let entries = state.bestiary.view(&db.lock().unwrap(), &uuid);
+                if let Err(e) = write_server_bestiary(&stream, entries) {
+                    error!(target:LOG_TARGET, "error sending the bestiary to {}: {}", ip, e);
+                    break;
+                }
+            }
+            ClientEvent::Leaderboard(request) => {
+                let leaderboard = {
+                    let mut leaderboards = state.leaderboards.lock().unwrap();
+                    leaderboards.get(&db.lock().unwrap(), &config, request.category, request.page)
+                };
+                if let Err(e) = write_server_leaderboard(&stream, leaderboard) {
+                    error!(target:LOG_TARGET, "error sending leaderboard to {}: {}", ip, e);
+                    break;
+                }
+            }
             ClientEvent::DropItem(item_name) => {
                 // avoid sql injections :)
                 let safe_name = item_name.escape_debug().to_string().replace("'", "");
@@ -389,10 +691,263 @@ pub fn handle_connection(stream: TcpStream, db: Arc<Mutex<Database>>, tarc: Arc<
                 }
             }
             ClientEvent::Attack => {
-                // todo(eric): game logic
+                // the same attack is used for fighting enemies and other players
+                let in_duel = state.duels.lock().unwrap().in_duel(&uuid);
+                let result = if in_duel {
+                    duel::attack(&state, &uuid)
+                } else {
+                    encounter::attack(&state, &config, &uuid)
+                };
+                if let Err(e) = result {
+                    if let Err(e) = write_server_event(&stream, e) {
+                        error!(target:LOG_TARGET, "Failed to send event to {}: {}", ip, e);
+                        break;
+                    }
+                }
             }
             ClientEvent::TryFlee => {
-                // todo(eric): game logic
+                let result = if state.duels.lock().unwrap().in_duel(&uuid) {
+                    Err(format!("You can not flee from a duel, use 'duel forfeit' to give up"))
+                } else {
+                    encounter::flee(&state, &config, &uuid)
+                };
+                if let Err(e) = result {
+                    if let Err(e) = write_server_event(&stream, e) {
+                        error!(target:LOG_TARGET, "Failed to send event to {}: {}", ip, e);
+                        break;
+                    }
+                }
+            }
+            ClientEvent::Party(action) => {
+                if let Err(e) = party::handle_action(&state, &config, &uuid, username.as_str(), action) {
+                    if let Err(e) = write_server_event(&stream, e) {
+                        error!(target:LOG_TARGET, "Failed to send event to {}: {}", ip, e);
+                        break;
+                    }
+                }
+            }
+            ClientEvent::Duel(action) => {
+                if let Err(e) = duel::handle_action(&state, &config, &uuid, username.as_str(), action) {
+                    if let Err(e) = write_server_event(&stream, e) {
+                        error!(target:LOG_TARGET, "Failed to send event to {}: {}", ip, e);
+                        break;
+                    }
+                }
+            }
+            ClientEvent::Quest(action) => {
+                if let Err(e) = quest::handle_action(&state, &config, &uuid, username.as_str(), action) {
+                    if let Err(e) = write_server_event(&stream, e) {
+                        error!(target:LOG_TARGET, "Failed to send event to {}: {}", ip, e);
+                        break;
+                    }
+                }
+            }
+            ClientEvent::UseItem(name) => {
+                if let Err(e) = consumable::use_item(&state, &config, &uuid, username.as_str(), name.as_str()) {
+                    if let Err(e) = write_server_event(&stream, e) {
+                        error!(target:LOG_TARGET, "Failed to send event to {}: {}", ip, e);
+                        break;
+                    }
+                }
+            }
+            ClientEvent::Consumables => {
+                let owned = state.consumables.owned(&db.lock().unwrap(), &uuid);
+                if let Err(e) = write_server_consumables(&stream, owned) {
+                    error!(target:LOG_TARGET, "error sending consumables to {}: {}", ip, e);
+                    break;
+                }
+            }
+            ClientEvent::Shop(action) => {
+                let sent = match consumable::shop(&state, &config, &uuid, username.as_str(), action) {
+                    Ok(Some(stock)) => write_server_shop(&stream, stock),
+                    Ok(None) => Ok(()),
+                    Err(e) => write_server_event(&stream, e),
+                };
+                if let Err(e) = sent {
+                    error!(target:LOG_TARGET, "Failed to send shop to {}: {}", ip, e);
+                    break;
+                }
+            }
+            ClientEvent::Salvage(item_name) => {
+                let message = crafting::salvage(&state, &uuid, username.as_str(), item_name.as_str()).unwrap_or_else(|e| e);
+                if let Err(e) = write_server_event(&stream, message) {
+                    error!(target:LOG_TARGET, "Failed to send event to {}: {}", ip, e);
+                    break;
+                }
+            }
+            ClientEvent::Craft(recipe) => {
+                // crafting sends the recipes again so the player sees what they have left
+                if !recipe.is_empty() {
+                    let message = match crafting::craft(&state, &config, &uuid, username.as_str(), recipe.as_str()) {
+                        Ok(item) => {
+                            if item.rarity == ItemRarity::Legendary {
+                                sessions.lock().unwrap().announce(BroadcastKind::Loot,
+                                    format!("{} crafted a Legendary item: {}!", username, item.name));
+                            }
+                            format!("You crafted a {} {}: '{}'", item.rarity.to_string(), item.item_type.to_string(), item.name)
+                        }
+                        Err(e) => e,
+                    };
+                    if let Err(e) = write_server_event(&stream, message) {
+                        error!(target:LOG_TARGET, "Failed to send event to {}: {}", ip, e);
+                        break;
+                    }
+                }
+                let view = state.crafting.view(&db.lock().unwrap(), &uuid);
+                if let Err(e) = write_server_crafting(&stream, view) {
+                    error!(target:LOG_TARGET, "error sending recipes to {}: {}", ip, e);
+                    break;
+                }
+            }
+            ClientEvent::Upgrade(item_name) => {
+                let message = crafting::upgrade(&state, &config, &uuid, username.as_str(), item_name.as_str()).unwrap_or_else(|e| e);
+                if let Err(e) = write_server_event(&stream, message) {
+                    error!(target:LOG_TARGET, "Failed to send event to {}: {}", ip, e);
+                    break;
+                }
+            }
+            ClientEvent::Bank(action) => {
+                let result = match action.kind {
+                    BankActionKind::List => bank::check_access(&state, &config, &uuid).map(|_| None),
+                    BankActionKind::Deposit => bank::deposit(&state, &config, &uuid, username.as_str(), action.item.as_str()).map(Some),
+                    BankActionKind::Withdraw => bank::withdraw(&state, &config, &uuid, username.as_str(), action.item.as_str()).map(Some),
+                };
+                let sent = match result {
+                    // the bank is sent again so the player sees what is in it now
+                    Ok(message) => message.map_or(Ok(()), |m| write_server_event(&stream, m))
+                        .and_then(|_| {
+                            let view = bank::view(&db.lock().unwrap(), &config, &uuid);
+                            write_server_bank(&stream, view)
+                        }),
+                    Err(e) => write_server_event(&stream, e),
+                };
+                if let Err(e) = sent {
+                    error!(target:LOG_TARGET, "Failed to send bank to {}: {}", ip, e);
+                    break;
+                }
+            }
+            ClientEvent::Auction(action) => {
+                let result = match action.kind {
+                    AuctionActionKind::Search => auction::check_access(&state, &uuid).map(|_| None),
+                    AuctionActionKind::Sell => auction::sell(&state, &config, &uuid, username.as_str(), &action).map(Some),
+                    AuctionActionKind::Bid => auction::bid(&state, &config, &uuid, username.as_str(), action.listing, action.price).map(Some),
+                    AuctionActionKind::Buyout => auction::buyout(&state, &config, &uuid, username.as_str(), action.listing).map(Some),
+                    AuctionActionKind::Cancel => auction::cancel(&state, &config, &uuid, username.as_str(), action.listing).map(Some),
+                };
+                let sent = match result {
+                    // the search is sent again so the player sees the listings as they are now
+                    Ok(message) => message.map_or(Ok(()), |m| write_server_event(&stream, m))
+                        .and_then(|_| {
+                            let listings = auction::search(&db.lock().unwrap(), &config, &uuid, &action.search);
+                            write_server_auctions(&stream, listings)
+                        }),
+                    Err(e) => write_server_event(&stream, e),
+                };
+                if let Err(e) = sent {
+                    error!(target:LOG_TARGET, "Failed to send auctions to {}: {}", ip, e);
+                    break;
+                }
+            }
+            ClientEvent::Mail(action) => {
+                let result = match action.kind {
+                    MailActionKind::List => Ok(None),
+                    MailActionKind::Read => mail::read(&state, &uuid, action.number).map(|_| None),
+                    MailActionKind::Claim => mail::claim(&state, &config, &uuid, username.as_str(), action.number).map(Some),
+                    MailActionKind::Delete => mail::delete(&state, &uuid, action.number).map(Some),
+                    MailActionKind::Send => mail::send(&state, &config, &uuid, username.as_str(), &action).map(Some),
+                };
+                let sent = match result {
+                    // the mailbox is sent again so the player sees what is in it now
+                    Ok(message) => message.map_or(Ok(()), |m| write_server_event(&stream, m))
+                        .and_then(|_| {
+                            let mailbox = mail::mailbox(&db.lock().unwrap(), &uuid);
+                            write_server_mail(&stream, mailbox)
+                        }),
+                    Err(e) => write_server_event(&stream, e),
+                };
+                if let Err(e) = sent {
+                    error!(target:LOG_TARGET, "Failed to send mail to {}: {}", ip, e);
+                    break;
+                }
+            }
+            ClientEvent::Guild(action) => {
+                let sent = match guild::handle_action(&state, &config, &uuid, username.as_str(), &action) {
+                    // the guild or its bank is sent again so the player sees it as it is now
+                    Ok(message) => message.map_or(Ok(()), |m| write_server_event(&stream, m)).and_then(|_| match action.kind {
+                        GuildActionKind::Bank | GuildActionKind::Deposit | GuildActionKind::Withdraw => {
+                            let view = guild::bank_view(&db.lock().unwrap(), &config, &uuid);
+                            write_server_guild_bank(&stream, view)
+                        }
+                        GuildActionKind::Info => write_server_guild(&stream, guild::view(&state, &uuid, action.target.trim())),
+                        _ => write_server_guild(&stream, guild::view(&state, &uuid, "")),
+                    }),
+                    Err(e) => write_server_event(&stream, e),
+                };
+                if let Err(e) = sent {
+                    error!(target:LOG_TARGET, "Failed to send guild to {}: {}", ip, e);
+                    break;
+                }
+            }
+            ClientEvent::Skill(action) => {
+                let sent = match class::handle_action(&state, &config, &uuid, username.as_str(), &action) {
+                    // skills used in a fight answer through the fight instead
+                    Ok(_) if action.kind == SkillActionKind::Use => Ok(()),
+                    Ok(message) => message.map_or(Ok(()), |m| write_server_event(&stream, m))
+                        .and_then(|_| {
+                            let tree = state.classes.tree(&db.lock().unwrap(), &uuid);
+                            write_server_skills(&stream, tree)
+                        }),
+                    Err(e) => write_server_event(&stream, e),
+                };
+                if let Err(e) = sent {
+                    error!(target:LOG_TARGET, "Failed to send skills to {}: {}", ip, e);
+                    break;
+                }
+            }
+            ClientEvent::Command(line) => {
+                // the role is read each time so changes from the console apply immediately
+                let sender = CommandSender::Player {
+                    uuid: uuid.clone(),
+                    username: username.clone(),
+                    role: db.lock().unwrap().get_player_role(&uuid),
+                };
+                let ctx = CommandContext {
+                    db: Arc::clone(&db),
+                    sessions: Arc::clone(&sessions),
+                    terminate: Arc::clone(&tarc),
+                    config: Arc::clone(&config),
+                };
+                info!(target:LOG_TARGET, "{} issued the command '{}'", username, line);
+                let out = match command::execute(&ctx, &sender, line.as_str()) {
+                    Ok(out) => out,
+                    Err(e) => e,
+                };
+                if out.is_empty() {
+                    continue;
+                }
+                if let Err(e) = write_server_event(&stream, out) {
+                    error!(target:LOG_TARGET, "Failed to send command output to {}: {}", ip, e);
+                    break;
+                }
+            }
+            ClientEvent::Chat(chat_data) => {
+                let result = if chat_limiter.try_send() {
+                    chat::route(&db, &sessions, &config, &uuid, username.as_str(), chat_data)
+                } else {
+                    Err(format!("You are sending messages too quickly, slow down!"))
+                };
+                // errors are shown in the player's chat as a system message
+                if let Err(e) = result {
+                    let system = ChatData {
+                        channel: ChatChannel::System,
+                        sender: String::new(), target: String::new(),
+                        message: e,
+                    };
+                    if let Err(e) = write_server_chat(&stream, system) {
+                        error!(target:LOG_TARGET, "Failed to send chat message to {}: {}", ip, e);
+                        break;
+                    }
+                }
             }
             ClientEvent::Error(err) => {
                 error!(target:LOG_TARGET, "{} encountered an error: {}", ip, err.msg);
@@ -402,8 +957,17 @@ pub fn handle_connection(stream: TcpStream, db: Arc<Mutex<Database>>, tarc: Arc<
             }
         }
 
+        if let Err(e) = send_stats(&stream, &state, &uuid, &mut last_stats) {
+            error!(target:LOG_TARGET, "Failed to send stats to {}: {}", ip, e);
+            break;
+        }
     }
 
     // clean up stuff and properly disconnect the user
+    duel::disconnect(&state, &uuid, username.as_str());
+    encounter::disconnect(&state, &config, &uuid, username.as_str());
+    party::disconnect(&state, &uuid, username.as_str());
+    quest::disconnect(&state, &uuid);
+    sessions.lock().unwrap().remove(&uuid);
     db.lock().unwrap().set_player_inactive(&uuid);
 }
\ No newline at end of file
@@ -0,0 +1,675 @@
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use std::net::SocketAddr;
+use log::{info, warn};
+use uuid::Uuid;
+use snd_network_lib::broadcast_data::BroadcastKind;
+use snd_network_lib::systime;
+use crate::config::ServerConfig;
+use crate::database::{Database, PlayerValueDB};
+use crate::item::{Item, ItemRarity, ItemType};
+use crate::mail;
+use crate::moderation::{Ban, cidr_contains, expires_at, format_time, is_valid_cidr, parse_duration};
+use crate::permission::{Permission, Role};
+use crate::player::SKILL_POINTS_PER_LEVEL;
+use crate::session::{SessionMessage, SessionRegistry};
+
+const LOG_TARGET: &str = "command";
+
+/// the times (in seconds before shutdown) to warn players of a delayed shutdown
+const SHUTDOWN_WARNINGS: [u64; 8] = [300, 60, 30, 10, 5, 3, 2, 1];
+
+/// every command's usage and description along with the permission needed to use it
+const HELP: [(&str, &str, Option<Permission>); 24] = [
+    ("help", "show this message", None),
+    ("ignore <user>", "hide a player's chat messages", None),
+    ("unignore <user>", "show a player's chat messages again", None),
+    ("ignores", "list the players you are ignoring", None),
+    ("list", "list online players", Some(Permission::ListPlayers)),
+    ("kick <user> [reason]", "disconnect a player", Some(Permission::Kick)),
+    ("ban <user> [duration] [reason]", "ban a player and disconnect them", Some(Permission::Ban)),
+    ("unban <user>", "remove a player's ban", Some(Permission::Ban)),
+    ("bans", "list active bans", Some(Permission::Ban)),
+    ("mute <user> [duration] [reason]", "stop a player from chatting", Some(Permission::Mute)),
+    ("unmute <user>", "let a player chat again", Some(Permission::Mute)),
+    ("ipban <ip/cidr|user> [duration] [reason]", "ban an address or range", Some(Permission::IpBan)),
+    ("unipban <ip/cidr>", "remove an ip ban", Some(Permission::IpBan)),
+    ("audit [count]", "show recent moderation actions", Some(Permission::ViewAudit)),
+    ("broadcast <message>", "send a message to every online player", Some(Permission::Broadcast)),
+    ("invsee <user>", "list the items in a player's inventory", Some(Permission::InspectInventory)),
+    ("inspect <user> <item>", "view an item in a player's inventory", Some(Permission::InspectInventory)),
+    ("give <user> <type> <rarity> [level]", "give a player a new item", Some(Permission::Give)),
+    ("sysmail <user|all> <gold> [type rarity] <message>", "mail gold, an item and a message from the server", Some(Permission::Give)),
+    ("setlevel <user> <level>", "set a player's level", Some(Permission::SetLevel)),
+    ("tp <user> <region>", "move a player to a region", Some(Permission::Teleport)),
+    ("role <user> [player|moderator|admin]", "view or change a player's role", Some(Permission::ManageRoles)),
+    ("roles", "list every staff member", Some(Permission::ManageRoles)),
+    ("shutdown [delay]", "shut the server down after delay seconds", Some(Permission::Shutdown)),
+];
+
+/// Everything a command needs to operate on the live server
+#[derive(Clone)]
+pub struct CommandContext {
+    pub db: Arc<Mutex<Database>>,
+    pub sessions: Arc<Mutex<SessionRegistry>>,
+    pub terminate: Arc<AtomicBool>,
+    pub config: Arc<ServerConfig>,
+}
+
+/// Who is running a command, recorded in the audit log
+#[derive(Clone, Debug)]
+pub enum CommandSender {
+    Console,
+    Player { uuid: Uuid, username: String, role: Role },
+}
+
+impl CommandSender {
+    pub fn name(&self) -> String {
+        match self {
+            Self::Console => format!("console"),
+            Self::Player { username, .. } => username.clone(),
+        }
+    }
+
+    /// the console can do anything, players are limited by their role
+    pub fn has(&self, permission: Permission) -> bool {
+        match self {
+            Self::Console => true,
+            Self::Player { role, .. } => role.has(permission),
+        }
+    }
+}
+
+/// the permission needed to run a command, None if anyone can run it
+fn required_permission(cmd: &str) -> Option<Permission> {
+    match cmd {
+        "list" => Some(Permission::ListPlayers),
+        "kick" => Some(Permission::Kick),
+        "ban" | "unban" | "bans" => Some(Permission::Ban),
+        "mute" | "unmute" => Some(Permission::Mute),
+        "ipban" | "unipban" => Some(Permission::IpBan),
+        "audit" => Some(Permission::ViewAudit),
+        "broadcast" | "say" => Some(Permission::Broadcast),
+        "invsee" | "inspect" => Some(Permission::InspectInventory),
+        "give" | "sysmail" => Some(Permission::Give),
+        "setlevel" => Some(Permission::SetLevel),
+        "tp" => Some(Permission::Teleport),
+        "role" | "roles" => Some(Permission::ManageRoles),
+        "shutdown" | "stop" => Some(Permission::Shutdown),
+        _ => None,
+    }
+}
+
+/// Runs a command line, returning the output to show to whoever ran it
+pub fn execute(ctx: &CommandContext, sender: &CommandSender, line: &str) -> Result<String, String> {
+    let mut args = line.split_whitespace().collect::<Vec<&str>>();
+    if args.is_empty() {
+        return Ok(String::new());
+    }
+    let cmd = args.remove(0).to_ascii_lowercase();
+
+    if let Some(permission) = required_permission(cmd.as_str()) {
+        if !sender.has(permission) {
+            warn!(target:LOG_TARGET, "{} tried to use '{}' without permission", sender.name(), cmd);
+            return Err(format!("You do not have permission to use '{}'", cmd));
+        }
+    }
+
+    match cmd.as_str() {
+        "help" | "?" => Ok(help(sender)),
+        "list" => list(ctx),
+        "kick" => kick(ctx, sender, &args),
+        "ban" => ban(ctx, sender, &args),
+        "unban" => unban(ctx, sender, &args),
+        "ipban" => ip_ban(ctx, sender, &args),
+        "unipban" => ip_unban(ctx, sender, &args),
+        "bans" => bans(ctx),
+        "mute" => mute(ctx, sender, &args),
+        "unmute" => unmute(ctx, sender, &args),
+        "ignore" => ignore(ctx, sender, &args, true),
+        "unignore" => ignore(ctx, sender, &args, false),
+        "ignores" => ignores(ctx, sender),
+        "audit" => audit_log(ctx, &args),
+        "broadcast" | "say" => broadcast(ctx, sender, &args),
+        "give" => give(ctx, sender, &args),
+        "sysmail" => system_mail(ctx, sender, &args),
+        "setlevel" => set_level(ctx, sender, &args),
+        "tp" => teleport(ctx, sender, &args),
+        "invsee" => inventory(ctx, &args),
+        "inspect" => inspect(ctx, &args),
+        "role" => role(ctx, sender, &args),
+        "roles" => roles(ctx),
+        "shutdown" | "stop" => shutdown(ctx, sender, &args),
+        _ => Err(format!("Unknown command '{}', type 'help' for a list of commands", cmd)),
+    }
+}
+
+/// finds a player's uuid by username, checking online players first
+fn find_player(ctx: &CommandContext, username: &str) -> Result<Uuid, String> {
+    if let Some(uuid) = ctx.sessions.lock().unwrap().find_by_name(username) {
+        return Ok(uuid);
+    }
+    let safe_name = username.escape_debug().to_string().replace("'", "");
+    ctx.db.lock().unwrap().uuid_from_username(safe_name)
+        .ok_or(format!("No player named '{}' exists", username))
+}
+
+fn usage<T>(usage: &str) -> Result<T, String> {
+    Err(format!("Usage: {}", usage))
+}
+
+/// records a moderation action in the audit log and the server log
+fn audit(ctx: &CommandContext, sender: &CommandSender, action: &str, target: &str, details: &str) {
+    let actor = sender.name();
+    if details.is_empty() {
+        info!(target:LOG_TARGET, "{} used {} on {}", actor, action, target);
+    } else {
+        info!(target:LOG_TARGET, "{} used {} on {}: {}", actor, action, target, details);
+    }
+    if !ctx.db.lock().unwrap().log_moderation(actor.as_str(), action, target, details) {
+        warn!(target:LOG_TARGET, "Failed to write {} by {} to the audit log", action, actor);
+    }
+}
+
+/// splits the arguments after a target into an optional ban duration and a reason
+fn duration_and_reason(args: &[&str], default_reason: &str) -> (Option<u64>, String) {
+    let mut duration = None;
+    let mut rest = args;
+    if let Some(first) = args.get(0) {
+        if let Some(d) = parse_duration(first) {
+            duration = d;
+            rest = &args[1..];
+        }
+    }
+    let reason = if rest.is_empty() { default_reason.to_string() } else { rest.join(" ") };
+    (duration, reason)
+}
+
+fn describe_duration(duration: Option<u64>) -> String {
+    match expires_at(systime().as_secs(), duration) {
+        Some(e) => format!("until {}", format_time(e)),
+        None => format!("permanently"),
+    }
+}
+
+/// stops staff from using commands on players with the same or a higher role than their own
+fn check_rank(ctx: &CommandContext, sender: &CommandSender, target: &Uuid) -> Result<(), String> {
+    if let CommandSender::Player { uuid, role, .. } = sender {
+        if uuid == target {
+            return Err(format!("You can not use that on yourself"));
+        }
+        let target_role = ctx.db.lock().unwrap().get_player_role(target);
+        if target_role >= *role {
+            return Err(format!("You can not use that on a player with the role {}", target_role.to_string()));
+        }
+    }
+    Ok(())
+}
+
+fn help(sender: &CommandSender) -> String {
+    let mut out = format!("Commands:");
+    for (usage, description, permission) in HELP.iter() {
+        if permission.map(|p| sender.has(p)).unwrap_or(true) {
+            out.push_str(format!("\n  {:<40} {}", usage, description).as_str());
+        }
+    }
+    if sender.has(Permission::Ban) || sender.has(Permission::Mute) {
+        out.push_str("\nDurations are written like 30m, 12h, 7d or perm (the default)");
+    }
+    out
+}
+
+fn list(ctx: &CommandContext) -> Result<String, String> {
+    let sessions = ctx.sessions.lock().unwrap();
+    if sessions.len() == 0 {
+        return Ok(format!("There are no players online"));
+    }
+    let mut out = format!("{} player(s) online:", sessions.len());
+    for s in sessions.all() {
+        let online_for = SystemTime::now().duration_since(s.connected_at).unwrap_or(Duration::ZERO);
+        out.push_str(format!("\n  {} ({}) from {} - online for {}m",
+                             s.username, s.uuid, s.ip, online_for.as_secs() / 60).as_str());
+    }
+    Ok(out)
+}
+
+fn kick(ctx: &CommandContext, sender: &CommandSender, args: &[&str]) -> Result<String, String> {
+    if args.is_empty() {
+        return usage("kick <user> [reason]");
+    }
+    let reason = if args.len() > 1 { args[1..].join(" ") } else { format!("Kicked by {}", sender.name()) };
+    let uuid = find_player(ctx, args[0])?;
+    check_rank(ctx, sender, &uuid)?;
+    if !ctx.sessions.lock().unwrap().send(&uuid, SessionMessage::Kick(reason.clone())) {
+        return Err(format!("{} is not online", args[0]));
+    }
+    audit(ctx, sender, "kick", args[0], reason.as_str());
+    Ok(format!("Kicked {}", args[0]))
+}
+
+fn ban(ctx: &CommandContext, sender: &CommandSender, args: &[&str]) -> Result<String, String> {
+    if args.is_empty() {
+        return usage("ban <user> [duration] [reason]");
+    }
+    let (duration, reason) = duration_and_reason(&args[1..], "Banned by an admin");
+    let uuid = find_player(ctx, args[0])?;
+    check_rank(ctx, sender, &uuid)?;
+    if !ctx.db.lock().unwrap().ban_player(&uuid, reason.clone(), sender.name(), duration) {
+        return Err(format!("Failed to write the ban to the database"));
+    }
+    let ban = ctx.db.lock().unwrap().get_player_ban(&uuid);
+    if let Some(b) = ban {
+        ctx.sessions.lock().unwrap().send(&uuid, SessionMessage::Kick(b.message()));
+    }
+    audit(ctx, sender, "ban", args[0], format!("{} ({})", reason, describe_duration(duration)).as_str());
+    Ok(format!("Banned {} {}", args[0], describe_duration(duration)))
+}
+
+fn unban(ctx: &CommandContext, sender: &CommandSender, args: &[&str]) -> Result<String, String> {
+    if args.len() != 1 {
+        return usage("unban <user>");
+    }
+    let uuid = find_player(ctx, args[0])?;
+    check_rank(ctx, sender, &uuid)?;
+    {
+        let db = ctx.db.lock().unwrap();
+        if db.get_player_ban(&uuid).is_none() {
+            return Err(format!("{} is not banned", args[0]));
+        }
+        if !db.unban_player(&uuid) {
+            return Err(format!("Failed to remove the ban from the database"));
+        }
+    }
+    audit(ctx, sender, "unban", args[0], "");
+    Ok(format!("Unbanned {}", args[0]))
+}
+
+fn mute(ctx: &CommandContext, sender: &CommandSender, args: &[&str]) -> Result<String, String> {
+    if args.is_empty() {
+        return usage("mute <user> [duration] [reason]");
+    }
+    let (duration, reason) = duration_and_reason(&args[1..], "Muted by a moderator");
+    let uuid = find_player(ctx, args[0])?;
+    check_rank(ctx, sender, &uuid)?;
+    if !ctx.db.lock().unwrap().mute_player(&uuid, reason.clone(), sender.name(), duration) {
+        return Err(format!("Failed to write the mute to the database"));
+    }
+    let mute = ctx.db.lock().unwrap().get_player_mute(&uuid);
+    if let Some(m) = mute {
+        ctx.sessions.lock().unwrap().send(&uuid, SessionMessage::Event(m.message()));
+    }
+    audit(ctx, sender, "mute", args[0], format!("{} ({})", reason, describe_duration(duration)).as_str());
+    Ok(format!("Muted {} {}", args[0], describe_duration(duration)))
+}
+
+fn unmute(ctx: &CommandContext, sender: &CommandSender, args: &[&str]) -> Result<String, String> {
+    if args.len() != 1 {
+        return usage("unmute <user>");
+    }
+    let uuid = find_player(ctx, args[0])?;
+    check_rank(ctx, sender, &uuid)?;
+    {
+        let db = ctx.db.lock().unwrap();
+        if db.get_player_mute(&uuid).is_none() {
+            return Err(format!("{} is not muted", args[0]));
+        }
+        if !db.unmute_player(&uuid) {
+            return Err(format!("Failed to remove the mute from the database"));
+        }
+    }
+    ctx.sessions.lock().unwrap().send(&uuid, SessionMessage::Event(format!("You are no longer muted")));
+    audit(ctx, sender, "unmute", args[0], "");
+    Ok(format!("Unmuted {}", args[0]))
+}
+
+fn ignore(ctx: &CommandContext, sender: &CommandSender, args: &[&str], ignoring: bool) -> Result<String, String> {
+    let player = match sender {
+        CommandSender::Player { uuid, .. } => uuid,
+        CommandSender::Console => return Err(format!("Only players can ignore others")),
+    };
+    if args.len() != 1 {
+        return usage(if ignoring { "ignore <user>" } else { "unignore <user>" });
+    }
+    let uuid = find_player(ctx, args[0])?;
+    if uuid == *player {
+        return Err(format!("You can not ignore yourself"));
+    }
+    let db = ctx.db.lock().unwrap();
+    if ignoring {
+        if !db.ignore_player(player, &uuid) {
+            return Err(format!("Failed to write to the database"));
+        }
+        Ok(format!("You are now ignoring {}", args[0]))
+    } else {
+        if !db.unignore_player(player, &uuid) {
+            return Err(format!("Failed to write to the database"));
+        }
+        Ok(format!("You are no longer ignoring {}", args[0]))
+    }
+}
+
+fn ignores(ctx: &CommandContext, sender: &CommandSender) -> Result<String, String> {
+    let player = match sender {
+        CommandSender::Player { uuid, .. } => uuid,
+        CommandSender::Console => return Err(format!("Only players can ignore others")),
+    };
+    let ignored = ctx.db.lock().unwrap().get_ignored(player);
+    if ignored.is_empty() {
+        return Ok(format!("You are not ignoring anyone"));
+    }
+    Ok(format!("You are ignoring: {}", ignored.join(", ")))
+}
+
+fn ip_ban(ctx: &CommandContext, sender: &CommandSender, args: &[&str]) -> Result<String, String> {
+    if args.is_empty() {
+        return usage("ipban <ip/cidr|user> [duration] [reason]");
+    }
+    // allow banning the address of an online player by their name
+    let online = ctx.sessions.lock().unwrap().find_by_name(args[0]);
+    let cidr = if is_valid_cidr(args[0]) {
+        args[0].to_string()
+    } else if let Some(uuid) = online {
+        check_rank(ctx, sender, &uuid)?;
+        let sessions = ctx.sessions.lock().unwrap();
+        // the player may have disconnected while their rank was checked
+        let ip = sessions.get(&uuid).ok_or(format!("{} is no longer online", args[0]))?.ip.clone();
+        ip.parse::<SocketAddr>().map(|a| a.ip().to_string())
+            .map_err(|_| format!("{} does not have a valid ip address", args[0]))?
+    } else {
+        return Err(format!("'{}' is not a valid ip address, CIDR range or online player", args[0]));
+    };
+
+    let (duration, reason) = duration_and_reason(&args[1..], "Banned by an admin");
+    if !ctx.db.lock().unwrap().ban_ip(cidr.clone(), reason.clone(), sender.name(), duration) {
+        return Err(format!("Failed to write the ban to the database"));
+    }
+
+    // disconnect everyone connected from inside the range, telling them about this ban rather than any other that overlaps it
+    let now = systime().as_secs();
+    let ban = Ban {
+        uuid: None,
+        ip: Some(cidr.clone()),
+        reason: reason.clone(),
+        banned_by: sender.name(),
+        time: now,
+        expires: expires_at(now, duration),
+    };
+    {
+        let sessions = ctx.sessions.lock().unwrap();
+        for s in sessions.all() {
+            if let Ok(addr) = s.ip.parse::<SocketAddr>() {
+                if cidr_contains(cidr.as_str(), &addr.ip()) {
+                    sessions.send(&s.uuid, SessionMessage::Kick(ban.message()));
+                }
+            }
+        }
+    }
+    audit(ctx, sender, "ipban", cidr.as_str(), format!("{} ({})", reason, describe_duration(duration)).as_str());
+    Ok(format!("Banned {} {}", cidr, describe_duration(duration)))
+}
+
+fn ip_unban(ctx: &CommandContext, sender: &CommandSender, args: &[&str]) -> Result<String, String> {
+    if args.len() != 1 || !is_valid_cidr(args[0]) {
+        return usage("unipban <ip/cidr>");
+    }
+    if !ctx.db.lock().unwrap().unban_ip(args[0].to_string()) {
+        return Err(format!("Failed to remove the ban from the database"));
+    }
+    audit(ctx, sender, "unipban", args[0], "");
+    Ok(format!("Removed any bans on {}", args[0]))
+}
+
+fn bans(ctx: &CommandContext) -> Result<String, String> {
+    let db = ctx.db.lock().unwrap();
+    let bans = db.get_all_bans();
+    if bans.is_empty() {
+        return Ok(format!("There are no active bans"));
+    }
+    let mut out = format!("{} active ban(s):", bans.len());
+    for b in bans {
+        let target = match (&b.uuid, &b.ip) {
+            (Some(uuid), _) => db.get_player_value(uuid, PlayerValueDB::Username).unwrap_or(uuid.to_string()),
+            (None, Some(ip)) => ip.clone(),
+            (None, None) => format!("?"),
+        };
+        let expires = match b.expires {
+            Some(e) => format!("until {}", format_time(e)),
+            None => format!("permanent"),
+        };
+        out.push_str(format!("\n  {} - {} by {} on {}, {}", target, b.reason, b.banned_by, format_time(b.time), expires).as_str());
+    }
+    Ok(out)
+}
+
+fn audit_log(ctx: &CommandContext, args: &[&str]) -> Result<String, String> {
+    let count = match args.get(0) {
+        Some(c) => c.parse::<u32>().map_err(|_| format!("Invalid count '{}'", c))?,
+        None => 10,
+    };
+    let entries = ctx.db.lock().unwrap().get_audit_log(count);
+    if entries.is_empty() {
+        return Ok(format!("The audit log is empty"));
+    }
+    let mut out = format!("Last {} moderation action(s):", entries.len());
+    for e in entries {
+        out.push_str(format!("\n  [{}] {} {} {}{}", format_time(e.time), e.actor, e.action, e.target,
+                             if e.details.is_empty() { String::new() } else { format!(": {}", e.details) }).as_str());
+    }
+    Ok(out)
+}
+
+fn broadcast(ctx: &CommandContext, sender: &CommandSender, args: &[&str]) -> Result<String, String> {
+    if args.is_empty() {
+        return usage("broadcast <message>");
+    }
+    let msg = args.join(" ");
+    ctx.sessions.lock().unwrap().announce(BroadcastKind::Announcement, msg.clone());
+    audit(ctx, sender, "broadcast", "everyone", msg.as_str());
+    Ok(format!("Broadcast sent"))
+}
+
+fn give(ctx: &CommandContext, sender: &CommandSender, args: &[&str]) -> Result<String, String> {
+    if args.len() < 3 || args.len() > 4 {
+        return usage("give <user> <type> <rarity> [level]");
+    }
+    let uuid = find_player(ctx, args[0])?;
+    check_rank(ctx, sender, &uuid)?;
+    let item_type = ItemType::from_str(args[1])
+        .map_err(|_| format!("Invalid item type '{}': expected sword, shield, helmet, chestplate, leggings or boots", args[1]))?;
+    let rarity = ItemRarity::from_str(args[2])
+        .map_err(|_| format!("Invalid rarity '{}': expected common, rare, epic or legendary", args[2]))?;
+    let level = match args.get(3) {
+        Some(l) => l.parse::<u32>().ok().filter(|l| *l <= ctx.config.player_max_level)
+            .ok_or(format!("Invalid level '{}', it must be at most {}", l, ctx.config.player_max_level))?,
+        None => ctx.db.lock().unwrap().get_player_level(&uuid).unwrap_or(1),
+    };
+
+    let item = Item::new_rand(item_type, &uuid, level, rarity);
+    if !ctx.db.lock().unwrap().new_item(&item) {
+        return Err(format!("Failed to write the item to the database"));
+    }
+    ctx.sessions.lock().unwrap().send(&uuid,
+                                      SessionMessage::Event(format!("You were given '{}'!", item.name)));
+    audit(ctx, sender, "give", args[0], format!("{} ({} {} level {})", item.name, args[2], args[1], item.level).as_str());
+    Ok(format!("Gave {} '{}'", args[0], item.name))
+}
+
+fn system_mail(ctx: &CommandContext, sender: &CommandSender, args: &[&str]) -> Result<String, String> {
+    if args.len() < 3 {
+        return usage("sysmail <user|all> <gold> [type rarity] <message>");
+    }
+    let gold = args[1].parse::<u32>().map_err(|_| format!("Invalid gold '{}'", args[1]))?;
+    let item = match (args.get(2).and_then(|t| ItemType::from_str(t).ok()), args.get(3).and_then(|r| ItemRarity::from_str(r).ok())) {
+        (Some(item_type), Some(rarity)) => Some((item_type, rarity)),
+        _ => None,
+    };
+    let message = args[if item.is_some() { 4 } else { 2 }..].join(" ");
+    if message.is_empty() {
+        return usage("sysmail <user|all> <gold> [type rarity] <message>");
+    }
+    let recipients = if args[0].eq_ignore_ascii_case("all") {
+        ctx.db.lock().unwrap().get_all_players()
+    } else {
+        vec![find_player(ctx, args[0])?]
+    };
+
+    let db = ctx.db.lock().unwrap();
+    let sessions = ctx.sessions.lock().unwrap();
+    let mut sent = 0;
+    for uuid in &recipients {
+        // each player gets an item made for their own level
+        let items = item.map(|(item_type, rarity)|
+            Item::new_rand(item_type, uuid, db.get_player_level(uuid).unwrap_or(1), rarity)).into_iter().collect::<Vec<Item>>();
+        if mail::send_system(&db, &sessions, uuid, message.clone(), gold, &items) {
+            sent += 1;
+        }
+    }
+    drop(sessions);
+    drop(db);
+    let attached = if item.is_some() { format!(" and a {} {}", args[3], args[2]) } else { String::new() };
+    audit(ctx, sender, "sysmail", args[0], format!("{} gold{}: {}", gold, attached, message).as_str());
+    if sent < recipients.len() {
+        return Err(format!("Only sent mail to {} of {} players", sent, recipients.len()));
+    }
+    Ok(format!("Sent mail to {} player(s)", sent))
+}
+
+fn set_level(ctx: &CommandContext, sender: &CommandSender, args: &[&str]) -> Result<String, String> {
+    if args.len() != 2 {
+        return usage("setlevel <user> <level>");
+    }
+    let uuid = find_player(ctx, args[0])?;
+    check_rank(ctx, sender, &uuid)?;
+    let level = args[1].parse::<u32>().ok().filter(|l| *l > 0 && *l <= ctx.config.player_max_level)
+        .ok_or(format!("Invalid level '{}', it must be between 1 and {}", args[1], ctx.config.player_max_level))?;
+    {
+        let db = ctx.db.lock().unwrap();
+        let old = db.get_player_level(&uuid).unwrap_or(1);
+        if !db.set_player_level(&uuid, level) {
+            return Err(format!("Failed to write the level to the database"));
+        }
+        // raising a level gives the skill points for it like leveling up does, lowering one keeps them
+        if level > old {
+            db.add_skill_points(&uuid, (level - old).saturating_mul(SKILL_POINTS_PER_LEVEL));
+        }
+    }
+    ctx.sessions.lock().unwrap().send(&uuid,
+                                      SessionMessage::Event(format!("Your level was set to {}", level)));
+    audit(ctx, sender, "setlevel", args[0], level.to_string().as_str());
+    Ok(format!("Set the level of {} to {}", args[0], level))
+}
+
+fn teleport(ctx: &CommandContext, sender: &CommandSender, args: &[&str]) -> Result<String, String> {
+    if args.len() < 2 {
+        return usage("tp <user> <region>");
+    }
+    let uuid = find_player(ctx, args[0])?;
+    check_rank(ctx, sender, &uuid)?;
+    let region = args[1..].join(" ").replace("'", "");
+    if !ctx.db.lock().unwrap().set_player_region(&uuid, region.clone()) {
+        return Err(format!("Failed to write the region to the database"));
+    }
+    ctx.sessions.lock().unwrap().send(&uuid,
+                                      SessionMessage::Event(format!("You were moved to {}", region)));
+    audit(ctx, sender, "tp", args[0], region.as_str());
+    Ok(format!("Moved {} to {}", args[0], region))
+}
+
+fn inventory(ctx: &CommandContext, args: &[&str]) -> Result<String, String> {
+    if args.len() != 1 {
+        return usage("invsee <user>");
+    }
+    let uuid = find_player(ctx, args[0])?;
+    let items = ctx.db.lock().unwrap().get_player_items(&uuid).unwrap_or(Vec::new());
+    if items.is_empty() {
+        return Ok(format!("{}'s inventory is empty", args[0]));
+    }
+    let mut out = format!("{}'s inventory ({} items):", args[0], items.len());
+    for i in items {
+        out.push_str(format!("\n  '{}' - level {} {:?} {:?}", i.name, i.level, i.rarity, i.item_type).as_str());
+    }
+    Ok(out)
+}
+
+fn inspect(ctx: &CommandContext, args: &[&str]) -> Result<String, String> {
+    if args.len() < 2 {
+        return usage("inspect <user> <item>");
+    }
+    let uuid = find_player(ctx, args[0])?;
+    let item_name = args[1..].join(" ").escape_debug().to_string().replace("'", "");
+    let db = ctx.db.lock().unwrap();
+    let item = db.item_uuid_from_name(item_name.clone(), &uuid)
+        .and_then(|item_uuid| db.get_item(&item_uuid))
+        .ok_or(format!("{} does not have an item named '{}'", args[0], item_name))?;
+    Ok(format!("Name:   {}\nType:   {:?}\nLevel:  {}\nRarity: {:?}\n{}",
+               item.name, item.item_type, item.level, item.rarity,
+               if item.item_type == ItemType::Sword { format!("Damage: {}", item.damage) }
+               else { format!("Defense: {}", item.defense) }))
+}
+
+fn role(ctx: &CommandContext, sender: &CommandSender, args: &[&str]) -> Result<String, String> {
+    if args.is_empty() || args.len() > 2 {
+        return usage("role <user> [player|moderator|admin]");
+    }
+    let uuid = find_player(ctx, args[0])?;
+    if args.len() == 1 {
+        let role = ctx.db.lock().unwrap().get_player_role(&uuid);
+        return Ok(format!("{} has the role {}", args[0], role.to_string()));
+    }
+
+    let role = Role::from_str(args[1])
+        .map_err(|_| format!("Invalid role '{}': expected player, moderator or admin", args[1]))?;
+    if !ctx.db.lock().unwrap().set_player_role(&uuid, role) {
+        return Err(format!("Failed to write the role to the database"));
+    }
+    ctx.sessions.lock().unwrap().send(&uuid,
+                                      SessionMessage::Event(format!("Your role is now {}", role.to_string())));
+    audit(ctx, sender, "role", args[0], role.to_string().as_str());
+    Ok(format!("{} now has the role {}", args[0], role.to_string()))
+}
+
+fn roles(ctx: &CommandContext) -> Result<String, String> {
+    let staff = ctx.db.lock().unwrap().get_staff();
+    if staff.is_empty() {
+        return Ok(format!("There are no staff members"));
+    }
+    let mut out = format!("{} staff member(s):", staff.len());
+    for (username, role) in staff {
+        out.push_str(format!("\n  {} - {}", username, role.to_string()).as_str());
+    }
+    Ok(out)
+}
+
+fn shutdown(ctx: &CommandContext, sender: &CommandSender, args: &[&str]) -> Result<String, String> {
+    let delay = match args.get(0) {
+        Some(d) => d.parse::<u64>().map_err(|_| format!("Invalid delay '{}'", d))?,
+        None => 0,
+    };
+    audit(ctx, sender, "shutdown", "server", format!("in {} seconds", delay).as_str());
+    if delay == 0 {
+        ctx.terminate.store(true, Ordering::SeqCst);
+        return Ok(format!("Shutting down..."));
+    }
+
+    let sessions = Arc::clone(&ctx.sessions);
+    let terminate = Arc::clone(&ctx.terminate);
+    thread::spawn(move || {
+        let mut remaining = delay;
+        while remaining > 0 {
+            if terminate.load(Ordering::SeqCst) {
+                return;
+            }
+            sessions.lock().unwrap().announce(BroadcastKind::Shutdown,
+                format!("The server is shutting down in {} seconds!", remaining));
+            // sleep until the next time players should be warned
+            let next = SHUTDOWN_WARNINGS.iter().find(|w| **w < remaining).cloned().unwrap_or(0);
+            thread::sleep(Duration::from_secs(remaining - next));
+            remaining = next;
+        }
+        terminate.store(true, Ordering::SeqCst);
+    });
+    Ok(format!("The server will shut down in {} seconds", delay))
+}
@@ -1,19 +1,846 @@
+use std::collections::HashMap;
 use std::fs::OpenOptions;
 use std::fs;
 use std::path::Path;
 use std::io::Write;
+use std::str::FromStr;
+use log::LevelFilter;
 use serde::Deserialize;
+use crate::logging::LogFormat;
+use crate::crafting::FailurePenalty;
+use crate::health::RegenMode;
+use crate::party::LootRule;
+use crate::player::START_REGION;
 use crate::read_config_raw;
 
+/// The contents written to a new config file when one does not exist yet
+pub const DEFAULT_CONFIG: &str = "\
+[server]\
+\n# ip: the ip to listen on\
+\n# surround with '[' and ']' for Ipv6 addresses\
+\n# defaults to 0.0.0.0 and will listen on your machines current IP\
+\nip = \"0.0.0.0\"\
+\n# port: the port to listen on\
+\n# defaults to 2277\
+\nport = \"2277\"\
+\n# motd: the message sent to clients when they log in\
+\nmotd = \"Welcome to SnD! We are still in ALPHA, so expect some bugs!\"\
+\n# keepalive_interval: time in seconds between keepalive packets\
+\nkeepalive_interval = 20\
+\n# accepted_client_version: the client version allowed to connect\
+\naccepted_client_version = \"0.1.0\"\
+\n\
+\n[database]\
+\n# name: the sqlite database to use (.sqlite is added if missing)\
+\nname = \"snd\"\
+\n\
+\n[data]\
+\n# dir: the folder with the game data files such as achievements.toml, missing files are created with the defaults\
+\ndir = \"data\"\
+\n\
+\n[logging]\
+\n# level: one of off, error, warn, info, debug or trace\
+\nlevel = \"trace\"\
+\n# stdout: if logs should be printed to the console\
+\nstdout = true\
+\n# file: the path of a log file, leave empty to disable file logging\
+\nfile = \"logs/server.log\"\
+\n# format: the format of the log file, either \"text\" or \"json\" (one json object per line)\
+\nformat = \"text\"\
+\n# max_file_size: size in bytes before the log file is rotated\
+\nmax_file_size = 10485760\
+\n# max_files: how many rotated log files to keep\
+\nmax_files = 5\
+\n\
+\n# targets: per-target level filters that override the level above\
+\n[logging.targets]\
+\n# client_handler = \"debug\"\
+\n\
+\n[accounts]\
+\n# length limits for usernames and passwords on signup\
+\nusername_min_length = 3\
+\nusername_max_length = 16\
+\npassword_min_length = 4\
+\npassword_max_length = 32\
+\n\
+\n[player]\
+\n# max_level: the highest level a player can be set to or given items for with commands\
+\nmax_level = 1000\
+\n\
+\n[chat]\
+\n# max_message_length: the longest chat message a player can send\
+\nmax_message_length = 200\
+\n# rate_limit_messages: how many messages a player can send every rate_limit_seconds\
+\nrate_limit_messages = 5\
+\nrate_limit_seconds = 10\
+\n\
+\n[party]\
+\n# max_size: the most players that can be in one party\
+\nmax_size = 4\
+\n# loot_rule: how encounter rewards are split, one of \"even\", \"damage\" or \"random\"\
+\nloot_rule = \"even\"\
+\n# turn_timeout: seconds a player has to take their turn in a party encounter before it is skipped, players fighting alone are never skipped\
+\nturn_timeout = 30\
+\n# invite_timeout: seconds before a party invite expires\
+\ninvite_timeout = 60\
+\n# flee_chance: the percent chance of fleeing from an encounter\
+\nflee_chance = 50\
+\n\
+\n[duel]\
+\n# turn_timeout: seconds a player has to take their turn in a duel before it is skipped\
+\nturn_timeout = 30\
+\n# challenge_timeout: seconds before a duel challenge expires\
+\nchallenge_timeout = 60\
+\n# max_wager_items: the most items each player can wager in a duel\
+\nmax_wager_items = 5\
+\n\
+\n[quests]\
+\n# giver_chance: the percent chance of meeting a quest giver with each step\
+\ngiver_chance = 3\
+\n# max_active: the most quests a player can have at once\
+\nmax_active = 5\
+\n\
+\n[consumables]\
+\n# max_stack: the most of each consumable a player can carry\
+\nmax_stack = 99\
+\n# drop_chance: the percent chance of each player getting a consumable when an enemy is defeated\
+\ndrop_chance = 25\
+\n# find_chance: the percent of items found while walking that are consumables\
+\nfind_chance = 30\
+\n\
+\n[inventory]\
+\n# capacity: how many items a player can carry before buying more space\
+\ncapacity = 30\
+\n# max_capacity: the most items a player can carry after buying more space\
+\nmax_capacity = 100\
+\n# expand_slots: how much space each expansion adds\
+\nexpand_slots = 5\
+\n# expand_cost: the gold the first expansion costs, every expansion after it costs this much more\
+\nexpand_cost = 100\
+\n\
+\n[bank]\
+\n# capacity: how many items each player can keep in their bank\
+\ncapacity = 100\
+\n# towns: the regions players can use their bank in\
+\ntowns = [\"Plains of Arenlok\"]\
+\n# at_merchant: if players can also use their bank anywhere they can trade with the merchant, outside of fights\
+\nat_merchant = false\
+\n\
+\n[auction]\
+\n# max_listings: how many items each player can have for sale at once\
+\nmax_listings = 10\
+\n# default_hours: how long a listing lasts when the seller does not say\
+\ndefault_hours = 24\
+\n# max_hours: the longest a listing can last\
+\nmax_hours = 72\
+\n# fee_percent: the share of the sale price the seller loses as a fee\
+\nfee_percent = 5\
+\n# min_raise_percent: how much higher than the highest bid a new bid must be\
+\nmin_raise_percent = 5\
+\n# check_seconds: how often the server looks for listings that have ended\
+\ncheck_seconds = 30\
+\n\
+\n[mail]\
+\n# max_mail: how many letters a mailbox can hold, mail from the server is always delivered\
+\nmax_mail = 50\
+\n# postage: the gold it costs to send a letter\
+\npostage = 5\
+\n# max_length: the most characters a letter can have\
+\nmax_length = 500\
+\n\
+\n[guild]\
+\n# create_cost: the gold it costs to start a guild\
+\ncreate_cost = 500\
+\n# max_members: the most players a guild can have\
+\nmax_members = 50\
+\n# bank_capacity: how many items a guild can keep in its bank, it is used in the same places as a player's bank\
+\nbank_capacity = 100\
+\n\
+\n[skills]\
+\n# reset_cost: the gold it costs to take back every skill point or change class, picking a first class is free\
+\nreset_cost = 200\
+\n\
+\n[upgrade]\
+\n# max_level: the most times an item can be upgraded\
+\nmax_level = 10\
+\n# success_chance: the percent chance of an item's first upgrade working\
+\nsuccess_chance = 90\
+\n# chance_loss: how much lower the chance is for every upgrade the item already has\
+\nchance_loss = 8\
+\n# min_chance: the lowest the chance of an upgrade working can fall to\
+\nmin_chance = 10\
+\n# gold: the gold an item's first upgrade costs, every upgrade after it costs this much more\
+\ngold = 25\
+\n# stat_percent: the percent each upgrade raises an item's damage or defense by\
+\nstat_percent = 10\
+\n# failure_penalty: what happens to the item when an upgrade fails, one of \"none\", \"downgrade\" or \"destroy\"\
+\nfailure_penalty = \"downgrade\"\
+\n\
+\n[death]\
+\n# exp_loss_percent: the percent of a player's exp towards their next level lost when they are defeated\
+\nexp_loss_percent = 10\
+\n# drop_item_chance: the percent chance of losing a random item when defeated\
+\ndrop_item_chance = 0\
+\n# reset_region: if defeated players wake up back in the starting region\
+\nreset_region = false\
+\n# respawn_health_percent: the percent of their max health defeated players wake up with\
+\nrespawn_health_percent = 100\
+\n\
+\n[regen]\
+\n# mode: how players heal outside of fights, one of \"steps\", \"time\" or \"none\"\
+\nmode = \"steps\"\
+\n# amount: how much health is healed each time\
+\namount = 1\
+\n# interval: how many steps or seconds between each heal\
+\ninterval = 5\
+\n\
+\n[leaderboard]\
+\n# page_size: how many players are shown on each page of a leaderboard\
+\npage_size = 10\
+\n# cache_seconds: how long a leaderboard page is kept before it is read from the database again\
+\ncache_seconds = 60\
+\n";
+
+/// The prefix of all environment variables that override config values
+/// e.g. `SND_SERVER_PORT` overrides `port` in the `[server]` section
+const ENV_PREFIX: &str = "SND";
+
 #[derive(Debug, Deserialize)]
 pub struct Config {
-    pub server: Option<Server>
+    pub server: Option<Server>,
+    pub database: Option<DatabaseConf>,
+    pub data: Option<DataConf>,
+    pub logging: Option<Logging>,
+    pub accounts: Option<Accounts>,
+    pub player: Option<PlayerConf>,
+    pub chat: Option<Chat>,
+    pub party: Option<PartyConf>,
+    pub duel: Option<Duel>,
+    pub quests: Option<Quests>,
+    pub consumables: Option<Consumables>,
+    pub inventory: Option<Inventory>,
+    pub bank: Option<Bank>,
+    pub auction: Option<Auction>,
+    pub mail: Option<Mail>,
+    pub guild: Option<Guild>,
+    pub skills: Option<Skills>,
+    pub upgrade: Option<Upgrade>,
+    pub death: Option<Death>,
+    pub regen: Option<Regen>,
+    pub leaderboard: Option<Leaderboard>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Server {
     pub ip: Option<String>,
-    pub port: Option<String>
+    pub port: Option<String>,
+    pub motd: Option<String>,
+    pub keepalive_interval: Option<u64>,
+    pub accepted_client_version: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DatabaseConf {
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DataConf {
+    pub dir: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Logging {
+    pub level: Option<String>,
+    pub stdout: Option<bool>,
+    pub file: Option<String>,
+    pub format: Option<String>,
+    pub max_file_size: Option<u64>,
+    pub max_files: Option<usize>,
+    pub targets: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Accounts {
+    pub username_min_length: Option<usize>,
+    pub username_max_length: Option<usize>,
+    pub password_min_length: Option<usize>,
+    pub password_max_length: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlayerConf {
+    pub max_level: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Chat {
+    pub max_message_length: Option<usize>,
+    pub rate_limit_messages: Option<usize>,
+    pub rate_limit_seconds: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PartyConf {
+    pub max_size: Option<usize>,
+    pub loot_rule: Option<String>,
+    pub turn_timeout: Option<u64>,
+    pub invite_timeout: Option<u64>,
+    pub flee_chance: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Duel {
+    pub turn_timeout: Option<u64>,
+    pub challenge_timeout: Option<u64>,
+    pub max_wager_items: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Quests {
+    pub giver_chance: Option<u32>,
+    pub max_active: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Consumables {
+    pub max_stack: Option<u32>,
+    pub drop_chance: Option<u32>,
+    pub find_chance: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Inventory {
+    pub capacity: Option<u32>,
+    pub max_capacity: Option<u32>,
+    pub expand_slots: Option<u32>,
+    pub expand_cost: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Bank {
+    pub capacity: Option<u32>,
+    pub towns: Option<Vec<String>>,
+    pub at_merchant: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Auction {
+    pub max_listings: Option<u32>,
+    pub default_hours: Option<u32>,
+    pub max_hours: Option<u32>,
+    pub fee_percent: Option<u32>,
+    pub min_raise_percent: Option<u32>,
+    pub check_seconds: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Guild {
+    pub create_cost: Option<u32>,
+    pub max_members: Option<u32>,
+    pub bank_capacity: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Skills {
+    pub reset_cost: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Mail {
+    pub max_mail: Option<u32>,
+    pub postage: Option<u32>,
+    pub max_length: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Upgrade {
+    pub max_level: Option<u32>,
+    pub success_chance: Option<u32>,
+    pub chance_loss: Option<u32>,
+    pub min_chance: Option<u32>,
+    pub gold: Option<u32>,
+    pub stat_percent: Option<u32>,
+    pub failure_penalty: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Death {
+    pub exp_loss_percent: Option<u32>,
+    pub drop_item_chance: Option<u32>,
+    pub reset_region: Option<bool>,
+    pub respawn_health_percent: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Regen {
+    pub mode: Option<String>,
+    pub amount: Option<u32>,
+    pub interval: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Leaderboard {
+    pub page_size: Option<u32>,
+    pub cache_seconds: Option<u64>,
+}
+
+/// The fully resolved server configuration.
+/// Built from the defaults, then the config file, then environment variables.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub ip: String,
+    pub port: String,
+    pub motd: String,
+    pub keepalive_interval: u64,
+    pub accepted_client_version: String,
+    pub database_name: String,
+    pub data_dir: String,
+    pub log_level: LevelFilter,
+    pub log_targets: Vec<(String, LevelFilter)>,
+    pub log_stdout: bool,
+    pub log_file: Option<String>,
+    pub log_format: LogFormat,
+    pub log_max_file_size: u64,
+    pub log_max_files: usize,
+    pub username_min_length: usize,
+    pub username_max_length: usize,
+    pub password_min_length: usize,
+    pub password_max_length: usize,
+    pub player_max_level: u32,
+    pub chat_max_message_length: usize,
+    pub chat_rate_limit_messages: usize,
+    pub chat_rate_limit_seconds: u64,
+    pub party_max_size: usize,
+    pub party_loot_rule: LootRule,
+    pub party_turn_timeout: u64,
+    pub party_invite_timeout: u64,
+    pub party_flee_chance: u32,
+    pub duel_turn_timeout: u64,
+    pub duel_challenge_timeout: u64,
+    pub duel_max_wager_items: usize,
+    pub quest_giver_chance: u32,
+    pub quest_max_active: usize,
+    pub consumable_max_stack: u32,
+    pub consumable_drop_chance: u32,
+    pub consumable_find_chance: u32,
+    pub inventory_capacity: u32,
+    pub inventory_max_capacity: u32,
+    pub inventory_expand_slots: u32,
+    pub inventory_expand_cost: u32,
+    pub bank_capacity: u32,
+    pub bank_towns: Vec<String>,
+    pub bank_at_merchant: bool,
+    pub auction_max_listings: u32,
+    pub auction_default_hours: u32,
+    pub auction_max_hours: u32,
+    pub auction_fee_percent: u32,
+    pub auction_min_raise_percent: u32,
+    pub auction_check_seconds: u64,
+    pub mail_max_mail: u32,
+    pub mail_postage: u32,
+    pub mail_max_length: usize,
+    pub guild_create_cost: u32,
+    pub guild_max_members: u32,
+    pub guild_bank_capacity: u32,
+    pub skills_reset_cost: u32,
+    pub upgrade_max_level: u32,
+    pub upgrade_success_chance: u32,
+    pub upgrade_chance_loss: u32,
+    pub upgrade_min_chance: u32,
+    pub upgrade_gold: u32,
+    pub upgrade_stat_percent: u32,
+    pub upgrade_failure_penalty: FailurePenalty,
+    pub death_exp_loss_percent: u32,
+    pub death_drop_item_chance: u32,
+    pub death_reset_region: bool,
+    pub death_respawn_health_percent: u32,
+    pub regen_mode: RegenMode,
+    pub regen_amount: u32,
+    pub regen_interval: u64,
+    pub leaderboard_page_size: u32,
+    pub leaderboard_cache_seconds: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            ip: format!("0.0.0.0"),
+            port: format!("2277"),
+            motd: format!("Welcome to SnD! We are still in ALPHA, so expect some bugs!"),
+            keepalive_interval: 20,
+            accepted_client_version: format!("0.1.0"),
+            database_name: format!("snd"),
+            data_dir: format!("data"),
+            log_level: LevelFilter::Trace,
+            log_targets: Vec::new(),
+            log_stdout: true,
+            log_file: Some(format!("logs/server.log")),
+            log_format: LogFormat::Text,
+            log_max_file_size: 10 * 1024 * 1024,
+            log_max_files: 5,
+            username_min_length: 3,
+            username_max_length: 16,
+            password_min_length: 4,
+            password_max_length: 32,
+            player_max_level: 1000,
+            chat_max_message_length: 200,
+            chat_rate_limit_messages: 5,
+            chat_rate_limit_seconds: 10,
+            party_max_size: 4,
+            party_loot_rule: LootRule::Even,
+            party_turn_timeout: 30,
+            party_invite_timeout: 60,
+            party_flee_chance: 50,
+            duel_turn_timeout: 30,
+            duel_challenge_timeout: 60,
+            duel_max_wager_items: 5,
+            quest_giver_chance: 3,
+            quest_max_active: 5,
+            consumable_max_stack: 99,
+            consumable_drop_chance: 25,
+            consumable_find_chance: 30,
+            inventory_capacity: 30,
+            inventory_max_capacity: 100,
+            inventory_expand_slots: 5,
+            inventory_expand_cost: 100,
+            bank_capacity: 100,
+            bank_towns: vec![START_REGION.to_string()],
+            bank_at_merchant: false,
+            auction_max_listings: 10,
+            auction_default_hours: 24,
+            auction_max_hours: 72,
+            auction_fee_percent: 5,
+            auction_min_raise_percent: 5,
+            auction_check_seconds: 30,
+            mail_max_mail: 50,
+            mail_postage: 5,
+            mail_max_length: 500,
+            guild_create_cost: 500,
+            guild_max_members: 50,
+            guild_bank_capacity: 100,
+            skills_reset_cost: 200,
+            upgrade_max_level: 10,
+            upgrade_success_chance: 90,
+            upgrade_chance_loss: 8,
+            upgrade_min_chance: 10,
+            upgrade_gold: 25,
+            upgrade_stat_percent: 10,
+            upgrade_failure_penalty: FailurePenalty::Downgrade,
+            death_exp_loss_percent: 10,
+            death_drop_item_chance: 0,
+            death_reset_region: false,
+            death_respawn_health_percent: 100,
+            regen_mode: RegenMode::Steps,
+            regen_amount: 1,
+            regen_interval: 5,
+            leaderboard_page_size: 10,
+            leaderboard_cache_seconds: 60,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// reads the config file at the path (creating it if needed) and applies environment overrides
+    pub fn load(path: &Path) -> Self {
+        let mut config = Self::default();
+        config.apply_file(read_config(path, DEFAULT_CONFIG.to_string()));
+        config.apply_env();
+        config.clamp();
+        config
+    }
+
+    fn apply_file(&mut self, file: Config) {
+        if let Some(server) = file.server {
+            if let Some(ip) = server.ip { self.ip = ip; }
+            if let Some(port) = server.port { self.port = port; }
+            if let Some(motd) = server.motd { self.motd = motd; }
+            if let Some(interval) = server.keepalive_interval { self.keepalive_interval = interval; }
+            if let Some(ver) = server.accepted_client_version { self.accepted_client_version = ver; }
+        }
+        if let Some(database) = file.database {
+            if let Some(name) = database.name { self.database_name = name; }
+        }
+        if let Some(data) = file.data {
+            if let Some(dir) = data.dir { self.data_dir = dir; }
+        }
+        if let Some(logging) = file.logging {
+            if let Some(level) = logging.level {
+                self.log_level = parse_level(level.as_str());
+            }
+            if let Some(stdout) = logging.stdout { self.log_stdout = stdout; }
+            if let Some(file) = logging.file { self.log_file = non_empty(file); }
+            if let Some(format) = logging.format { self.log_format = parse_format(format.as_str()); }
+            if let Some(size) = logging.max_file_size { self.log_max_file_size = size; }
+            if let Some(files) = logging.max_files { self.log_max_files = files; }
+            if let Some(targets) = logging.targets {
+                self.log_targets = targets.iter()
+                    .map(|(target, level)| (target.clone(), parse_level(level.as_str())))
+                    .collect();
+            }
+        }
+        if let Some(accounts) = file.accounts {
+            if let Some(v) = accounts.username_min_length { self.username_min_length = v; }
+            if let Some(v) = accounts.username_max_length { self.username_max_length = v; }
+            if let Some(v) = accounts.password_min_length { self.password_min_length = v; }
+            if let Some(v) = accounts.password_max_length { self.password_max_length = v; }
+        }
+        if let Some(player) = file.player {
+            if let Some(v) = player.max_level { self.player_max_level = v; }
+        }
+        if let Some(chat) = file.chat {
+            if let Some(v) = chat.max_message_length { self.chat_max_message_length = v; }
+            if let Some(v) = chat.rate_limit_messages { self.chat_rate_limit_messages = v; }
+            if let Some(v) = chat.rate_limit_seconds { self.chat_rate_limit_seconds = v; }
+        }
+        if let Some(party) = file.party {
+            if let Some(v) = party.max_size { self.party_max_size = v; }
+            if let Some(v) = party.loot_rule { self.party_loot_rule = parse_loot_rule(v.as_str()); }
+            if let Some(v) = party.turn_timeout { self.party_turn_timeout = v; }
+            if let Some(v) = party.invite_timeout { self.party_invite_timeout = v; }
+            if let Some(v) = party.flee_chance { self.party_flee_chance = v; }
+        }
+        if let Some(duel) = file.duel {
+            if let Some(v) = duel.turn_timeout { self.duel_turn_timeout = v; }
+            if let Some(v) = duel.challenge_timeout { self.duel_challenge_timeout = v; }
+            if let Some(v) = duel.max_wager_items { self.duel_max_wager_items = v; }
+        }
+        if let Some(quests) = file.quests {
+            if let Some(v) = quests.giver_chance { self.quest_giver_chance = v; }
+            if let Some(v) = quests.max_active { self.quest_max_active = v; }
+        }
+        if let Some(consumables) = file.consumables {
+            if let Some(v) = consumables.max_stack { self.consumable_max_stack = v; }
+            if let Some(v) = consumables.drop_chance { self.consumable_drop_chance = v; }
+            if let Some(v) = consumables.find_chance { self.consumable_find_chance = v; }
+        }
+        if let Some(inventory) = file.inventory {
+            if let Some(v) = inventory.capacity { self.inventory_capacity = v; }
+            if let Some(v) = inventory.max_capacity { self.inventory_max_capacity = v; }
+            if let Some(v) = inventory.expand_slots { self.inventory_expand_slots = v; }
+            if let Some(v) = inventory.expand_cost { self.inventory_expand_cost = v; }
+        }
+        if let Some(bank) = file.bank {
+            if let Some(v) = bank.capacity { self.bank_capacity = v; }
+            if let Some(v) = bank.towns { self.bank_towns = v; }
+            if let Some(v) = bank.at_merchant { self.bank_at_merchant = v; }
+        }
+        if let Some(auction) = file.auction {
+            if let Some(v) = auction.max_listings { self.auction_max_listings = v; }
+            if let Some(v) = auction.max_hours { self.auction_max_hours = v; }
+            if let Some(v) = auction.default_hours { self.auction_default_hours = v; }
+            if let Some(v) = auction.fee_percent { self.auction_fee_percent = v; }
+            if let Some(v) = auction.min_raise_percent { self.auction_min_raise_percent = v; }
+            if let Some(v) = auction.check_seconds { self.auction_check_seconds = v; }
+        }
+        if let Some(mail) = file.mail {
+            if let Some(v) = mail.max_mail { self.mail_max_mail = v; }
+            if let Some(v) = mail.postage { self.mail_postage = v; }
+            if let Some(v) = mail.max_length { self.mail_max_length = v; }
+        }
+        if let Some(guild) = file.guild {
+            if let Some(v) = guild.create_cost { self.guild_create_cost = v; }
+            if let Some(v) = guild.max_members { self.guild_max_members = v; }
+            if let Some(v) = guild.bank_capacity { self.guild_bank_capacity = v; }
+        }
+        if let Some(skills) = file.skills {
+            if let Some(v) = skills.reset_cost { self.skills_reset_cost = v; }
+        }
+        if let Some(upgrade) = file.upgrade {
+            if let Some(v) = upgrade.max_level { self.upgrade_max_level = v; }
+            if let Some(v) = upgrade.success_chance { self.upgrade_success_chance = v; }
+            if let Some(v) = upgrade.chance_loss { self.upgrade_chance_loss = v; }
+            if let Some(v) = upgrade.min_chance { self.upgrade_min_chance = v; }
+            if let Some(v) = upgrade.gold { self.upgrade_gold = v; }
+            if let Some(v) = upgrade.stat_percent { self.upgrade_stat_percent = v; }
+            if let Some(v) = upgrade.failure_penalty { self.upgrade_failure_penalty = parse_failure_penalty(v.as_str()); }
+        }
+        if let Some(death) = file.death {
+            if let Some(v) = death.exp_loss_percent { self.death_exp_loss_percent = v; }
+            if let Some(v) = death.drop_item_chance { self.death_drop_item_chance = v; }
+            if let Some(v) = death.reset_region { self.death_reset_region = v; }
+            if let Some(v) = death.respawn_health_percent { self.death_respawn_health_percent = v; }
+        }
+        if let Some(regen) = file.regen {
+            if let Some(v) = regen.mode { self.regen_mode = parse_regen_mode(v.as_str()); }
+            if let Some(v) = regen.amount { self.regen_amount = v; }
+            if let Some(v) = regen.interval { self.regen_interval = v; }
+        }
+        if let Some(leaderboard) = file.leaderboard {
+            if let Some(v) = leaderboard.page_size { self.leaderboard_page_size = v; }
+            if let Some(v) = leaderboard.cache_seconds { self.leaderboard_cache_seconds = v; }
+        }
+    }
+
+    fn apply_env(&mut self) {
+        env_override("SERVER_IP", &mut self.ip);
+        env_override("SERVER_PORT", &mut self.port);
+        env_override("SERVER_MOTD", &mut self.motd);
+        env_override("SERVER_KEEPALIVE_INTERVAL", &mut self.keepalive_interval);
+        env_override("SERVER_ACCEPTED_CLIENT_VERSION", &mut self.accepted_client_version);
+        env_override("DATABASE_NAME", &mut self.database_name);
+        env_override("DATA_DIR", &mut self.data_dir);
+        if let Ok(level) = std::env::var(format!("{}_LOGGING_LEVEL", ENV_PREFIX)) {
+            self.log_level = parse_level(level.as_str());
+        }
+        env_override("LOGGING_STDOUT", &mut self.log_stdout);
+        if let Ok(file) = std::env::var(format!("{}_LOGGING_FILE", ENV_PREFIX)) {
+            self.log_file = non_empty(file);
+        }
+        if let Ok(format) = std::env::var(format!("{}_LOGGING_FORMAT", ENV_PREFIX)) {
+            self.log_format = parse_format(format.as_str());
+        }
+        env_override("LOGGING_MAX_FILE_SIZE", &mut self.log_max_file_size);
+        env_override("LOGGING_MAX_FILES", &mut self.log_max_files);
+        // targets are given as a comma separated list, e.g. `client_handler=debug,main=info`
+        if let Ok(targets) = std::env::var(format!("{}_LOGGING_TARGETS", ENV_PREFIX)) {
+            self.log_targets = targets.split(',')
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(target, level)| (target.trim().to_string(), parse_level(level.trim())))
+                .collect();
+        }
+        env_override("ACCOUNTS_USERNAME_MIN_LENGTH", &mut self.username_min_length);
+        env_override("ACCOUNTS_USERNAME_MAX_LENGTH", &mut self.username_max_length);
+        env_override("ACCOUNTS_PASSWORD_MIN_LENGTH", &mut self.password_min_length);
+        env_override("ACCOUNTS_PASSWORD_MAX_LENGTH", &mut self.password_max_length);
+        env_override("PLAYER_MAX_LEVEL", &mut self.player_max_level);
+        env_override("CHAT_MAX_MESSAGE_LENGTH", &mut self.chat_max_message_length);
+        env_override("CHAT_RATE_LIMIT_MESSAGES", &mut self.chat_rate_limit_messages);
+        env_override("CHAT_RATE_LIMIT_SECONDS", &mut self.chat_rate_limit_seconds);
+        env_override("PARTY_MAX_SIZE", &mut self.party_max_size);
+        if let Ok(rule) = std::env::var(format!("{}_PARTY_LOOT_RULE", ENV_PREFIX)) {
+            self.party_loot_rule = parse_loot_rule(rule.as_str());
+        }
+        env_override("PARTY_TURN_TIMEOUT", &mut self.party_turn_timeout);
+        env_override("PARTY_INVITE_TIMEOUT", &mut self.party_invite_timeout);
+        env_override("PARTY_FLEE_CHANCE", &mut self.party_flee_chance);
+        env_override("DUEL_TURN_TIMEOUT", &mut self.duel_turn_timeout);
+        env_override("DUEL_CHALLENGE_TIMEOUT", &mut self.duel_challenge_timeout);
+        env_override("DUEL_MAX_WAGER_ITEMS", &mut self.duel_max_wager_items);
+        env_override("QUESTS_GIVER_CHANCE", &mut self.quest_giver_chance);
+        env_override("QUESTS_MAX_ACTIVE", &mut self.quest_max_active);
+        env_override("CONSUMABLES_MAX_STACK", &mut self.consumable_max_stack);
+        env_override("CONSUMABLES_DROP_CHANCE", &mut self.consumable_drop_chance);
+        env_override("CONSUMABLES_FIND_CHANCE", &mut self.consumable_find_chance);
+        env_override("INVENTORY_CAPACITY", &mut self.inventory_capacity);
+        env_override("INVENTORY_MAX_CAPACITY", &mut self.inventory_max_capacity);
+        env_override("INVENTORY_EXPAND_SLOTS", &mut self.inventory_expand_slots);
+        env_override("INVENTORY_EXPAND_COST", &mut self.inventory_expand_cost);
+        env_override("BANK_CAPACITY", &mut self.bank_capacity);
+        // towns are given as a comma separated list, e.g. `Plains of Arenlok,Port Vell`
+        if let Ok(towns) = std::env::var(format!("{}_BANK_TOWNS", ENV_PREFIX)) {
+            self.bank_towns = towns.split(',').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect();
+        }
+        env_override("BANK_AT_MERCHANT", &mut self.bank_at_merchant);
+        env_override("AUCTION_MAX_LISTINGS", &mut self.auction_max_listings);
+        env_override("AUCTION_DEFAULT_HOURS", &mut self.auction_default_hours);
+        env_override("AUCTION_MAX_HOURS", &mut self.auction_max_hours);
+        env_override("AUCTION_FEE_PERCENT", &mut self.auction_fee_percent);
+        env_override("AUCTION_MIN_RAISE_PERCENT", &mut self.auction_min_raise_percent);
+        env_override("AUCTION_CHECK_SECONDS", &mut self.auction_check_seconds);
+        env_override("MAIL_MAX_MAIL", &mut self.mail_max_mail);
+        env_override("MAIL_POSTAGE", &mut self.mail_postage);
+        env_override("MAIL_MAX_LENGTH", &mut self.mail_max_length);
+        env_override("GUILD_CREATE_COST", &mut self.guild_create_cost);
+        env_override("GUILD_MAX_MEMBERS", &mut self.guild_max_members);
+        env_override("GUILD_BANK_CAPACITY", &mut self.guild_bank_capacity);
+        env_override("SKILLS_RESET_COST", &mut self.skills_reset_cost);
+        env_override("UPGRADE_MAX_LEVEL", &mut self.upgrade_max_level);
+        env_override("UPGRADE_SUCCESS_CHANCE", &mut self.upgrade_success_chance);
+        env_override("UPGRADE_CHANCE_LOSS", &mut self.upgrade_chance_loss);
+        env_override("UPGRADE_MIN_CHANCE", &mut self.upgrade_min_chance);
+        env_override("UPGRADE_GOLD", &mut self.upgrade_gold);
+        env_override("UPGRADE_STAT_PERCENT", &mut self.upgrade_stat_percent);
+        if let Ok(penalty) = std::env::var(format!("{}_UPGRADE_FAILURE_PENALTY", ENV_PREFIX)) {
+            self.upgrade_failure_penalty = parse_failure_penalty(penalty.as_str());
+        }
+        env_override("DEATH_EXP_LOSS_PERCENT", &mut self.death_exp_loss_percent);
+        env_override("DEATH_DROP_ITEM_CHANCE", &mut self.death_drop_item_chance);
+        env_override("DEATH_RESET_REGION", &mut self.death_reset_region);
+        env_override("DEATH_RESPAWN_HEALTH_PERCENT", &mut self.death_respawn_health_percent);
+        if let Ok(mode) = std::env::var(format!("{}_REGEN_MODE", ENV_PREFIX)) {
+            self.regen_mode = parse_regen_mode(mode.as_str());
+        }
+        env_override("REGEN_AMOUNT", &mut self.regen_amount);
+        env_override("REGEN_INTERVAL", &mut self.regen_interval);
+        env_override("LEADERBOARD_PAGE_SIZE", &mut self.leaderboard_page_size);
+        env_override("LEADERBOARD_CACHE_SECONDS", &mut self.leaderboard_cache_seconds);
+    }
+
+    /// keeps values from the file and environment inside of the ranges the server can use
+    fn clamp(&mut self) {
+        self.player_max_level = self.player_max_level.max(1);
+        self.party_flee_chance = self.party_flee_chance.min(100);
+        self.quest_giver_chance = self.quest_giver_chance.min(100);
+        self.consumable_max_stack = self.consumable_max_stack.max(1);
+        self.consumable_drop_chance = self.consumable_drop_chance.min(100);
+        self.consumable_find_chance = self.consumable_find_chance.min(100);
+        self.inventory_capacity = self.inventory_capacity.max(1);
+        self.inventory_expand_slots = self.inventory_expand_slots.max(1);
+        self.auction_max_hours = self.auction_max_hours.max(1);
+        self.auction_default_hours = self.auction_default_hours.clamp(1, self.auction_max_hours);
+        self.auction_fee_percent = self.auction_fee_percent.min(100);
+        self.auction_check_seconds = self.auction_check_seconds.max(1);
+        self.mail_max_length = self.mail_max_length.max(1);
+        self.guild_max_members = self.guild_max_members.max(1);
+        self.upgrade_success_chance = self.upgrade_success_chance.min(100);
+        self.upgrade_min_chance = self.upgrade_min_chance.min(100);
+        self.death_exp_loss_percent = self.death_exp_loss_percent.min(100);
+        self.death_drop_item_chance = self.death_drop_item_chance.min(100);
+        self.death_respawn_health_percent = self.death_respawn_health_percent.clamp(1, 100);
+        self.regen_interval = self.regen_interval.max(1);
+        self.leaderboard_page_size = self.leaderboard_page_size.max(1);
+    }
+}
+
+/// overrides the value with the environment variable `SND_<key>` if it is set and valid
+fn env_override<T: FromStr>(key: &str, value: &mut T) {
+    let var = format!("{}_{}", ENV_PREFIX, key);
+    if let Ok(raw) = std::env::var(var.as_str()) {
+        match raw.parse::<T>() {
+            Ok(v) => *value = v,
+            Err(_) => eprintln!("Ignoring invalid value '{}' for environment variable {}", raw, var),
+        }
+    }
+}
+
+fn parse_level(raw: &str) -> LevelFilter {
+    LevelFilter::from_str(raw).unwrap_or_else(|_| {
+        eprintln!("Invalid log level '{}', defaulting to trace", raw);
+        LevelFilter::Trace
+    })
+}
+
+fn parse_format(raw: &str) -> LogFormat {
+    LogFormat::from_str(raw).unwrap_or_else(|_| {
+        eprintln!("Invalid log format '{}', defaulting to text", raw);
+        LogFormat::Text
+    })
+}
+
+fn parse_loot_rule(raw: &str) -> LootRule {
+    LootRule::from_str(raw).unwrap_or_else(|_| {
+        eprintln!("Invalid loot rule '{}', defaulting to even", raw);
+        LootRule::Even
+    })
+}
+
+fn parse_regen_mode(raw: &str) -> RegenMode {
+    RegenMode::from_str(raw).unwrap_or_else(|_| {
+        eprintln!("Invalid regen mode '{}', defaulting to steps", raw);
+        RegenMode::Steps
+    })
+}
+
+fn parse_failure_penalty(raw: &str) -> FailurePenalty {
+    FailurePenalty::from_str(raw).unwrap_or_else(|_| {
+        eprintln!("Invalid upgrade failure penalty '{}', defaulting to downgrade", raw);
+        FailurePenalty::Downgrade
+    })
+}
+
+fn non_empty(s: String) -> Option<String> {
+    if s.trim().is_empty() { None } else { Some(s) }
 }
 
 pub fn read_config(path: &Path, default: String) -> Config {
@@ -44,5 +871,5 @@ pub fn read_config(path: &Path, default: String) -> Config {
         data = default;
     }
 
-    toml::from_str(data.as_str()).expect("Could not read config: Please make sure it is valid and has all keys defined, according to the server-config-example.toml")
-}
\ No newline at end of file
+    toml::from_str(data.as_str()).expect("Could not read config: Please make sure it is valid TOML. Every key is optional and falls back to its default")
+}
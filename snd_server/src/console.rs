@@ -0,0 +1,45 @@
+use std::io::{self, BufRead};
+use std::sync::atomic::Ordering;
+use std::thread;
+use log::{error, info, warn};
+use crate::command::{execute, CommandContext, CommandSender};
+
+const LOG_TARGET: &str = "console";
+
+/// Starts reading admin commands from stdin on a new thread.
+/// The thread is not joined on shutdown as it is usually blocked reading a line
+pub fn start_console(ctx: CommandContext) {
+    let spawned = thread::Builder::new()
+        .name(format!("console"))
+        .spawn(move || {
+            let stdin = io::stdin();
+            for line in stdin.lock().lines() {
+                let line = match line {
+                    Ok(l) => l,
+                    Err(e) => {
+                        error!(target:LOG_TARGET, "Failed to read from the console: {}", e);
+                        break;
+                    }
+                };
+                if ctx.terminate.load(Ordering::SeqCst) {
+                    break;
+                }
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                match execute(&ctx, &CommandSender::Console, line.trim()) {
+                    Ok(out) => {
+                        for l in out.lines() {
+                            info!(target:LOG_TARGET, "{}", l);
+                        }
+                    }
+                    Err(e) => warn!(target:LOG_TARGET, "{}", e),
+                }
+            }
+        });
+
+    if let Err(e) = spawned {
+        error!(target:LOG_TARGET, "Failed to start the admin console: {}", e);
+    }
+}
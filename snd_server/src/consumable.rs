@@ -0,0 +1,331 @@
+use log::{error, info, warn};
+use rand::{Rng, thread_rng};
+use serde::Deserialize;
+use uuid::Uuid;
+use snd_network_lib::consumable_data::{ConsumableData, ShopActionData, ShopActionKind};
+use crate::config::ServerConfig;
+use crate::data::read_data_file;
+use crate::database::Database;
+use crate::encounter;
+use crate::health;
+use crate::session::SessionMessage;
+use crate::state::ServerState;
+
+const LOG_TARGET: &str = "consumable";
+
+/// The name of the consumables file in the data folder
+const CONSUMABLES_FILE: &str = "consumables.toml";
+
+/// The contents written to a new consumables file when one does not exist yet
+pub const DEFAULT_CONSUMABLES: &str = r#"# Every consumable needs a unique id, a name, a description, a kind and an effect.
+# kind is potion, food or scroll and is only shown to players.
+# effect is one of:
+#   heal: heals amount health, can be used in and out of fights
+#   attack_buff: the player's attacks deal amount percent more damage for the next turns rounds of a fight
+#   defense_buff: the player takes amount percent less damage for the next turns rounds of a fight
+#   flee: always escapes the fight the player is in
+# Optional settings:
+#   price: the gold the merchant sells it for, it is not sold if this is 0 or missing
+#   drop_weight: how likely it is to be found or dropped compared to the others, it is never found if this is 0 or missing
+# Changing the id of a consumable will remove it from every player's bag
+
+[[consumable]]
+id = "minor_health_potion"
+name = "Minor Health Potion"
+description = "A small red vial that heals 30 health."
+kind = "potion"
+effect = "heal"
+amount = 30
+price = 15
+drop_weight = 10
+
+[[consumable]]
+id = "health_potion"
+name = "Health Potion"
+description = "A large red flask that heals 80 health."
+kind = "potion"
+effect = "heal"
+amount = 80
+price = 40
+drop_weight = 4
+
+[[consumable]]
+id = "bread"
+name = "Bread"
+description = "A loaf of bread that heals 10 health."
+kind = "food"
+effect = "heal"
+amount = 10
+price = 5
+drop_weight = 12
+
+[[consumable]]
+id = "roast_meat"
+name = "Roast Meat"
+description = "A hearty meal that heals 25 health."
+kind = "food"
+effect = "heal"
+amount = 25
+price = 12
+drop_weight = 6
+
+[[consumable]]
+id = "strength_elixir"
+name = "Strength Elixir"
+description = "Your attacks deal 50% more damage for 3 rounds."
+kind = "potion"
+effect = "attack_buff"
+amount = 50
+turns = 3
+price = 30
+drop_weight = 3
+
+[[consumable]]
+id = "iron_skin_tonic"
+name = "Iron Skin Tonic"
+description = "You take 50% less damage for 3 rounds."
+kind = "potion"
+effect = "defense_buff"
+amount = 50
+turns = 3
+price = 30
+drop_weight = 3
+
+[[consumable]]
+id = "scroll_of_escape"
+name = "Scroll of Escape"
+description = "Reading it always gets you out of a fight."
+kind = "scroll"
+effect = "flee"
+price = 25
+drop_weight = 3
+"#;
+
+/// What using a consumable does
+#[derive(Copy, Clone, Debug)]
+pub enum Effect {
+    Heal(u32),
+    AttackBuff { percent: u32, turns: u32 },
+    DefenseBuff { percent: u32, turns: u32 },
+    Flee,
+}
+
+pub struct Consumable {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub kind: String,
+    pub effect: Effect,
+    price: u32,
+    drop_weight: u32,
+}
+
+impl Consumable {
+    pub fn as_data(&self, quantity: u32) -> ConsumableData {
+        ConsumableData {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            description: self.description.clone(),
+            kind: self.kind.clone(),
+            quantity,
+            price: self.price,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ConsumableFile {
+    consumable: Option<Vec<RawConsumable>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawConsumable {
+    id: String,
+    name: String,
+    description: String,
+    kind: String,
+    effect: String,
+    amount: Option<u32>,
+    turns: Option<u32>,
+    price: Option<u32>,
+    drop_weight: Option<u32>,
+}
+
+impl RawConsumable {
+    fn parse(self) -> Result<Consumable, String> {
+        let kind = self.kind.to_ascii_lowercase();
+        if !matches!(kind.as_str(), "potion" | "food" | "scroll") {
+            return Err(format!("unknown kind '{}'", self.kind));
+        }
+        let amount = || match self.amount {
+            Some(amount) if amount > 0 => Ok(amount),
+            _ => Err(format!("the {} effect needs an amount of at least 1", self.effect)),
+        };
+        let turns = || match self.turns {
+            Some(turns) if turns > 0 => Ok(turns),
+            _ => Err(format!("the {} effect needs at least 1 turn", self.effect)),
+        };
+        let effect = match self.effect.to_ascii_lowercase().as_str() {
+            "heal" => Effect::Heal(amount()?),
+            "attack_buff" => Effect::AttackBuff { percent: amount()?, turns: turns()? },
+            "defense_buff" => Effect::DefenseBuff { percent: amount()?.min(90), turns: turns()? },
+            "flee" => Effect::Flee,
+            other => return Err(format!("unknown effect '{}'", other)),
+        };
+
+        Ok(Consumable {
+            id: self.id,
+            name: self.name,
+            description: self.description,
+            kind,
+            effect,
+            price: self.price.unwrap_or(0),
+            drop_weight: self.drop_weight.unwrap_or(0),
+        })
+    }
+}
+
+/// Every consumable in the game, read from the data folder when the server starts
+pub struct ConsumableList {
+    consumables: Vec<Consumable>,
+}
+
+impl ConsumableList {
+    pub fn load(config: &ServerConfig) -> Self {
+        let raw = read_data_file(config, CONSUMABLES_FILE, DEFAULT_CONSUMABLES);
+        Self::parse(raw.as_str())
+    }
+
+    /// reads every consumable in a consumables file, skipping the invalid ones
+    pub fn parse(raw: &str) -> Self {
+        let file = toml::from_str::<ConsumableFile>(raw).unwrap_or_else(|e| {
+            error!(target:LOG_TARGET, "Invalid {}, using the default consumables: {}", CONSUMABLES_FILE, e);
+            toml::from_str(DEFAULT_CONSUMABLES).expect("The default consumables are invalid")
+        });
+
+        let mut consumables: Vec<Consumable> = Vec::new();
+        for raw in file.consumable.unwrap_or_default() {
+            let id = raw.id.clone();
+            if consumables.iter().any(|c| c.id == id) {
+                warn!(target:LOG_TARGET, "Skipping consumable '{}': the id is used more than once", id);
+                continue;
+            }
+            match raw.parse() {
+                Ok(consumable) => consumables.push(consumable),
+                Err(e) => warn!(target:LOG_TARGET, "Skipping consumable '{}': {}", id, e),
+            }
+        }
+        info!(target:LOG_TARGET, "Loaded {} consumables", consumables.len());
+        Self { consumables }
+    }
+
+    fn by_id(&self, id: &str) -> Option<&Consumable> {
+        self.consumables.iter().find(|c| c.id == id)
+    }
+
+    /// finds a consumable by the name players see, or its id
+    pub fn by_name(&self, name: &str) -> Option<&Consumable> {
+        self.consumables.iter().find(|c| c.name.eq_ignore_ascii_case(name) || c.id.eq_ignore_ascii_case(name))
+    }
+
+    /// everything the merchant sells
+    pub fn stock(&self) -> Vec<ConsumableData> {
+        self.consumables.iter().filter(|c| c.price > 0).map(|c| c.as_data(0)).collect()
+    }
+
+    /// the consumables a player has, stacks of consumables that were removed from the file are left out
+    pub fn owned(&self, db: &Database, uuid: &Uuid) -> Vec<ConsumableData> {
+        db.get_player_consumables(uuid).into_iter()
+            .filter_map(|(id, quantity)| self.by_id(id.as_str()).map(|c| c.as_data(quantity)))
+            .collect()
+    }
+
+    /// picks a random consumable to find or drop by their drop weights
+    pub fn rand_drop(&self) -> Option<&Consumable> {
+        // the weights come from the data file, so they are added up as u64 to never overflow
+        let total = self.consumables.iter().map(|c| c.drop_weight as u64).sum::<u64>();
+        if total == 0 {
+            return None;
+        }
+        let mut roll = thread_rng().gen_range(0..total);
+        for c in &self.consumables {
+            if roll < c.drop_weight as u64 {
+                return Some(c);
+            }
+            roll -= c.drop_weight as u64;
+        }
+        None
+    }
+}
+
+/// Uses one of a consumable, in the player's fight if they are in one.
+/// Returns an error to show the player if they can not use it
+pub fn use_item(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str, name: &str) -> Result<(), String> {
+    let consumable = state.consumables.by_name(name).ok_or(format!("There is no item called '{}'", name))?;
+    if !state.db.lock().unwrap().get_player_consumables(uuid).iter().any(|(id, _)| *id == consumable.id) {
+        return Err(format!("You do not have any {}", consumable.name));
+    }
+    if state.duels.lock().unwrap().in_duel(uuid) {
+        return Err(format!("You can not use items in a duel"));
+    }
+    if state.encounters.lock().unwrap().in_fight(uuid) {
+        return encounter::use_consumable(state, config, uuid, consumable);
+    }
+
+    let heal = match consumable.effect {
+        Effect::Heal(amount) => amount,
+        Effect::Flee => return Err(format!("There is nothing to flee from")),
+        Effect::AttackBuff { .. } | Effect::DefenseBuff { .. } => return Err(format!("You can only use {} in a fight", consumable.name)),
+    };
+    let message = {
+        let db = state.db.lock().unwrap();
+        if db.get_player_health(uuid) >= db.get_player_max_health(uuid) {
+            return Err(format!("You are already at full health"));
+        }
+        if !db.take_consumable(uuid, consumable.id.as_str()) {
+            return Err(format!("You do not have any {}", consumable.name));
+        }
+        let healed = health::heal(&db, uuid, heal);
+        format!("You used {} and healed {} health", consumable.name, healed)
+    };
+    info!(target:LOG_TARGET, "{} used {}", username, consumable.id);
+    let sessions = state.sessions.lock().unwrap();
+    sessions.send(uuid, SessionMessage::Event(message));
+    sessions.send(uuid, SessionMessage::StatsChanged);
+    Ok(())
+}
+
+/// Lists the merchant's stock or buys from it, players can only shop outside of fights.
+/// Returns the stock to show the player, or an error if they can not buy
+pub fn shop(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str, action: ShopActionData) -> Result<Option<Vec<ConsumableData>>, String> {
+    if action.kind == ShopActionKind::List {
+        return Ok(Some(state.consumables.stock()));
+    }
+    if state.encounters.lock().unwrap().in_fight(uuid) {
+        return Err(format!("The merchant will not trade with you in the middle of a fight"));
+    }
+    let consumable = state.consumables.by_name(action.item.as_str())
+        .filter(|c| c.price > 0)
+        .ok_or(format!("The merchant does not sell '{}'", action.item))?;
+    let quantity = action.quantity.max(1).min(config.consumable_max_stack);
+    let cost = consumable.price.checked_mul(quantity)
+        .ok_or(format!("You can not afford {} {}", quantity, consumable.name))?;
+    {
+        let db = state.db.lock().unwrap();
+        let gold = db.get_player_gold(uuid).unwrap_or(0);
+        if gold < cost {
+            return Err(format!("{} {} costs {} gold but you only have {}", quantity, consumable.name, cost, gold));
+        }
+        let owned = db.get_player_consumables(uuid).into_iter()
+            .find(|(id, _)| *id == consumable.id).map_or(0, |(_, q)| q);
+        if owned + quantity > config.consumable_max_stack {
+            return Err(format!("You can only carry {} {}", config.consumable_max_stack, consumable.name));
+        }
+        if !db.buy_consumable(uuid, consumable.id.as_str(), quantity, cost, config.consumable_max_stack) {
+            return Err(format!("Failed to buy {}", consumable.name));
+        }
+    }
+    info!(target:LOG_TARGET, "{} bought {} {} for {} gold", username, quantity, consumable.id, cost);
+    state.sessions.lock().unwrap().send(uuid, SessionMessage::Event(format!("You bought {} {} for {} gold", quantity, consumable.name, cost)));
+    Ok(None)
+}
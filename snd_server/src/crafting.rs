@@ -0,0 +1,492 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use log::{error, info, warn};
+use rand::{Rng, thread_rng};
+use serde::Deserialize;
+use uuid::Uuid;
+use snd_network_lib::crafting_data::{CraftingData, MaterialData, RecipeData};
+use crate::config::ServerConfig;
+use crate::data::read_data_file;
+use crate::database::Database;
+use crate::inventory;
+use crate::item::{Item, ItemRarity, ItemType};
+use crate::state::ServerState;
+
+const LOG_TARGET: &str = "crafting";
+
+/// The name of the crafting file in the data folder
+const CRAFTING_FILE: &str = "crafting.toml";
+
+/// The contents written to a new crafting file when one does not exist yet
+pub const DEFAULT_CRAFTING: &str = r#"# Materials are what items break into when they are salvaged.
+# Every material needs a unique id and a name. Optional settings:
+#   from: the item types that break into it, every type if it is missing
+#   min_rarity: only items of at least this rarity break into it
+#   amount: how many every item gives, 1 if it is missing
+#   per_levels: one more is given for every this many levels of the item, no more are given if it is 0 or missing
+# Changing the id of a material will remove it from every player
+
+[[material]]
+id = "scrap_metal"
+name = "Scrap Metal"
+from = ["sword", "shield", "helmet", "chestplate"]
+amount = 2
+per_levels = 5
+
+[[material]]
+id = "leather"
+name = "Leather"
+from = ["chestplate", "leggings", "boots"]
+amount = 2
+per_levels = 5
+
+[[material]]
+id = "arcane_dust"
+name = "Arcane Dust"
+min_rarity = "rare"
+amount = 1
+per_levels = 10
+
+[[material]]
+id = "starlight_shard"
+name = "Starlight Shard"
+min_rarity = "epic"
+amount = 1
+
+# Recipes make a new item at about the player's level.
+# Every recipe needs a unique id, a name, the item type it makes and the materials it takes by their ids.
+# Optional settings:
+#   min_rarity: the rarity is rolled like a found item but is never lower than this
+#   gold: the gold it costs on top of the materials
+
+[[recipe]]
+id = "leather_boots"
+name = "Leather Boots"
+item = "boots"
+materials = { leather = 4 }
+
+[[recipe]]
+id = "forged_sword"
+name = "Forged Sword"
+item = "sword"
+min_rarity = "rare"
+materials = { scrap_metal = 6, arcane_dust = 2 }
+
+[[recipe]]
+id = "tower_shield"
+name = "Tower Shield"
+item = "shield"
+min_rarity = "rare"
+materials = { scrap_metal = 8, arcane_dust = 2 }
+
+[[recipe]]
+id = "hunters_leggings"
+name = "Hunter's Leggings"
+item = "leggings"
+min_rarity = "rare"
+materials = { leather = 6, arcane_dust = 1 }
+
+[[recipe]]
+id = "starforged_blade"
+name = "Starforged Blade"
+item = "sword"
+min_rarity = "epic"
+materials = { scrap_metal = 12, arcane_dust = 4, starlight_shard = 2 }
+gold = 100
+
+[[recipe]]
+id = "starforged_plate"
+name = "Starforged Plate"
+item = "chestplate"
+min_rarity = "epic"
+materials = { scrap_metal = 10, leather = 4, starlight_shard = 2 }
+gold = 100
+
+# Upgrading an item takes these materials on top of gold, times the upgrade the item is going to.
+# The gold and the chance of an upgrade working are set in the [upgrade] section of the config.
+
+[upgrade]
+materials = { scrap_metal = 2, arcane_dust = 1 }
+"#;
+
+/// What happens to an item when upgrading it fails, the gold and materials are always lost
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum FailurePenalty {
+    /// nothing happens to the item
+    None,
+    /// the item loses one upgrade
+    Downgrade,
+    /// the item is destroyed
+    Destroy,
+}
+
+impl FromStr for FailurePenalty {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "downgrade" => Ok(Self::Downgrade),
+            "destroy" => Ok(Self::Destroy),
+            _ => Err(()),
+        }
+    }
+}
+
+struct Material {
+    id: String,
+    name: String,
+    /// the item types that break into it, empty for every type
+    from: Vec<ItemType>,
+    min_rarity: ItemRarity,
+    amount: u32,
+    per_levels: u32,
+}
+
+impl Material {
+    /// how many of this material an item breaks into
+    fn salvaged_from(&self, item: &Item) -> u32 {
+        if !self.from.is_empty() && !self.from.contains(&item.item_type) {
+            return 0;
+        }
+        if (item.rarity as u32) < (self.min_rarity as u32) {
+            return 0;
+        }
+        match self.per_levels {
+            0 => self.amount,
+            per => self.amount + item.level / per,
+        }
+    }
+}
+
+struct Recipe {
+    id: String,
+    name: String,
+    item_type: ItemType,
+    min_rarity: ItemRarity,
+    /// the id and amount of every material it takes
+    materials: Vec<(String, u32)>,
+    gold: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct CraftingFile {
+    material: Option<Vec<RawMaterial>>,
+    recipe: Option<Vec<RawRecipe>>,
+    upgrade: Option<RawUpgrade>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawMaterial {
+    id: String,
+    name: String,
+    from: Option<Vec<String>>,
+    min_rarity: Option<String>,
+    amount: Option<u32>,
+    per_levels: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawRecipe {
+    id: String,
+    name: String,
+    item: String,
+    min_rarity: Option<String>,
+    materials: BTreeMap<String, u32>,
+    gold: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawUpgrade {
+    materials: BTreeMap<String, u32>,
+}
+
+fn parse_rarity(rarity: Option<String>) -> Result<ItemRarity, String> {
+    match rarity {
+        Some(r) => ItemRarity::from_str(r.as_str()).map_err(|_| format!("unknown rarity '{}'", r)),
+        None => Ok(ItemRarity::Common),
+    }
+}
+
+impl RawMaterial {
+    fn parse(self) -> Result<Material, String> {
+        let from = self.from.unwrap_or_default().iter()
+            .map(|t| ItemType::from_str(t.as_str()).map_err(|_| format!("unknown item type '{}'", t)))
+            .collect::<Result<Vec<ItemType>, String>>()?;
+        Ok(Material {
+            id: self.id,
+            name: self.name,
+            from,
+            min_rarity: parse_rarity(self.min_rarity)?,
+            amount: self.amount.unwrap_or(1),
+            per_levels: self.per_levels.unwrap_or(0),
+        })
+    }
+}
+
+impl RawRecipe {
+    fn parse(self, materials: &[Material]) -> Result<Recipe, String> {
+        let item_type = ItemType::from_str(self.item.as_str()).map_err(|_| format!("unknown item type '{}'", self.item))?;
+        if let Some(unknown) = self.materials.keys().find(|id| !materials.iter().any(|m| m.id == **id)) {
+            return Err(format!("unknown material '{}'", unknown));
+        }
+        if self.materials.values().all(|amt| *amt == 0) {
+            return Err(format!("it needs at least one material"));
+        }
+        Ok(Recipe {
+            id: self.id,
+            name: self.name,
+            item_type,
+            min_rarity: parse_rarity(self.min_rarity)?,
+            materials: self.materials.into_iter().filter(|(_, amt)| *amt > 0).collect(),
+            gold: self.gold.unwrap_or(0),
+        })
+    }
+}
+
+/// Every material and recipe, read from the data folder when the server starts
+pub struct CraftingList {
+    materials: Vec<Material>,
+    recipes: Vec<Recipe>,
+    /// the id and amount of every material the first upgrade of an item takes
+    upgrade_materials: Vec<(String, u32)>,
+}
+
+impl CraftingList {
+    pub fn load(config: &ServerConfig) -> Self {
+        let raw = read_data_file(config, CRAFTING_FILE, DEFAULT_CRAFTING);
+        Self::parse(raw.as_str())
+    }
+
+    /// reads every material and recipe in a crafting file, skipping the invalid ones
+    pub fn parse(raw: &str) -> Self {
+        let file = toml::from_str::<CraftingFile>(raw).unwrap_or_else(|e| {
+            error!(target:LOG_TARGET, "Invalid {}, using the default recipes: {}", CRAFTING_FILE, e);
+            toml::from_str(DEFAULT_CRAFTING).expect("The default recipes are invalid")
+        });
+
+        let mut materials: Vec<Material> = Vec::new();
+        for raw in file.material.unwrap_or_default() {
+            let id = raw.id.clone();
+            if materials.iter().any(|m| m.id == id) {
+                warn!(target:LOG_TARGET, "Skipping material '{}': the id is used more than once", id);
+                continue;
+            }
+            match raw.parse() {
+                Ok(material) => materials.push(material),
+                Err(e) => warn!(target:LOG_TARGET, "Skipping material '{}': {}", id, e),
+            }
+        }
+        let mut recipes: Vec<Recipe> = Vec::new();
+        for raw in file.recipe.unwrap_or_default() {
+            let id = raw.id.clone();
+            if recipes.iter().any(|r| r.id == id) {
+                warn!(target:LOG_TARGET, "Skipping recipe '{}': the id is used more than once", id);
+                continue;
+            }
+            match raw.parse(&materials) {
+                Ok(recipe) => recipes.push(recipe),
+                Err(e) => warn!(target:LOG_TARGET, "Skipping recipe '{}': {}", id, e),
+            }
+        }
+        let mut upgrade_materials = Vec::new();
+        for (id, amt) in file.upgrade.map(|u| u.materials).unwrap_or_default() {
+            if !materials.iter().any(|m| m.id == id) {
+                warn!(target:LOG_TARGET, "Skipping upgrade material '{}': there is no material with that id", id);
+                continue;
+            }
+            if amt > 0 {
+                upgrade_materials.push((id, amt));
+            }
+        }
+        info!(target:LOG_TARGET, "Loaded {} materials and {} recipes", materials.len(), recipes.len());
+        Self { materials, recipes, upgrade_materials }
+    }
+
+    pub fn has_material(&self, id: &str) -> bool {
+        self.materials.iter().any(|m| m.id == id)
+    }
+
+    pub fn material_name(&self, id: &str) -> String {
+        self.materials.iter().find(|m| m.id == id).map_or(id.to_string(), |m| m.name.clone())
+    }
+
+    /// e.g. "6 Scrap Metal, 2 Arcane Dust and 100 gold"
+    fn describe_cost(&self, materials: &[(String, u32)], gold: u32) -> String {
+        let mut parts = materials.iter()
+            .map(|(id, amt)| format!("{} {}", amt, self.material_name(id)))
+            .collect::<Vec<String>>();
+        if gold > 0 {
+            parts.push(format!("{} gold", gold));
+        }
+        match parts.split_last() {
+            Some((last, rest)) if !rest.is_empty() => format!("{} and {}", rest.join(", "), last),
+            _ => parts.join(""),
+        }
+    }
+
+    /// if the player has all of the materials and gold something costs
+    fn can_afford(materials: &[(String, u32)], gold: u32, owned: &[(String, u32)], owned_gold: u32) -> bool {
+        owned_gold >= gold && materials.iter().all(|(id, amt)| {
+            owned.iter().any(|(owned_id, quantity)| owned_id == id && quantity >= amt)
+        })
+    }
+
+    /// the materials upgrading an item to the next upgrade takes
+    fn upgrade_cost(&self, next: u32) -> Vec<(String, u32)> {
+        self.upgrade_materials.iter().map(|(id, amt)| (id.clone(), amt * next)).collect()
+    }
+
+    /// every recipe and the materials the player has
+    pub fn view(&self, db: &Database, uuid: &Uuid) -> CraftingData {
+        let owned = db.get_player_materials(uuid);
+        let gold = db.get_player_gold(uuid).unwrap_or(0);
+        CraftingData {
+            recipes: self.recipes.iter().map(|r| RecipeData {
+                name: r.name.clone(),
+                result: match r.min_rarity {
+                    ItemRarity::Common => r.item_type.to_string(),
+                    rarity => format!("{} {} or better", rarity.to_string(), r.item_type.to_string()),
+                },
+                cost: self.describe_cost(&r.materials, r.gold),
+                craftable: Self::can_afford(&r.materials, r.gold, &owned, gold),
+            }).collect(),
+            materials: owned.iter()
+                .map(|(id, quantity)| MaterialData { name: self.material_name(id), quantity: *quantity })
+                .collect(),
+        }
+    }
+}
+
+/// Breaks one of the player's items into materials.
+/// Returns what they got to show them, or an error if they can not salvage it
+pub fn salvage(state: &ServerState, uuid: &Uuid, username: &str, item_name: &str) -> Result<String, String> {
+    let crafting = &state.crafting;
+    let (item, gained) = {
+        let db = state.db.lock().unwrap();
+        let item = db.item_uuid_from_name(item_name.replace("'", ""), uuid)
+            .and_then(|item_uuid| db.get_item(&item_uuid))
+            .filter(|item| item.owner == *uuid)
+            .ok_or(format!("You do not have an item called '{}'", item_name))?;
+        let gained = crafting.materials.iter()
+            .map(|m| (m.id.clone(), m.salvaged_from(&item)))
+            .filter(|(_, amt)| *amt > 0)
+            .collect::<Vec<(String, u32)>>();
+        if gained.is_empty() {
+            return Err(format!("'{}' can not be salvaged into anything", item.name));
+        }
+        if !db.salvage_item(&item, &gained) {
+            return Err(format!("Failed to salvage '{}'", item.name));
+        }
+        (item, gained)
+    };
+    info!(target:LOG_TARGET, "{} salvaged '{}'", username, item.name);
+    Ok(format!("You salvaged '{}' into {}", item.name, gained.iter()
+        .map(|(id, amt)| format!("{} {}", amt, crafting.material_name(id)))
+        .collect::<Vec<String>>().join(", ")))
+}
+
+/// Crafts an item from a recipe, its rarity is rolled but never below the recipe's minimum.
+/// Returns the crafted item, or an error if the player can not craft it
+pub fn craft(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str, recipe_name: &str) -> Result<Item, String> {
+    let crafting = &state.crafting;
+    let recipe = crafting.recipes.iter()
+        .find(|r| r.name.eq_ignore_ascii_case(recipe_name) || r.id.eq_ignore_ascii_case(recipe_name))
+        .ok_or(format!("There is no recipe called '{}'", recipe_name))?;
+    let item = {
+        let db = state.db.lock().unwrap();
+        let gold = db.get_player_gold(uuid).unwrap_or(0);
+        if !CraftingList::can_afford(&recipe.materials, recipe.gold, &db.get_player_materials(uuid), gold) {
+            return Err(format!("{} takes {}", recipe.name, crafting.describe_cost(&recipe.materials, recipe.gold)));
+        }
+        if inventory::is_full(&db, config, uuid) {
+            return Err(format!("Your inventory is full, make room before crafting {}", recipe.name));
+        }
+        let level = db.get_player_level(uuid).unwrap_or(1);
+        let item = Item::new_rand(recipe.item_type, uuid, level, ItemRarity::new_rand().at_least(recipe.min_rarity));
+        if !db.craft_item(&item, &recipe.materials, recipe.gold) {
+            return Err(format!("Failed to craft {}", recipe.name));
+        }
+        item
+    };
+    info!(target:LOG_TARGET, "{} crafted '{}' from {}", username, item.name, recipe.id);
+    Ok(item)
+}
+
+/// the percent chance of upgrading an item that already has this many upgrades working
+fn upgrade_chance(config: &ServerConfig, upgrades: u32) -> u32 {
+    config.upgrade_success_chance.saturating_sub(config.upgrade_chance_loss * upgrades)
+        .max(config.upgrade_min_chance)
+}
+
+/// raises or lowers an item's damage or defense by one upgrade, stats that are 0 stay 0
+fn upgrade_stat(stat: u32, percent: u32, raise: bool) -> u32 {
+    if stat == 0 {
+        return 0;
+    }
+    if raise {
+        stat + (stat * percent / 100).max(1)
+    } else {
+        // undoes a raise as closely as rounding allows
+        stat.saturating_sub((stat * percent / (100 + percent)).max(1)).max(1)
+    }
+}
+
+/// Upgrades one of the player's items for gold and materials, raising its level and damage or defense.
+/// The chance of it working falls with every upgrade the item has, and a failure can cost the item an upgrade or the item itself.
+/// Returns what happened to show the player, or an error if they can not upgrade it
+pub fn upgrade(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str, item_name: &str) -> Result<String, String> {
+    if state.encounters.lock().unwrap().in_fight(uuid) || state.duels.lock().unwrap().in_duel(uuid) {
+        return Err(format!("You can not upgrade items in the middle of a fight"));
+    }
+    let crafting = &state.crafting;
+    let (item, message) = {
+        let db = state.db.lock().unwrap();
+        let mut item = db.item_uuid_from_name(item_name.replace("'", ""), uuid)
+            .and_then(|item_uuid| db.get_item(&item_uuid))
+            .filter(|item| item.owner == *uuid)
+            .ok_or(format!("You do not have an item called '{}'", item_name))?;
+        if item.upgrades >= config.upgrade_max_level {
+            return Err(format!("'{}' can not be upgraded any further", item.name));
+        }
+        let next = item.upgrades + 1;
+        let materials = crafting.upgrade_cost(next);
+        let gold = config.upgrade_gold * next;
+        if !CraftingList::can_afford(&materials, gold, &db.get_player_materials(uuid), db.get_player_gold(uuid).unwrap_or(0)) {
+            return Err(format!("Upgrading '{}' to +{} takes {}", item.name, next, crafting.describe_cost(&materials, gold)));
+        }
+
+        let chance = upgrade_chance(config, item.upgrades);
+        let percent = config.upgrade_stat_percent;
+        let mut destroyed = false;
+        let message = if thread_rng().gen_range(0..100) < chance {
+            item.upgrades = next;
+            item.level += 1;
+            item.damage = upgrade_stat(item.damage, percent, true);
+            item.defense = upgrade_stat(item.defense, percent, true);
+            format!("You upgraded '{}' to +{} ({}% chance), it is now level {}", item.name, item.upgrades, chance, item.level)
+        } else {
+            match config.upgrade_failure_penalty {
+                FailurePenalty::Downgrade if item.upgrades > 0 => {
+                    item.upgrades -= 1;
+                    item.level = item.level.saturating_sub(1).max(1);
+                    item.damage = upgrade_stat(item.damage, percent, false);
+                    item.defense = upgrade_stat(item.defense, percent, false);
+                    format!("Upgrading '{}' failed ({}% chance) and it fell back to +{}", item.name, chance, item.upgrades)
+                }
+                FailurePenalty::Destroy => {
+                    destroyed = true;
+                    format!("Upgrading '{}' failed ({}% chance) and it was destroyed", item.name, chance)
+                }
+                FailurePenalty::None | FailurePenalty::Downgrade =>
+                    format!("Upgrading '{}' failed ({}% chance), the gold and materials were lost", item.name, chance),
+            }
+        };
+        if !db.upgrade_item(&item, destroyed, &materials, gold) {
+            return Err(format!("Failed to upgrade '{}'", item.name));
+        }
+        (item, message)
+    };
+    info!(target:LOG_TARGET, "{} upgraded '{}': {}", username, item.name, message);
+    Ok(message)
+}
@@ -0,0 +1,26 @@
+use std::fs;
+use std::path::Path;
+use log::{error, info};
+use crate::config::ServerConfig;
+
+const LOG_TARGET: &str = "data";
+
+/// Reads a game data file from the data folder, writing the default contents first if it does not exist yet.
+/// The defaults are used if the file can not be read
+pub fn read_data_file(config: &ServerConfig, name: &str, default: &str) -> String {
+    let path = Path::new(config.data_dir.as_str()).join(name);
+    if !path.exists() {
+        let written = fs::create_dir_all(config.data_dir.as_str())
+            .and_then(|_| fs::write(&path, default));
+        match written {
+            Ok(_) => info!(target:LOG_TARGET, "Created {} with the defaults", path.display()),
+            Err(e) => error!(target:LOG_TARGET, "Failed to create {}: {}", path.display(), e),
+        }
+        return default.to_string();
+    }
+
+    fs::read_to_string(&path).unwrap_or_else(|e| {
+        error!(target:LOG_TARGET, "Failed to read {}, using the defaults: {}", path.display(), e);
+        default.to_string()
+    })
+}
@@ -1,21 +1,32 @@
+use std::net::IpAddr;
 use std::process::exit;
 use std::str::FromStr;
 use log::{error, info};
 use sqlite::{Connection, State};
 use uuid::Uuid;
-use crate::item::{Item, ItemRarity, ItemType};
-use crate::player::Player;
+use snd_network_lib::auction_data::AuctionSearchData;
+use snd_network_lib::guild_data::GuildRank;
+use snd_network_lib::leaderboard_data::LeaderboardCategory;
+use snd_network_lib::systime;
+use crate::auction::Listing;
+use crate::item::{Item, ItemRarity, ItemType, SpecialAbility};
+use crate::guild::Guild;
+use crate::mail::Mail;
+use crate::moderation::{AuditEntry, Ban, cidr_contains, expires_at, Mute};
+use crate::permission::Role;
+use crate::player::{BASE_HEALTH, exp_to_level, max_health, Player, SKILL_POINTS_PER_LEVEL, START_REGION};
 
-#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub enum LoginFailReason {
-    Unauthorized, Unrecognized, AlreadyOnline,
+    Unauthorized, Unrecognized, AlreadyOnline, Banned(Ban),
 }
 
 #[derive(Eq, PartialEq, Copy, Clone, Debug)]
 pub enum PlayerValueDB {
     UUID, Username, Password,
     Level, Exp, Steps, Health,
-    CurrentRegion, Active,
+    CurrentRegion, Active, Role,
+    Gold, DuelWins, DuelLosses, Kills,
 }
 
 impl ToString for PlayerValueDB {
@@ -30,6 +41,11 @@ impl ToString for PlayerValueDB {
             Self::Health => "health",
             Self::CurrentRegion => "current_region",
             Self::Active => "active",
+            Self::Role => "role",
+            Self::Gold => "gold",
+            Self::DuelWins => "duel_wins",
+            Self::DuelLosses => "duel_losses",
+            Self::Kills => "kills",
         }.to_string()
     }
 }
@@ -38,7 +54,7 @@ impl ToString for PlayerValueDB {
 pub enum ItemValueDB {
     UUID, Owner, Name, Type,
     Level, Damage, Defense,
-    SpecialAbility, Rarity,
+    SpecialAbility, Rarity, Upgrades,
 }
 
 impl ToString for ItemValueDB {
@@ -53,6 +69,7 @@ impl ToString for ItemValueDB {
             Self::Defense => "defense",
             Self::SpecialAbility => "special_ability",
             Self::Rarity => "rarity",
+            Self::Upgrades => "upgrades",
         }.to_string()
     }
 }
@@ -75,9 +92,220 @@ impl Database {
             exit(1);
         }
         let connection = connection_result.unwrap();
-        Self {
+        let db = Self {
             connection
+        };
+        if let Err(e) = db.init_tables() {
+            eprintln!("Failed to create the database tables: {}", e);
+            exit(1);
+        }
+        db
+    }
+
+    /// creates any tables that do not exist yet so a fresh database can be used
+    fn init_tables(&self) -> sqlite::Result<()> {
+        // sqlite only checks foreign keys when asked to, once per connection
+        self.connection.execute("PRAGMA foreign_keys = ON;")?;
+        self.connection.execute("\
+        CREATE TABLE IF NOT EXISTS players (\
+            uuid           TEXT,\
+            username       TEXT,\
+            password       TEXT,\
+            level          integer,\
+            exp            integer,\
+            steps          integer,\
+            health         integer,\
+            current_region TEXT,\
+            active         integer,\
+            role           TEXT DEFAULT 'player',\
+            gold           integer DEFAULT 0,\
+            duel_wins      integer DEFAULT 0,\
+            duel_losses    integer DEFAULT 0,\
+            kills          integer DEFAULT 0,\
+            inventory_slots integer DEFAULT 0,\
+            class          TEXT DEFAULT '',\
+            skill_points   integer DEFAULT 0,\
+            health_percent integer DEFAULT 100\
+        );\
+        CREATE TABLE IF NOT EXISTS items (\
+            owner           TEXT,\
+            type            integer,\
+            level           integer,\
+            damage          integer,\
+            defense         integer,\
+            special_ability TEXT,\
+            name            TEXT,\
+            uuid            integer,\
+            rarity          integer,\
+            upgrades        integer DEFAULT 0\
+        );\
+        CREATE TABLE IF NOT EXISTS bans (\
+            uuid      TEXT,\
+            reason    TEXT,\
+            banned_by TEXT,\
+            time      integer,\
+            ip        TEXT,\
+            expires   integer\
+        );\
+        CREATE TABLE IF NOT EXISTS mutes (\
+            uuid     TEXT,\
+            reason   TEXT,\
+            muted_by TEXT,\
+            time     integer,\
+            expires  integer\
+        );\
+        CREATE TABLE IF NOT EXISTS ignores (\
+            uuid    TEXT,\
+            ignored TEXT\
+        );\
+        CREATE TABLE IF NOT EXISTS audit_log (\
+            time    integer,\
+            actor   TEXT,\
+            action  TEXT,\
+            target  TEXT,\
+            details TEXT\
+        );\
+        CREATE TABLE IF NOT EXISTS escrow (\
+            id    TEXT,\
+            owner TEXT,\
+            gold  integer,\
+            item  TEXT\
+        );\
+        CREATE TABLE IF NOT EXISTS achievements (\
+            uuid TEXT,\
+            id   TEXT,\
+            time integer\
+        );\
+        CREATE UNIQUE INDEX IF NOT EXISTS achievements_by_player ON achievements (uuid, id);\
+        CREATE TABLE IF NOT EXISTS quests (\
+            uuid     TEXT,\
+            id       TEXT,\
+            progress integer,\
+            done     integer\
+        );\
+        CREATE UNIQUE INDEX IF NOT EXISTS quests_by_player ON quests (uuid, id);\
+        CREATE TABLE IF NOT EXISTS consumables (\
+            owner    TEXT,\
+            id       TEXT,\
+            quantity integer\
+        );\
+        CREATE UNIQUE INDEX IF NOT EXISTS consumables_by_owner ON consumables (owner, id);\
+        CREATE TABLE IF NOT EXISTS materials (\
+            owner    TEXT,\
+            id       TEXT,\
+            quantity integer\
+        );\
+        CREATE UNIQUE INDEX IF NOT EXISTS materials_by_owner ON materials (owner, id);\
+        CREATE TABLE IF NOT EXISTS auctions (\
+            number    INTEGER PRIMARY KEY AUTOINCREMENT,\
+            id        TEXT,\
+            seller    TEXT,\
+            item      TEXT,\
+            start_bid integer,\
+            bid       integer,\
+            bidder    TEXT,\
+            buyout    integer,\
+            expires   integer\
+        );\
+        CREATE TABLE IF NOT EXISTS mail (\
+            number    INTEGER PRIMARY KEY AUTOINCREMENT,\
+            id        TEXT,\
+            recipient TEXT,\
+            sender    TEXT,\
+            message   TEXT,\
+            gold      integer,\
+            time      integer,\
+            read      integer DEFAULT 0,\
+            claimed   integer DEFAULT 0\
+        );\
+        CREATE INDEX IF NOT EXISTS mail_by_recipient ON mail (recipient);\
+        CREATE UNIQUE INDEX IF NOT EXISTS players_by_uuid ON players (uuid);\
+        CREATE TABLE IF NOT EXISTS guilds (\
+            id      TEXT PRIMARY KEY,\
+            name    TEXT NOT NULL UNIQUE COLLATE NOCASE,\
+            created integer\
+        );\
+        CREATE TABLE IF NOT EXISTS guild_members (\
+            uuid   TEXT PRIMARY KEY REFERENCES players (uuid) ON DELETE CASCADE,\
+            guild  TEXT NOT NULL REFERENCES guilds (id) ON DELETE CASCADE,\
+            rank   integer,\
+            joined integer\
+        );\
+        CREATE INDEX IF NOT EXISTS guild_members_by_guild ON guild_members (guild);\
+        CREATE TABLE IF NOT EXISTS guild_invites (\
+            uuid  TEXT NOT NULL REFERENCES players (uuid) ON DELETE CASCADE,\
+            guild TEXT NOT NULL REFERENCES guilds (id) ON DELETE CASCADE,\
+            time  integer,\
+            PRIMARY KEY (uuid, guild)\
+        );\
+        CREATE TABLE IF NOT EXISTS player_skills (\
+            uuid  TEXT NOT NULL REFERENCES players (uuid) ON DELETE CASCADE,\
+            skill TEXT NOT NULL,\
+            rank  integer,\
+            PRIMARY KEY (uuid, skill)\
+        );\
+        CREATE TABLE IF NOT EXISTS bestiary (\
+            uuid  TEXT NOT NULL REFERENCES players (uuid) ON DELETE CASCADE,\
+            race  TEXT NOT NULL,\
+            kills integer,\
+            PRIMARY KEY (uuid, race)\
+        );")?;
+
+        // columns added after the table was first created
+        self.add_column_if_missing("players", "role", "TEXT DEFAULT 'player'")?;
+        self.add_column_if_missing("players", "gold", "integer DEFAULT 0")?;
+        self.add_column_if_missing("players", "duel_wins", "integer DEFAULT 0")?;
+        self.add_column_if_missing("players", "duel_losses", "integer DEFAULT 0")?;
+        self.add_column_if_missing("players", "kills", "integer DEFAULT 0")?;
+        self.add_column_if_missing("players", "inventory_slots", "integer DEFAULT 0")?;
+        self.add_column_if_missing("players", "class", "TEXT DEFAULT ''")?;
+        self.add_column_if_missing("players", "health_percent", "integer DEFAULT 100")?;
+        // players from before skills get the points for the levels they already have
+        if self.add_column_if_missing("players", "skill_points", "integer DEFAULT 0")? {
+            self.connection.execute(format!("UPDATE players SET skill_points = (level - 1) * {}", SKILL_POINTS_PER_LEVEL))?;
+        }
+        self.add_column_if_missing("items", "upgrades", "integer DEFAULT 0")?;
+        self.add_column_if_missing("bans", "ip", "TEXT")?;
+        self.add_column_if_missing("bans", "expires", "integer")?;
+
+        // indexes for the leaderboards, made after the columns they use exist
+        self.connection.execute("\
+        CREATE INDEX IF NOT EXISTS players_by_level ON players (level DESC, exp DESC, username);\
+        CREATE INDEX IF NOT EXISTS players_by_steps ON players (steps DESC, username);\
+        CREATE INDEX IF NOT EXISTS players_by_kills ON players (kills DESC, username);\
+        CREATE INDEX IF NOT EXISTS players_by_gold ON players (gold DESC, username);\
+        CREATE INDEX IF NOT EXISTS players_by_duel_wins ON players (duel_wins DESC, username);")?;
+        Ok(())
+    }
+
+    /// runs every statement or none of them if one fails
+    fn transaction(&self, statements: Vec<String>) -> bool {
+        let r = self.connection.execute(format!("BEGIN TRANSACTION; {}; COMMIT;", statements.join("; ")));
+        if let Err(e) = r {
+            error!("Rolling back a failed transaction: {}", e);
+            let _ = self.connection.execute("ROLLBACK;");
+            return false;
+        }
+        true
+    }
+
+    /// adds a column to a table created by an older version of the server.
+    /// Returns true if the column was added
+    fn add_column_if_missing(&self, table: &str, column: &str, definition: &str) -> sqlite::Result<bool> {
+        let mut exists = false;
+        self.connection.iterate(format!("PRAGMA table_info({})", table), |pairs| {
+            for (col, val) in pairs {
+                if *col == "name" && *val == Some(column) {
+                    exists = true;
+                }
+            }
+            true
+        })?;
+
+        if !exists {
+            self.connection.execute(format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))?;
         }
+        Ok(!exists)
     }
 
     pub fn get_value<S: Into<String>>(&self, select: S, table: S, key: S, key_value: S) -> Option<String> {
@@ -150,6 +378,9 @@ impl Database {
         let found_pass = self.get_player_value(&uuid, PlayerValueDB::Password);
         if let Some(p) = found_pass {
             if p == password {
+                if let Some(ban) = self.get_player_ban(&uuid) {
+                    return Err(LoginFailReason::Banned(ban));
+                }
                 return Ok(uuid);
             }
         } else {
@@ -164,7 +395,57 @@ impl Database {
     }
 
     pub fn get_player_items(&self, owner_uuid: &Uuid) -> Option<Vec<Item>> {
-        let stmt = format!("SELECT * FROM items WHERE owner IS '{}'", owner_uuid.to_string());
+        self.get_items_owned_by(owner_uuid.to_string().as_str())
+    }
+
+    /// the owner of the items in a player's bank, so they are kept out of their inventory
+    fn bank_owner(uuid: &Uuid) -> String {
+        format!("bank:{}", uuid)
+    }
+
+    /// gets the items a player keeps in their bank
+    pub fn get_bank_items(&self, uuid: &Uuid) -> Vec<Item> {
+        self.get_items_owned_by(Self::bank_owner(uuid).as_str()).unwrap_or_default()
+    }
+
+    /// the owner of the items attached to a letter until they are claimed
+    fn mail_owner(id: &Uuid) -> String {
+        format!("mail:{}", id)
+    }
+
+    /// the owner of the items in a guild's bank
+    fn guild_bank_owner(id: &Uuid) -> String {
+        format!("guild:{}", id)
+    }
+
+    /// gets how many items a player keeps in their bank
+    pub fn count_bank_items(&self, uuid: &Uuid) -> u32 {
+        self.get_u32("COUNT(*)", "items", "owner", Self::bank_owner(uuid).as_str()).unwrap_or(0)
+    }
+
+    /// finds an item in a player's bank by its name
+    pub fn bank_item_uuid_from_name(&self, name: String, uuid: &Uuid) -> Option<Uuid> {
+        self.item_uuid_owned_by(name, Self::bank_owner(uuid).as_str())
+    }
+
+    /// moves an item from a player's inventory to their bank, returning false if it is not in their inventory
+    pub fn deposit_item(&self, uuid: &Uuid, item: &Uuid) -> bool {
+        let r = self.connection.execute(format!("UPDATE items SET owner = '{}' WHERE uuid IS '{}' AND owner IS '{}'",
+                                                Self::bank_owner(uuid), item, uuid));
+
+        r.is_ok() && self.connection.change_count() > 0
+    }
+
+    /// moves an item from a player's bank to their inventory, returning false if it is not in their bank
+    pub fn withdraw_item(&self, uuid: &Uuid, item: &Uuid) -> bool {
+        let r = self.connection.execute(format!("UPDATE items SET owner = '{}' WHERE uuid IS '{}' AND owner IS '{}'",
+                                                uuid, item, Self::bank_owner(uuid)));
+
+        r.is_ok() && self.connection.change_count() > 0
+    }
+
+    fn get_items_owned_by(&self, owner: &str) -> Option<Vec<Item>> {
+        let stmt = format!("SELECT * FROM items WHERE owner IS '{}'", owner);
         let mut items = Vec::new();
 
         let r = self.connection.iterate(stmt, |pairs| {
@@ -175,6 +456,8 @@ impl Database {
             let mut damage = 9999;
             let mut defense = 9999;
             let mut name = format!("Glitched Sword");
+            let mut ability = SpecialAbility::None;
+            let mut upgrades = 0;
             for (col, val) in pairs {
                 if val.is_some() {
                     let v = val.unwrap();
@@ -197,6 +480,9 @@ impl Database {
                         "rarity" => rarity = ItemRarity::from(v.parse::<u32>()
                             .expect(format!("Invalid rarity value in database in item owned by {}: '{}' should be integer",
                                             uuid.to_string(), v).as_str())),
+                        // items made before abilities existed, or with one that was removed, have none
+                        "special_ability" => ability = SpecialAbility::from_str(v).unwrap_or(SpecialAbility::None),
+                        "upgrades" => upgrades = v.parse::<u32>().unwrap_or(0),
                         _ => {}
                     }
                 }
@@ -205,7 +491,7 @@ impl Database {
                 uuid,
                 owner: uuid.clone(),
                 name, item_type, rarity,
-                level, damage, defense,
+                level, damage, defense, ability, upgrades,
             });
             true
         });
@@ -217,10 +503,13 @@ impl Database {
         }
     }
 
-    pub fn new_player(&self, player: &Player, password: String) -> bool {
+    /// adds a new level 1 player of a class, `health_percent` is the class's share of the normal max health
+    pub fn new_player(&self, player: &Player, password: String, class: &str, health_percent: u32) -> bool {
         let r = self.connection.execute(
-            format!("INSERT INTO players VALUES ('{}','{}','{}','{}','{}','{}','{}','{}','{}')",
-                    player.uuid, player.name, password, 1, 0, 0, 100, "Plains of Arenlok", 0));
+            format!("INSERT INTO players (uuid, username, password, level, exp, steps, health, current_region, active, role, class, health_percent) \
+                     VALUES ('{}','{}','{}','{}','{}','{}','{}','{}','{}','{}','{}','{}')",
+                    player.uuid, player.name, password, 1, 0, 0, BASE_HEALTH * health_percent / 100, START_REGION, 0,
+                    Role::Player.to_string(), class.replace("'", "''"), health_percent));
 
         r.is_ok()
     }
@@ -291,6 +580,10 @@ impl Database {
         self.set_player_value(uuid, PlayerValueDB::Level, (current.unwrap() + amt).to_string())
     }
 
+    pub fn set_player_level(&self, uuid: &Uuid, level: u32) -> bool {
+        self.set_player_value(uuid, PlayerValueDB::Level, level.to_string())
+    }
+
     pub fn check_levelup(&self, uuid: &Uuid) -> bool {
         // get the player's level
         let player_level_query = self.get_player_level(&uuid);
@@ -305,7 +598,7 @@ impl Database {
         }
         let mut player_exp = player_exp_query.unwrap();
         // the required amount of exp to level up
-        let mut required_exp = (player_level * 50) / 2;
+        let mut required_exp = exp_to_level(player_level);
 
         // how many levels to add
         let mut added_levels = 0;
@@ -318,11 +611,18 @@ impl Database {
             // increment the level to set to
             added_levels += 1;
             // set the new required exp for the next level
-            required_exp = ((player_level + added_levels) * 50) / 2;
+            required_exp = exp_to_level(player_level + added_levels);
         }
         // write the new values of exp and levels
         self.set_player_exp(&uuid, player_exp);
         self.inc_player_level_by(&uuid, added_levels);
+        // leveling up heals the player to their new max health and gives them skill points
+        if added_levels > 0 {
+            self.add_skill_points(&uuid, added_levels * SKILL_POINTS_PER_LEVEL);
+            if let Some(max) = self.get_player_max_health(&uuid) {
+                self.set_player_health(&uuid, max);
+            }
+        }
 
         true
     }
@@ -335,38 +635,401 @@ impl Database {
         None
     }
 
+    /// the most health the player can have at their level, changed by their class and skills
+    pub fn get_player_max_health(&self, uuid: &Uuid) -> Option<u32> {
+        let percent = self.get_u32("health_percent", "players", "uuid", uuid.to_string().as_str()).unwrap_or(100);
+        self.get_player_level(uuid)
+            .map(|level| (max_health(level) as u64 * percent as u64 / 100).clamp(1, u32::MAX as u64) as u32)
+    }
+
+    pub fn set_player_health(&self, uuid: &Uuid, health: u32) -> bool {
+        self.set_player_value(uuid, PlayerValueDB::Health, health.to_string())
+    }
+
+    /// heals the player, never above their max health
     pub fn add_player_health(&self, uuid: &Uuid, amt: u32) -> bool {
-        let current = self.get_player_health(uuid);
-        if current.is_none() { return false; }
-        self.set_player_value(uuid, PlayerValueDB::Health, (current.unwrap() + amt).to_string())
+        let (current, max) = match (self.get_player_health(uuid), self.get_player_max_health(uuid)) {
+            (Some(current), Some(max)) => (current, max),
+            _ => return false,
+        };
+        self.set_player_health(uuid, current.saturating_add(amt).min(max))
     }
 
+    /// hurts the player, never below 0 health
     pub fn remove_player_health(&self, uuid: &Uuid, amt: u32) -> bool {
         let current = self.get_player_health(uuid);
         if current.is_none() { return false; }
-        self.set_player_value(uuid, PlayerValueDB::Health, (current.unwrap() - amt).to_string())
+        self.set_player_health(uuid, current.unwrap().saturating_sub(amt))
+    }
+
+    pub fn get_player_gold(&self, uuid: &Uuid) -> Option<u32> {
+        self.get_u32("gold", "players", "uuid", uuid.to_string().as_str())
+    }
+
+    pub fn add_player_gold(&self, uuid: &Uuid, amt: u32) -> bool {
+        let r = self.connection.execute(format!("UPDATE players SET gold = gold + {} WHERE uuid IS '{}'", amt, uuid));
+
+        r.is_ok()
+    }
+
+    pub fn get_player_kills(&self, uuid: &Uuid) -> Option<u32> {
+        self.get_u32("kills", "players", "uuid", uuid.to_string().as_str())
+    }
+
+    pub fn inc_player_kills(&self, uuid: &Uuid) -> bool {
+        let r = self.connection.execute(format!("UPDATE players SET kills = kills + 1 WHERE uuid IS '{}'", uuid));
+
+        r.is_ok()
+    }
+
+    pub fn count_players(&self) -> u32 {
+        let mut count = 0;
+        let _ = self.connection.iterate("SELECT COUNT(*) FROM players", |pairs| {
+            for (_, val) in pairs {
+                count = val.and_then(|v| v.parse().ok()).unwrap_or(0);
+            }
+            true
+        });
+        count
+    }
+
+    /// gets the uuid of every player
+    pub fn get_all_players(&self) -> Vec<Uuid> {
+        let mut players = Vec::new();
+        let _ = self.connection.iterate("SELECT uuid FROM players", |pairs| {
+            for (_, val) in pairs {
+                if let Ok(u) = Uuid::from_str(val.unwrap_or("")) {
+                    players.push(u);
+                }
+            }
+            true
+        });
+        players
+    }
+
+    /// gets the username and ranked value of the players on a leaderboard, best first.
+    /// Sorts with the leaderboard indexes so only the rows asked for are read
+    pub fn get_leaderboard(&self, category: LeaderboardCategory, offset: u32, limit: u32) -> Vec<(String, u64)> {
+        let (column, order) = match category {
+            LeaderboardCategory::Level => ("level", "level DESC, exp DESC"),
+            LeaderboardCategory::Steps => ("steps", "steps DESC"),
+            LeaderboardCategory::Kills => ("kills", "kills DESC"),
+            LeaderboardCategory::Wealth => ("gold", "gold DESC"),
+            LeaderboardCategory::Duels => ("duel_wins", "duel_wins DESC"),
+        };
+        let mut entries = Vec::new();
+        let _ = self.connection.iterate(
+            format!("SELECT username, {} FROM players ORDER BY {}, username LIMIT {} OFFSET {}", column, order, limit, offset),
+            |pairs| {
+                let mut username = String::new();
+                let mut value = 0;
+                for (col, val) in pairs {
+                    let v = val.unwrap_or("");
+                    if *col == "username" {
+                        username = v.to_string();
+                    } else {
+                        value = v.parse().unwrap_or(0);
+                    }
+                }
+                entries.push((username, value));
+                true
+            });
+        entries
+    }
+
+    /// gets the id and unlock time of every achievement a player has unlocked
+    pub fn get_player_achievements(&self, uuid: &Uuid) -> Vec<(String, u64)> {
+        let mut unlocked = Vec::new();
+        let _ = self.connection.iterate(
+            format!("SELECT id, time FROM achievements WHERE uuid IS '{}' ORDER BY time", uuid), |pairs| {
+                let mut id = String::new();
+                let mut time = 0;
+                for (col, val) in pairs {
+                    let v = val.unwrap_or("");
+                    match *col {
+                        "id" => id = v.to_string(),
+                        "time" => time = v.parse().unwrap_or(0),
+                        _ => {}
+                    }
+                }
+                unlocked.push((id, time));
+                true
+            });
+        unlocked
+    }
+
+    /// unlocks an achievement for a player, returning false if it was already unlocked
+    pub fn unlock_achievement(&self, uuid: &Uuid, id: &str) -> bool {
+        // the unique index makes this ignore achievements the player already has
+        let r = self.connection.execute(format!("INSERT OR IGNORE INTO achievements VALUES ('{}', '{}', {})",
+                                                uuid, id.replace("'", "''"), systime().as_secs()));
+
+        r.is_ok() && self.connection.change_count() > 0
+    }
+
+    /// gets the id, progress and if it has been turned in for every quest a player has accepted
+    pub fn get_player_quests(&self, uuid: &Uuid) -> Vec<(String, u32, bool)> {
+        let mut quests = Vec::new();
+        let _ = self.connection.iterate(
+            format!("SELECT id, progress, done FROM quests WHERE uuid IS '{}' ORDER BY rowid", uuid), |pairs| {
+                let mut id = String::new();
+                let mut progress = 0;
+                let mut done = false;
+                for (col, val) in pairs {
+                    let v = val.unwrap_or("");
+                    match *col {
+                        "id" => id = v.to_string(),
+                        "progress" => progress = v.parse().unwrap_or(0),
+                        "done" => done = v == "1",
+                        _ => {}
+                    }
+                }
+                quests.push((id, progress, done));
+                true
+            });
+        quests
+    }
+
+    /// starts a quest for a player, restarting it if they have done it before
+    pub fn start_quest(&self, uuid: &Uuid, id: &str) -> bool {
+        let r = self.connection.execute(format!("INSERT OR REPLACE INTO quests VALUES ('{}', '{}', 0, 0)",
+                                                uuid, id.replace("'", "''")));
+
+        r.is_ok()
+    }
+
+    pub fn set_quest_progress(&self, uuid: &Uuid, id: &str, progress: u32) -> bool {
+        let r = self.connection.execute(format!("UPDATE quests SET progress = {} WHERE uuid IS '{}' AND id IS '{}'",
+                                                progress, uuid, id.replace("'", "''")));
+
+        r.is_ok()
+    }
+
+    /// forgets a quest the player has not turned in yet
+    pub fn abandon_quest(&self, uuid: &Uuid, id: &str) -> bool {
+        let r = self.connection.execute(format!("DELETE FROM quests WHERE uuid IS '{}' AND id IS '{}' AND done IS 0",
+                                                uuid, id.replace("'", "''")));
+
+        r.is_ok() && self.connection.change_count() > 0
+    }
+
+    /// marks a quest as turned in, returning false if it already was so the rewards are only given once
+    pub fn finish_quest(&self, uuid: &Uuid, id: &str) -> bool {
+        let r = self.connection.execute(format!("UPDATE quests SET done = 1 WHERE uuid IS '{}' AND id IS '{}' AND done IS 0",
+                                                uuid, id.replace("'", "''")));
+
+        r.is_ok() && self.connection.change_count() > 0
+    }
+
+    /// gets the id and quantity of every stack of consumables a player has
+    pub fn get_player_consumables(&self, uuid: &Uuid) -> Vec<(String, u32)> {
+        let mut stacks = Vec::new();
+        let _ = self.connection.iterate(
+            format!("SELECT id, quantity FROM consumables WHERE owner IS '{}' AND quantity > 0 ORDER BY rowid", uuid), |pairs| {
+                let mut id = String::new();
+                let mut quantity = 0;
+                for (col, val) in pairs {
+                    let v = val.unwrap_or("");
+                    match *col {
+                        "id" => id = v.to_string(),
+                        "quantity" => quantity = v.parse().unwrap_or(0),
+                        _ => {}
+                    }
+                }
+                stacks.push((id, quantity));
+                true
+            });
+        stacks
+    }
+
+    fn add_consumable_statements(&self, uuid: &Uuid, id: &str, amt: u32, max_stack: u32) -> Vec<String> {
+        let id = id.replace("'", "''");
+        vec![
+            format!("INSERT OR IGNORE INTO consumables VALUES ('{}', '{}', 0)", uuid, id),
+            format!("UPDATE consumables SET quantity = MIN(quantity + {}, {}) WHERE owner IS '{}' AND id IS '{}'", amt, max_stack, uuid, id),
+        ]
+    }
+
+    /// adds to a player's stack of a consumable, never above the max stack size
+    pub fn add_consumable(&self, uuid: &Uuid, id: &str, amt: u32, max_stack: u32) -> bool {
+        self.transaction(self.add_consumable_statements(uuid, id, amt, max_stack))
+    }
+
+    /// takes one of a consumable from a player, returning false if they do not have any
+    pub fn take_consumable(&self, uuid: &Uuid, id: &str) -> bool {
+        let r = self.connection.execute(format!("UPDATE consumables SET quantity = quantity - 1 WHERE owner IS '{}' AND id IS '{}' AND quantity > 0",
+                                                uuid, id.replace("'", "''")));
+        let taken = r.is_ok() && self.connection.change_count() > 0;
+        if taken {
+            let _ = self.connection.execute(format!("DELETE FROM consumables WHERE owner IS '{}' AND quantity <= 0", uuid));
+        }
+        taken
+    }
+
+    /// takes the price from a player and adds to their stack of a consumable in one transaction.
+    /// The caller should check the player can afford it while holding the database lock
+    pub fn buy_consumable(&self, uuid: &Uuid, id: &str, amt: u32, price: u32, max_stack: u32) -> bool {
+        let mut statements = vec![format!("UPDATE players SET gold = gold - {} WHERE uuid IS '{}'", price, uuid)];
+        statements.extend(self.add_consumable_statements(uuid, id, amt, max_stack));
+        self.transaction(statements)
+    }
+
+    /// gets the id and quantity of every crafting material a player has
+    pub fn get_player_materials(&self, uuid: &Uuid) -> Vec<(String, u32)> {
+        let mut stacks = Vec::new();
+        let _ = self.connection.iterate(
+            format!("SELECT id, quantity FROM materials WHERE owner IS '{}' AND quantity > 0 ORDER BY rowid", uuid), |pairs| {
+                let mut id = String::new();
+                let mut quantity = 0;
+                for (col, val) in pairs {
+                    let v = val.unwrap_or("");
+                    match *col {
+                        "id" => id = v.to_string(),
+                        "quantity" => quantity = v.parse().unwrap_or(0),
+                        _ => {}
+                    }
+                }
+                stacks.push((id, quantity));
+                true
+            });
+        stacks
+    }
+
+    /// gives a player crafting materials
+    pub fn add_materials(&self, uuid: &Uuid, materials: &[(String, u32)]) -> bool {
+        let mut statements = Vec::new();
+        for (id, amt) in materials {
+            let id = id.replace("'", "''");
+            statements.push(format!("INSERT OR IGNORE INTO materials VALUES ('{}', '{}', 0)", uuid, id));
+            statements.push(format!("UPDATE materials SET quantity = quantity + {} WHERE owner IS '{}' AND id IS '{}'", amt, uuid, id));
+        }
+        self.transaction(statements)
+    }
+
+    /// deletes an item and gives its owner the materials it broke into in one transaction
+    pub fn salvage_item(&self, item: &Item, materials: &[(String, u32)]) -> bool {
+        let mut statements = vec![format!("DELETE FROM items WHERE uuid IS '{}'", item.uuid)];
+        for (id, amt) in materials {
+            let id = id.replace("'", "''");
+            statements.push(format!("INSERT OR IGNORE INTO materials VALUES ('{}', '{}', 0)", item.owner, id));
+            statements.push(format!("UPDATE materials SET quantity = quantity + {} WHERE owner IS '{}' AND id IS '{}'", amt, item.owner, id));
+        }
+        self.transaction(statements)
+    }
+
+    /// takes the materials and gold a recipe costs and gives the player the crafted item in one transaction.
+    /// The caller should check the player has everything while holding the database lock
+    pub fn craft_item(&self, item: &Item, materials: &[(String, u32)], gold: u32) -> bool {
+        let mut statements = vec![format!("UPDATE players SET gold = gold - {} WHERE uuid IS '{}'", gold, item.owner)];
+        for (id, amt) in materials {
+            statements.push(format!("UPDATE materials SET quantity = quantity - {} WHERE owner IS '{}' AND id IS '{}'",
+                                    amt, item.owner, id.replace("'", "''")));
+        }
+        statements.push(format!("DELETE FROM materials WHERE owner IS '{}' AND quantity <= 0", item.owner));
+        statements.push(self.new_item_statement(item));
+        self.transaction(statements)
+    }
+
+    /// gets how many items a player has
+    pub fn count_player_items(&self, uuid: &Uuid) -> u32 {
+        self.get_u32("COUNT(*)", "items", "owner", uuid.to_string().as_str()).unwrap_or(0)
+    }
+
+    /// gets how many inventory slots a player has bought on top of the configured capacity
+    pub fn get_inventory_slots(&self, uuid: &Uuid) -> u32 {
+        self.get_u32("inventory_slots", "players", "uuid", uuid.to_string().as_str()).unwrap_or(0)
+    }
+
+    /// takes the gold more inventory space costs and gives the player the slots in one transaction.
+    /// The caller should check the player can afford it while holding the database lock
+    pub fn expand_inventory(&self, uuid: &Uuid, slots: u32, gold: u32) -> bool {
+        self.transaction(vec![
+            format!("UPDATE players SET gold = gold - {} WHERE uuid IS '{}'", gold, uuid),
+            format!("UPDATE players SET inventory_slots = inventory_slots + {} WHERE uuid IS '{}'", slots, uuid),
+        ])
+    }
+
+    /// gets how many duels a player has won and lost
+    pub fn get_duel_record(&self, uuid: &Uuid) -> (u32, u32) {
+        (self.get_u32("duel_wins", "players", "uuid", uuid.to_string().as_str()).unwrap_or(0),
+         self.get_u32("duel_losses", "players", "uuid", uuid.to_string().as_str()).unwrap_or(0))
     }
 
     pub fn get_player_region(&self, uuid: &Uuid) -> Option<String> {
         self.get_player_value(uuid, PlayerValueDB::CurrentRegion)
     }
 
+    /// gets the uuids of every player in a region
+    pub fn get_players_in_region(&self, region: &str) -> Vec<Uuid> {
+        let mut players = Vec::new();
+        let _ = self.connection.iterate(
+            format!("SELECT uuid FROM players WHERE current_region IS '{}'", region.replace("'", "''")),
+            |pairs| {
+                for (_, val) in pairs {
+                    if let Ok(u) = Uuid::from_str(val.unwrap_or("")) {
+                        players.push(u);
+                    }
+                }
+                true
+            });
+        players
+    }
+
     pub fn set_player_region(&self, uuid: &Uuid, region: String) -> bool {
         self.set_player_value(uuid, PlayerValueDB::CurrentRegion, region)
     }
 
+    pub fn get_player_role(&self, uuid: &Uuid) -> Role {
+        self.get_player_value(uuid, PlayerValueDB::Role)
+            .and_then(|r| Role::from_str(r.as_str()).ok())
+            .unwrap_or(Role::Player)
+    }
+
+    pub fn set_player_role(&self, uuid: &Uuid, role: Role) -> bool {
+        self.set_player_value(uuid, PlayerValueDB::Role, role.to_string())
+    }
+
+    /// gets the username and role of every player with a role above player
+    pub fn get_staff(&self) -> Vec<(String, Role)> {
+        let mut staff = Vec::new();
+        let _ = self.connection.iterate(
+            format!("SELECT username, role FROM players WHERE role IS NOT NULL AND role IS NOT '{}' ORDER BY username",
+                    Role::Player.to_string()), |pairs| {
+                let mut username = String::new();
+                let mut role = Role::Player;
+                for (col, val) in pairs {
+                    let v = val.unwrap_or("");
+                    match *col {
+                        "username" => username = v.to_string(),
+                        "role" => role = Role::from_str(v).unwrap_or(Role::Player),
+                        _ => {}
+                    }
+                }
+                staff.push((username, role));
+                true
+            });
+        staff
+    }
+
+    fn new_item_statement(&self, item: &Item) -> String {
+        format!("INSERT INTO items VALUES ('{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}')",
+                item.owner.to_string(), item.item_type as u32, item.level, item.damage,
+                item.defense, item.ability.to_string(), item.name, item.uuid, item.rarity as u32, item.upgrades)
+    }
+
     pub fn new_item(&self, item: &Item) -> bool {
-        let r = self.connection.execute(
-            format!("INSERT INTO items VALUES ('{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}')",
-                    item.owner.to_string(), item.item_type as u32, item.level, item.damage,
-                    item.defense, "NONE", item.name, item.uuid, item.rarity as u32));
+        let r = self.connection.execute(self.new_item_statement(item));
 
         r.is_ok()
     }
 
     pub fn item_uuid_from_name(&self, name: String, owner: &Uuid) -> Option<Uuid> {
+        self.item_uuid_owned_by(name, owner.to_string().as_str())
+    }
+
+    fn item_uuid_owned_by(&self, name: String, owner: &str) -> Option<Uuid> {
         let mut statement = self.connection
-            .prepare(format!("SELECT uuid FROM items WHERE name IS '{}' AND owner IS '{}'", name, owner.to_string()))
+            .prepare(format!("SELECT uuid FROM items WHERE name IS '{}' AND owner IS '{}'", name, owner))
             .expect("Failed to prepare statement for database interaction.");
         let state = statement.next();
         if state.is_err() {
@@ -410,13 +1073,19 @@ impl Database {
         let defense = defense_raw.parse::<u32>().expect("failed to parse u32 from database!");
         let damage_raw = self.get_item_value(uuid, ItemValueDB::Damage).unwrap();
         let damage = damage_raw.parse::<u32>().expect("failed to parse u32 from database!");
+        let ability = self.get_item_value(uuid, ItemValueDB::SpecialAbility)
+            .and_then(|a| SpecialAbility::from_str(a.as_str()).ok())
+            .unwrap_or(SpecialAbility::None);
+        let upgrades = self.get_item_value(uuid, ItemValueDB::Upgrades)
+            .and_then(|u| u.parse::<u32>().ok())
+            .unwrap_or(0);
 
         Some(Item {
             uuid: uuid.clone(),
             owner: owner_op.unwrap(),
             name, item_type: ItemType::from(itype), level,
             rarity: ItemRarity::from(rarity),
-            defense, damage
+            defense, damage, ability, upgrades,
         })
     }
 
@@ -427,23 +1096,844 @@ impl Database {
         r.is_ok()
     }
 
+    fn update_item_statement(&self, item: &Item) -> String {
+        format!("\
+        UPDATE items \
+        SET owner='{}', \
+            name='{}', \
+            type='{}', \
+            level='{}', \
+            damage='{}', \
+            defense='{}', \
+            special_ability='{}', \
+            rarity='{}', \
+            upgrades='{}' \
+        WHERE uuid='{}'", item.owner, item.name.replace("'", "''"), item.item_type as u32, item.level, item.damage,
+                item.defense, item.ability.to_string(), item.rarity as u32, item.upgrades, item.uuid)
+    }
+
     pub fn update_item(&self, item: &Item) -> bool {
-        let r = self.connection.execute(format!("\
-        UPDATE items\
-        SET owner='{}',\
-            name='{}'\
-            type='{}'\
-            level='{}'\
-            damage='{}'\
-            defense='{}'\
-            rarity='{}'\
-        WHERE uuid='{}'", item.owner, item.name, item.item_type as u32, item.level, item.damage, item.defense, item.rarity as u32, item.uuid));
+        let r = self.connection.execute(self.update_item_statement(item));
 
         r.is_ok()
     }
 
+    /// takes the gold and materials an upgrade costs and saves the item like update_item does, or deletes it
+    /// if it was destroyed, in one transaction.
+    /// The caller should check the player has everything while holding the database lock
+    pub fn upgrade_item(&self, item: &Item, destroyed: bool, materials: &[(String, u32)], gold: u32) -> bool {
+        let mut statements = vec![format!("UPDATE players SET gold = gold - {} WHERE uuid IS '{}'", gold, item.owner)];
+        for (id, amt) in materials {
+            statements.push(format!("UPDATE materials SET quantity = quantity - {} WHERE owner IS '{}' AND id IS '{}'",
+                                    amt, item.owner, id.replace("'", "''")));
+        }
+        statements.push(format!("DELETE FROM materials WHERE owner IS '{}' AND quantity <= 0", item.owner));
+        if destroyed {
+            statements.push(format!("DELETE FROM items WHERE uuid IS '{}'", item.uuid));
+        } else {
+            statements.push(self.update_item_statement(item));
+        }
+        self.transaction(statements)
+    }
+
     pub fn set_item_value(&self, item: &Item, value: ItemValueDB) -> bool {
         self.set_value("items", value.to_string().as_str(),
                        item.get_value_from_ivdb(value).as_str(), "uuid", item.uuid.to_string().as_str())
     }
+
+    /// Takes gold and items from players and holds them until the escrow is paid out or refunded.
+    /// Every stake is taken in one transaction, so either all of them are held or none are.
+    /// The caller should check the players can afford their stakes while holding the database lock
+    pub fn escrow(&self, id: &Uuid, stakes: &[(Uuid, u32, Vec<Uuid>)]) -> bool {
+        let statements = self.escrow_statements(id, stakes);
+        if statements.is_empty() {
+            return true;
+        }
+        self.transaction(statements)
+    }
+
+    fn escrow_statements(&self, id: &Uuid, stakes: &[(Uuid, u32, Vec<Uuid>)]) -> Vec<String> {
+        let mut statements = Vec::new();
+        for (owner, gold, items) in stakes {
+            if *gold > 0 {
+                statements.push(format!("UPDATE players SET gold = gold - {} WHERE uuid IS '{}'", gold, owner));
+                statements.push(format!("INSERT INTO escrow (id, owner, gold, item) VALUES ('{}', '{}', {}, '')", id, owner, gold));
+            }
+            for item in items {
+                // held items belong to the escrow so they can not be used or dropped
+                statements.push(format!("UPDATE items SET owner = '{}' WHERE uuid IS '{}'", id, item));
+                statements.push(format!("INSERT INTO escrow (id, owner, gold, item) VALUES ('{}', '{}', 0, '{}')", id, owner, item));
+            }
+        }
+        statements
+    }
+
+    fn release_escrow_statements(&self, id: &Uuid, to: &Uuid) -> Vec<String> {
+        vec![
+            format!("UPDATE players SET gold = gold + (SELECT COALESCE(SUM(gold), 0) FROM escrow WHERE id IS '{}') WHERE uuid IS '{}'", id, to),
+            format!("UPDATE items SET owner = '{}' WHERE owner IS '{}'", to, id),
+            format!("DELETE FROM escrow WHERE id IS '{}'", id),
+        ]
+    }
+
+    /// gives everything held in an escrow to one player
+    pub fn release_escrow(&self, id: &Uuid, to: &Uuid) -> bool {
+        self.transaction(self.release_escrow_statements(id, to))
+    }
+
+    /// gives everything held in an escrow back to the players it was taken from
+    pub fn refund_escrow(&self, id: &Uuid) -> bool {
+        self.transaction(self.refund_escrow_statements(id))
+    }
+
+    fn refund_escrow_statements(&self, id: &Uuid) -> Vec<String> {
+        vec![
+            format!("UPDATE players SET gold = gold + (SELECT COALESCE(SUM(e.gold), 0) FROM escrow e WHERE e.id IS '{0}' AND e.owner IS players.uuid) \
+                     WHERE uuid IN (SELECT owner FROM escrow WHERE id IS '{0}')", id),
+            format!("UPDATE items SET owner = (SELECT e.owner FROM escrow e WHERE e.item IS items.uuid AND e.id IS '{0}') WHERE owner IS '{0}'", id),
+            format!("DELETE FROM escrow WHERE id IS '{}'", id),
+        ]
+    }
+
+    /// refunds every escrow left over from a server that stopped before it was settled.
+    /// Auction listings keep their escrow until they end, so they are left alone.
+    /// Returns how many escrows were refunded
+    pub fn refund_all_escrow(&self) -> usize {
+        let mut ids = Vec::new();
+        let _ = self.connection.iterate("SELECT DISTINCT id FROM escrow WHERE id NOT IN (SELECT id FROM auctions)", |pairs| {
+            for (_, val) in pairs {
+                if let Some(id) = val.and_then(|v| Uuid::from_str(v).ok()) {
+                    ids.push(id);
+                }
+            }
+            true
+        });
+        ids.iter().filter(|id| self.refund_escrow(id)).count()
+    }
+
+    /// pays the wagers of a duel out to the winner and records the result in one transaction
+    pub fn settle_duel(&self, id: &Uuid, winner: &Uuid, loser: &Uuid) -> bool {
+        let mut statements = self.release_escrow_statements(id, winner);
+        statements.push(format!("UPDATE players SET duel_wins = duel_wins + 1 WHERE uuid IS '{}'", winner));
+        statements.push(format!("UPDATE players SET duel_losses = duel_losses + 1 WHERE uuid IS '{}'", loser));
+        self.transaction(statements)
+    }
+
+    /// puts an item up for sale, holding it in the listing's escrow until the listing ends
+    pub fn list_auction(&self, listing: &Listing) -> bool {
+        let mut statements = self.escrow_statements(&listing.id, &[(listing.seller, 0, vec![listing.item])]);
+        statements.push(format!("INSERT INTO auctions (id, seller, item, start_bid, bid, bidder, buyout, expires) \
+                                 VALUES ('{}', '{}', '{}', {}, 0, '', {}, {})",
+                                listing.id, listing.seller, listing.item, listing.start_bid, listing.buyout, listing.expires));
+        self.transaction(statements)
+    }
+
+    /// reads every listing matching the condition, ending soonest first.
+    /// The condition can use the listing as `a` and its item as `i`
+    fn get_auctions(&self, condition: String) -> Vec<Listing> {
+        let mut listings = Vec::new();
+        let _ = self.connection.iterate(
+            format!("SELECT a.* FROM auctions a JOIN items i ON i.uuid IS a.item WHERE ({}) ORDER BY a.expires, a.number", condition),
+            |pairs| {
+                let mut listing = Listing {
+                    number: 0, id: Uuid::nil(), seller: Uuid::nil(), item: Uuid::nil(),
+                    start_bid: 0, bid: 0, bidder: None, buyout: 0, expires: 0,
+                };
+                for (col, val) in pairs {
+                    let v = val.unwrap_or("");
+                    match *col {
+                        "number" => listing.number = v.parse::<u32>().unwrap_or(0),
+                        "id" => listing.id = Uuid::from_str(v).unwrap_or_default(),
+                        "seller" => listing.seller = Uuid::from_str(v).unwrap_or_default(),
+                        "item" => listing.item = Uuid::from_str(v).unwrap_or_default(),
+                        "start_bid" => listing.start_bid = v.parse::<u32>().unwrap_or(0),
+                        "bid" => listing.bid = v.parse::<u32>().unwrap_or(0),
+                        "bidder" => listing.bidder = Uuid::from_str(v).ok(),
+                        "buyout" => listing.buyout = v.parse::<u32>().unwrap_or(0),
+                        "expires" => listing.expires = v.parse::<u64>().unwrap_or(0),
+                        _ => {}
+                    }
+                }
+                listings.push(listing);
+                true
+            });
+        listings
+    }
+
+    /// finds the listings that match a search, `uuid` is the player searching
+    pub fn search_auctions(&self, search: &AuctionSearchData, uuid: &Uuid) -> Vec<Listing> {
+        let mut conditions = vec![format!("1")];
+        if let Some(itype) = search.itype {
+            conditions.push(format!("i.type = {}", itype));
+        }
+        if let Some(rarity) = search.rarity {
+            conditions.push(format!("i.rarity = {}", rarity));
+        }
+        if let Some(min) = search.min_level {
+            conditions.push(format!("i.level >= {}", min));
+        }
+        if let Some(max) = search.max_level {
+            conditions.push(format!("i.level <= {}", max));
+        }
+        if !search.name.is_empty() {
+            conditions.push(format!("i.name LIKE '%{}%'", search.name.replace("'", "''").replace("%", "").replace("_", "")));
+        }
+        if search.mine {
+            conditions.push(format!("(a.seller IS '{0}' OR a.bidder IS '{0}')", uuid));
+        }
+        self.get_auctions(conditions.join(" AND "))
+    }
+
+    pub fn get_auction(&self, number: u32) -> Option<Listing> {
+        self.get_auctions(format!("a.number = {}", number)).into_iter().next()
+    }
+
+    /// the listings that ran out of time
+    pub fn get_ended_auctions(&self) -> Vec<Listing> {
+        self.get_auctions(format!("a.expires <= {}", systime().as_secs()))
+    }
+
+    /// gets how many items a player has for sale
+    pub fn count_player_auctions(&self, uuid: &Uuid) -> u32 {
+        self.get_u32("COUNT(*)", "auctions", "seller", uuid.to_string().as_str()).unwrap_or(0)
+    }
+
+    /// gives the highest bidder on a listing their gold back
+    fn refund_bid_statements(&self, listing: &Listing) -> Vec<String> {
+        match listing.bidder {
+            Some(bidder) => vec![
+                format!("UPDATE players SET gold = gold + {} WHERE uuid IS '{}'", listing.bid, bidder),
+                format!("DELETE FROM escrow WHERE id IS '{}' AND gold > 0", listing.id),
+            ],
+            None => Vec::new(),
+        }
+    }
+
+    /// pays the seller, gives the item to the buyer and removes the listing
+    fn sell_auction_statements(&self, listing: &Listing, buyer: &Uuid, payout: u32) -> Vec<String> {
+        vec![
+            format!("UPDATE players SET gold = gold + {} WHERE uuid IS '{}'", payout, listing.seller),
+            format!("UPDATE items SET owner = '{}' WHERE owner IS '{}'", buyer, listing.id),
+            format!("DELETE FROM escrow WHERE id IS '{}'", listing.id),
+            format!("DELETE FROM auctions WHERE number = {}", listing.number),
+        ]
+    }
+
+    /// Holds a new highest bid on a listing, giving the last highest bidder their gold back.
+    /// The caller should check the bidder can afford it while holding the database lock
+    pub fn bid_auction(&self, listing: &Listing, bidder: &Uuid, amount: u32) -> bool {
+        let mut statements = self.refund_bid_statements(listing);
+        statements.extend(self.escrow_statements(&listing.id, &[(bidder.clone(), amount, Vec::new())]));
+        statements.push(format!("UPDATE auctions SET bid = {}, bidder = '{}' WHERE number = {}", amount, bidder, listing.number));
+        self.transaction(statements)
+    }
+
+    /// Sells a listing to a player for its buyout price, giving the highest bidder their gold back.
+    /// The caller should check the buyer can afford it while holding the database lock
+    pub fn buyout_auction(&self, listing: &Listing, buyer: &Uuid, payout: u32) -> bool {
+        let mut statements = self.refund_bid_statements(listing);
+        statements.push(format!("UPDATE players SET gold = gold - {} WHERE uuid IS '{}'", listing.buyout, buyer));
+        statements.extend(self.sell_auction_statements(listing, buyer, payout));
+        self.transaction(statements)
+    }
+
+    /// sells a listing that ended to its highest bidder, their bid is already held
+    pub fn settle_auction(&self, listing: &Listing, payout: u32) -> bool {
+        match listing.bidder {
+            Some(bidder) => self.transaction(self.sell_auction_statements(listing, &bidder, payout)),
+            None => false,
+        }
+    }
+
+    /// gives a listing's item back to the seller and any bid back to the bidder, then removes it
+    pub fn return_auction(&self, listing: &Listing) -> bool {
+        let mut statements = self.refund_escrow_statements(&listing.id);
+        statements.push(format!("DELETE FROM auctions WHERE number = {}", listing.number));
+        self.transaction(statements)
+    }
+
+    /// adds a letter to the recipient's mailbox
+    fn new_mail_statement(&self, mail: &Mail) -> String {
+        format!("INSERT INTO mail (id, recipient, sender, message, gold, time) VALUES ('{}', '{}', '{}', '{}', {}, {})",
+                mail.id, mail.recipient, mail.sender.replace("'", "''"), mail.message.replace("'", "''"), mail.gold, mail.time)
+    }
+
+    /// Sends a letter from a player, taking the gold sent and the postage from them and attaching items from their inventory.
+    /// The caller should check the sender can afford it and has the items while holding the database lock
+    pub fn send_mail(&self, mail: &Mail, sender: &Uuid, items: &[Uuid], cost: u32) -> bool {
+        let mut statements = vec![
+            self.new_mail_statement(mail),
+            format!("UPDATE players SET gold = gold - {} WHERE uuid IS '{}'", cost, sender),
+        ];
+        for item in items {
+            statements.push(format!("UPDATE items SET owner = '{}' WHERE uuid IS '{}' AND owner IS '{}'",
+                                    Self::mail_owner(&mail.id), item, sender));
+        }
+        self.transaction(statements)
+    }
+
+    /// sends a letter from the server with new items attached
+    pub fn send_system_mail(&self, mail: &Mail, items: &[Item]) -> bool {
+        let mut statements = vec![self.new_mail_statement(mail)];
+        for item in items {
+            statements.push(self.new_item_statement(item));
+            statements.push(format!("UPDATE items SET owner = '{}' WHERE uuid IS '{}'", Self::mail_owner(&mail.id), item.uuid));
+        }
+        self.transaction(statements)
+    }
+
+    /// reads every letter matching the condition, newest first
+    fn get_mail(&self, condition: String) -> Vec<Mail> {
+        let mut letters = Vec::new();
+        let _ = self.connection.iterate(
+            format!("SELECT * FROM mail WHERE {} ORDER BY number DESC", condition),
+            |pairs| {
+                let mut mail = Mail {
+                    number: 0, id: Uuid::nil(), recipient: Uuid::nil(),
+                    sender: String::new(), message: String::new(),
+                    gold: 0, time: 0, read: false, claimed: false,
+                };
+                for (col, val) in pairs {
+                    let v = val.unwrap_or("");
+                    match *col {
+                        "number" => mail.number = v.parse::<u32>().unwrap_or(0),
+                        "id" => mail.id = Uuid::from_str(v).unwrap_or_default(),
+                        "recipient" => mail.recipient = Uuid::from_str(v).unwrap_or_default(),
+                        "sender" => mail.sender = v.to_string(),
+                        "message" => mail.message = v.to_string(),
+                        "gold" => mail.gold = v.parse::<u32>().unwrap_or(0),
+                        "time" => mail.time = v.parse::<u64>().unwrap_or(0),
+                        "read" => mail.read = v == "1",
+                        "claimed" => mail.claimed = v == "1",
+                        _ => {}
+                    }
+                }
+                letters.push(mail);
+                true
+            });
+        letters
+    }
+
+    /// gets every letter in a player's mailbox, newest first
+    pub fn get_mailbox(&self, uuid: &Uuid) -> Vec<Mail> {
+        self.get_mail(format!("recipient IS '{}'", uuid))
+    }
+
+    /// gets a letter by its number, only if it is in the player's mailbox
+    pub fn get_player_mail(&self, uuid: &Uuid, number: u32) -> Option<Mail> {
+        self.get_mail(format!("recipient IS '{}' AND number = {}", uuid, number)).into_iter().next()
+    }
+
+    /// gets the items attached to a letter that have not been claimed
+    pub fn get_mail_items(&self, mail: &Mail) -> Vec<Item> {
+        self.get_items_owned_by(Self::mail_owner(&mail.id).as_str()).unwrap_or_default()
+    }
+
+    /// gets how many letters are in a player's mailbox
+    pub fn count_mail(&self, uuid: &Uuid) -> u32 {
+        self.get_u32("COUNT(*)", "mail", "recipient", uuid.to_string().as_str()).unwrap_or(0)
+    }
+
+    /// gets how many letters a player has not read yet
+    pub fn count_unread_mail(&self, uuid: &Uuid) -> u32 {
+        self.get_mail(format!("recipient IS '{}' AND read = 0", uuid)).len() as u32
+    }
+
+    pub fn read_mail(&self, mail: &Mail) -> bool {
+        let r = self.connection.execute(format!("UPDATE mail SET read = 1 WHERE number = {}", mail.number));
+
+        r.is_ok()
+    }
+
+    /// gives the gold and items attached to a letter to its recipient
+    pub fn claim_mail(&self, mail: &Mail) -> bool {
+        self.transaction(vec![
+            format!("UPDATE players SET gold = gold + {} WHERE uuid IS '{}'", mail.gold, mail.recipient),
+            format!("UPDATE items SET owner = '{}' WHERE owner IS '{}'", mail.recipient, Self::mail_owner(&mail.id)),
+            format!("UPDATE mail SET read = 1, claimed = 1 WHERE number = {}", mail.number),
+        ])
+    }
+
+    /// removes a letter and anything still attached to it
+    pub fn delete_mail(&self, mail: &Mail) -> bool {
+        self.transaction(vec![
+            format!("DELETE FROM items WHERE owner IS '{}'", Self::mail_owner(&mail.id)),
+            format!("DELETE FROM mail WHERE number = {}", mail.number),
+        ])
+    }
+
+    /// Starts a guild with the player as its leader, taking the cost from them.
+    /// The caller should check the player can afford it while holding the database lock
+    pub fn create_guild(&self, guild: &Guild, leader: &Uuid, cost: u32) -> bool {
+        self.transaction(vec![
+            format!("INSERT INTO guilds (id, name, created) VALUES ('{}', '{}', {})", guild.id, guild.name.replace("'", "''"), guild.created),
+            format!("INSERT INTO guild_members (uuid, guild, rank, joined) VALUES ('{}', '{}', {}, {})",
+                    leader, guild.id, GuildRank::Leader as u32, guild.created),
+            format!("DELETE FROM guild_invites WHERE uuid IS '{}'", leader),
+            format!("UPDATE players SET gold = gold - {} WHERE uuid IS '{}'", cost, leader),
+        ])
+    }
+
+    /// reads every guild matching the condition, oldest first
+    fn get_guilds(&self, condition: String) -> Vec<Guild> {
+        let mut guilds = Vec::new();
+        let _ = self.connection.iterate(
+            format!("SELECT * FROM guilds WHERE {} ORDER BY created", condition),
+            |pairs| {
+                let mut guild = Guild { id: Uuid::nil(), name: String::new(), created: 0 };
+                for (col, val) in pairs {
+                    let v = val.unwrap_or("");
+                    match *col {
+                        "id" => guild.id = Uuid::from_str(v).unwrap_or_default(),
+                        "name" => guild.name = v.to_string(),
+                        "created" => guild.created = v.parse::<u64>().unwrap_or(0),
+                        _ => {}
+                    }
+                }
+                guilds.push(guild);
+                true
+            });
+        guilds
+    }
+
+    /// finds a guild by its name (case insensitive)
+    pub fn get_guild_by_name(&self, name: &str) -> Option<Guild> {
+        self.get_guilds(format!("name = '{}'", name.replace("'", "''"))).into_iter().next()
+    }
+
+    /// gets the guild a player is in and their rank in it
+    pub fn get_player_guild(&self, uuid: &Uuid) -> Option<(Guild, GuildRank)> {
+        let rank = self.get_u32("rank", "guild_members", "uuid", uuid.to_string().as_str())?;
+        let guild = self.get_guilds(format!("id IN (SELECT guild FROM guild_members WHERE uuid IS '{}')", uuid)).into_iter().next()?;
+        Some((guild, GuildRank::from(rank)))
+    }
+
+    /// gets every member of a guild and their rank, highest rank first then by when they joined
+    pub fn get_guild_members(&self, guild: &Guild) -> Vec<(Uuid, GuildRank)> {
+        let mut members = Vec::new();
+        let _ = self.connection.iterate(
+            format!("SELECT uuid, rank FROM guild_members WHERE guild IS '{}' ORDER BY rank DESC, joined", guild.id),
+            |pairs| {
+                let mut uuid = None;
+                let mut rank = GuildRank::Member;
+                for (col, val) in pairs {
+                    let v = val.unwrap_or("");
+                    match *col {
+                        "uuid" => uuid = Uuid::from_str(v).ok(),
+                        "rank" => rank = GuildRank::from(v.parse::<u32>().unwrap_or(0)),
+                        _ => {}
+                    }
+                }
+                if let Some(u) = uuid {
+                    members.push((u, rank));
+                }
+                true
+            });
+        members
+    }
+
+    pub fn count_guild_members(&self, guild: &Guild) -> u32 {
+        self.get_u32("COUNT(*)", "guild_members", "guild", guild.id.to_string().as_str()).unwrap_or(0)
+    }
+
+    /// invites a player to a guild, inviting them again only updates the time
+    pub fn invite_to_guild(&self, guild: &Guild, uuid: &Uuid) -> bool {
+        let r = self.connection.execute(format!("INSERT OR REPLACE INTO guild_invites (uuid, guild, time) VALUES ('{}', '{}', {})",
+                                                uuid, guild.id, systime().as_secs()));
+
+        r.is_ok()
+    }
+
+    /// gets the guilds that invited a player, oldest first
+    pub fn get_guild_invites(&self, uuid: &Uuid) -> Vec<Guild> {
+        self.get_guilds(format!("id IN (SELECT guild FROM guild_invites WHERE uuid IS '{}')", uuid))
+    }
+
+    /// forgets a guild's invite to a player, returning false if there was none
+    pub fn remove_guild_invite(&self, guild: &Guild, uuid: &Uuid) -> bool {
+        let r = self.connection.execute(format!("DELETE FROM guild_invites WHERE uuid IS '{}' AND guild IS '{}'", uuid, guild.id));
+
+        r.is_ok() && self.connection.change_count() > 0
+    }
+
+    /// adds a player to a guild as a member, forgetting every invite they had
+    pub fn join_guild(&self, guild: &Guild, uuid: &Uuid) -> bool {
+        self.transaction(vec![
+            format!("INSERT INTO guild_members (uuid, guild, rank, joined) VALUES ('{}', '{}', {}, {})",
+                    uuid, guild.id, GuildRank::Member as u32, systime().as_secs()),
+            format!("DELETE FROM guild_invites WHERE uuid IS '{}'", uuid),
+        ])
+    }
+
+    /// removes a player from their guild
+    pub fn leave_guild(&self, uuid: &Uuid) -> bool {
+        let r = self.connection.execute(format!("DELETE FROM guild_members WHERE uuid IS '{}'", uuid));
+
+        r.is_ok() && self.connection.change_count() > 0
+    }
+
+    pub fn set_guild_rank(&self, uuid: &Uuid, rank: GuildRank) -> bool {
+        let r = self.connection.execute(format!("UPDATE guild_members SET rank = {} WHERE uuid IS '{}'", rank as u32, uuid));
+
+        r.is_ok() && self.connection.change_count() > 0
+    }
+
+    /// makes another member the leader of a guild, the old leader becomes an officer
+    pub fn transfer_guild(&self, from: &Uuid, to: &Uuid) -> bool {
+        self.transaction(vec![
+            format!("UPDATE guild_members SET rank = {} WHERE uuid IS '{}'", GuildRank::Officer as u32, from),
+            format!("UPDATE guild_members SET rank = {} WHERE uuid IS '{}'", GuildRank::Leader as u32, to),
+        ])
+    }
+
+    /// removes a guild, its members and its invites go with it
+    pub fn disband_guild(&self, guild: &Guild) -> bool {
+        let r = self.connection.execute(format!("DELETE FROM guilds WHERE id IS '{}'", guild.id));
+
+        r.is_ok() && self.connection.change_count() > 0
+    }
+
+    /// gets the items in a guild's bank
+    pub fn get_guild_bank_items(&self, guild: &Guild) -> Vec<Item> {
+        self.get_items_owned_by(Self::guild_bank_owner(&guild.id).as_str()).unwrap_or_default()
+    }
+
+    pub fn count_guild_bank_items(&self, guild: &Guild) -> u32 {
+        self.get_u32("COUNT(*)", "items", "owner", Self::guild_bank_owner(&guild.id).as_str()).unwrap_or(0)
+    }
+
+    /// finds an item in a guild's bank by its name
+    pub fn guild_bank_item_uuid_from_name(&self, name: String, guild: &Guild) -> Option<Uuid> {
+        self.item_uuid_owned_by(name, Self::guild_bank_owner(&guild.id).as_str())
+    }
+
+    /// moves an item from a player's inventory to their guild's bank, returning false if it is not in their inventory
+    pub fn deposit_guild_item(&self, guild: &Guild, uuid: &Uuid, item: &Uuid) -> bool {
+        let r = self.connection.execute(format!("UPDATE items SET owner = '{}' WHERE uuid IS '{}' AND owner IS '{}'",
+                                                Self::guild_bank_owner(&guild.id), item, uuid));
+
+        r.is_ok() && self.connection.change_count() > 0
+    }
+
+    /// moves an item from a guild's bank to a player's inventory, returning false if it is not in the bank
+    pub fn withdraw_guild_item(&self, guild: &Guild, uuid: &Uuid, item: &Uuid) -> bool {
+        let r = self.connection.execute(format!("UPDATE items SET owner = '{}' WHERE uuid IS '{}' AND owner IS '{}'",
+                                                uuid, item, Self::guild_bank_owner(&guild.id)));
+
+        r.is_ok() && self.connection.change_count() > 0
+    }
+
+    /// gets the id of the player's class, empty if they have not picked one
+    pub fn get_player_class(&self, uuid: &Uuid) -> Option<String> {
+        self.get_value("class", "players", "uuid", uuid.to_string().as_str())
+    }
+
+    /// sets the share of the normal max health the player's class and skills give them, keeping their health below it
+    pub fn set_health_percent(&self, uuid: &Uuid, percent: u32) -> bool {
+        let r = self.connection.execute(format!("UPDATE players SET health_percent = {} WHERE uuid IS '{}'", percent, uuid));
+        if r.is_err() {
+            return false;
+        }
+        match (self.get_player_health(uuid), self.get_player_max_health(uuid)) {
+            (Some(health), Some(max)) if health > max => self.set_player_health(uuid, max),
+            _ => true,
+        }
+    }
+
+    /// gets the skill points a player has not spent yet
+    pub fn get_skill_points(&self, uuid: &Uuid) -> u32 {
+        self.get_u32("skill_points", "players", "uuid", uuid.to_string().as_str()).unwrap_or(0)
+    }
+
+    pub fn add_skill_points(&self, uuid: &Uuid, amt: u32) -> bool {
+        let r = self.connection.execute(format!("UPDATE players SET skill_points = skill_points + {} WHERE uuid IS '{}'", amt, uuid));
+
+        r.is_ok()
+    }
+
+    /// gets the id and rank of every skill a player learned
+    pub fn get_player_skills(&self, uuid: &Uuid) -> Vec<(String, u32)> {
+        let mut skills = Vec::new();
+        let _ = self.connection.iterate(
+            format!("SELECT skill, rank FROM player_skills WHERE uuid IS '{}' AND rank > 0", uuid), |pairs| {
+                let mut skill = String::new();
+                let mut rank = 0;
+                for (col, val) in pairs {
+                    let v = val.unwrap_or("");
+                    match *col {
+                        "skill" => skill = v.to_string(),
+                        "rank" => rank = v.parse().unwrap_or(0),
+                        _ => {}
+                    }
+                }
+                skills.push((skill, rank));
+                true
+            });
+        skills
+    }
+
+    /// spends one of the player's skill points on a skill, returning false if they have none left
+    pub fn learn_skill(&self, uuid: &Uuid, skill: &str) -> bool {
+        if self.get_skill_points(uuid) == 0 {
+            return false;
+        }
+        let skill = skill.replace("'", "''");
+        self.transaction(vec![
+            format!("UPDATE players SET skill_points = skill_points - 1 WHERE uuid IS '{}'", uuid),
+            format!("INSERT OR IGNORE INTO player_skills VALUES ('{}', '{}', 0)", uuid, skill),
+            format!("UPDATE player_skills SET rank = rank + 1 WHERE uuid IS '{}' AND skill IS '{}'", uuid, skill),
+        ])
+    }
+
+    /// gives back every skill point the player spent and sets their class, taking the cost in gold.
+    /// The caller should check the player can afford it while holding the database lock
+    pub fn reset_skills(&self, uuid: &Uuid, class: &str, gold: u32) -> bool {
+        self.transaction(vec![
+            format!("UPDATE players SET skill_points = skill_points + \
+                     (SELECT IFNULL(SUM(rank), 0) FROM player_skills WHERE uuid IS '{}') WHERE uuid IS '{}'", uuid, uuid),
+            format!("DELETE FROM player_skills WHERE uuid IS '{}'", uuid),
+            format!("UPDATE players SET gold = gold - {}, class = '{}' WHERE uuid IS '{}'", gold, class.replace("'", "''"), uuid),
+        ])
+    }
+
+    /// gets the race and kills of every enemy the player has defeated
+    pub fn get_bestiary(&self, uuid: &Uuid) -> Vec<(String, u32)> {
+        let mut entries = Vec::new();
+        let _ = self.connection.iterate(
+            format!("SELECT race, kills FROM bestiary WHERE uuid IS '{}'", uuid), |pairs| {
+                let mut race = String::new();
+                let mut kills = 0;
+                for (col, val) in pairs {
+                    let v = val.unwrap_or("");
+                    match *col {
+                        "race" => race = v.to_string(),
+                        "kills" => kills = v.parse().unwrap_or(0),
+                        _ => {}
+                    }
+                }
+                entries.push((race, kills));
+                true
+            });
+        entries
+    }
+
+    /// counts a kill of a race in the player's bestiary, returning true if it is the first one
+    pub fn add_bestiary_kill(&self, uuid: &Uuid, race: &str) -> bool {
+        let first = !self.get_bestiary(uuid).iter().any(|(r, _)| r == race);
+        let race = race.replace("'", "''");
+        self.transaction(vec![
+            format!("INSERT OR IGNORE INTO bestiary VALUES ('{}', '{}', 0)", uuid, race),
+            format!("UPDATE bestiary SET kills = kills + 1 WHERE uuid IS '{}' AND race IS '{}'", uuid, race),
+        ]) && first
+    }
+
+    pub fn ban_player(&self, uuid: &Uuid, reason: String, banned_by: String, duration: Option<u64>) -> bool {
+        // replace any existing ban so the newest reason is shown
+        self.unban_player(uuid);
+        self.insert_ban(Some(uuid), None, reason, banned_by, duration)
+    }
+
+    /// bans an ip address or CIDR range
+    pub fn ban_ip(&self, cidr: String, reason: String, banned_by: String, duration: Option<u64>) -> bool {
+        self.unban_ip(cidr.clone());
+        self.insert_ban(None, Some(cidr), reason, banned_by, duration)
+    }
+
+    fn insert_ban(&self, uuid: Option<&Uuid>, ip: Option<String>, reason: String, banned_by: String, duration: Option<u64>) -> bool {
+        let now = systime().as_secs();
+        let r = self.connection.execute(
+            format!("INSERT INTO bans (uuid, ip, reason, banned_by, time, expires) VALUES ('{}', '{}', '{}', '{}', '{}', '{}')",
+                    uuid.map(|u| u.to_string()).unwrap_or_default(),
+                    ip.unwrap_or_default().replace("'", ""),
+                    reason.replace("'", "''"), banned_by.replace("'", "''"),
+                    now, expires_at(now, duration).unwrap_or(0)));
+
+        r.is_ok()
+    }
+
+    pub fn unban_player(&self, uuid: &Uuid) -> bool {
+        let r = self.connection.execute(format!("DELETE FROM bans WHERE uuid IS '{}'", uuid));
+
+        r.is_ok()
+    }
+
+    pub fn unban_ip(&self, cidr: String) -> bool {
+        let r = self.connection.execute(format!("DELETE FROM bans WHERE ip IS '{}'", cidr.replace("'", "")));
+
+        r.is_ok()
+    }
+
+    /// reads every ban matching the condition, skipping bans that have expired
+    fn get_bans(&self, condition: String) -> Vec<Ban> {
+        let mut bans = Vec::new();
+        let now = systime().as_secs();
+        let _ = self.connection.iterate(
+            format!("SELECT * FROM bans WHERE ({}) AND (expires IS NULL OR expires = 0 OR expires > {})", condition, now),
+            |pairs| {
+                let mut ban = Ban {
+                    uuid: None, ip: None,
+                    reason: String::new(), banned_by: String::new(),
+                    time: 0, expires: None,
+                };
+                for (col, val) in pairs {
+                    let v = val.unwrap_or("");
+                    match *col {
+                        "uuid" => ban.uuid = Uuid::from_str(v).ok(),
+                        "ip" => ban.ip = if v.is_empty() { None } else { Some(v.to_string()) },
+                        "reason" => ban.reason = v.to_string(),
+                        "banned_by" => ban.banned_by = v.to_string(),
+                        "time" => ban.time = v.parse::<u64>().unwrap_or(0),
+                        "expires" => ban.expires = v.parse::<u64>().ok().filter(|e| *e != 0),
+                        _ => {}
+                    }
+                }
+                bans.push(ban);
+                true
+            });
+        bans
+    }
+
+    /// returns the active ban on a player's account, if there is one
+    pub fn get_player_ban(&self, uuid: &Uuid) -> Option<Ban> {
+        self.get_bans(format!("uuid IS '{}'", uuid)).into_iter().next()
+    }
+
+    /// returns an active ban covering the ip, if there is one
+    pub fn get_ip_ban(&self, ip: &IpAddr) -> Option<Ban> {
+        self.get_bans(format!("ip IS NOT NULL AND ip != ''")).into_iter()
+            .find(|b| cidr_contains(b.ip.as_ref().unwrap(), ip))
+    }
+
+    /// returns every active ban
+    pub fn get_all_bans(&self) -> Vec<Ban> {
+        self.get_bans(format!("1"))
+    }
+
+    pub fn mute_player(&self, uuid: &Uuid, reason: String, muted_by: String, duration: Option<u64>) -> bool {
+        self.unmute_player(uuid);
+        let now = systime().as_secs();
+        let r = self.connection.execute(
+            format!("INSERT INTO mutes (uuid, reason, muted_by, time, expires) VALUES ('{}', '{}', '{}', '{}', '{}')",
+                    uuid, reason.replace("'", "''"), muted_by.replace("'", "''"),
+                    now, expires_at(now, duration).unwrap_or(0)));
+
+        r.is_ok()
+    }
+
+    pub fn unmute_player(&self, uuid: &Uuid) -> bool {
+        let r = self.connection.execute(format!("DELETE FROM mutes WHERE uuid IS '{}'", uuid));
+
+        r.is_ok()
+    }
+
+    /// returns the active mute on a player, if there is one
+    pub fn get_player_mute(&self, uuid: &Uuid) -> Option<Mute> {
+        let mut mute = None;
+        let now = systime().as_secs();
+        let _ = self.connection.iterate(
+            format!("SELECT * FROM mutes WHERE uuid IS '{}' AND (expires IS NULL OR expires = 0 OR expires > {})", uuid, now),
+            |pairs| {
+                let mut m = Mute {
+                    reason: String::new(), muted_by: String::new(),
+                    time: 0, expires: None,
+                };
+                for (col, val) in pairs {
+                    let v = val.unwrap_or("");
+                    match *col {
+                        "reason" => m.reason = v.to_string(),
+                        "muted_by" => m.muted_by = v.to_string(),
+                        "time" => m.time = v.parse::<u64>().unwrap_or(0),
+                        "expires" => m.expires = v.parse::<u64>().ok().filter(|e| *e != 0),
+                        _ => {}
+                    }
+                }
+                mute = Some(m);
+                false
+            });
+        mute
+    }
+
+    pub fn ignore_player(&self, uuid: &Uuid, ignored: &Uuid) -> bool {
+        self.unignore_player(uuid, ignored);
+        let r = self.connection.execute(
+            format!("INSERT INTO ignores (uuid, ignored) VALUES ('{}', '{}')", uuid, ignored));
+
+        r.is_ok()
+    }
+
+    pub fn unignore_player(&self, uuid: &Uuid, ignored: &Uuid) -> bool {
+        let r = self.connection.execute(
+            format!("DELETE FROM ignores WHERE uuid IS '{}' AND ignored IS '{}'", uuid, ignored));
+
+        r.is_ok()
+    }
+
+    /// gets the usernames of every player the player is ignoring
+    pub fn get_ignored(&self, uuid: &Uuid) -> Vec<String> {
+        let mut ignored = Vec::new();
+        let _ = self.connection.iterate(
+            format!("SELECT players.username FROM ignores JOIN players ON players.uuid = ignores.ignored \
+                     WHERE ignores.uuid IS '{}' ORDER BY players.username", uuid),
+            |pairs| {
+                for (_, val) in pairs {
+                    ignored.push(val.unwrap_or("").to_string());
+                }
+                true
+            });
+        ignored
+    }
+
+    /// gets the uuids of every player ignoring the player
+    pub fn get_ignored_by(&self, uuid: &Uuid) -> Vec<Uuid> {
+        let mut ignoring = Vec::new();
+        let _ = self.connection.iterate(
+            format!("SELECT uuid FROM ignores WHERE ignored IS '{}'", uuid),
+            |pairs| {
+                for (_, val) in pairs {
+                    if let Ok(u) = Uuid::from_str(val.unwrap_or("")) {
+                        ignoring.push(u);
+                    }
+                }
+                true
+            });
+        ignoring
+    }
+
+    /// records a moderation action in the audit log
+    pub fn log_moderation(&self, actor: &str, action: &str, target: &str, details: &str) -> bool {
+        let r = self.connection.execute(
+            format!("INSERT INTO audit_log VALUES ('{}', '{}', '{}', '{}', '{}')",
+                    systime().as_secs(), actor.replace("'", "''"), action.replace("'", "''"),
+                    target.replace("'", "''"), details.replace("'", "''")));
+
+        r.is_ok()
+    }
+
+    /// returns the most recent entries in the audit log, newest first
+    pub fn get_audit_log(&self, count: u32) -> Vec<AuditEntry> {
+        let mut entries = Vec::new();
+        let _ = self.connection.iterate(
+            format!("SELECT * FROM audit_log ORDER BY time DESC LIMIT {}", count),
+            |pairs| {
+                let mut entry = AuditEntry {
+                    time: 0,
+                    actor: String::new(), action: String::new(),
+                    target: String::new(), details: String::new(),
+                };
+                for (col, val) in pairs {
+                    let v = val.unwrap_or("");
+                    match *col {
+                        "time" => entry.time = v.parse::<u64>().unwrap_or(0),
+                        "actor" => entry.actor = v.to_string(),
+                        "action" => entry.action = v.to_string(),
+                        "target" => entry.target = v.to_string(),
+                        "details" => entry.details = v.to_string(),
+                        _ => {}
+                    }
+                }
+                entries.push(entry);
+                true
+            });
+        entries
+    }
 }
\ No newline at end of file
@@ -0,0 +1,398 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use log::{error, info};
+use rand::{Rng, thread_rng};
+use uuid::Uuid;
+use snd_network_lib::duel_data::{DuelActionData, DuelActionKind};
+use snd_network_lib::encounter_data::EncounterData;
+use snd_network_lib::enemy_data::EnemyData;
+use snd_network_lib::loot_data::LootData;
+use crate::config::ServerConfig;
+use crate::database::Database;
+use crate::encounter::{abilities, attack_power, crit, defense, roll};
+use crate::game_event::{self, GameEvent};
+use crate::player::BASE_HEALTH;
+use crate::session::{SessionMessage, SessionRegistry};
+use crate::state::ServerState;
+
+const LOG_TARGET: &str = "duel";
+
+/// A duel one player has offered another
+struct Challenge {
+    from: Uuid,
+    from_name: String,
+    /// the gold both players wager
+    gold: u32,
+    /// the names of the items the challenger wagers
+    items: Vec<String>,
+    sent: Instant,
+}
+
+/// A player in a duel. Duels do not hurt players, both start with the base health so only levels change the damage
+struct Duelist {
+    uuid: Uuid,
+    name: String,
+    level: u32,
+    health: u32,
+}
+
+/// A turn based fight between two players
+struct Duel {
+    /// the id of the escrow holding the wagers
+    id: Uuid,
+    duelists: [Duelist; 2],
+    turn: usize,
+    turn_started: Instant,
+    /// the total gold wagered by both players
+    gold: u32,
+}
+
+impl Duel {
+    /// an update about the duel as seen by one of the duelists, their opponent is shown as the enemy
+    fn update<S: Into<String>>(&self, to: usize, message: S) -> EncounterData {
+        let opponent = &self.duelists[1 - to];
+        EncounterData {
+            enemy: EnemyData {
+                name: opponent.name.clone(),
+                race: format!("Duelist"),
+                level: opponent.level,
+                health: opponent.health,
+                max_health: BASE_HEALTH,
+            },
+            attk: None,
+            flee: None,
+            win: None,
+            lost: None,
+            start: false,
+            hit: None,
+            message: message.into(),
+            turn: self.duelists[self.turn].name.clone(),
+            effects: Vec::new(),
+        }
+    }
+
+    /// sends an update to both duelists, letting the caller fill in the details for each
+    fn send_both<F: Fn(usize, &mut EncounterData)>(&self, sessions: &Arc<Mutex<SessionRegistry>>, message: &str, fill: F) {
+        let sessions = sessions.lock().unwrap();
+        for i in 0..2 {
+            let mut data = self.update(i, message);
+            fill(i, &mut data);
+            sessions.send(&self.duelists[i].uuid, SessionMessage::Encounter(data));
+        }
+    }
+
+    fn index_of(&self, uuid: &Uuid) -> usize {
+        if self.duelists[0].uuid == *uuid { 0 } else { 1 }
+    }
+}
+
+/// Keeps track of duel challenges and the duels being fought
+pub struct DuelRegistry {
+    /// pending challenges by the challenged player, a new challenge replaces the old one
+    challenges: HashMap<Uuid, Challenge>,
+    duels: HashMap<Uuid, Duel>,
+    by_player: HashMap<Uuid, Uuid>,
+}
+
+impl DuelRegistry {
+    pub fn new() -> Self {
+        Self {
+            challenges: HashMap::new(),
+            duels: HashMap::new(),
+            by_player: HashMap::new(),
+        }
+    }
+
+    pub fn in_duel(&self, uuid: &Uuid) -> bool {
+        self.by_player.contains_key(uuid)
+    }
+
+    fn duel_of(&mut self, uuid: &Uuid) -> Option<&mut Duel> {
+        let id = self.by_player.get(uuid)?;
+        self.duels.get_mut(id)
+    }
+
+    fn remove(&mut self, id: &Uuid) -> Option<Duel> {
+        let duel = self.duels.remove(id)?;
+        for d in &duel.duelists {
+            self.by_player.remove(&d.uuid);
+        }
+        Some(duel)
+    }
+}
+
+/// Handles a duel action from a player.
+/// Returns an error to show the player if the action could not be done
+pub fn handle_action(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str, action: DuelActionData) -> Result<(), String> {
+    match action.kind {
+        DuelActionKind::Challenge => challenge(state, config, uuid, username, action),
+        DuelActionKind::Accept => accept(state, config, uuid, username, action.items),
+        DuelActionKind::Decline => {
+            let challenge = state.duels.lock().unwrap().challenges.remove(uuid)
+                .ok_or(format!("You have not been challenged to a duel"))?;
+            notify(&state.sessions, &challenge.from, format!("{} declined your duel", username));
+            notify(&state.sessions, uuid, format!("Declined the duel from {}", challenge.from_name));
+            Ok(())
+        }
+        DuelActionKind::Forfeit => {
+            let mut duels = state.duels.lock().unwrap();
+            let duel = duels.duel_of(uuid).ok_or(format!("You are not in a duel"))?;
+            let (id, winner) = (duel.id.clone(), 1 - duel.index_of(uuid));
+            let duel = duels.remove(&id).unwrap();
+            drop(duels);
+            finish(state, duel, winner, format!("{} forfeit the duel", username));
+            Ok(())
+        }
+    }
+}
+
+fn challenge(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str, action: DuelActionData) -> Result<(), String> {
+    let (target, target_name) = {
+        let sessions = state.sessions.lock().unwrap();
+        let target = sessions.find_by_name(action.target.as_str())
+            .ok_or(format!("{} is not online", action.target))?;
+        (target, sessions.get(&target).unwrap().username.clone())
+    };
+    if target == *uuid {
+        return Err(format!("You can not duel yourself"));
+    }
+    if action.items.len() > config.duel_max_wager_items {
+        return Err(format!("You can only wager up to {} items", config.duel_max_wager_items));
+    }
+    // the winner takes both wagers, so the pot has to fit
+    if action.gold.checked_mul(2).is_none() {
+        return Err(format!("You can only wager up to {} gold", u32::MAX / 2));
+    }
+    // the wager is checked again when the duel starts in case it changed in the meantime
+    {
+        let db = state.db.lock().unwrap();
+        check_wager(&db, uuid, action.gold, &action.items)?;
+    }
+
+    let mut duels = state.duels.lock().unwrap();
+    if duels.in_duel(uuid) || duels.in_duel(&target) {
+        return Err(format!("You can not challenge {} right now", target_name));
+    }
+    duels.challenges.insert(target.clone(), Challenge {
+        from: uuid.clone(),
+        from_name: username.to_string(),
+        gold: action.gold,
+        items: action.items.clone(),
+        sent: Instant::now(),
+    });
+    drop(duels);
+
+    info!(target:LOG_TARGET, "{} challenged {} to a duel for {} gold and {} items", username, target_name, action.gold, action.items.len());
+    notify(&state.sessions, &target, format!("{} challenged you to a duel{}! Use 'duel accept [items to wager]' or 'duel decline'",
+                                            username, describe_wager(action.gold, &action.items)));
+    notify(&state.sessions, uuid, format!("Challenged {} to a duel", target_name));
+    Ok(())
+}
+
+fn accept(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str, items: Vec<String>) -> Result<(), String> {
+    if items.len() > config.duel_max_wager_items {
+        return Err(format!("You can only wager up to {} items", config.duel_max_wager_items));
+    }
+    let encounters = state.encounters.lock().unwrap();
+    let mut duels = state.duels.lock().unwrap();
+    // check the wager before using up the challenge so a mistake in it can be fixed by accepting again
+    let own_items = {
+        let challenge = duels.challenges.get(uuid).ok_or(format!("You have not been challenged to a duel"))?;
+        check_wager(&state.db.lock().unwrap(), uuid, challenge.gold, &items)?
+    };
+    let challenge = duels.challenges.remove(uuid).unwrap();
+    let pot = challenge.gold.checked_mul(2)
+        .ok_or(format!("The wager from {} is too large", challenge.from_name))?;
+    if challenge.sent.elapsed() > Duration::from_secs(config.duel_challenge_timeout) {
+        return Err(format!("The duel from {} has expired", challenge.from_name));
+    }
+    if !state.sessions.lock().unwrap().is_online(&challenge.from) {
+        return Err(format!("{} is no longer online", challenge.from_name));
+    }
+    if encounters.in_fight(uuid) || duels.in_duel(uuid) {
+        return Err(format!("You can not start a duel while fighting"));
+    }
+    if encounters.in_fight(&challenge.from) || duels.in_duel(&challenge.from) {
+        return Err(format!("{} is busy fighting", challenge.from_name));
+    }
+
+    // take both wagers in one go so neither player can lose their wager without the other
+    let id = Uuid::new_v4();
+    let (challenger_level, level) = {
+        let db = state.db.lock().unwrap();
+        let challenger_items = check_wager(&db, &challenge.from, challenge.gold, &challenge.items)
+            .map_err(|_| format!("{} can no longer afford their wager", challenge.from_name))?;
+        if !db.escrow(&id, &[(challenge.from.clone(), challenge.gold, challenger_items), (uuid.clone(), challenge.gold, own_items)]) {
+            return Err(format!("Failed to hold the wagers, the duel was cancelled"));
+        }
+        (db.get_player_level(&challenge.from).unwrap_or(1), db.get_player_level(uuid).unwrap_or(1))
+    };
+
+    let duel = Duel {
+        id: id.clone(),
+        duelists: [
+            Duelist { uuid: challenge.from.clone(), name: challenge.from_name.clone(), level: challenger_level, health: BASE_HEALTH },
+            Duelist { uuid: uuid.clone(), name: username.to_string(), level, health: BASE_HEALTH },
+        ],
+        turn: thread_rng().gen_range(0..2),
+        turn_started: Instant::now(),
+        gold: pot,
+    };
+    info!(target:LOG_TARGET, "{} accepted the duel from {}", username, challenge.from_name);
+    let message = format!("The duel between {} and {} has begun{}!", challenge.from_name, username,
+                          describe_wager(duel.gold, &[challenge.items, items].concat()));
+    duel.send_both(&state.sessions, message.as_str(), |_, data| data.start = true);
+    duels.by_player.insert(challenge.from, id.clone());
+    duels.by_player.insert(uuid.clone(), id.clone());
+    duels.duels.insert(id, duel);
+    Ok(())
+}
+
+/// Attacks the other player if it is the player's turn.
+/// Returns an error to show the player if they can not attack
+pub fn attack(state: &ServerState, uuid: &Uuid) -> Result<(), String> {
+    let mut duels = state.duels.lock().unwrap();
+    let duel = duels.duel_of(uuid).ok_or(format!("You are not in a duel"))?;
+    let me = duel.index_of(uuid);
+    if duel.turn != me {
+        return Err(format!("It is {}'s turn", duel.duelists[duel.turn].name));
+    }
+    let (damage, critical, mine, theirs) = {
+        let db = state.db.lock().unwrap();
+        let (mine, theirs) = (abilities(&db, uuid), abilities(&db, &duel.duelists[1 - me].uuid));
+        let (damage, critical) = crit(roll(attack_power(&db, uuid)), &mine);
+        (damage.saturating_sub(defense(&db, &duel.duelists[1 - me].uuid) / 2).max(1), critical, mine, theirs)
+    };
+    let opponent = &mut duel.duelists[1 - me];
+    let damage = damage.min(opponent.health);
+    opponent.health -= damage;
+    let mut message = format!("{} hit {} for {} damage{}", duel.duelists[me].name, duel.duelists[1 - me].name, damage,
+                              if critical { ", a critical hit!" } else { "!" });
+
+    // lifesteal and thorns only change the health the duelists have in the duel, and thorns can not win it
+    let won = duel.duelists[1 - me].health == 0;
+    let attacker = &mut duel.duelists[me];
+    let healed = (damage * mine.lifesteal / 100).min(BASE_HEALTH - attacker.health.min(BASE_HEALTH));
+    attacker.health += healed;
+    let thorns = if won { 0 } else { (damage * theirs.thorns / 100).min(attacker.health.saturating_sub(1)) };
+    attacker.health -= thorns;
+    if healed > 0 {
+        message.push_str(format!(" They stole {} health.", healed).as_str());
+    }
+    if thorns > 0 {
+        message.push_str(format!(" {}'s thorns hit back for {}.", duel.duelists[1 - me].name, thorns).as_str());
+    }
+
+    if won {
+        let id = duel.id.clone();
+        let duel = duels.remove(&id).unwrap();
+        drop(duels);
+        finish(state, duel, me, message);
+        return Ok(());
+    }
+
+    duel.turn = 1 - me;
+    duel.turn_started = Instant::now();
+    duel.send_both(&state.sessions, message.as_str(), |i, data| {
+        // the attacker sees the damage they dealt and the defender sees the hit they took
+        if i == me { data.attk = Some(damage); } else { data.hit = Some(damage); }
+    });
+    Ok(())
+}
+
+/// Skips the turn of a duelist who took too long, called regularly from both duelists' handlers
+pub fn check_turn_timeout(state: &ServerState, config: &ServerConfig, uuid: &Uuid) {
+    let mut duels = state.duels.lock().unwrap();
+    if let Some(duel) = duels.duel_of(uuid) {
+        if duel.turn_started.elapsed() < Duration::from_secs(config.duel_turn_timeout) {
+            return;
+        }
+        let message = format!("{} took too long and lost their turn", duel.duelists[duel.turn].name);
+        duel.turn = 1 - duel.turn;
+        duel.turn_started = Instant::now();
+        duel.send_both(&state.sessions, message.as_str(), |_, _| {});
+    }
+}
+
+/// Ends the duel of a player that disconnected, counting it as a loss, and forgets their challenges
+pub fn disconnect(state: &ServerState, uuid: &Uuid, username: &str) {
+    let mut duels = state.duels.lock().unwrap();
+    duels.challenges.remove(uuid);
+    duels.challenges.retain(|_, c| c.from != *uuid);
+    let ended = match duels.duel_of(uuid) {
+        Some(duel) => {
+            let (id, winner) = (duel.id.clone(), 1 - duel.index_of(uuid));
+            duels.remove(&id).map(|d| (d, winner))
+        }
+        None => None,
+    };
+    drop(duels);
+    if let Some((duel, winner)) = ended {
+        finish(state, duel, winner, format!("{} left the duel", username));
+    }
+}
+
+/// pays out the wagers to the winner and tells both players how the duel ended
+fn finish(state: &ServerState, duel: Duel, winner: usize, message: String) {
+    let (w, l) = (&duel.duelists[winner], &duel.duelists[1 - winner]);
+    let items = {
+        let db = state.db.lock().unwrap();
+        // the wagered items are owned by the escrow until it is paid out
+        let items = db.get_player_items(&duel.id).unwrap_or_default();
+        if !db.settle_duel(&duel.id, &w.uuid, &l.uuid) {
+            error!(target:LOG_TARGET, "Failed to pay out the duel between {} and {}, refunding the wagers", w.name, l.name);
+            db.refund_escrow(&duel.id);
+        }
+        items
+    };
+    info!(target:LOG_TARGET, "{} won the duel against {}", w.name, l.name);
+
+    duel.send_both(&state.sessions, message.as_str(), |i, data| {
+        data.turn = String::new();
+        if i == winner {
+            data.message = format!("{} You won the duel against {}!", message, l.name);
+            data.win = Some(LootData {
+                items: items.iter().map(|item| item.as_data()).collect(),
+                exp: 0,
+                gold: duel.gold,
+                consumables: Vec::new(),
+                materials: Vec::new(),
+            });
+        } else {
+            data.message = format!("{} You lost the duel against {}.", message, w.name);
+            data.lost = Some(true);
+        }
+    });
+    game_event::fire(state, &w.uuid, GameEvent::DuelWin);
+}
+
+/// checks a player has the gold and items they want to wager, returning the uuids of the items
+fn check_wager(db: &Database, uuid: &Uuid, gold: u32, items: &[String]) -> Result<Vec<Uuid>, String> {
+    if db.get_player_gold(uuid).unwrap_or(0) < gold {
+        return Err(format!("You do not have {} gold to wager", gold));
+    }
+    let mut uuids: Vec<Uuid> = Vec::new();
+    for name in items {
+        let item = db.item_uuid_from_name(name.replace("'", ""), uuid)
+            .ok_or(format!("You do not have an item called '{}'", name))?;
+        if uuids.contains(&item) {
+            return Err(format!("You can only wager '{}' once", name));
+        }
+        uuids.push(item);
+    }
+    Ok(uuids)
+}
+
+fn describe_wager(gold: u32, items: &[String]) -> String {
+    match (gold, items.len()) {
+        (0, 0) => String::new(),
+        (_, 0) => format!(" for {} gold", gold),
+        (0, _) => format!(" for {}", items.join(", ")),
+        _ => format!(" for {} gold and {}", gold, items.join(", ")),
+    }
+}
+
+fn notify<S: Into<String>>(sessions: &Arc<Mutex<SessionRegistry>>, uuid: &Uuid, msg: S) {
+    sessions.lock().unwrap().send(uuid, SessionMessage::Event(msg.into()));
+}
@@ -0,0 +1,864 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use log::info;
+use rand::{Rng, thread_rng};
+use rand_distr::{Normal, Distribution};
+use uuid::Uuid;
+use snd_network_lib::broadcast_data::BroadcastKind;
+use snd_network_lib::encounter_data::EncounterData;
+use snd_network_lib::loot_data::LootData;
+use snd_network_lib::status_data::StatusKind;
+use crate::bestiary::{Loot, LootItem, Move};
+use crate::class::{ClassStats, Skill, SkillEffect};
+use crate::config::ServerConfig;
+use crate::consumable::{Consumable, ConsumableList, Effect};
+use crate::database::Database;
+use crate::enemy::Enemy;
+use crate::game_event::{self, GameEvent};
+use crate::health;
+use crate::inventory;
+use crate::item::{Abilities, Item, ItemRarity, ItemType};
+use crate::party::{self, LootRule};
+use crate::session::{SessionMessage, SessionRegistry};
+use crate::state::ServerState;
+use crate::status::{StatusEffect, StatusEffects};
+
+const LOG_TARGET: &str = "encounter";
+/// the rounds the poison from a venom item lasts
+const VENOM_TURNS: u32 = 3;
+/// how much less damage an enemy weakened by a frost item deals and the rounds it lasts
+const FROST_WEAKEN: u32 = 25;
+const FROST_TURNS: u32 = 2;
+/// how much less damage a guarding enemy takes until its next turn
+const GUARD_SHIELD: u32 = 50;
+
+/// A player taking part in an encounter
+struct Fighter {
+    uuid: Uuid,
+    name: String,
+    damage_dealt: u32,
+    /// the buffs from consumables and skills and what the enemy did to the player
+    effects: StatusEffects,
+    /// the bonuses from the player's class and skills, worked out when the fight starts
+    stats: ClassStats,
+    /// the rounds left before each skill the player used can be used again
+    cooldowns: HashMap<String, u32>,
+}
+
+impl Fighter {
+    /// counts down the rounds left on the player's status effects and skill cooldowns, removing the ones that ran out
+    fn tick(&mut self) {
+        self.effects.tick();
+        self.cooldowns.values_mut().for_each(|rounds| *rounds -= 1);
+        self.cooldowns.retain(|_, rounds| *rounds > 0);
+    }
+
+    /// returns an error to show the player if the skill is still cooling down
+    fn ready(&self, skill: &Skill) -> Result<(), String> {
+        match self.cooldowns.get(&skill.id) {
+            Some(rounds) => Err(format!("{} can be used again in {} rounds", skill.name, rounds)),
+            None => Ok(()),
+        }
+    }
+
+    fn used(&mut self, skill: &Skill) {
+        if skill.cooldown > 0 {
+            self.cooldowns.insert(skill.id.clone(), skill.cooldown);
+        }
+    }
+}
+
+/// A fight between an enemy and one or more players who take turns attacking it.
+/// The enemy attacks a random player once everyone has had their turn
+struct Encounter {
+    enemy: Enemy,
+    /// what the players and the enemy's own abilities did to the enemy
+    enemy_effects: StatusEffects,
+    /// the players still fighting, in turn order
+    fighters: Vec<Fighter>,
+    turn: usize,
+    turn_started: Instant,
+    /// how many players started the fight, the rewards scale with it
+    party_size: u32,
+}
+
+impl Encounter {
+    fn uuids(&self) -> Vec<Uuid> {
+        self.fighters.iter().map(|f| f.uuid.clone()).collect()
+    }
+
+    /// the name of the player who goes after the current one
+    fn next_turn_name(&self) -> String {
+        self.fighters.get(self.turn + 1).or(self.fighters.first())
+            .map(|f| f.name.clone()).unwrap_or_default()
+    }
+
+    /// an update about the fight to send to the players
+    fn update<S: Into<String>>(&self, message: S) -> EncounterData {
+        EncounterData {
+            enemy: self.enemy.as_data(),
+            attk: None,
+            flee: None,
+            win: None,
+            lost: None,
+            start: false,
+            hit: None,
+            message: message.into(),
+            turn: self.fighters.get(self.turn).map(|f| f.name.clone()).unwrap_or_default(),
+            effects: self.enemy_effects.as_data("").into_iter()
+                .chain(self.fighters.iter().flat_map(|f| f.effects.as_data(f.name.as_str())))
+                .collect(),
+        }
+    }
+
+    /// poison and bleeding hurt everyone who has them, but never finish anyone off.
+    /// Returns what happened to tell the players and the players who were hurt
+    fn wounds(&mut self, db: &Database) -> (Vec<String>, Vec<Uuid>) {
+        let mut messages = Vec::new();
+        let mut hurt = Vec::new();
+        let damage = self.enemy_effects.damage(self.enemy.max_health).min(self.enemy.health.saturating_sub(1));
+        if damage > 0 {
+            self.enemy.health -= damage;
+            messages.push(format!("{} took {} damage from its wounds.", self.enemy.title(), damage));
+        }
+        for f in self.fighters.iter() {
+            let health = db.get_player_health(&f.uuid).unwrap_or(0);
+            let damage = f.effects.damage(db.get_player_max_health(&f.uuid).unwrap_or(0)).min(health.saturating_sub(1));
+            if damage > 0 {
+                db.remove_player_health(&f.uuid, damage);
+                messages.push(format!("{} took {} damage from their wounds.", f.name, damage));
+                hurt.push(f.uuid.clone());
+            }
+        }
+        (messages, hurt)
+    }
+
+    /// counts down the enemy's and every player's status effects and cooldowns
+    fn tick(&mut self) {
+        self.enemy_effects.tick();
+        self.fighters.iter_mut().for_each(Fighter::tick);
+    }
+}
+
+/// Keeps track of every fight going on
+pub struct EncounterRegistry {
+    encounters: HashMap<u32, Encounter>,
+    by_player: HashMap<Uuid, u32>,
+    next_id: u32,
+}
+
+impl EncounterRegistry {
+    pub fn new() -> Self {
+        Self {
+            encounters: HashMap::new(),
+            by_player: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn in_fight(&self, uuid: &Uuid) -> bool {
+        self.by_player.contains_key(uuid)
+    }
+
+    /// takes a player out of a fight without ending their turn.
+    /// Returns true if it was their turn
+    fn remove_fighter(&mut self, id: u32, uuid: &Uuid) -> bool {
+        self.by_player.remove(uuid);
+        let enc = self.encounters.get_mut(&id).unwrap();
+        let index = enc.fighters.iter().position(|f| f.uuid == *uuid).unwrap();
+        let was_turn = index == enc.turn;
+        enc.fighters.remove(index);
+        if index < enc.turn {
+            enc.turn -= 1;
+        }
+        was_turn
+    }
+
+    /// moves on to the next player's turn, the enemy attacks when the round is over.
+    /// `advance` is false if the player whose turn it was has already left the fight
+    /// Stunned players lose their turn and it moves on again
+    fn end_turn(&mut self, id: u32, advance: bool, config: &ServerConfig, db: &Arc<Mutex<Database>>, sessions: &Arc<Mutex<SessionRegistry>>) {
+        let mut advance = advance;
+        loop {
+            let enc = self.encounters.get_mut(&id).unwrap();
+            if advance {
+                enc.turn += 1;
+            }
+            if enc.turn >= enc.fighters.len() {
+                enc.turn = 0;
+                self.end_round(id, config, db, sessions);
+            }
+            let enc = match self.encounters.get_mut(&id) {
+                Some(enc) => enc,
+                None => return,
+            };
+            enc.turn_started = Instant::now();
+            if !enc.fighters[enc.turn].effects.has(StatusKind::Stun) {
+                return;
+            }
+            let mut skipped = enc.update(format!("{} is stunned and loses their turn!", enc.fighters[enc.turn].name));
+            skipped.turn = enc.next_turn_name();
+            send(sessions, &enc.uuids(), skipped);
+            advance = true;
+        }
+    }
+
+    /// ends the round: wounds hurt everyone who has them, the enemy takes the next step of its pattern unless it is stunned
+    /// and then every status effect counts down. Ends the fight if every player is defeated
+    fn end_round(&mut self, id: u32, config: &ServerConfig, db: &Arc<Mutex<Database>>, sessions: &Arc<Mutex<SessionRegistry>>) {
+        let enc = self.encounters.get_mut(&id).unwrap();
+        if enc.fighters.is_empty() {
+            self.end(id);
+            return;
+        }
+        let (mut messages, hurt) = enc.wounds(&db.lock().unwrap());
+        for uuid in &hurt {
+            sessions.lock().unwrap().send(uuid, SessionMessage::StatsChanged);
+        }
+        if enc.enemy_effects.has(StatusKind::Stun) {
+            messages.push(format!("{} is stunned and can not attack!", enc.enemy.title()));
+            enc.tick();
+            send(sessions, &enc.uuids(), enc.update(messages.join(" ")));
+            return;
+        }
+        let action = enc.enemy.next_move();
+        if action == Move::Guard {
+            messages.push(format!("{} raised its guard and takes half damage until its next turn!", enc.enemy.title()));
+            enc.tick();
+            enc.enemy_effects.apply(StatusEffect::new(StatusKind::Shield, GUARD_SHIELD, 1));
+            send(sessions, &enc.uuids(), enc.update(messages.join(" ")));
+            return;
+        }
+
+        let target = &enc.fighters[thread_rng().gen_range(0..enc.fighters.len())];
+        let (uuid, name) = (target.uuid.clone(), target.name.clone());
+        let shield = (target.effects.amount(StatusKind::Shield) + target.stats.guard).min(90);
+        let armor = target.stats.armor;
+
+        let (damage, abilities, defeat) = {
+            let db = db.lock().unwrap();
+            let health = db.get_player_health(&uuid).unwrap_or(0);
+            let power = if action == Move::Heavy { enc.enemy.damage * 3 / 2 } else { enc.enemy.damage };
+            let damage = enc.enemy_effects.outgoing(roll(power)) * (100 - shield) / 100;
+            let damage = damage.saturating_sub(defense(&db, &uuid) * armor / 100 / 2).max(1).min(health);
+            let abilities = abilities(&db, &uuid);
+            db.remove_player_health(&uuid, damage);
+            let defeat = if damage >= health { Some(health::defeat(&db, config, &uuid, name.as_str())) } else { None };
+            (damage, abilities, defeat)
+        };
+        sessions.lock().unwrap().send(&uuid, SessionMessage::StatsChanged);
+
+        // thorns never finish off the enemy, that is left to the players' attacks
+        let thorns = (damage * abilities.thorns / 100).min(enc.enemy.health.saturating_sub(1));
+        enc.enemy.health -= thorns;
+        // the effects put on this round only count down from the next one so they last every round they say
+        enc.tick();
+
+        let defeat = match defeat {
+            Some(defeat) => defeat,
+            None => {
+                messages.push(match action {
+                    Move::Heavy => format!("{} hit {} with a heavy blow for {} damage!", enc.enemy.title(), name, damage),
+                    _ => format!("{} hit {} for {} damage!", enc.enemy.title(), name, damage),
+                });
+                if thorns > 0 {
+                    messages.push(format!("{}'s thorns hit back for {}.", name, thorns));
+                }
+                if let Some(ability) = enc.enemy.use_ability(action == Move::Ability).cloned() {
+                    let effect = ability.effect(enc.enemy.damage);
+                    let (applied, who) = match ability.on_self() {
+                        true => (enc.enemy_effects.apply(effect), enc.enemy.title()),
+                        false => (enc.fighters.iter_mut().find(|f| f.uuid == uuid).unwrap().effects.apply(effect), name.clone()),
+                    };
+                    if applied {
+                        messages.push(format!("{} used {}, {} {}!", enc.enemy.title(), ability.name, who, effect.describe()));
+                    }
+                }
+                if abilities.weaken > 0 && thread_rng().gen_range(0..100) < abilities.weaken {
+                    let effect = StatusEffect::new(StatusKind::Weaken, FROST_WEAKEN, FROST_TURNS);
+                    if enc.enemy_effects.apply(effect) {
+                        messages.push(format!("{}'s frost chilled {}, it {}!", name, enc.enemy.title(), effect.describe()));
+                    }
+                }
+                let mut hit = enc.update(messages.join(" "));
+                hit.hit = Some(damage);
+                send(sessions, &enc.uuids(), hit);
+                return;
+            }
+        };
+
+        info!(target:LOG_TARGET, "{} was defeated by {}", name, enc.enemy.title());
+        self.remove_fighter(id, &uuid);
+        let enc = self.encounters.get(&id).unwrap();
+        let mut lost = enc.update(format!("{} hit you for {} damage and defeated you!", enc.enemy.title(), damage));
+        lost.lost = Some(true);
+        lost.turn = String::new();
+        send(sessions, &[uuid.clone()], lost);
+        sessions.lock().unwrap().send(&uuid, SessionMessage::Event(defeat));
+        if enc.fighters.is_empty() {
+            self.end(id);
+            return;
+        }
+        messages.push(format!("{} was defeated by {}!", name, enc.enemy.title()));
+        send(sessions, &enc.uuids(), enc.update(messages.join(" ")));
+    }
+
+    fn end(&mut self, id: u32) {
+        if let Some(enc) = self.encounters.remove(&id) {
+            for f in enc.fighters {
+                self.by_player.remove(&f.uuid);
+            }
+        }
+    }
+
+    /// the enemy was killed, split the rewards between the players still fighting
+    /// hands out the rewards for defeating the enemy.
+    /// Returns the events for each fighter that could unlock achievements
+    fn win(&mut self, id: u32, config: &ServerConfig, consumables: &ConsumableList, db: &Arc<Mutex<Database>>,
+           sessions: &Arc<Mutex<SessionRegistry>>) -> Vec<(Uuid, GameEvent)> {
+        let rule = config.party_loot_rule;
+        let enc = self.encounters.remove(&id).unwrap();
+        for f in &enc.fighters {
+            self.by_player.remove(&f.uuid);
+        }
+        info!(target:LOG_TARGET, "{} was defeated by {}", enc.enemy.title(),
+            enc.fighters.iter().map(|f| f.name.as_str()).collect::<Vec<&str>>().join(", "));
+
+        let total_exp = (enc.enemy.level * 8 + 10) * enc.party_size;
+        let item_count = thread_rng().gen_range(1..=enc.fighters.len());
+        let mut items = (0..item_count)
+            .map(|_| Item::new_rand(ItemType::rand(), &Uuid::nil(), enc.enemy.level, ItemRarity::new_rand()))
+            .collect::<Vec<Item>>();
+
+        let fighters = enc.fighters.len() as u32;
+        // damage can be as high as the enemy's health, so shares are worked out in u64
+        let total_damage = enc.fighters.iter().map(|f| f.damage_dealt as u64).sum::<u64>().max(1);
+        let split = |total: u32| enc.fighters.iter().map(|f| match rule {
+            LootRule::Damage => (total as u64 * f.damage_dealt as u64 / total_damage) as u32,
+            LootRule::Even | LootRule::Random => total / fighters,
+        }).collect::<Vec<u32>>();
+        let mut exp = split(total_exp);
+        let gold = split((enc.enemy.level * 4 + 5) * enc.party_size);
+
+        // decide who gets each item by their index in the fighters
+        let owners = match rule {
+            LootRule::Even => {
+                let start = thread_rng().gen_range(0..enc.fighters.len());
+                (0..items.len()).map(|i| (start + i) % enc.fighters.len()).collect::<Vec<usize>>()
+            }
+            LootRule::Damage => {
+                items.sort_by(|a, b| (b.rarity as u32, b.level).cmp(&(a.rarity as u32, a.level)));
+                let mut ranked = (0..enc.fighters.len()).collect::<Vec<usize>>();
+                ranked.sort_by(|a, b| enc.fighters[*b].damage_dealt.cmp(&enc.fighters[*a].damage_dealt));
+                (0..items.len()).map(|i| ranked[i % ranked.len()]).collect::<Vec<usize>>()
+            }
+            LootRule::Random => (0..items.len()).map(|_| thread_rng().gen_range(0..enc.fighters.len())).collect(),
+        };
+
+        let mut loot: Vec<Vec<Item>> = enc.fighters.iter().map(|_| Vec::new()).collect();
+        for (mut item, owner) in items.into_iter().zip(owners) {
+            item.owner = enc.fighters[owner].uuid.clone();
+            loot[owner].push(item);
+        }
+        // every fighter gets their own roll for a consumable
+        let drops = enc.fighters.iter()
+            .map(|_| if thread_rng().gen_range(0..100) < config.consumable_drop_chance { consumables.rand_drop() } else { None })
+            .collect::<Vec<Option<&Consumable>>>();
+        // and for each of the enemy's own drops
+        let extras = enc.fighters.iter()
+            .map(|_| enc.enemy.loot.iter().filter(|l| thread_rng().gen_range(0..100) < l.chance).collect())
+            .collect::<Vec<Vec<&Loot>>>();
+
+        let mut left_behind = vec![0; enc.fighters.len()];
+        let mut discovered = vec![false; enc.fighters.len()];
+        {
+            let db = db.lock().unwrap();
+            for (i, f) in enc.fighters.iter().enumerate() {
+                // items that do not fit in the fighter's inventory are left behind
+                let space = inventory::free_space(&db, config, &f.uuid) as usize;
+                left_behind[i] = loot[i].len().saturating_sub(space);
+                loot[i].truncate(space);
+                for item in &loot[i] {
+                    db.new_item(item);
+                }
+                if let Some(c) = drops[i] {
+                    db.add_consumable(&f.uuid, c.id.as_str(), 1, config.consumable_max_stack);
+                }
+                for loot in &extras[i] {
+                    match &loot.item {
+                        LootItem::Consumable { id, .. } => db.add_consumable(&f.uuid, id.as_str(), loot.amount, config.consumable_max_stack),
+                        LootItem::Material { id, .. } => db.add_materials(&f.uuid, &[(id.clone(), loot.amount)]),
+                    };
+                }
+                discovered[i] = db.add_bestiary_kill(&f.uuid, enc.enemy.race.as_str());
+                exp[i] = abilities(&db, &f.uuid).exp(exp[i]);
+                db.add_player_exp(&f.uuid, exp[i]);
+                db.add_player_gold(&f.uuid, gold[i]);
+                db.inc_player_kills(&f.uuid);
+                db.check_levelup(&f.uuid);
+            }
+        }
+
+        let sessions = sessions.lock().unwrap();
+        for (i, f) in enc.fighters.iter().enumerate() {
+            let mut win = enc.update(format!("{} was defeated!", enc.enemy.title()));
+            win.turn = String::new();
+            win.win = Some(LootData {
+                items: loot[i].iter().map(|item| item.as_data()).collect(),
+                exp: exp[i],
+                gold: gold[i],
+                consumables: drops[i].iter().map(|c| c.name.clone())
+                    .chain(extras[i].iter().flat_map(|l| match &l.item {
+                        LootItem::Consumable { name, .. } => vec![name.clone(); l.amount as usize],
+                        LootItem::Material { .. } => Vec::new(),
+                    }))
+                    .collect(),
+                materials: extras[i].iter().filter_map(|l| match &l.item {
+                    LootItem::Material { name, .. } => Some(format!("{} {}", l.amount, name)),
+                    LootItem::Consumable { .. } => None,
+                }).collect(),
+            });
+            sessions.send(&f.uuid, SessionMessage::Encounter(win));
+            if discovered[i] {
+                sessions.send(&f.uuid, SessionMessage::Event(
+                    format!("The {} was added to your bestiary, type 'bestiary' to see it", enc.enemy.race)));
+            }
+            if left_behind[i] > 0 {
+                sessions.send(&f.uuid, SessionMessage::Event(
+                    format!("Your inventory is full, {} items were left behind! 'inv expand' buys more space", left_behind[i])));
+            }
+            for item in loot[i].iter().filter(|item| item.rarity == ItemRarity::Legendary) {
+                sessions.announce(BroadcastKind::Loot, format!("{} found a Legendary item: {}!", f.name, item.name));
+            }
+        }
+
+        let mut events = Vec::new();
+        for (i, f) in enc.fighters.iter().enumerate() {
+            events.push((f.uuid.clone(), GameEvent::Kill { race: enc.enemy.race.clone(), boss: enc.enemy.boss }));
+            events.extend(loot[i].iter().map(|item| (f.uuid.clone(), GameEvent::FindItem(item.item_type, item.rarity))));
+        }
+        events
+    }
+}
+
+/// Starts a fight for a player and every online member of their party that is not already fighting
+pub fn start(state: &ServerState, uuid: &Uuid) {
+    let ServerState { db, sessions, parties, encounters, duels, classes, bestiary, .. } = state;
+    let members = parties.lock().unwrap().members_of(uuid);
+    let fighters = {
+        let mut encounters = encounters.lock().unwrap();
+        if encounters.in_fight(uuid) {
+            return;
+        }
+
+        // the player who found the enemy goes first
+        let mut joining = vec![uuid.clone()];
+        {
+            let duels = duels.lock().unwrap();
+            if duels.in_duel(uuid) {
+                return;
+            }
+            joining.extend(members.into_iter().filter(|m| m != uuid && !encounters.in_fight(m) && !duels.in_duel(m)));
+        }
+        let mut fighters = {
+            let sessions = sessions.lock().unwrap();
+            joining.iter()
+                .filter_map(|m| sessions.get(m).map(|s| Fighter {
+                    uuid: m.clone(), name: s.username.clone(), damage_dealt: 0, effects: StatusEffects::default(),
+                    stats: ClassStats::default(), cooldowns: HashMap::new(),
+                }))
+                .collect::<Vec<Fighter>>()
+        };
+        if fighters.is_empty() {
+            return;
+        }
+
+        let (levels, region) = {
+            let db = db.lock().unwrap();
+            for f in fighters.iter_mut() {
+                f.stats = classes.stats(&db, &f.uuid);
+            }
+            (fighters.iter().map(|f| db.get_player_level(&f.uuid).unwrap_or(1)).sum::<u32>(),
+             db.get_player_region(uuid).unwrap_or_default())
+        };
+        let party_size = fighters.len() as u32;
+        let template = bestiary.pick(region.as_str(), levels / party_size);
+        let enemy = Enemy::new_rand(template, levels / party_size, party_size);
+        info!(target:LOG_TARGET, "{} encountered {} (level {})",
+            fighters.iter().map(|f| f.name.as_str()).collect::<Vec<&str>>().join(", "), enemy.title(), enemy.level);
+
+        let id = encounters.next_id;
+        encounters.next_id += 1;
+        for f in &fighters {
+            encounters.by_player.insert(f.uuid.clone(), id);
+        }
+        let enc = Encounter { enemy, enemy_effects: StatusEffects::default(), fighters, turn: 0, turn_started: Instant::now(), party_size };
+        let mut data = enc.update(format!("A level {} {} appeared!", enc.enemy.level, enc.enemy.title()));
+        data.start = true;
+        send(sessions, &enc.uuids(), data);
+        // everyone online hears about bosses
+        if enc.enemy.boss {
+            sessions.lock().unwrap().announce(BroadcastKind::WorldBoss, format!("{} found a level {} {} in the {}!",
+                enc.fighters.iter().map(|f| f.name.as_str()).collect::<Vec<&str>>().join(", "), enc.enemy.level, enc.enemy.title(), region));
+        }
+        let fighters = enc.uuids();
+        encounters.encounters.insert(id, enc);
+        fighters
+    };
+    party::refresh(state, &fighters);
+}
+
+/// Attacks the enemy if it is the player's turn.
+/// Returns an error to show the player if they can not attack
+pub fn attack(state: &ServerState, config: &ServerConfig, uuid: &Uuid) -> Result<(), String> {
+    strike(state, config, uuid, None)
+}
+
+/// Attacks the enemy on the player's turn, harder if it is done with a strike skill and their rank in it
+fn strike(state: &ServerState, config: &ServerConfig, uuid: &Uuid, skill: Option<(&Skill, u32)>) -> Result<(), String> {
+    let ServerState { db, sessions, encounters, .. } = state;
+    let (power, abilities) = {
+        let db = db.lock().unwrap();
+        (attack_power(&db, uuid), abilities(&db, uuid))
+    };
+    let (fighters, events) = {
+        let mut encounters = encounters.lock().unwrap();
+        let id = current_turn(&encounters, uuid)?;
+        let fighters = encounters.encounters.get(&id).unwrap().uuids();
+
+        let enc = encounters.encounters.get_mut(&id).unwrap();
+        let turn = enc.turn;
+        let fighter = &mut enc.fighters[turn];
+        let mut boost = 0;
+        if let Some((skill, rank)) = skill {
+            fighter.ready(skill)?;
+            fighter.used(skill);
+            if let SkillEffect::Strike(amount) = skill.effect {
+                boost += amount * rank;
+            }
+        }
+        let mut abilities = abilities;
+        abilities.crit += fighter.stats.crit;
+        abilities.lifesteal += fighter.stats.lifesteal;
+        let damage = fighter.effects.outgoing(roll(power) * fighter.stats.attack / 100) * (100 + boost) / 100;
+        let (damage, critical) = crit(damage, &abilities);
+        let damage = (damage * (100 - enc.enemy_effects.amount(StatusKind::Shield).min(100)) / 100).max(1).min(enc.enemy.health);
+        enc.enemy.health -= damage;
+        enc.fighters[turn].damage_dealt = enc.fighters[turn].damage_dealt.saturating_add(damage);
+        let mut message = match skill {
+            Some((skill, _)) => format!("{} used {} on {} for {} damage{}", enc.fighters[turn].name, skill.name, enc.enemy.title(), damage,
+                                        if critical { ", a critical hit!" } else { "!" }),
+            None => format!("{} hit {} for {} damage{}", enc.fighters[turn].name, enc.enemy.title(), damage,
+                            if critical { ", a critical hit!" } else { "!" }),
+        };
+        let healed = health::heal(&db.lock().unwrap(), uuid, damage * abilities.lifesteal / 100);
+        if healed > 0 {
+            message.push_str(format!(" They stole {} health.", healed).as_str());
+            sessions.lock().unwrap().send(uuid, SessionMessage::StatsChanged);
+        }
+        if enc.enemy.health > 0 {
+            let mut inflicted = Vec::new();
+            if abilities.poison > 0 && damage > 0 {
+                inflicted.push(StatusEffect::new(StatusKind::Poison, (damage * abilities.poison / 100).max(1), VENOM_TURNS));
+            }
+            if abilities.stun > 0 && thread_rng().gen_range(0..100) < abilities.stun {
+                inflicted.push(StatusEffect::new(StatusKind::Stun, 0, 1));
+            }
+            for effect in inflicted {
+                if enc.enemy_effects.apply(effect) {
+                    message.push_str(format!(" {} {}!", enc.enemy.title(), effect.describe()).as_str());
+                }
+            }
+        }
+        let mut data = enc.update(message);
+        data.attk = Some(damage);
+
+        if enc.enemy.health == 0 {
+            data.turn = String::new();
+            send(sessions, &fighters, data);
+            let events = encounters.win(id, config, &state.consumables, db, sessions);
+            (fighters, events)
+        } else {
+            data.turn = enc.next_turn_name();
+            send(sessions, &fighters, data);
+            encounters.end_turn(id, true, config, db, sessions);
+            (fighters, Vec::new())
+        }
+    };
+    party::refresh(state, &fighters);
+    for (player, event) in events {
+        game_event::fire(state, &player, event);
+    }
+    Ok(())
+}
+
+/// Tries to flee from the fight if it is the player's turn.
+/// Returns an error to show the player if they can not flee
+pub fn flee(state: &ServerState, config: &ServerConfig, uuid: &Uuid) -> Result<(), String> {
+    let ServerState { db, sessions, encounters, .. } = state;
+    let (fighters, escaped) = {
+        let mut encounters = encounters.lock().unwrap();
+        let id = current_turn(&encounters, uuid)?;
+        let fighters = encounters.encounters.get(&id).unwrap().uuids();
+
+        let enc = encounters.encounters.get(&id).unwrap();
+        let name = enc.fighters[enc.turn].name.clone();
+        let chance = (config.party_flee_chance as i32 + enc.fighters[enc.turn].stats.flee).clamp(0, 100);
+        let escaped = thread_rng().gen_range(0..100) < chance;
+        if escaped {
+            let mut fled = enc.update(format!("You fled from {}!", enc.enemy.title()));
+            fled.flee = Some(true);
+            fled.turn = String::new();
+            send(sessions, &[uuid.clone()], fled);
+            leave_fight(&mut encounters, id, uuid, format!("{} fled from the fight!", name), config, db, sessions);
+        } else {
+            let mut failed = enc.update(format!("{} tried to flee but {} blocked the way!", name, enc.enemy.title()));
+            failed.flee = Some(false);
+            failed.turn = enc.next_turn_name();
+            send(sessions, &fighters, failed);
+            encounters.end_turn(id, true, config, db, sessions);
+        }
+        (fighters, escaped)
+    };
+    party::refresh(state, &fighters);
+    if escaped {
+        game_event::fire(state, uuid, GameEvent::Flee);
+    }
+    Ok(())
+}
+
+/// Uses a consumable on the player's turn, which ends it unless it gets them out of the fight.
+/// Returns an error to show the player if they can not use it
+pub fn use_consumable(state: &ServerState, config: &ServerConfig, uuid: &Uuid, consumable: &Consumable) -> Result<(), String> {
+    let ServerState { db, sessions, encounters, .. } = state;
+    let (fighters, escaped) = {
+        let mut encounters = encounters.lock().unwrap();
+        let id = current_turn(&encounters, uuid)?;
+        let fighters = encounters.encounters.get(&id).unwrap().uuids();
+        let healed = {
+            let db = db.lock().unwrap();
+            if let Effect::Heal(_) = consumable.effect {
+                if db.get_player_health(uuid) >= db.get_player_max_health(uuid) {
+                    return Err(format!("You are already at full health"));
+                }
+            }
+            if !db.take_consumable(uuid, consumable.id.as_str()) {
+                return Err(format!("You do not have any {}", consumable.name));
+            }
+            match consumable.effect {
+                Effect::Heal(amount) => health::heal(&db, uuid, amount),
+                _ => 0,
+            }
+        };
+
+        let enc = encounters.encounters.get_mut(&id).unwrap();
+        let turn = enc.turn;
+        let name = enc.fighters[turn].name.clone();
+        let message = match consumable.effect {
+            Effect::Heal(_) => format!("{} used {} and healed {} health!", name, consumable.name, healed),
+            Effect::AttackBuff { percent, turns } => {
+                enc.fighters[turn].effects.apply(StatusEffect::new(StatusKind::Strength, percent, turns));
+                format!("{} used {} and deals {}% more damage for {} rounds!", name, consumable.name, percent, turns)
+            }
+            Effect::DefenseBuff { percent, turns } => {
+                enc.fighters[turn].effects.apply(StatusEffect::new(StatusKind::Shield, percent, turns));
+                format!("{} used {} and takes {}% less damage for {} rounds!", name, consumable.name, percent, turns)
+            }
+            Effect::Flee => String::new(),
+        };
+
+        let escaped = matches!(consumable.effect, Effect::Flee);
+        if escaped {
+            let mut fled = enc.update(format!("You used {} and escaped from {}!", consumable.name, enc.enemy.title()));
+            fled.flee = Some(true);
+            fled.turn = String::new();
+            send(sessions, &[uuid.clone()], fled);
+            leave_fight(&mut encounters, id, uuid, format!("{} used {} and escaped from the fight!", name, consumable.name),
+                        config, db, sessions);
+        } else {
+            let mut data = enc.update(message);
+            data.turn = enc.next_turn_name();
+            send(sessions, &fighters, data);
+            encounters.end_turn(id, true, config, db, sessions);
+        }
+        (fighters, escaped)
+    };
+    sessions.lock().unwrap().send(uuid, SessionMessage::StatsChanged);
+    party::refresh(state, &fighters);
+    if escaped {
+        game_event::fire(state, uuid, GameEvent::Flee);
+    }
+    Ok(())
+}
+
+/// Uses an active skill on the player's turn, which ends it.
+/// Returns an error to show the player if they can not use it
+pub fn use_skill(state: &ServerState, config: &ServerConfig, uuid: &Uuid, name: &str) -> Result<(), String> {
+    let ServerState { db, sessions, encounters, .. } = state;
+    if !encounters.lock().unwrap().in_fight(uuid) {
+        return Err(format!("You can only use skills in a fight"));
+    }
+    let (skill, rank) = state.classes.active_skill(&db.lock().unwrap(), uuid, name)?;
+    if let SkillEffect::Strike(_) = skill.effect {
+        return strike(state, config, uuid, Some((skill, rank)));
+    }
+
+    let fighters = {
+        let mut encounters = encounters.lock().unwrap();
+        let id = current_turn(&encounters, uuid)?;
+        let fighters = encounters.encounters.get(&id).unwrap().uuids();
+        let enc = encounters.encounters.get_mut(&id).unwrap();
+        let turn = enc.turn;
+        enc.fighters[turn].ready(skill)?;
+        let name = enc.fighters[turn].name.clone();
+        let message = match skill.effect {
+            SkillEffect::Heal(amount) => {
+                let healed = {
+                    let db = db.lock().unwrap();
+                    let max = db.get_player_max_health(uuid).unwrap_or(0);
+                    if db.get_player_health(uuid) >= Some(max) {
+                        return Err(format!("You are already at full health"));
+                    }
+                    health::heal(&db, uuid, max * amount * rank / 100)
+                };
+                format!("{} used {} and healed {} health!", name, skill.name, healed)
+            }
+            SkillEffect::AttackBuff { percent, turns } => {
+                enc.fighters[turn].effects.apply(StatusEffect::new(StatusKind::Strength, percent * rank, turns));
+                format!("{} used {} and deals {}% more damage for {} rounds!", name, skill.name, percent * rank, turns)
+            }
+            SkillEffect::DefenseBuff { percent, turns } => {
+                let percent = (percent * rank).min(90);
+                enc.fighters[turn].effects.apply(StatusEffect::new(StatusKind::Shield, percent, turns));
+                format!("{} used {} and takes {}% less damage for {} rounds!", name, skill.name, percent, turns)
+            }
+            _ => return Err(format!("{} is always on, it does not need to be used", skill.name)),
+        };
+        enc.fighters[turn].used(skill);
+
+        let mut data = enc.update(message);
+        data.turn = enc.next_turn_name();
+        send(sessions, &fighters, data);
+        encounters.end_turn(id, true, config, db, sessions);
+        fighters
+    };
+    sessions.lock().unwrap().send(uuid, SessionMessage::StatsChanged);
+    party::refresh(state, &fighters);
+    Ok(())
+}
+
+/// Skips the turn of a player who took too long so the rest of their party is not kept waiting,
+/// called regularly from every fighting player's handler. Players fighting alone are never skipped
+pub fn check_turn_timeout(state: &ServerState, config: &ServerConfig, uuid: &Uuid) {
+    let ServerState { db, sessions, encounters, .. } = state;
+    let mut encounters = encounters.lock().unwrap();
+    let id = match encounters.by_player.get(uuid) {
+        Some(id) => *id,
+        None => return,
+    };
+    let enc = encounters.encounters.get(&id).unwrap();
+    if enc.fighters.len() <= 1 || enc.turn_started.elapsed() < Duration::from_secs(config.party_turn_timeout) {
+        return;
+    }
+    let mut skipped = enc.update(format!("{} took too long and lost their turn", enc.fighters[enc.turn].name));
+    skipped.turn = enc.next_turn_name();
+    send(sessions, &enc.uuids(), skipped);
+    encounters.end_turn(id, true, config, db, sessions);
+}
+
+/// Takes a player that disconnected out of their fight
+pub fn disconnect(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str) {
+    let ServerState { db, sessions, encounters, .. } = state;
+    let mut encounters = encounters.lock().unwrap();
+    if let Some(id) = encounters.by_player.get(uuid).cloned() {
+        leave_fight(&mut encounters, id, uuid, format!("{} left the fight!", username), config, db, sessions);
+    }
+}
+
+/// the id of the fight the player is in, if it is their turn
+fn current_turn(encounters: &EncounterRegistry, uuid: &Uuid) -> Result<u32, String> {
+    let id = *encounters.by_player.get(uuid).ok_or(format!("You are not in a fight"))?;
+    let enc = encounters.encounters.get(&id).unwrap();
+    let current = &enc.fighters[enc.turn];
+    if current.uuid != *uuid {
+        return Err(format!("It is {}'s turn", current.name));
+    }
+    Ok(id)
+}
+
+fn leave_fight(encounters: &mut EncounterRegistry, id: u32, uuid: &Uuid, message: String, config: &ServerConfig,
+               db: &Arc<Mutex<Database>>, sessions: &Arc<Mutex<SessionRegistry>>) {
+    let was_turn = encounters.remove_fighter(id, uuid);
+    if encounters.encounters.get(&id).unwrap().fighters.is_empty() {
+        encounters.end(id);
+        return;
+    }
+    if was_turn {
+        encounters.end_turn(id, false, config, db, sessions);
+    }
+    if let Some(enc) = encounters.encounters.get(&id) {
+        send(sessions, &enc.uuids(), enc.update(message));
+    }
+}
+
+/// the items a player fights with, their best sword and their best armor of each type
+pub fn equipped(db: &Database, uuid: &Uuid) -> Vec<Item> {
+    let mut best: HashMap<u32, Item> = HashMap::new();
+    for item in db.get_player_items(uuid).unwrap_or_default() {
+        let stat = |i: &Item| if i.item_type == ItemType::Sword { i.damage } else { i.defense };
+        match best.get(&item.item_type.into()) {
+            Some(current) if stat(current) >= stat(&item) => {}
+            _ => { best.insert(item.item_type.into(), item); }
+        }
+    }
+    best.into_values().collect()
+}
+
+/// the special abilities of the items a player fights with
+pub fn abilities(db: &Database, uuid: &Uuid) -> Abilities {
+    Abilities::of(&equipped(db, uuid))
+}
+
+/// the base damage of a player's attack from their level and best sword
+pub fn attack_power(db: &Database, uuid: &Uuid) -> u32 {
+    let level = db.get_player_level(uuid).unwrap_or(1);
+    let sword = equipped(db, uuid).iter()
+        .filter(|i| i.item_type == ItemType::Sword)
+        .map(|i| i.damage)
+        .sum::<u32>();
+    level.saturating_mul(2).saturating_add(2).saturating_add(sword)
+}
+
+/// the total defense of the best armor a player has of each type
+pub fn defense(db: &Database, uuid: &Uuid) -> u32 {
+    equipped(db, uuid).iter()
+        .filter(|i| i.item_type != ItemType::Sword)
+        .map(|i| i.defense)
+        .sum()
+}
+
+/// doubles the damage of an attack if the player's crit chance hits.
+/// Returns the damage and if it was a critical hit
+pub fn crit(damage: u32, abilities: &Abilities) -> (u32, bool) {
+    if abilities.crit > 0 && thread_rng().gen_range(0..100) < abilities.crit {
+        (damage * 2, true)
+    } else {
+        (damage, false)
+    }
+}
+
+/// varies damage by around 20% either way
+pub fn roll(base: u32) -> u32 {
+    match Normal::new(base as f32, base as f32 * 0.2) {
+        Ok(normal) => normal.sample(&mut thread_rng()).round().max(1.0) as u32,
+        Err(_) => base.max(1),
+    }
+}
+
+fn send(sessions: &Arc<Mutex<SessionRegistry>>, players: &[Uuid], data: EncounterData) {
+    let sessions = sessions.lock().unwrap();
+    for p in players {
+        sessions.send(p, SessionMessage::Encounter(data.clone()));
+    }
+}
@@ -0,0 +1,141 @@
+use rand::{Rng, thread_rng};
+use rand::seq::SliceRandom;
+use rand_distr::{Normal, Distribution};
+use snd_network_lib::enemy_data::EnemyData;
+use snd_network_lib::status_data::StatusKind;
+use crate::bestiary::{EnemyTemplate, Loot, Move};
+use crate::status::StatusEffect;
+
+/// the percent chance that an enemy is a boss
+const BOSS_CHANCE: u32 = 5;
+
+/// Something an enemy can do when it attacks, putting a status effect on the player it hits.
+/// Strength and shield effects are put on the enemy itself instead
+#[derive(Clone, Debug)]
+pub struct EnemyAbility {
+    pub name: String,
+    pub kind: StatusKind,
+    /// for poison the percent of the enemy's damage it deals each round, otherwise the percent of the effect
+    pub amount: u32,
+    pub turns: u32,
+    /// the percent chance it is used with each attack, bosses use it twice as often
+    pub chance: u32,
+}
+
+impl EnemyAbility {
+    /// if the effect goes on the enemy rather than the player it hits
+    pub fn on_self(&self) -> bool {
+        matches!(self.kind, StatusKind::Strength | StatusKind::Shield)
+    }
+
+    /// the effect it puts on someone when used by an enemy that deals this much damage
+    pub fn effect(&self, damage: u32) -> StatusEffect {
+        let amount = match self.kind {
+            StatusKind::Poison => (damage * self.amount / 100).max(1),
+            _ => self.amount,
+        };
+        StatusEffect::new(self.kind, amount, self.turns)
+    }
+
+    /// what the ability does, shown in the bestiary
+    pub fn describe(&self) -> String {
+        let effect = match self.kind {
+            StatusKind::Poison => format!("poisons for {}% of its damage each round", self.amount),
+            StatusKind::Bleed => format!("causes bleeding for {}% of max health each round", self.amount),
+            StatusKind::Stun => format!("stuns"),
+            StatusKind::Weaken => format!("makes you deal {}% less damage", self.amount),
+            StatusKind::Strength => format!("makes it deal {}% more damage", self.amount),
+            StatusKind::Shield => format!("makes it take {}% less damage", self.amount),
+        };
+        let turns = match self.turns {
+            1 => format!("a round"),
+            turns => format!("{} rounds", turns),
+        };
+        format!("{}: {}% chance, {} for {}", self.name, self.chance, effect, turns)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Enemy {
+    pub name: String,
+    pub race: String,
+    pub level: u32,
+    pub health: u32,
+    pub max_health: u32,
+    pub damage: u32,
+    /// bosses are a few levels higher with much more health
+    pub boss: bool,
+    pub ability: Option<EnemyAbility>,
+    /// what it does each round in order, starting over at the end
+    pattern: Vec<Move>,
+    /// where it is in its pattern
+    step: usize,
+    /// the extra drops every player who defeats it rolls for
+    pub loot: Vec<Loot>,
+}
+
+impl Enemy {
+    /// generates an enemy of a kind around a level, made tougher for each extra player it has to fight
+    pub fn new_rand(template: &EnemyTemplate, around_level: u32, players: u32) -> Self {
+        let mut rng = thread_rng();
+        let normal = Normal::new(around_level as f32, 1.5)
+            .expect("Failed to create Normal Distribution for enemy generation.");
+        let boss = rng.gen_range(0..100) < BOSS_CHANCE;
+        let level = template.clamp_level(normal.sample(&mut rng).round().max(1.0) as u32) + if boss { 3 } else { 0 };
+
+        let base_health = template.health_at(level) * if boss { 3 } else { 1 };
+        // each extra player adds half of the base health so parties still have to work for it
+        let max_health = base_health + base_health * players.saturating_sub(1) / 2;
+
+        Self {
+            name: template.names.choose(&mut rng).unwrap().to_string(),
+            race: template.race.clone(),
+            level,
+            health: max_health,
+            max_health,
+            damage: template.damage_at(level) + rng.gen_range(0..=3),
+            boss,
+            ability: template.ability.clone(),
+            pattern: template.pattern.clone(),
+            step: 0,
+            loot: template.loot.clone(),
+        }
+    }
+
+    /// what the enemy does this round, moving on to the next step of its pattern
+    pub fn next_move(&mut self) -> Move {
+        let step = self.pattern.get(self.step % self.pattern.len().max(1)).copied().unwrap_or(Move::Attack);
+        self.step += 1;
+        step
+    }
+
+    /// rolls if the enemy uses its ability with this attack, it always does if `forced` by its pattern
+    pub fn use_ability(&self, forced: bool) -> Option<&EnemyAbility> {
+        let ability = self.ability.as_ref()?;
+        let chance = if self.boss { ability.chance * 2 } else { ability.chance };
+        (forced || thread_rng().gen_range(0..100) < chance).then_some(ability)
+    }
+
+    /// the race shown to players, e.g. `Goblin` or `Goblin Boss`
+    pub fn display_race(&self) -> String {
+        match self.boss {
+            true => format!("{} Boss", self.race),
+            false => self.race.clone(),
+        }
+    }
+
+    /// the name and race of the enemy, e.g. `Grubnak the Goblin`
+    pub fn title(&self) -> String {
+        format!("{} the {}", self.name, self.display_race())
+    }
+
+    pub fn as_data(&self) -> EnemyData {
+        EnemyData {
+            name: self.name.clone(),
+            race: self.display_race(),
+            level: self.level,
+            health: self.health,
+            max_health: self.max_health,
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use uuid::Uuid;
+use crate::achievement;
+use crate::item::{ItemRarity, ItemType};
+use crate::quest;
+use crate::state::ServerState;
+
+/// Something that happened to a player which could unlock an achievement or count towards a quest
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum GameEvent {
+    Step,
+    FindItem(ItemType, ItemRarity),
+    /// the player helped defeat an enemy of the race
+    Kill { race: String, boss: bool },
+    Flee,
+    DuelWin,
+}
+
+/// Lets the achievements and quests know about the event.
+/// No locks can be held when this is called
+pub fn fire(state: &ServerState, uuid: &Uuid, event: GameEvent) {
+    achievement::trigger(state, uuid, &event);
+    quest::progress(state, uuid, &event);
+}
@@ -0,0 +1,344 @@
+use log::info;
+use uuid::Uuid;
+use snd_network_lib::guild_data::{GuildActionData, GuildActionKind, GuildData, GuildMemberData, GuildRank};
+use snd_network_lib::inventory_data::InventoryData;
+use snd_network_lib::systime;
+use crate::bank;
+use crate::config::ServerConfig;
+use crate::database::{Database, PlayerValueDB};
+use crate::inventory;
+use crate::mail;
+use crate::session::{SessionMessage, SessionRegistry};
+use crate::state::ServerState;
+
+const LOG_TARGET: &str = "guild";
+/// the shortest and longest a guild's name can be
+const NAME_LENGTH: (usize, usize) = (3, 24);
+
+/// A group of players that share a bank and a chat channel, unlike parties they last while their members are offline
+#[derive(Clone, Debug)]
+pub struct Guild {
+    pub id: Uuid,
+    pub name: String,
+    /// when it was started, in seconds since the unix epoch
+    pub created: u64,
+}
+
+/// Checks a name for a new guild, returning it with its spaces tidied up or an error to show the player
+fn check_name(name: &str) -> Result<String, String> {
+    let name = name.split_whitespace().collect::<Vec<&str>>().join(" ");
+    let length = name.chars().count();
+    if length < NAME_LENGTH.0 || length > NAME_LENGTH.1 {
+        return Err(format!("Guild names must be {} to {} characters long", NAME_LENGTH.0, NAME_LENGTH.1));
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == ' ') {
+        return Err(format!("Guild names can only have letters, numbers and spaces"));
+    }
+    Ok(name)
+}
+
+/// the guild a player is in and their rank, or an error to show them if they are not in one
+fn membership(db: &Database, uuid: &Uuid) -> Result<(Guild, GuildRank), String> {
+    db.get_player_guild(uuid).ok_or(format!("You are not in a guild"))
+}
+
+/// finds another member of the player's guild by their username
+fn find_member(db: &Database, guild: &Guild, username: &str) -> Result<(Uuid, GuildRank), String> {
+    let uuid = db.uuid_from_username(username.replace("'", ""))
+        .ok_or(format!("No player named '{}' exists", username))?;
+    db.get_guild_members(guild).into_iter().find(|(m, _)| *m == uuid)
+        .ok_or(format!("{} is not in your guild", username))
+}
+
+/// tells every online member of a guild something, and has their clients show their stats again if `stats_changed`
+fn notify_members(db: &Database, sessions: &SessionRegistry, guild: &Guild, message: String, stats_changed: bool) {
+    for (member, _) in db.get_guild_members(guild) {
+        sessions.send(&member, SessionMessage::Event(message.clone()));
+        if stats_changed {
+            sessions.send(&member, SessionMessage::StatsChanged);
+        }
+    }
+}
+
+/// A guild as the player sees it. `name` picks another guild to look at, otherwise the player's own guild is shown.
+/// A player that is not in a guild sees an empty guild with the invites they have
+pub fn view(state: &ServerState, uuid: &Uuid, name: &str) -> GuildData {
+    let db = state.db.lock().unwrap();
+    let sessions = state.sessions.lock().unwrap();
+    let own = db.get_player_guild(uuid);
+    let guild = if name.is_empty() { own.clone().map(|(g, _)| g) } else { db.get_guild_by_name(name) };
+    let mut data = GuildData {
+        name: String::new(),
+        members: Vec::new(),
+        rank: GuildRank::Member,
+        invites: db.get_guild_invites(uuid).into_iter().map(|g| g.name).collect(),
+    };
+    if let Some(guild) = guild {
+        data.members = db.get_guild_members(&guild).iter().map(|(m, rank)| GuildMemberData {
+            name: db.get_player_value(m, PlayerValueDB::Username).unwrap_or_default(),
+            rank: *rank,
+            level: db.get_player_level(m).unwrap_or(1),
+            online: sessions.is_online(m),
+        }).collect();
+        if let Some((_, rank)) = own.filter(|(g, _)| g.id == guild.id) {
+            data.rank = rank;
+        }
+        data.name = guild.name;
+    }
+    data
+}
+
+/// the items in the bank of the player's guild, empty if they are not in one
+pub fn bank_view(db: &Database, config: &ServerConfig, uuid: &Uuid) -> InventoryData {
+    let items = db.get_player_guild(uuid).map(|(g, _)| db.get_guild_bank_items(&g)).unwrap_or_default();
+    InventoryData {
+        count: items.len() as u32,
+        items: items.iter().map(|i| i.as_data()).collect(),
+        capacity: config.guild_bank_capacity,
+    }
+}
+
+/// Handles a guild action from a player.
+/// Returns what happened to show the player, or an error if the action could not be done
+pub fn handle_action(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str, action: &GuildActionData) -> Result<Option<String>, String> {
+    let target = action.target.trim();
+    match action.kind {
+        GuildActionKind::Info => {
+            if !target.is_empty() && state.db.lock().unwrap().get_guild_by_name(target).is_none() {
+                return Err(format!("There is no guild called '{}'", target));
+            }
+            Ok(None)
+        }
+        GuildActionKind::Create => create(state, config, uuid, username, target).map(Some),
+        GuildActionKind::Invite => invite(state, config, uuid, username, target).map(Some),
+        GuildActionKind::Accept => accept(state, config, uuid, username, target).map(Some),
+        GuildActionKind::Decline => decline(state, uuid, username, target).map(Some),
+        GuildActionKind::Leave => leave(state, uuid, username).map(Some),
+        GuildActionKind::Kick => kick(state, uuid, username, target).map(Some),
+        GuildActionKind::Promote => set_rank(state, uuid, username, target, true).map(Some),
+        GuildActionKind::Demote => set_rank(state, uuid, username, target, false).map(Some),
+        GuildActionKind::Disband => disband(state, uuid, username).map(Some),
+        GuildActionKind::Bank => {
+            bank::check_access(state, config, uuid)?;
+            membership(&state.db.lock().unwrap(), uuid)?;
+            Ok(None)
+        }
+        GuildActionKind::Deposit => deposit(state, config, uuid, username, target).map(Some),
+        GuildActionKind::Withdraw => withdraw(state, config, uuid, username, target).map(Some),
+    }
+}
+
+fn create(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str, name: &str) -> Result<String, String> {
+    let name = check_name(name)?;
+    let db = state.db.lock().unwrap();
+    if let Some((guild, _)) = db.get_player_guild(uuid) {
+        return Err(format!("You are already in {}, leave it first", guild.name));
+    }
+    if db.get_guild_by_name(name.as_str()).is_some() {
+        return Err(format!("There is already a guild called '{}'", name));
+    }
+    let gold = db.get_player_gold(uuid).unwrap_or(0);
+    if gold < config.guild_create_cost {
+        return Err(format!("Starting a guild costs {} gold but you only have {}", config.guild_create_cost, gold));
+    }
+    let guild = Guild { id: Uuid::new_v4(), name, created: systime().as_secs() };
+    if !db.create_guild(&guild, uuid, config.guild_create_cost) {
+        return Err(format!("Failed to start {}", guild.name));
+    }
+    state.sessions.lock().unwrap().send(uuid, SessionMessage::StatsChanged);
+    info!(target:LOG_TARGET, "{} started the guild {}", username, guild.name);
+    Ok(format!("You started {} for {} gold, invite players with 'guild invite <user>'", guild.name, config.guild_create_cost))
+}
+
+fn invite(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str, target: &str) -> Result<String, String> {
+    let db = state.db.lock().unwrap();
+    let (guild, rank) = membership(&db, uuid)?;
+    if rank < GuildRank::Officer {
+        return Err(format!("Only officers and the leader can invite players"));
+    }
+    let invited = db.uuid_from_username(target.replace("'", ""))
+        .ok_or(format!("No player named '{}' exists", target))?;
+    if db.get_player_guild(&invited).is_some() {
+        return Err(format!("{} is already in a guild", target));
+    }
+    if db.count_guild_members(&guild) >= config.guild_max_members {
+        return Err(format!("{} is full, it can only have {} members", guild.name, config.guild_max_members));
+    }
+    if !db.invite_to_guild(&guild, &invited) {
+        return Err(format!("Failed to invite {}", target));
+    }
+    // players who are offline find the invite in their mailbox
+    mail::notify(&db, &state.sessions.lock().unwrap(), &invited,
+                 format!("{} invited you to join {}! Use 'guild accept {}' or 'guild decline {}'", username, guild.name, guild.name, guild.name));
+    info!(target:LOG_TARGET, "{} invited {} to {}", username, target, guild.name);
+    Ok(format!("You invited {} to {}", target, guild.name))
+}
+
+/// the guild an invite is from, `name` can be left empty when the player only has one invite
+fn find_invite(db: &Database, uuid: &Uuid, name: &str) -> Result<Guild, String> {
+    let mut invites = db.get_guild_invites(uuid);
+    if name.is_empty() {
+        return match invites.len() {
+            0 => Err(format!("You do not have a guild invite")),
+            1 => Ok(invites.remove(0)),
+            _ => Err(format!("You have invites from {}, say which one", invites.iter().map(|g| g.name.clone()).collect::<Vec<String>>().join(", "))),
+        };
+    }
+    invites.into_iter().find(|g| g.name.eq_ignore_ascii_case(name))
+        .ok_or(format!("You do not have an invite from '{}'", name))
+}
+
+fn accept(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str, name: &str) -> Result<String, String> {
+    let db = state.db.lock().unwrap();
+    if let Some((guild, _)) = db.get_player_guild(uuid) {
+        return Err(format!("You are already in {}, leave it first", guild.name));
+    }
+    let guild = find_invite(&db, uuid, name)?;
+    if db.count_guild_members(&guild) >= config.guild_max_members {
+        return Err(format!("{} is full", guild.name));
+    }
+    if !db.join_guild(&guild, uuid) {
+        return Err(format!("Failed to join {}", guild.name));
+    }
+    notify_members(&db, &state.sessions.lock().unwrap(), &guild, format!("{} joined {}", username, guild.name), false);
+    state.sessions.lock().unwrap().send(uuid, SessionMessage::StatsChanged);
+    info!(target:LOG_TARGET, "{} joined {}", username, guild.name);
+    Ok(format!("Welcome to {}! Type 'gc <message>' to talk to your guild", guild.name))
+}
+
+fn decline(state: &ServerState, uuid: &Uuid, username: &str, name: &str) -> Result<String, String> {
+    let db = state.db.lock().unwrap();
+    let guild = find_invite(&db, uuid, name)?;
+    if !db.remove_guild_invite(&guild, uuid) {
+        return Err(format!("Failed to decline the invite from {}", guild.name));
+    }
+    info!(target:LOG_TARGET, "{} declined the invite from {}", username, guild.name);
+    Ok(format!("You declined the invite from {}", guild.name))
+}
+
+fn leave(state: &ServerState, uuid: &Uuid, username: &str) -> Result<String, String> {
+    let db = state.db.lock().unwrap();
+    let (guild, rank) = membership(&db, uuid)?;
+    if rank == GuildRank::Leader {
+        return Err(format!("The leader can not leave, promote an officer to leader with 'guild promote <user>' or use 'guild disband'"));
+    }
+    if !db.leave_guild(uuid) {
+        return Err(format!("Failed to leave {}", guild.name));
+    }
+    let sessions = state.sessions.lock().unwrap();
+    notify_members(&db, &sessions, &guild, format!("{} left the guild", username), false);
+    sessions.send(uuid, SessionMessage::StatsChanged);
+    info!(target:LOG_TARGET, "{} left {}", username, guild.name);
+    Ok(format!("You left {}", guild.name))
+}
+
+fn kick(state: &ServerState, uuid: &Uuid, username: &str, target: &str) -> Result<String, String> {
+    let db = state.db.lock().unwrap();
+    let (guild, rank) = membership(&db, uuid)?;
+    if rank < GuildRank::Officer {
+        return Err(format!("Only officers and the leader can kick players"));
+    }
+    let (kicked, kicked_rank) = find_member(&db, &guild, target)?;
+    if kicked == *uuid {
+        return Err(format!("You can not kick yourself, use 'guild leave' instead"));
+    }
+    if kicked_rank >= rank {
+        return Err(format!("You can only kick players with a lower rank than you"));
+    }
+    if !db.leave_guild(&kicked) {
+        return Err(format!("Failed to kick {}", target));
+    }
+    let sessions = state.sessions.lock().unwrap();
+    mail::notify(&db, &sessions, &kicked, format!("You were kicked from {} by {}", guild.name, username));
+    sessions.send(&kicked, SessionMessage::StatsChanged);
+    notify_members(&db, &sessions, &guild, format!("{} was kicked from the guild by {}", target, username), false);
+    info!(target:LOG_TARGET, "{} kicked {} from {}", username, target, guild.name);
+    Ok(format!("You kicked {} from {}", target, guild.name))
+}
+
+/// Moves a member up or down a rank, only the leader can do this.
+/// Promoting an officer makes them the leader and the old leader an officer
+fn set_rank(state: &ServerState, uuid: &Uuid, username: &str, target: &str, promote: bool) -> Result<String, String> {
+    let db = state.db.lock().unwrap();
+    let (guild, rank) = membership(&db, uuid)?;
+    if rank != GuildRank::Leader {
+        return Err(format!("Only the leader can change ranks"));
+    }
+    let (member, member_rank) = find_member(&db, &guild, target)?;
+    if member == *uuid {
+        return Err(format!("You can not change your own rank"));
+    }
+    let (done, new_rank) = match (promote, member_rank) {
+        (true, GuildRank::Member) => (db.set_guild_rank(&member, GuildRank::Officer), GuildRank::Officer),
+        (true, _) => (db.transfer_guild(uuid, &member), GuildRank::Leader),
+        (false, GuildRank::Officer) => (db.set_guild_rank(&member, GuildRank::Member), GuildRank::Member),
+        (false, _) => return Err(format!("{} is already a member, the lowest rank", target)),
+    };
+    if !done {
+        return Err(format!("Failed to change the rank of {}", target));
+    }
+    notify_members(&db, &state.sessions.lock().unwrap(), &guild,
+                   format!("{} made {} {} of the guild", username, target, new_rank.to_string().to_ascii_lowercase()), false);
+    info!(target:LOG_TARGET, "{} made {} {} of {}", username, target, new_rank.to_string(), guild.name);
+    Ok(format!("{} is now {} of {}", target, new_rank.to_string().to_ascii_lowercase(), guild.name))
+}
+
+fn disband(state: &ServerState, uuid: &Uuid, username: &str) -> Result<String, String> {
+    let db = state.db.lock().unwrap();
+    let (guild, rank) = membership(&db, uuid)?;
+    if rank != GuildRank::Leader {
+        return Err(format!("Only the leader can disband the guild"));
+    }
+    if db.count_guild_bank_items(&guild) > 0 {
+        return Err(format!("Take everything out of the guild bank before disbanding"));
+    }
+    let members = db.get_guild_members(&guild);
+    if !db.disband_guild(&guild) {
+        return Err(format!("Failed to disband {}", guild.name));
+    }
+    let sessions = state.sessions.lock().unwrap();
+    for (member, _) in members.iter().filter(|(m, _)| m != uuid) {
+        mail::notify(&db, &sessions, member, format!("{} was disbanded by {}", guild.name, username));
+        sessions.send(member, SessionMessage::StatsChanged);
+    }
+    sessions.send(uuid, SessionMessage::StatsChanged);
+    info!(target:LOG_TARGET, "{} disbanded {}", username, guild.name);
+    Ok(format!("You disbanded {}", guild.name))
+}
+
+/// moves one of the player's items into their guild's bank, any member can do this
+fn deposit(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str, item_name: &str) -> Result<String, String> {
+    bank::check_access(state, config, uuid)?;
+    let db = state.db.lock().unwrap();
+    let (guild, _) = membership(&db, uuid)?;
+    let item = db.item_uuid_from_name(item_name.replace("'", ""), uuid)
+        .ok_or(format!("You do not have an item called '{}'", item_name))?;
+    if db.count_guild_bank_items(&guild) >= config.guild_bank_capacity {
+        return Err(format!("The guild bank is full, it can only hold {} items", config.guild_bank_capacity));
+    }
+    if !db.deposit_guild_item(&guild, uuid, &item) {
+        return Err(format!("Failed to deposit '{}'", item_name));
+    }
+    info!(target:LOG_TARGET, "{} put '{}' in the bank of {}", username, item_name, guild.name);
+    Ok(format!("You put '{}' in the guild bank", item_name))
+}
+
+/// moves an item from the guild's bank into the player's inventory, only officers and the leader can do this
+fn withdraw(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str, item_name: &str) -> Result<String, String> {
+    bank::check_access(state, config, uuid)?;
+    let db = state.db.lock().unwrap();
+    let (guild, rank) = membership(&db, uuid)?;
+    if rank < GuildRank::Officer {
+        return Err(format!("Only officers and the leader can take items out of the guild bank"));
+    }
+    let item = db.guild_bank_item_uuid_from_name(item_name.replace("'", ""), &guild)
+        .ok_or(format!("There is no item called '{}' in the guild bank", item_name))?;
+    if inventory::is_full(&db, config, uuid) {
+        return Err(format!("Your inventory is full, make room before taking '{}' out of the guild bank", item_name));
+    }
+    if !db.withdraw_guild_item(&guild, uuid, &item) {
+        return Err(format!("Failed to withdraw '{}'", item_name));
+    }
+    info!(target:LOG_TARGET, "{} took '{}' out of the bank of {}", username, item_name, guild.name);
+    Ok(format!("You took '{}' out of the guild bank", item_name))
+}
@@ -0,0 +1,99 @@
+use std::str::FromStr;
+use log::info;
+use rand::{Rng, thread_rng};
+use rand::seq::SliceRandom;
+use uuid::Uuid;
+use crate::config::ServerConfig;
+use crate::database::Database;
+use crate::player::{BASE_HEALTH, START_REGION};
+
+const LOG_TARGET: &str = "health";
+
+/// How players heal outside of fights
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum RegenMode {
+    /// every few steps the player takes
+    Steps,
+    /// every few seconds the player is online and not fighting
+    Time,
+    /// players only heal by leveling up or being defeated
+    None,
+}
+
+impl FromStr for RegenMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "steps" => Ok(Self::Steps),
+            "time" => Ok(Self::Time),
+            "none" => Ok(Self::None),
+            _ => Err(()),
+        }
+    }
+}
+
+/// heals the player by the regen amount.
+/// Returns true if their health changed
+pub fn regen(db: &Database, config: &ServerConfig, uuid: &Uuid) -> bool {
+    match (db.get_player_health(uuid), db.get_player_max_health(uuid)) {
+        (Some(health), Some(max)) if health < max && config.regen_amount > 0 => db.add_player_health(uuid, config.regen_amount),
+        _ => false,
+    }
+}
+
+/// heals the player without going over their max health.
+/// Returns how much health they actually gained
+pub fn heal(db: &Database, uuid: &Uuid, amount: u32) -> u32 {
+    let before = db.get_player_health(uuid).unwrap_or(0);
+    db.add_player_health(uuid, amount);
+    db.get_player_health(uuid).unwrap_or(0).saturating_sub(before)
+}
+
+/// heals the player if regen is done with steps and they just took one that heals them.
+/// Returns true if their health changed
+pub fn regen_step(db: &Database, config: &ServerConfig, uuid: &Uuid) -> bool {
+    if config.regen_mode != RegenMode::Steps {
+        return false;
+    }
+    match db.get_player_steps(uuid) {
+        Some(steps) if (steps as u64).is_multiple_of(config.regen_interval) => regen(db, config, uuid),
+        _ => false,
+    }
+}
+
+/// Takes the penalties for being defeated from the player and wakes them back up.
+/// Returns what happened to tell the player about it
+pub fn defeat(db: &Database, config: &ServerConfig, uuid: &Uuid, username: &str) -> String {
+    let mut lost = Vec::new();
+    // only exp towards the next level is lost, so players can never lose a level
+    let exp = db.get_player_exp(uuid).unwrap_or(0);
+    let exp_lost = exp * config.death_exp_loss_percent / 100;
+    if exp_lost > 0 && db.set_player_exp(uuid, exp - exp_lost) {
+        lost.push(format!("{} exp", exp_lost));
+    }
+    if thread_rng().gen_range(0..100) < config.death_drop_item_chance {
+        let items = db.get_player_items(uuid).unwrap_or_default();
+        if let Some(item) = items.choose(&mut thread_rng()) {
+            if db.drop_item(item) {
+                lost.push(format!("'{}'", item.name));
+            }
+        }
+    }
+
+    let mut message = if lost.is_empty() {
+        format!("You were defeated")
+    } else {
+        format!("You were defeated and lost {}", lost.join(" and "))
+    };
+    if config.death_reset_region && db.get_player_region(uuid).is_some_and(|r| r != START_REGION) {
+        db.set_player_region(uuid, START_REGION.to_string());
+        message.push_str(format!(", and woke up back in the {}", START_REGION).as_str());
+    }
+    let max = db.get_player_max_health(uuid).unwrap_or(BASE_HEALTH);
+    db.set_player_health(uuid, (max * config.death_respawn_health_percent / 100).max(1));
+
+    info!(target:LOG_TARGET, "{} was defeated and lost {}", username,
+        if lost.is_empty() { format!("nothing") } else { lost.join(", ") });
+    message
+}
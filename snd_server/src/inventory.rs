@@ -0,0 +1,77 @@
+use std::cmp::Reverse;
+use log::info;
+use uuid::Uuid;
+use snd_network_lib::inventory_data::{InventoryData, InventoryRequestData, InventorySort};
+use snd_network_lib::item_data::ItemData;
+use crate::config::ServerConfig;
+use crate::database::Database;
+use crate::encounter;
+use crate::item::Item;
+use crate::state::ServerState;
+
+const LOG_TARGET: &str = "inventory";
+
+/// the most items a player can carry, including the space they bought
+pub fn capacity(db: &Database, config: &ServerConfig, uuid: &Uuid) -> u32 {
+    (config.inventory_capacity + db.get_inventory_slots(uuid))
+        .min(config.inventory_max_capacity)
+        .max(config.inventory_capacity)
+}
+
+/// how many more items a player can carry
+pub fn free_space(db: &Database, config: &ServerConfig, uuid: &Uuid) -> u32 {
+    capacity(db, config, uuid).saturating_sub(db.count_player_items(uuid))
+}
+
+pub fn is_full(db: &Database, config: &ServerConfig, uuid: &Uuid) -> bool {
+    free_space(db, config, uuid) == 0
+}
+
+/// the player's items that match the request, sorted the way they asked
+pub fn view(db: &Database, config: &ServerConfig, uuid: &Uuid, request: &InventoryRequestData) -> InventoryData {
+    let items = db.get_player_items(uuid).unwrap_or_default();
+    let count = items.len() as u32;
+    let equipped = encounter::equipped(db, uuid).iter().map(|i| i.uuid).collect::<Vec<Uuid>>();
+
+    let mut shown = items.into_iter()
+        .filter(|i| request.itype.map_or(true, |t| i.item_type as u32 == t))
+        .filter(|i| request.rarity.map_or(true, |r| i.rarity as u32 == r))
+        .filter(|i| !request.equipped || equipped.contains(&i.uuid))
+        .collect::<Vec<Item>>();
+    match request.sort {
+        InventorySort::Found => {}
+        InventorySort::Type => shown.sort_by_key(|i| (i.item_type as u32, Reverse(i.rarity as u32), Reverse(i.level))),
+        InventorySort::Rarity => shown.sort_by_key(|i| (Reverse(i.rarity as u32), Reverse(i.level))),
+        InventorySort::Level => shown.sort_by_key(|i| (Reverse(i.level), Reverse(i.rarity as u32))),
+    }
+
+    InventoryData {
+        items: shown.iter()
+            .map(|i| ItemData { equipped: equipped.contains(&i.uuid), ..i.as_data() })
+            .collect(),
+        count,
+        capacity: capacity(db, config, uuid),
+    }
+}
+
+/// Buys more inventory space with gold, every expansion costs more than the last.
+/// Returns what happened to show the player, or an error if they can not buy more space
+pub fn expand(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str) -> Result<String, String> {
+    let db = state.db.lock().unwrap();
+    let current = capacity(&db, config, uuid);
+    if current >= config.inventory_max_capacity {
+        return Err(format!("Your inventory can not hold more than {} items", current));
+    }
+    let bought = db.get_inventory_slots(uuid) / config.inventory_expand_slots;
+    let cost = config.inventory_expand_cost * (bought + 1);
+    let slots = config.inventory_expand_slots.min(config.inventory_max_capacity - current);
+    let gold = db.get_player_gold(uuid).unwrap_or(0);
+    if gold < cost {
+        return Err(format!("{} more inventory space costs {} gold but you only have {}", slots, cost, gold));
+    }
+    if !db.expand_inventory(uuid, slots, cost) {
+        return Err(format!("Failed to expand your inventory"));
+    }
+    info!(target:LOG_TARGET, "{} bought {} inventory slots for {} gold", username, slots, cost);
+    Ok(format!("You bought {} more inventory space for {} gold, you can now carry {} items", slots, cost, current + slots))
+}
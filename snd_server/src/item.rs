@@ -1,3 +1,4 @@
+use std::str::FromStr;
 use rand::{Rng, thread_rng};
 use rand_distr::{Normal, Distribution};
 use uuid::Uuid;
@@ -17,6 +18,35 @@ impl ItemType {
     }
 }
 
+impl ToString for ItemType {
+    fn to_string(&self) -> String {
+        match self {
+            Self::Sword      => "sword",
+            Self::Shield     => "shield",
+            Self::Helmet     => "helmet",
+            Self::Chestplate => "chestplate",
+            Self::Leggings   => "leggings",
+            Self::Boots      => "boots",
+        }.to_string()
+    }
+}
+
+impl FromStr for ItemType {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sword"      => Ok(Self::Sword),
+            "shield"     => Ok(Self::Shield),
+            "helmet"     => Ok(Self::Helmet),
+            "chestplate" => Ok(Self::Chestplate),
+            "leggings"   => Ok(Self::Leggings),
+            "boots"      => Ok(Self::Boots),
+            _ => Err(()),
+        }
+    }
+}
+
 impl Into<u32> for ItemType {
     fn into(self) -> u32 {
         match self {
@@ -52,10 +82,10 @@ impl ItemRarity {
     pub fn new_rand() -> Self {
         let rng = thread_rng().gen_range(0..100);
         match rng {
-            _ if rng < 20 => Self::Rare,      // 30% chance
+            _ if rng < 20 => Self::Rare,      // 20% chance
             _ if rng < 35 => Self::Epic,      // 15% chance
-            100           => Self::Legendary, // 1% chance
-            _             => Self::Common     // 55% chance
+            99            => Self::Legendary, // 1% chance
+            _             => Self::Common     // 64% chance
         }
     }
 
@@ -68,11 +98,41 @@ impl ItemRarity {
         }
     }
 
+    /// this rarity, or the minimum if this one is lower
+    pub fn at_least(self, min: ItemRarity) -> Self {
+        if (self as u32) < (min as u32) { min } else { self }
+    }
+
     pub fn get_weight(&self, level: u32) -> u32 {
         self.get_multiplier() * (level / ((level / 2).max(1)))
     }
 }
 
+impl ToString for ItemRarity {
+    fn to_string(&self) -> String {
+        match self {
+            Self::Common    => "common",
+            Self::Rare      => "rare",
+            Self::Epic      => "epic",
+            Self::Legendary => "legendary",
+        }.to_string()
+    }
+}
+
+impl FromStr for ItemRarity {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "common"    => Ok(Self::Common),
+            "rare"      => Ok(Self::Rare),
+            "epic"      => Ok(Self::Epic),
+            "legendary" => Ok(Self::Legendary),
+            _ => Err(()),
+        }
+    }
+}
+
 impl Into<u32> for ItemRarity {
     fn into(self) -> u32 {
         match self {
@@ -95,6 +155,148 @@ impl From<u32> for ItemRarity {
     }
 }
 
+/// A bonus rolled on some Epic and Legendary items, each holds how strong it is as a percent.
+/// Only the items a player fights with count, their best sword and their best armor of each type
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SpecialAbility {
+    None,
+    /// heals the player by a share of the damage they deal
+    Lifesteal(u32),
+    /// a chance for an attack to deal double damage
+    Crit(u32),
+    /// hits back enemies for a share of the damage they deal to the player
+    Thorns(u32),
+    /// more exp from steps and fights
+    BonusExp(u32),
+    /// a chance for a step that would only give exp to find an item instead
+    StepLuck(u32),
+    /// poisons the enemy for a share of the damage dealt each round
+    Poison(u32),
+    /// a chance for an attack to stun the enemy for a round
+    Stun(u32),
+    /// a chance to weaken enemies that hit the player
+    Weaken(u32),
+}
+
+impl SpecialAbility {
+    /// rolls the ability for a new item, Epic items only sometimes get one and Legendary items always do
+    pub fn new_rand(item_type: ItemType, rarity: ItemRarity) -> Self {
+        let (chance, strength) = match rarity {
+            ItemRarity::Epic => (40, 1),
+            ItemRarity::Legendary => (100, 2),
+            _ => return Self::None,
+        };
+        if thread_rng().gen_range(0..100) >= chance {
+            return Self::None;
+        }
+        // swords get the abilities that help them hit, armor gets the ones that help it take hits
+        let roll = thread_rng().gen_range(0..6);
+        match (item_type, roll) {
+            (ItemType::Sword, 0) => Self::Lifesteal(thread_rng().gen_range(5..=10) * strength),
+            (ItemType::Sword, 1) => Self::Crit(thread_rng().gen_range(5..=10) * strength),
+            (ItemType::Sword, 2) => Self::Poison(thread_rng().gen_range(10..=20) * strength),
+            (ItemType::Sword, 3) => Self::Stun(thread_rng().gen_range(5..=10) * strength),
+            (ItemType::Sword, 4) | (_, 3 | 4) => Self::BonusExp(thread_rng().gen_range(5..=15) * strength),
+            (_, 0 | 1) => Self::Thorns(thread_rng().gen_range(10..=20) * strength),
+            (_, 2) => Self::Weaken(thread_rng().gen_range(10..=20) * strength),
+            _ => Self::StepLuck(thread_rng().gen_range(5..=10) * strength),
+        }
+    }
+
+    /// what the ability does, shown when inspecting the item
+    pub fn describe(&self) -> String {
+        match self {
+            Self::None => String::new(),
+            Self::Lifesteal(p) => format!("Lifesteal: heals you for {}% of the damage you deal", p),
+            Self::Crit(p) => format!("Critical: {}% chance to deal double damage", p),
+            Self::Thorns(p) => format!("Thorns: hits back for {}% of the damage you take", p),
+            Self::BonusExp(p) => format!("Wisdom: {}% more exp", p),
+            Self::StepLuck(p) => format!("Fortune: {}% more likely to find items while walking", p),
+            Self::Poison(p) => format!("Venom: poisons enemies for {}% of your hits each round", p),
+            Self::Stun(p) => format!("Stagger: {}% chance to stun the enemy for a round", p),
+            Self::Weaken(p) => format!("Frost: {}% chance to weaken enemies that hit you", p),
+        }
+    }
+}
+
+/// stored in the special_ability column of the items table as e.g. "LIFESTEAL:10"
+impl ToString for SpecialAbility {
+    fn to_string(&self) -> String {
+        match self {
+            Self::None => format!("NONE"),
+            Self::Lifesteal(p) => format!("LIFESTEAL:{}", p),
+            Self::Crit(p) => format!("CRIT:{}", p),
+            Self::Thorns(p) => format!("THORNS:{}", p),
+            Self::BonusExp(p) => format!("BONUS_EXP:{}", p),
+            Self::StepLuck(p) => format!("STEP_LUCK:{}", p),
+            Self::Poison(p) => format!("POISON:{}", p),
+            Self::Stun(p) => format!("STUN:{}", p),
+            Self::Weaken(p) => format!("WEAKEN:{}", p),
+        }
+    }
+}
+
+impl FromStr for SpecialAbility {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("NONE") {
+            return Ok(Self::None);
+        }
+        let (name, percent) = s.split_once(':').ok_or(())?;
+        let percent = percent.parse::<u32>().map_err(|_| ())?;
+        match name.to_ascii_uppercase().as_str() {
+            "LIFESTEAL" => Ok(Self::Lifesteal(percent)),
+            "CRIT"      => Ok(Self::Crit(percent)),
+            "THORNS"    => Ok(Self::Thorns(percent)),
+            "BONUS_EXP" => Ok(Self::BonusExp(percent)),
+            "STEP_LUCK" => Ok(Self::StepLuck(percent)),
+            "POISON"    => Ok(Self::Poison(percent)),
+            "STUN"      => Ok(Self::Stun(percent)),
+            "WEAKEN"    => Ok(Self::Weaken(percent)),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The total strength of every special ability on the items a player fights with
+#[derive(Copy, Clone, Default, Debug)]
+pub struct Abilities {
+    pub lifesteal: u32,
+    pub crit: u32,
+    pub thorns: u32,
+    pub bonus_exp: u32,
+    pub step_luck: u32,
+    pub poison: u32,
+    pub stun: u32,
+    pub weaken: u32,
+}
+
+impl Abilities {
+    pub fn of(items: &[Item]) -> Self {
+        let mut total = Self::default();
+        for item in items {
+            match item.ability {
+                SpecialAbility::None => {}
+                SpecialAbility::Lifesteal(p) => total.lifesteal += p,
+                SpecialAbility::Crit(p) => total.crit += p,
+                SpecialAbility::Thorns(p) => total.thorns += p,
+                SpecialAbility::BonusExp(p) => total.bonus_exp += p,
+                SpecialAbility::StepLuck(p) => total.step_luck += p,
+                SpecialAbility::Poison(p) => total.poison += p,
+                SpecialAbility::Stun(p) => total.stun += p,
+                SpecialAbility::Weaken(p) => total.weaken += p,
+            }
+        }
+        total
+    }
+
+    /// adds the bonus exp to an amount of exp
+    pub fn exp(&self, amount: u32) -> u32 {
+        amount + amount * self.bonus_exp / 100
+    }
+}
+
 #[derive(Clone)]
 pub struct Item {
     pub uuid: Uuid,
@@ -105,6 +307,9 @@ pub struct Item {
     pub level: u32,
     pub damage: u32,
     pub defense: u32,
+    pub ability: SpecialAbility,
+    /// how many times the item has been upgraded
+    pub upgrades: u32,
 }
 
 impl Item {
@@ -140,6 +345,8 @@ impl Item {
             uuid,
             owner: owner.clone(),
             name, item_type, rarity, level, damage, defense,
+            ability: SpecialAbility::new_rand(item_type, rarity),
+            upgrades: 0,
         }
     }
 
@@ -152,8 +359,9 @@ impl Item {
             ItemValueDB::Level => self.level.to_string(),
             ItemValueDB::Damage => self.damage.to_string(),
             ItemValueDB::Defense => self.defense.to_string(),
-            ItemValueDB::SpecialAbility => "NONE".to_string(),
+            ItemValueDB::SpecialAbility => self.ability.to_string(),
             ItemValueDB::Rarity => (self.rarity.clone() as u32).to_string(),
+            ItemValueDB::Upgrades => self.upgrades.to_string(),
         }
     }
 
@@ -164,7 +372,10 @@ impl Item {
             itype: self.item_type as u32,
             rarity: self.rarity as u32,
             damage: Some(self.damage),
-            defense: Some(self.defense)
+            defense: Some(self.defense),
+            ability: self.ability.describe(),
+            upgrades: self.upgrades,
+            equipped: false,
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use snd_network_lib::leaderboard_data::{LeaderboardCategory, LeaderboardData, LeaderboardEntryData};
+use crate::config::ServerConfig;
+use crate::database::Database;
+
+struct CachedPage {
+    leaderboard: LeaderboardData,
+    read: Instant,
+}
+
+/// Keeps recently read leaderboard pages so players looking through them do not query the database every time.
+/// Pages are read again once they are older than `cache_seconds`
+pub struct LeaderboardCache {
+    pages: HashMap<(LeaderboardCategory, u32), CachedPage>,
+}
+
+impl LeaderboardCache {
+    pub fn new() -> Self {
+        Self {
+            pages: HashMap::new(),
+        }
+    }
+
+    /// gets a page of a leaderboard, the last page is sent if the page asked for is past the end
+    pub fn get(&mut self, db: &Database, config: &ServerConfig, category: LeaderboardCategory, page: u32) -> LeaderboardData {
+        let max_age = Duration::from_secs(config.leaderboard_cache_seconds);
+        self.pages.retain(|_, cached| cached.read.elapsed() < max_age);
+        if let Some(cached) = self.pages.get(&(category, page)) {
+            return cached.leaderboard.clone();
+        }
+
+        // pages past the end are the last page, which may already be cached
+        let page_size = config.leaderboard_page_size;
+        let pages = db.count_players().div_ceil(page_size).max(1);
+        let page = page.min(pages - 1);
+        if let Some(cached) = self.pages.get(&(category, page)) {
+            return cached.leaderboard.clone();
+        }
+        let entries = db.get_leaderboard(category, page * page_size, page_size).into_iter()
+            .enumerate()
+            .map(|(i, (name, value))| LeaderboardEntryData { rank: page * page_size + i as u32 + 1, name, value })
+            .collect();
+        let leaderboard = LeaderboardData { category, page, pages, entries };
+
+        self.pages.insert((category, page), CachedPage { leaderboard: leaderboard.clone(), read: Instant::now() });
+        leaderboard
+    }
+}
@@ -0,0 +1,242 @@
+use std::cell::RefCell;
+use std::fmt::Arguments;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::{fs, io};
+use better_term::{Color, Style};
+use log::{Level, Record};
+use uuid::Uuid;
+use crate::config::ServerConfig;
+
+/// How records are written to the log file
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum LogFormat {
+    Text, Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Information about what the current thread is handling, attached to json log records.
+/// Each client connection runs on its own thread, so this is set once per connection
+#[derive(Clone, Default)]
+struct LogContext {
+    uuid: Option<Uuid>,
+    ip: Option<String>,
+    event: Option<&'static str>,
+}
+
+thread_local! {
+    static CONTEXT: RefCell<LogContext> = RefCell::new(LogContext::default());
+}
+
+/// sets the ip of the connection handled by the current thread
+pub fn set_context_ip<S: Into<String>>(ip: S) {
+    CONTEXT.with(|c| c.borrow_mut().ip = Some(ip.into()));
+}
+
+/// sets the player handled by the current thread
+pub fn set_context_player(uuid: &Uuid) {
+    CONTEXT.with(|c| c.borrow_mut().uuid = Some(uuid.clone()));
+}
+
+/// Tags log records on the current thread with the event being handled until it is dropped,
+/// so records from after the event (keepalives, regen, timeouts) are not tagged with it
+pub struct EventContext;
+
+impl EventContext {
+    pub fn enter(event: &'static str) -> Self {
+        CONTEXT.with(|c| c.borrow_mut().event = Some(event));
+        Self
+    }
+}
+
+impl Drop for EventContext {
+    fn drop(&mut self) {
+        CONTEXT.with(|c| c.borrow_mut().event = None);
+    }
+}
+
+/// A log file that is rotated once it grows past a size limit.
+/// `server.log` is moved to `server.log.1`, `server.log.1` to `server.log.2`, etc.
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    max_files: usize,
+    // only rotate between lines so records are never split across files
+    line_start: bool,
+}
+
+impl RotatingFile {
+    fn open(path: &Path, max_size: u64, max_files: usize) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            if !dir.as_os_str().is_empty() && !dir.exists() {
+                fs::create_dir_all(dir)?;
+            }
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            file, size, max_size, max_files,
+            line_start: true,
+        })
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_os_string();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 0 {
+            // no backups are kept, just start the file over
+            self.file = File::create(&self.path)?;
+        } else {
+            // shift every backup up by one, dropping the oldest
+            for i in (1..self.max_files).rev() {
+                let from = self.rotated_path(i);
+                if from.exists() {
+                    fs::rename(&from, self.rotated_path(i + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+            self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.line_start && self.max_size > 0 && self.size >= self.max_size {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        if written > 0 {
+            self.line_start = buf[written - 1] == b'\n';
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn format_colored(out: fern::FormatCallback, message: &Arguments, record: &Record) {
+    let style = match record.level() {
+        Level::Error => Style::reset().fg(Color::Red).bold(),
+        Level::Warn => Style::reset().fg(Color::Yellow),
+        Level::Info => Style::reset().fg(Color::Cyan),
+        Level::Debug => Style::reset().fg(Color::White),
+        Level::Trace => Style::reset().fg(Color::BrightBlack),
+    };
+
+    let time = chrono::Local::now();
+
+    out.finish(format_args!(
+        "{bc}[{ic}{}{bc}][{ic}{}{bc}][{ic}{}{bc}] {}{}{bc}: {ic}{}",
+        time.format("%Y-%m-%d"),
+        time.format("%H:%M:%S"),
+        record.target(),
+        style,
+        record.level(),
+        message,
+        bc = Style::reset().fg(Color::BrightBlack),
+        ic = Style::reset().fg(Color::White),
+    ))
+}
+
+fn format_plain(out: fern::FormatCallback, message: &Arguments, record: &Record) {
+    let time = chrono::Local::now();
+    out.finish(format_args!(
+        "[{}][{}][{}] {}: {}",
+        time.format("%Y-%m-%d"),
+        time.format("%H:%M:%S"),
+        record.target(),
+        record.level(),
+        message,
+    ))
+}
+
+/// escapes a string to be placed inside of a json string literal
+fn json_escape(raw: &str) -> String {
+    let mut escaped = String::with_capacity(raw.len());
+    for c in raw.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(format!("\\u{:04x}", c as u32).as_str()),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn format_json(out: fern::FormatCallback, message: &Arguments, record: &Record) {
+    let context = CONTEXT.with(|c| c.borrow().clone());
+    let mut line = format!("{{\"time\":\"{}\",\"level\":\"{}\",\"target\":\"{}\",\"message\":\"{}\"",
+                           chrono::Local::now().to_rfc3339(),
+                           record.level(),
+                           json_escape(record.target()),
+                           json_escape(message.to_string().as_str()));
+    if let Some(uuid) = context.uuid {
+        line.push_str(format!(",\"uuid\":\"{}\"", uuid).as_str());
+    }
+    if let Some(ip) = context.ip {
+        line.push_str(format!(",\"ip\":\"{}\"", json_escape(ip.as_str())).as_str());
+    }
+    if let Some(event) = context.event {
+        line.push_str(format!(",\"event\":\"{}\"", event).as_str());
+    }
+    line.push('}');
+    out.finish(format_args!("{}", line))
+}
+
+/// Sets up the logger using the fern crate with the sinks in the config
+pub fn setup_logger(config: &ServerConfig) -> Result<(), fern::InitError> {
+    let mut dispatch = fern::Dispatch::new()
+        .level(config.log_level);
+    for (target, level) in config.log_targets.iter() {
+        dispatch = dispatch.level_for(target.clone(), level.clone());
+    }
+
+    if config.log_stdout {
+        dispatch = dispatch.chain(fern::Dispatch::new()
+            .format(format_colored)
+            .chain(std::io::stdout()));
+    }
+
+    if let Some(path) = &config.log_file {
+        let file = RotatingFile::open(Path::new(path), config.log_max_file_size, config.log_max_files)?;
+        let file_dispatch = match config.log_format {
+            LogFormat::Text => fern::Dispatch::new().format(format_plain),
+            LogFormat::Json => fern::Dispatch::new().format(format_json),
+        };
+        dispatch = dispatch.chain(file_dispatch.chain(fern::Output::writer(Box::new(file), "\n")));
+    }
+
+    dispatch.apply()?;
+    Ok(())
+}
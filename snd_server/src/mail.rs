@@ -0,0 +1,205 @@
+use log::{error, info};
+use uuid::Uuid;
+use snd_network_lib::mail_data::{MailActionData, MailData};
+use snd_network_lib::systime;
+use crate::config::ServerConfig;
+use crate::database::Database;
+use crate::inventory;
+use crate::item::Item;
+use crate::session::{SessionMessage, SessionRegistry};
+use crate::state::ServerState;
+
+const LOG_TARGET: &str = "mail";
+/// the name shown as the sender of mail from the server
+pub const SYSTEM_SENDER: &str = "System";
+/// the most items a player can attach to one letter
+const MAX_ITEMS: usize = 5;
+
+/// A letter in a player's mailbox, the items attached to it are held by the letter until they are claimed
+#[derive(Clone, Debug)]
+pub struct Mail {
+    /// the number players use to pick the letter
+    pub number: u32,
+    /// the owner of the attached items
+    pub id: Uuid,
+    pub recipient: Uuid,
+    /// the username of the sender, or `SYSTEM_SENDER`
+    pub sender: String,
+    pub message: String,
+    pub gold: u32,
+    /// when it was sent, in seconds since the unix epoch
+    pub time: u64,
+    pub read: bool,
+    pub claimed: bool,
+}
+
+impl Mail {
+    fn new(recipient: &Uuid, sender: &str, message: String, gold: u32) -> Self {
+        Self {
+            number: 0,
+            id: Uuid::new_v4(),
+            recipient: recipient.clone(),
+            sender: sender.to_string(),
+            message, gold,
+            time: systime().as_secs(),
+            read: false,
+            claimed: false,
+        }
+    }
+
+    /// if there is gold or items still waiting to be claimed
+    fn has_attachments(&self, db: &Database) -> bool {
+        !self.claimed && (self.gold > 0 || !db.get_mail_items(self).is_empty())
+    }
+}
+
+/// the letters in the player's mailbox, newest first
+pub fn mailbox(db: &Database, uuid: &Uuid) -> Vec<MailData> {
+    let now = systime().as_secs();
+    db.get_mailbox(uuid).iter().map(|m| MailData {
+        number: m.number,
+        sender: m.sender.clone(),
+        message: m.message.clone(),
+        gold: if m.claimed { 0 } else { m.gold },
+        items: db.get_mail_items(m).iter().map(|i| i.as_data()).collect(),
+        age: now.saturating_sub(m.time),
+        read: m.read,
+        claimed: m.claimed,
+    }).collect()
+}
+
+/// tells the player they have new mail if they are online
+fn announce(sessions: &SessionRegistry, mail: &Mail) {
+    sessions.send(&mail.recipient, SessionMessage::Event(
+        format!("You have new mail from {}, type 'mail' to read it", mail.sender)));
+    sessions.send(&mail.recipient, SessionMessage::StatsChanged);
+}
+
+/// Sends mail from the server, which is always delivered even if the mailbox is full.
+/// `items` are new items made for the recipient
+pub fn send_system(db: &Database, sessions: &SessionRegistry, recipient: &Uuid, message: String, gold: u32, items: &[Item]) -> bool {
+    let mail = Mail::new(recipient, SYSTEM_SENDER, message, gold);
+    if !db.send_system_mail(&mail, items) {
+        error!(target:LOG_TARGET, "Failed to send system mail to {}", recipient);
+        return false;
+    }
+    announce(sessions, &mail);
+    true
+}
+
+/// Shows a message to the player right away if they are online, or leaves it in their mailbox if they are not
+pub fn notify(db: &Database, sessions: &SessionRegistry, uuid: &Uuid, message: String) {
+    if !sessions.send(uuid, SessionMessage::Event(message.clone())) {
+        send_system(db, sessions, uuid, message, 0, &[]);
+    }
+}
+
+/// Sends a letter to another player with gold and items from the sender's inventory.
+/// Returns what happened to show the player, or an error if they can not send it
+pub fn send(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str, action: &MailActionData) -> Result<String, String> {
+    let (to, gold, item_names) = (action.to.as_str(), action.gold, &action.items);
+    let message = action.message.trim();
+    if message.is_empty() && gold == 0 && item_names.is_empty() {
+        return Err(format!("Your letter is empty"));
+    }
+    if message.chars().count() > config.mail_max_length {
+        return Err(format!("Letters can be at most {} characters long", config.mail_max_length));
+    }
+    if item_names.len() > MAX_ITEMS {
+        return Err(format!("You can only attach {} items to a letter", MAX_ITEMS));
+    }
+    if !item_names.is_empty() && (state.encounters.lock().unwrap().in_fight(uuid) || state.duels.lock().unwrap().in_duel(uuid)) {
+        return Err(format!("You can not send items in the middle of a fight"));
+    }
+
+    let db = state.db.lock().unwrap();
+    let recipient = db.uuid_from_username(to.replace("'", ""))
+        .ok_or(format!("No player named '{}' exists", to))?;
+    if recipient == *uuid {
+        return Err(format!("You can not send mail to yourself"));
+    }
+    // players who ignore the sender do not get their mail, without telling the sender why
+    if db.get_ignored_by(uuid).contains(&recipient) {
+        return Err(format!("{} can not receive your mail", to));
+    }
+    if db.count_mail(&recipient) >= config.mail_max_mail {
+        return Err(format!("{}'s mailbox is full", to));
+    }
+    let cost = gold.saturating_add(config.mail_postage);
+    let have = db.get_player_gold(uuid).unwrap_or(0);
+    if have < cost {
+        return Err(format!("Sending this costs {} gold with postage but you only have {}", cost, have));
+    }
+    let mut items = Vec::new();
+    for name in item_names {
+        let item = db.item_uuid_from_name(name.replace("'", ""), uuid)
+            .ok_or(format!("You do not have an item called '{}'", name))?;
+        if items.contains(&item) {
+            return Err(format!("You attached '{}' more than once", name));
+        }
+        items.push(item);
+    }
+
+    let mail = Mail::new(&recipient, username, message.to_string(), gold);
+    if !db.send_mail(&mail, uuid, &items, cost) {
+        return Err(format!("Failed to send your letter to {}", to));
+    }
+    announce(&state.sessions.lock().unwrap(), &mail);
+    info!(target:LOG_TARGET, "{} sent mail to {} with {} gold and {} items", username, to, gold, items.len());
+    Ok(format!("You sent a letter to {} for {} gold postage", to, config.mail_postage))
+}
+
+/// Marks a letter as read.
+/// Returns an error to show the player if it is not in their mailbox
+pub fn read(state: &ServerState, uuid: &Uuid, number: u32) -> Result<(), String> {
+    let db = state.db.lock().unwrap();
+    let mail = db.get_player_mail(uuid, number)
+        .ok_or(format!("There is no letter #{} in your mailbox", number))?;
+    if !mail.read && !db.read_mail(&mail) {
+        return Err(format!("Failed to open letter #{}", number));
+    }
+    Ok(())
+}
+
+/// Takes the gold and items attached to a letter.
+/// Returns what happened to show the player, or an error if they can not claim them
+pub fn claim(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str, number: u32) -> Result<String, String> {
+    if state.encounters.lock().unwrap().in_fight(uuid) || state.duels.lock().unwrap().in_duel(uuid) {
+        return Err(format!("You can not open your mail in the middle of a fight"));
+    }
+    let db = state.db.lock().unwrap();
+    let mail = db.get_player_mail(uuid, number)
+        .ok_or(format!("There is no letter #{} in your mailbox", number))?;
+    if !mail.has_attachments(&db) {
+        return Err(format!("There is nothing to take from letter #{}", number));
+    }
+    let items = db.get_mail_items(&mail);
+    if (items.len() as u32) > inventory::free_space(&db, config, uuid) {
+        return Err(format!("You need room for {} items in your inventory to claim letter #{}", items.len(), number));
+    }
+    if !db.claim_mail(&mail) {
+        return Err(format!("Failed to claim letter #{}", number));
+    }
+    info!(target:LOG_TARGET, "{} claimed {} gold and {} items from letter #{}", username, mail.gold, items.len(), number);
+    let mut got = Vec::new();
+    if mail.gold > 0 {
+        got.push(format!("{} gold", mail.gold));
+    }
+    got.extend(items.iter().map(|i| format!("'{}'", i.name)));
+    Ok(format!("You took {} from letter #{}", got.join(", "), number))
+}
+
+/// Throws a letter away, only once everything attached to it was claimed.
+/// Returns what happened to show the player, or an error if they can not delete it
+pub fn delete(state: &ServerState, uuid: &Uuid, number: u32) -> Result<String, String> {
+    let db = state.db.lock().unwrap();
+    let mail = db.get_player_mail(uuid, number)
+        .ok_or(format!("There is no letter #{} in your mailbox", number))?;
+    if mail.has_attachments(&db) {
+        return Err(format!("Claim what is attached to letter #{} before deleting it", number));
+    }
+    if !db.delete_mail(&mail) {
+        return Err(format!("Failed to delete letter #{}", number));
+    }
+    Ok(format!("You deleted letter #{}", number))
+}
@@ -3,22 +3,64 @@
 use std::fs::File;
 use std::io::Read;
 use std::net::TcpListener;
-use std::path::Path;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::{io, thread};
 use std::time::Duration;
-use better_term::{Color, Style};
-use log::{error, info, Level, LevelFilter};
+use log::{error, info};
+use crate::achievement::AchievementList;
+use crate::bestiary::Bestiary;
 use crate::client::handle_connection;
-use crate::config::read_config;
+use crate::command::CommandContext;
+use crate::config::ServerConfig;
+use crate::class::ClassList;
+use crate::consumable::ConsumableList;
+use crate::crafting::CraftingList;
 use crate::database::Database;
+use crate::console::start_console;
+use crate::duel::DuelRegistry;
+use crate::encounter::EncounterRegistry;
+use crate::leaderboard::LeaderboardCache;
+use crate::logging::setup_logger;
+use crate::party::PartyRegistry;
+use crate::quest::QuestList;
+use crate::session::{SessionMessage, SessionRegistry};
+use crate::state::ServerState;
 
 pub mod client;
 pub mod database;
+pub mod enemy;
 pub mod item;
 pub mod player;
+pub mod session;
+mod achievement;
+mod auction;
+mod bestiary;
+mod bank;
+mod chat;
+mod class;
+mod command;
 mod config;
+mod console;
+mod consumable;
+mod crafting;
+mod data;
+mod duel;
+mod encounter;
+mod game_event;
+mod guild;
+mod health;
+mod inventory;
+mod leaderboard;
+mod logging;
+mod mail;
+mod moderation;
+mod party;
+mod permission;
+mod quest;
+mod state;
+mod status;
 
 /***
  * Todo(eric):
@@ -28,16 +70,10 @@ mod config;
 ***/
 
 // versions
-pub const ACCEPTED_CLIENT_VERSION: &str = "0.1.0";
 pub const SERVER_VERSION: &str = env!("CARGO_PKG_VERSION");
 
-// info for the client
-pub const MOTD: &str = "Welcome to SnD! We are still in ALPHA, so expect some bugs!";
-pub const KEEPALIVE_INTERVAL: u64 = 20; // time in seconds to send the keepalive packet
-
 // How long the main loop should wait between checking for incoming connections to save cpu resources
 const MAIN_LOOP_WAIT_DELAY_MS: u64 = 20;
-const LOG_LEVEL_FILTER_AT: LevelFilter = LevelFilter::Trace;
 const LOG_TARGET: &str = "main";
 
 pub fn read_config_raw(file: &mut File) -> String {
@@ -47,85 +83,54 @@ pub fn read_config_raw(file: &mut File) -> String {
     config_content
 }
 
-fn setup_logger() -> Result<(), fern::InitError> {
-    fern::Dispatch::new()
-        .format(|out, message, record| {
-            let style = match record.level() {
-                Level::Error => Style::reset().fg(Color::Red).bold(),
-                Level::Warn => Style::reset().fg(Color::Yellow),
-                Level::Info => Style::reset().fg(Color::Cyan),
-                Level::Debug => Style::reset().fg(Color::White),
-                Level::Trace => Style::reset().fg(Color::BrightBlack),
-            };
-
-            let time = chrono::Local::now();
-
-            out.finish(format_args!(
-                "{bc}[{ic}{}{bc}][{ic}{}{bc}][{ic}{}{bc}] {}{}{bc}: {ic}{}",
-                time.format("%Y-%m-%d"),
-                time.format("%H:%M:%S"),
-                record.target(),
-                style,
-                record.level(),
-                message,
-                bc = Style::reset().fg(Color::BrightBlack),
-                ic = Style::reset().fg(Color::White),
-            ))
-        })
-        .level(LOG_LEVEL_FILTER_AT)
-        .chain(std::io::stdout())
-        //.chain(fern::log_file("output.log")?)
-        .apply()?;
-    Ok(())
+fn print_usage() {
+    eprintln!("Usage: snd_server [--config <path>]\
+    \n  --config <path>  the config file to use (defaults to ./config/config.toml)");
 }
 
-fn main() {
-    println!("{}", MOTD);
-    // setup the logger using the fern crate
-    if let Err(e) = setup_logger() {
-        eprintln!("Failed to initialize the logging system: {}", e);
-        return;
+/// Gets the path of the config file from the command line arguments, or the default location.
+/// Returns None if the arguments are invalid
+fn config_path_from_args() -> Option<PathBuf> {
+    let mut args = std::env::args().skip(1);
+    let mut path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" | "-c" => path = Some(PathBuf::from(args.next()?)),
+            _ => return None,
+        }
     }
 
-    info!(target:LOG_TARGET, "Reading configuration file...");
+    if let Some(p) = path {
+        return Some(p);
+    }
+
+    // default to the config folder in the current directory
+    let cdir = std::env::current_dir();
+    if let Err(e) = cdir {
+        eprintln!("Failed to access the current directory for the config file: {}", e);
+        return None;
+    }
+    Some(cdir.unwrap().join("config").join("config.toml"))
+}
+
+fn main() {
     // handle configuration
-    let cdir_r = std::env::current_dir();
-    if let Err(e) = cdir_r {
-        error!(target:LOG_TARGET, "Failed to create config file: no access! raw error: {}", e);
+    let config_path = config_path_from_args();
+    if config_path.is_none() {
+        print_usage();
         return;
     }
-    let cdir = cdir_r.unwrap();
-    let current_dir_r = cdir.as_path().to_str();
-    if current_dir_r.is_none() {
-        error!(target:LOG_TARGET, "Could not access the config file!");
+    let config = Arc::new(ServerConfig::load(config_path.unwrap().as_path()));
+
+    println!("{}", config.motd);
+    // setup the logger using the fern crate
+    if let Err(e) = setup_logger(&config) {
+        eprintln!("Failed to initialize the logging system: {}", e);
         return;
     }
-    let current_dir = current_dir_r.unwrap();
-    let config_path = format!("{}/config/config.toml", current_dir);
-    let raw_path = Path::new(&config_path);
-    let config = read_config(raw_path, format!("\
-    [server]\
-    \n# ip: the ip to listen on\
-    \n# surround with '[' and ']' for Ipv6 addresses\
-    \n# defaults to 0.0.0.0 and will listen on your machines current IP\
-    \nip = \"0.0.0.0\"\
-    \n# port: the port to listen on\
-    \n# defaults to 2277\
-    \nport = \"2277\""));
-
-    // set default values for the config
-    let mut ip = format!("0.0.0.0");
-    let mut port = format!("2277");
-
-    // if the configuration values are set, override defaults
-    if let Some(server_conf) = config.server {
-        if let Some(cfg_ip) = server_conf.ip {
-            ip = cfg_ip;
-        }
-        if let Some(cfg_port) = server_conf.port {
-            port = cfg_port;
-        }
-    }
+
+    let ip = config.ip.clone();
+    let port = config.port.clone();
 
     info!(target:LOG_TARGET, "Read config with the listening IP {} and the port {}", ip.clone(), port.clone());
     info!(target:LOG_TARGET, "Starting TCP Listener...");
@@ -147,8 +152,13 @@ fn main() {
 
     // create the database instance for the clients to use
     info!(target:LOG_TARGET, "Connecting to the database...");
-    let db = Arc::new(Mutex::new(Database::new("snd")));
+    let db = Arc::new(Mutex::new(Database::new(config.database_name.clone())));
     info!(target:LOG_TARGET, "Connected to the database!");
+    // give back any wagers held by a server that stopped in the middle of a duel
+    let refunded = db.lock().unwrap().refund_all_escrow();
+    if refunded > 0 {
+        info!(target:LOG_TARGET, "Refunded {} unsettled wagers", refunded);
+    }
 
     // create a flag for threads to access to let them know if the program is shutting down
     let terminate = Arc::new(AtomicBool::new(false));
@@ -163,26 +173,61 @@ fn main() {
         return;
     }
 
+    // keep track of every connected player so they can be reached from anywhere in the server
+    let sessions = Arc::new(Mutex::new(SessionRegistry::new()));
+    // the bestiary's loot is checked against the consumables and crafting materials
+    let consumables = ConsumableList::load(&config);
+    let crafting = CraftingList::load(&config);
+    let bestiary = Bestiary::load(&config, &consumables, &crafting);
+    // everything the connection handlers share, parties and fights are shared between the players in them
+    let state = ServerState {
+        db: Arc::clone(&db),
+        sessions: Arc::clone(&sessions),
+        parties: Arc::new(Mutex::new(PartyRegistry::new())),
+        encounters: Arc::new(Mutex::new(EncounterRegistry::new())),
+        duels: Arc::new(Mutex::new(DuelRegistry::new())),
+        leaderboards: Arc::new(Mutex::new(LeaderboardCache::new())),
+        achievements: Arc::new(AchievementList::load(&config)),
+        quests: Arc::new(QuestList::load(&config)),
+        consumables: Arc::new(consumables),
+        crafting: Arc::new(crafting),
+        classes: Arc::new(ClassList::load(&config)),
+        bestiary: Arc::new(bestiary),
+    };
+
+    // settle auction listings as they end, even for players who are offline
+    auction::start_expiry_task(state.clone(), Arc::clone(&config), Arc::clone(&terminate));
+
+    // start the admin console
+    start_console(CommandContext {
+        db: Arc::clone(&db),
+        sessions: Arc::clone(&sessions),
+        terminate: Arc::clone(&terminate),
+        config: Arc::clone(&config),
+    });
+
     // store the join handlers for closing later
     // todo(eric): I dont think this drops handlers that are no longer active
     let mut handlers = Vec::new();
 
     info!(target:LOG_TARGET, "Started listening at {}", full_ip);
-    info!(target:LOG_TARGET, "Accpting client version {}", ACCEPTED_CLIENT_VERSION);
+    info!(target:LOG_TARGET, "Accpting client version {}", config.accepted_client_version);
 
     // listen for incoming connections
     for stream in listener.incoming() {
         match stream {
             Ok(s) => {
-                // create a new reference to the database for the client to access
-                // save memory with only one database access and provide thread safety
-                let db_arc = Arc::clone(&db);
                 // create a reference to the terminate flag
                 let tarc = Arc::clone(&terminate);
+                // create a reference to the server configuration
+                let carc = Arc::clone(&config);
+                // create references to the database, online players and everything else the handlers share
+                // save memory with only one database access and provide thread safety
+                let sstate = state.clone();
 
                 // spawn a new thread with the client handler
                 handlers.push(thread::spawn(move || {
-                    handle_connection(s, db_arc, tarc);
+                    handle_connection(s, tarc, carc, sstate);
                 }));
             }
             Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
@@ -207,8 +252,9 @@ fn main() {
         }
     }
 
-    // store that the program is terminating and the clients should be disconnected
+    // store that the program is terminating and tell every connected client to disconnect
     terminate.store(true, Ordering::SeqCst);
+    sessions.lock().unwrap().broadcast(SessionMessage::Shutdown(format!("The server is shutting down!")));
 
     info!(target:LOG_TARGET, "Shutting down all active connections...");
     // ensure all threads are closed before shutting down the server
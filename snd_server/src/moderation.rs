@@ -0,0 +1,271 @@
+use std::net::IpAddr;
+use chrono::{Local, TimeZone};
+use uuid::Uuid;
+use snd_network_lib::systime;
+
+/// A ban on an account or an address range
+#[derive(Clone, Debug)]
+pub struct Ban {
+    pub uuid: Option<Uuid>,
+    /// an ip address or CIDR range such as `10.0.0.0/8`
+    pub ip: Option<String>,
+    pub reason: String,
+    pub banned_by: String,
+    pub time: u64,
+    /// when the ban expires in seconds since the epoch, None if it is permanent
+    pub expires: Option<u64>,
+}
+
+impl Ban {
+    pub fn is_expired(&self) -> bool {
+        match self.expires {
+            Some(e) => e <= systime().as_secs(),
+            None => false,
+        }
+    }
+
+    /// the message shown to a banned player when they try to connect
+    pub fn message(&self) -> String {
+        match self.expires {
+            Some(e) => format!("You are banned until {}: {}", format_time(e), self.reason),
+            None => format!("You are permanently banned: {}", self.reason),
+        }
+    }
+}
+
+/// A mute stopping a player from sending chat messages
+#[derive(Clone, Debug)]
+pub struct Mute {
+    pub reason: String,
+    pub muted_by: String,
+    pub time: u64,
+    /// when the mute expires in seconds since the epoch, None if it is permanent
+    pub expires: Option<u64>,
+}
+
+impl Mute {
+    /// the message shown to a muted player when they try to chat
+    pub fn message(&self) -> String {
+        match self.expires {
+            Some(e) => format!("You are muted until {}: {}", format_time(e), self.reason),
+            None => format!("You are muted: {}", self.reason),
+        }
+    }
+}
+
+/// A moderation action recorded in the audit log
+#[derive(Clone, Debug)]
+pub struct AuditEntry {
+    pub time: u64,
+    pub actor: String,
+    pub action: String,
+    pub target: String,
+    pub details: String,
+}
+
+/// formats seconds since the epoch as a local date and time
+pub fn format_time(epoch_secs: u64) -> String {
+    match Local.timestamp_opt(epoch_secs as i64, 0).single() {
+        Some(t) => t.format("%Y-%m-%d %H:%M").to_string(),
+        None => format!("{}", epoch_secs),
+    }
+}
+
+/// parses a ban duration such as `30m`, `12h`, `7d` or `perm`.
+/// Returns Some(None) for a permanent ban and None if the duration is invalid or too long
+pub fn parse_duration(raw: &str) -> Option<Option<u64>> {
+    let raw = raw.to_ascii_lowercase();
+    if raw == "perm" || raw == "permanent" {
+        return Some(None);
+    }
+    // split on a character boundary so reasons starting with a multibyte word are not mistaken for durations
+    let (split, _) = raw.char_indices().last()?;
+    let (amount, unit) = raw.split_at(split);
+    let amount = amount.parse::<u64>().ok()?;
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        "w" => 60 * 60 * 24 * 7,
+        _ => return None,
+    };
+    amount.checked_mul(multiplier).map(Some)
+}
+
+/// when a ban or mute lasting `duration` seconds from `now` expires, None if it is permanent.
+/// Durations too long to store are made permanent
+pub fn expires_at(now: u64, duration: Option<u64>) -> Option<u64> {
+    duration.and_then(|d| now.checked_add(d)).filter(|e| *e <= i64::MAX as u64)
+}
+
+/// parses an ip address or CIDR range into the address and prefix length
+fn parse_cidr(pattern: &str) -> Option<(IpAddr, u32)> {
+    match pattern.split_once('/') {
+        Some((addr, prefix)) => {
+            let addr = addr.parse::<IpAddr>().ok()?;
+            let prefix = prefix.parse::<u32>().ok()?;
+            let max = if addr.is_ipv4() { 32 } else { 128 };
+            if prefix > max {
+                return None;
+            }
+            Some((addr, prefix))
+        }
+        None => {
+            let addr = pattern.parse::<IpAddr>().ok()?;
+            Some((addr, if addr.is_ipv4() { 32 } else { 128 }))
+        }
+    }
+}
+
+/// checks if an ip address or CIDR range is valid
+pub fn is_valid_cidr(pattern: &str) -> bool {
+    parse_cidr(pattern).is_some()
+}
+
+/// checks if an ip is inside of an ip address or CIDR range
+pub fn cidr_contains(pattern: &str, ip: &IpAddr) -> bool {
+    let (addr, prefix) = match parse_cidr(pattern) {
+        Some(c) => c,
+        None => return false,
+    };
+    // clients connecting over ipv6 with an ipv4 address should still match ipv4 bans,
+    // only mapped addresses count since ones like ::1 would otherwise look like 0.0.0.1
+    let ip = match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().filter(|_| addr.is_ipv4()).map(IpAddr::V4).unwrap_or(*ip),
+        _ => *ip,
+    };
+
+    match (addr, ip) {
+        (IpAddr::V4(range), IpAddr::V4(ip)) => {
+            let mask = if prefix == 0 { 0 } else { u32::MAX << (32 - prefix) };
+            (u32::from(range) & mask) == (u32::from(ip) & mask)
+        }
+        (IpAddr::V6(range), IpAddr::V6(ip)) => {
+            let mask = if prefix == 0 { 0 } else { u128::MAX << (128 - prefix) };
+            (u128::from(range) & mask) == (u128::from(ip) & mask)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(raw: &str) -> IpAddr {
+        raw.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("30s"), Some(Some(30)));
+        assert_eq!(parse_duration("30m"), Some(Some(30 * 60)));
+        assert_eq!(parse_duration("12H"), Some(Some(12 * 60 * 60)));
+        assert_eq!(parse_duration("7d"), Some(Some(7 * 60 * 60 * 24)));
+        assert_eq!(parse_duration("2w"), Some(Some(2 * 60 * 60 * 24 * 7)));
+    }
+
+    #[test]
+    fn parses_permanent_durations() {
+        assert_eq!(parse_duration("perm"), Some(None));
+        assert_eq!(parse_duration("Permanent"), Some(None));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("d"), None);
+        assert_eq!(parse_duration("7"), None);
+        assert_eq!(parse_duration("7y"), None);
+        assert_eq!(parse_duration("-7d"), None);
+    }
+
+    #[test]
+    fn rejects_reasons_ending_in_multibyte_characters() {
+        assert_eq!(parse_duration("Tschüß"), None);
+        assert_eq!(parse_duration("café"), None);
+        assert_eq!(parse_duration("ß"), None);
+        assert_eq!(parse_duration("7ß"), None);
+    }
+
+    #[test]
+    fn rejects_durations_that_overflow() {
+        assert_eq!(parse_duration("99999999999999999w"), None);
+        assert_eq!(parse_duration(&format!("{}s", u64::MAX)), Some(Some(u64::MAX)));
+        assert_eq!(parse_duration(&format!("{}m", u64::MAX)), None);
+    }
+
+    #[test]
+    fn expiries_too_long_to_store_are_permanent() {
+        assert_eq!(expires_at(100, Some(50)), Some(150));
+        assert_eq!(expires_at(100, None), None);
+        assert_eq!(expires_at(100, Some(u64::MAX)), None);
+        assert_eq!(expires_at(100, Some(i64::MAX as u64)), None);
+    }
+
+    #[test]
+    fn parses_cidr_ranges() {
+        assert_eq!(parse_cidr("10.0.0.0/8"), Some((ip("10.0.0.0"), 8)));
+        assert_eq!(parse_cidr("10.1.2.3"), Some((ip("10.1.2.3"), 32)));
+        assert_eq!(parse_cidr("0.0.0.0/0"), Some((ip("0.0.0.0"), 0)));
+        assert_eq!(parse_cidr("2001:db8::/32"), Some((ip("2001:db8::"), 32)));
+        assert_eq!(parse_cidr("::1"), Some((ip("::1"), 128)));
+        assert_eq!(parse_cidr("::/128"), Some((ip("::"), 128)));
+    }
+
+    #[test]
+    fn rejects_invalid_cidr_ranges() {
+        assert_eq!(parse_cidr("10.0.0.0/33"), None);
+        assert_eq!(parse_cidr("2001:db8::/129"), None);
+        assert_eq!(parse_cidr("10.0.0.0/"), None);
+        assert_eq!(parse_cidr("10.0.0.0/-1"), None);
+        assert_eq!(parse_cidr("10.0.0/8"), None);
+        assert_eq!(parse_cidr("not an ip"), None);
+        assert!(!is_valid_cidr("300.0.0.1"));
+    }
+
+    #[test]
+    fn matches_ipv4_ranges() {
+        assert!(cidr_contains("10.0.0.0/8", &ip("10.1.2.3")));
+        assert!(!cidr_contains("10.0.0.0/8", &ip("11.0.0.1")));
+        assert!(cidr_contains("10.1.2.3", &ip("10.1.2.3")));
+        assert!(!cidr_contains("10.1.2.3", &ip("10.1.2.4")));
+        assert!(cidr_contains("192.168.1.0/31", &ip("192.168.1.1")));
+        assert!(!cidr_contains("192.168.1.0/31", &ip("192.168.1.2")));
+    }
+
+    #[test]
+    fn zero_prefix_matches_everything_of_the_same_family() {
+        assert!(cidr_contains("0.0.0.0/0", &ip("1.2.3.4")));
+        assert!(cidr_contains("0.0.0.0/0", &ip("255.255.255.255")));
+        assert!(cidr_contains("::/0", &ip("2001:db8::1")));
+        assert!(!cidr_contains("::/0", &ip("1.2.3.4")));
+    }
+
+    #[test]
+    fn matches_ipv6_ranges() {
+        assert!(cidr_contains("2001:db8::/32", &ip("2001:db8:ffff::1")));
+        assert!(!cidr_contains("2001:db8::/32", &ip("2001:db9::1")));
+        assert!(!cidr_contains("10.0.0.0/8", &ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn ipv4_mapped_ipv6_addresses_match_ipv4_ranges() {
+        assert!(cidr_contains("10.0.0.0/8", &ip("::ffff:10.1.2.3")));
+        assert!(!cidr_contains("10.0.0.0/8", &ip("::ffff:11.1.2.3")));
+        assert!(cidr_contains("10.1.2.3", &ip("::ffff:10.1.2.3")));
+        // ipv4 compatible addresses are not mapped
+        assert!(!cidr_contains("0.0.0.0/8", &ip("::1")));
+        assert!(!cidr_contains("0.0.0.0/0", &ip("::1")));
+        assert!(!cidr_contains("10.1.2.3", &ip("::10.1.2.3")));
+        // ipv6 ranges still compare against the full address
+        assert!(cidr_contains("::ffff:0:0/96", &ip("::ffff:10.1.2.3")));
+    }
+
+    #[test]
+    fn invalid_ranges_match_nothing() {
+        assert!(!cidr_contains("10.0.0.0/40", &ip("10.0.0.1")));
+        assert!(!cidr_contains("", &ip("10.0.0.1")));
+    }
+}
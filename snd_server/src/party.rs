@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use log::info;
+use uuid::Uuid;
+use snd_network_lib::party_data::{PartyActionData, PartyActionKind, PartyMemberData};
+use crate::config::ServerConfig;
+use crate::database::PlayerValueDB;
+use crate::session::{SessionMessage, SessionRegistry};
+use crate::state::ServerState;
+
+const LOG_TARGET: &str = "party";
+
+/// How the EXP and items from a won encounter are split between the players who fought
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum LootRule {
+    /// everyone gets the same EXP and the items are handed out in turn
+    Even,
+    /// EXP is split by the damage each player dealt and the best items go to the top damage dealers
+    Damage,
+    /// EXP is split evenly and each item goes to a random player
+    Random,
+}
+
+impl FromStr for LootRule {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "even" => Ok(Self::Even),
+            "damage" => Ok(Self::Damage),
+            "random" => Ok(Self::Random),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A group of players that share encounters
+pub struct Party {
+    pub leader: Uuid,
+    /// every member including the leader, in the order they joined
+    pub members: Vec<Uuid>,
+}
+
+struct Invite {
+    from: Uuid,
+    sent: Instant,
+}
+
+/// Keeps track of every party and pending invite. Parties only exist while their members are online
+pub struct PartyRegistry {
+    parties: HashMap<u32, Party>,
+    member_of: HashMap<Uuid, u32>,
+    /// pending invites by the invited player, a new invite replaces the old one
+    invites: HashMap<Uuid, Invite>,
+    next_id: u32,
+}
+
+impl PartyRegistry {
+    pub fn new() -> Self {
+        Self {
+            parties: HashMap::new(),
+            member_of: HashMap::new(),
+            invites: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn get(&self, uuid: &Uuid) -> Option<&Party> {
+        self.member_of.get(uuid).and_then(|id| self.parties.get(id))
+    }
+
+    /// the members of a player's party, or only the player if they are not in one
+    pub fn members_of(&self, uuid: &Uuid) -> Vec<Uuid> {
+        match self.get(uuid) {
+            Some(party) => party.members.clone(),
+            None => vec![uuid.clone()],
+        }
+    }
+
+    fn invite(&mut self, from: &Uuid, to: &Uuid, max_size: usize) -> Result<(), String> {
+        if let Some(party) = self.get(from) {
+            if party.leader != *from {
+                return Err(format!("Only the party leader can invite players"));
+            }
+            if party.members.len() >= max_size {
+                return Err(format!("Your party is full"));
+            }
+        }
+        if self.member_of.contains_key(to) {
+            return Err(format!("That player is already in a party"));
+        }
+        self.invites.insert(to.clone(), Invite { from: from.clone(), sent: Instant::now() });
+        Ok(())
+    }
+
+    /// joins the party of the player who sent the invite, creating it if needed.
+    /// Returns the members of the party before joining
+    fn accept(&mut self, uuid: &Uuid, timeout: Duration, max_size: usize) -> Result<Vec<Uuid>, String> {
+        let invite = self.invites.remove(uuid).ok_or(format!("You do not have a party invite"))?;
+        if invite.sent.elapsed() > timeout {
+            return Err(format!("Your party invite has expired"));
+        }
+        if self.member_of.contains_key(uuid) {
+            return Err(format!("You are already in a party, leave it first"));
+        }
+
+        let id = match self.member_of.get(&invite.from) {
+            Some(id) => *id,
+            None => {
+                let id = self.next_id;
+                self.next_id += 1;
+                self.parties.insert(id, Party { leader: invite.from.clone(), members: vec![invite.from.clone()] });
+                self.member_of.insert(invite.from.clone(), id);
+                id
+            }
+        };
+        let party = self.parties.get_mut(&id).unwrap();
+        if party.leader != invite.from {
+            return Err(format!("That invite is no longer valid"));
+        }
+        if party.members.len() >= max_size {
+            return Err(format!("That party is full"));
+        }
+        let before = party.members.clone();
+        party.members.push(uuid.clone());
+        self.member_of.insert(uuid.clone(), id);
+        Ok(before)
+    }
+
+    /// removes a player from their party, disbanding it if only one member is left.
+    /// Returns the members left in the party
+    pub fn leave(&mut self, uuid: &Uuid) -> Option<Vec<Uuid>> {
+        let id = self.member_of.remove(uuid)?;
+        let party = self.parties.get_mut(&id)?;
+        party.members.retain(|m| m != uuid);
+        let remaining = party.members.clone();
+
+        if remaining.len() < 2 {
+            self.parties.remove(&id);
+            for m in &remaining {
+                self.member_of.remove(m);
+            }
+        } else if party.leader == *uuid {
+            // the longest member takes over
+            party.leader = remaining[0].clone();
+        }
+        Some(remaining)
+    }
+
+    /// forgets every invite sent to or from a player
+    fn clear_invites(&mut self, uuid: &Uuid) {
+        self.invites.remove(uuid);
+        self.invites.retain(|_, invite| invite.from != *uuid);
+    }
+}
+
+/// Handles a party action from a player.
+/// Returns an error to show the player if the action could not be done
+pub fn handle_action(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str, action: PartyActionData) -> Result<(), String> {
+    let ServerState { sessions, parties, .. } = state;
+    match action.kind {
+        PartyActionKind::Invite => {
+            let (target, target_name) = {
+                let sessions = sessions.lock().unwrap();
+                let target = sessions.find_by_name(action.target.as_str())
+                    .ok_or(format!("{} is not online", action.target))?;
+                (target, sessions.get(&target).unwrap().username.clone())
+            };
+            if target == *uuid {
+                return Err(format!("You can not invite yourself"));
+            }
+            parties.lock().unwrap().invite(uuid, &target, config.party_max_size)?;
+            info!(target:LOG_TARGET, "{} invited {} to their party", username, target_name);
+            notify(sessions, &[target], format!("{} invited you to their party! Use 'party accept' or 'party decline'", username));
+            notify(sessions, &[uuid.clone()], format!("Invited {} to your party", target_name));
+        }
+        PartyActionKind::Accept => {
+            let before = parties.lock().unwrap()
+                .accept(uuid, Duration::from_secs(config.party_invite_timeout), config.party_max_size)?;
+            info!(target:LOG_TARGET, "{} joined a party", username);
+            notify(sessions, &before, format!("{} joined the party", username));
+            notify(sessions, &[uuid.clone()], format!("You joined the party"));
+            push_status(state, uuid);
+        }
+        PartyActionKind::Decline => {
+            let invite = parties.lock().unwrap().invites.remove(uuid)
+                .ok_or(format!("You do not have a party invite"))?;
+            notify(sessions, &[invite.from], format!("{} declined your party invite", username));
+            notify(sessions, &[uuid.clone()], format!("Declined the party invite"));
+        }
+        PartyActionKind::Leave => {
+            let remaining = parties.lock().unwrap().leave(uuid)
+                .ok_or(format!("You are not in a party"))?;
+            info!(target:LOG_TARGET, "{} left their party", username);
+            notify(sessions, &remaining, format!("{} left the party", username));
+            notify(sessions, &[uuid.clone()], format!("You left the party"));
+            push_status(state, uuid);
+            push_status_all(state, &remaining);
+        }
+        PartyActionKind::Kick => {
+            let target = sessions.lock().unwrap().find_by_name(action.target.as_str())
+                .ok_or(format!("{} is not online", action.target))?;
+            let remaining = {
+                let mut parties = parties.lock().unwrap();
+                let party = parties.get(uuid).ok_or(format!("You are not in a party"))?;
+                if party.leader != *uuid {
+                    return Err(format!("Only the party leader can kick players"));
+                }
+                if target == *uuid {
+                    return Err(format!("You can not kick yourself, leave the party instead"));
+                }
+                if !party.members.contains(&target) {
+                    return Err(format!("{} is not in your party", action.target));
+                }
+                parties.leave(&target).unwrap_or_default()
+            };
+            info!(target:LOG_TARGET, "{} kicked {} from their party", username, action.target);
+            notify(sessions, &[target], format!("You were kicked from the party"));
+            notify(sessions, &remaining, format!("{} was kicked from the party", action.target));
+            push_status(state, &target);
+            push_status_all(state, &remaining);
+        }
+        PartyActionKind::Status => {
+            push_status(state, uuid);
+        }
+    }
+    Ok(())
+}
+
+/// removes a player that disconnected from their party and any invites
+pub fn disconnect(state: &ServerState, uuid: &Uuid, username: &str) {
+    let ServerState { sessions, parties, .. } = state;
+    let remaining = {
+        let mut parties = parties.lock().unwrap();
+        parties.clear_invites(uuid);
+        parties.leave(uuid)
+    };
+    if let Some(remaining) = remaining {
+        notify(sessions, &remaining, format!("{} left the party", username));
+        push_status_all(state, &remaining);
+    }
+}
+
+/// sends the status of a player's party to every member so their clients can show it.
+/// A player that is not in a party is sent an empty status
+pub fn push_status(state: &ServerState, uuid: &Uuid) {
+    let ServerState { db, sessions, parties, .. } = state;
+    let party = parties.lock().unwrap().get(uuid).map(|p| (p.leader.clone(), p.members.clone()));
+    let (leader, members) = match party {
+        Some(p) => p,
+        None => {
+            sessions.lock().unwrap().send(uuid, SessionMessage::Party(Vec::new()));
+            return;
+        }
+    };
+
+    let status = {
+        let db = db.lock().unwrap();
+        members.iter().map(|m| {
+            let level = db.get_player_level(m).unwrap_or(1);
+            PartyMemberData {
+                name: db.get_player_value(m, PlayerValueDB::Username).unwrap_or_default(),
+                level,
+                health: db.get_player_health(m).unwrap_or(0),
+                max_health: db.get_player_max_health(m).unwrap_or(0),
+                leader: *m == leader,
+            }
+        }).collect::<Vec<PartyMemberData>>()
+    };
+
+    let sessions = sessions.lock().unwrap();
+    for m in &members {
+        sessions.send(m, SessionMessage::Party(status.clone()));
+    }
+}
+
+/// sends the party status to every party the players are in, once per party
+pub fn push_status_all(state: &ServerState, players: &[Uuid]) {
+    let mut done: Vec<Uuid> = Vec::new();
+    for p in players {
+        if done.contains(p) {
+            continue;
+        }
+        done.extend(state.parties.lock().unwrap().members_of(p));
+        push_status(state, p);
+    }
+}
+
+/// sends the party status to the parties of any of the players that are in one, such as after their health changes
+pub fn refresh(state: &ServerState, players: &[Uuid]) {
+    let in_party = {
+        let parties = state.parties.lock().unwrap();
+        players.iter().filter(|p| parties.get(p).is_some()).cloned().collect::<Vec<Uuid>>()
+    };
+    push_status_all(state, &in_party);
+}
+
+fn notify<S: Into<String>>(sessions: &Arc<Mutex<SessionRegistry>>, players: &[Uuid], msg: S) {
+    let msg = msg.into();
+    let sessions = sessions.lock().unwrap();
+    for p in players {
+        sessions.send(p, SessionMessage::Event(msg.clone()));
+    }
+}
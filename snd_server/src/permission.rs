@@ -0,0 +1,61 @@
+use std::str::FromStr;
+
+/// An account's role, stored in the `role` column of the players table
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Debug)]
+pub enum Role {
+    Player, Moderator, Admin,
+}
+
+impl Role {
+    /// checks if this role is allowed to perform an action
+    pub fn has(&self, permission: Permission) -> bool {
+        use Permission::*;
+        match self {
+            Self::Player => false,
+            Self::Moderator => matches!(permission, ListPlayers | Kick | Ban | Mute | Broadcast | InspectInventory),
+            // roles can only be changed from the server console
+            Self::Admin => permission != ManageRoles,
+        }
+    }
+}
+
+impl ToString for Role {
+    fn to_string(&self) -> String {
+        match self {
+            Self::Player => "player",
+            Self::Moderator => "moderator",
+            Self::Admin => "admin",
+        }.to_string()
+    }
+}
+
+impl FromStr for Role {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "player" => Ok(Self::Player),
+            "moderator" | "mod" => Ok(Self::Moderator),
+            "admin" => Ok(Self::Admin),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A privileged action that requires a role to perform
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Permission {
+    ListPlayers,
+    Kick,
+    Ban,
+    Mute,
+    IpBan,
+    ViewAudit,
+    Broadcast,
+    Give,
+    SetLevel,
+    Teleport,
+    InspectInventory,
+    Shutdown,
+    ManageRoles,
+}
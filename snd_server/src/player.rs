@@ -1,7 +1,46 @@
 use uuid::Uuid;
+use snd_network_lib::player_data::PlayerData;
+use crate::class::ClassList;
+use crate::database::Database;
 
 #[derive(Clone)]
 pub struct Player {
     pub uuid: Uuid,
     pub name: String,
-}
\ No newline at end of file
+}
+/// the most health a level 1 player can have, new players start with full health
+pub const BASE_HEALTH: u32 = 100;
+/// how much the most health a player can have goes up each level
+pub const HEALTH_PER_LEVEL: u32 = 10;
+/// the skill points a player gets each time they level up
+pub const SKILL_POINTS_PER_LEVEL: u32 = 1;
+/// the region new players start in
+pub const START_REGION: &str = "Plains of Arenlok";
+
+/// the most health a player can have at a level
+pub fn max_health(level: u32) -> u32 {
+    BASE_HEALTH.saturating_add(HEALTH_PER_LEVEL.saturating_mul(level.saturating_sub(1)))
+}
+
+/// the exp needed to go from a level to the next one
+pub fn exp_to_level(level: u32) -> u32 {
+    level.saturating_mul(50) / 2
+}
+
+/// the player's stats as shown in their stats panel
+pub fn stats(db: &Database, classes: &ClassList, uuid: &Uuid) -> Option<PlayerData> {
+    let level = db.get_player_level(uuid)?;
+    Some(PlayerData {
+        level,
+        exp: db.get_player_exp(uuid)?,
+        region: db.get_player_region(uuid)?,
+        steps: db.get_player_steps(uuid)?,
+        health: db.get_player_health(uuid)?,
+        max_health: db.get_player_max_health(uuid)?,
+        next_exp: exp_to_level(level),
+        unread_mail: db.count_unread_mail(uuid),
+        guild: db.get_player_guild(uuid).map(|(g, _)| g.name).unwrap_or_default(),
+        class: classes.of(db, uuid).map(|c| c.name.clone()).unwrap_or_default(),
+        skill_points: db.get_skill_points(uuid),
+    })
+}
@@ -0,0 +1,444 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+use log::{error, info, warn};
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use serde::Deserialize;
+use uuid::Uuid;
+use snd_network_lib::quest_data::{QuestActionData, QuestActionKind, QuestData, QuestState};
+use crate::config::ServerConfig;
+use crate::data::read_data_file;
+use crate::database::Database;
+use crate::game_event::{self, GameEvent};
+use crate::inventory;
+use crate::item::{Item, ItemRarity, ItemType};
+use crate::session::SessionMessage;
+use crate::state::ServerState;
+
+const LOG_TARGET: &str = "quest";
+
+/// The name of the quests file in the data folder
+const QUESTS_FILE: &str = "quests.toml";
+
+/// The contents written to a new quests file when one does not exist yet
+pub const DEFAULT_QUESTS: &str = r#"# Every quest needs a unique id, a name, a description, the giver who offers it, an objective and a count.
+# objective is one of:
+#   walk: take count steps, only in the region given as the target if there is one
+#   defeat: defeat count enemies, only of the race given as the target if there is one
+#   collect: find count items, only of the item type given as the target if there is one
+# Optional settings:
+#   region: the giver is only met in this region
+#   min_level: the level a player needs before the giver offers the quest
+#   repeatable: if the quest can be taken again after it is turned in
+#   reward_gold, reward_exp: given when the quest is turned in
+#   reward_item and reward_rarity: an item type given when the quest is turned in, made at the player's level
+# Changing the id of a quest will remove it from every player's quest log
+
+[[quest]]
+id = "goblin_trouble"
+name = "Goblin Trouble"
+description = "Goblins have been raiding the farms on the plains, drive them off."
+giver = "Farmer Bram"
+objective = "defeat"
+target = "Goblin"
+count = 3
+reward_gold = 40
+reward_exp = 60
+
+[[quest]]
+id = "wolf_pelts"
+name = "Wolf Pelts"
+description = "The hunter needs pelts before the winter comes."
+giver = "Hunter Maeve"
+objective = "defeat"
+target = "Wolf"
+count = 5
+reward_gold = 60
+reward_exp = 80
+reward_item = "boots"
+reward_rarity = "rare"
+
+[[quest]]
+id = "mapping_the_plains"
+name = "Mapping the Plains"
+description = "Walk the plains so they can finally be put on a map."
+giver = "Wandering Cartographer"
+objective = "walk"
+target = "Plains of Arenlok"
+count = 200
+reward_gold = 30
+reward_exp = 100
+
+[[quest]]
+id = "smith_supplies"
+name = "Supplies for the Smith"
+description = "The smith melts down old blades to forge new ones."
+giver = "Smith Oda"
+objective = "collect"
+target = "sword"
+count = 2
+repeatable = true
+reward_gold = 50
+reward_exp = 50
+
+[[quest]]
+id = "bandit_bounty"
+name = "Bandit Bounty"
+description = "A bounty has been posted for the bandits robbing travellers."
+giver = "Captain Hale"
+min_level = 5
+objective = "defeat"
+target = "Bandit"
+count = 5
+reward_gold = 120
+reward_exp = 150
+reward_item = "sword"
+reward_rarity = "epic"
+"#;
+
+/// What a player has to do to complete a quest
+#[derive(Clone, Debug)]
+enum Objective {
+    /// take steps, only in the region if there is one
+    Walk(Option<String>),
+    /// defeat enemies, only of the race if there is one
+    Defeat(Option<String>),
+    /// find items, only of the type if there is one
+    Collect(Option<ItemType>),
+}
+
+impl Objective {
+    /// if the event counts towards the objective, the region is only read for walk objectives that need it
+    fn counts(&self, event: &GameEvent, region: &mut dyn FnMut() -> Option<String>) -> bool {
+        match (self, event) {
+            (Self::Walk(None), GameEvent::Step) => true,
+            (Self::Walk(Some(target)), GameEvent::Step) => region().map_or(false, |r| r.eq_ignore_ascii_case(target)),
+            (Self::Defeat(target), GameEvent::Kill { race, .. }) => target.as_ref().map_or(true, |t| t.eq_ignore_ascii_case(race)),
+            (Self::Collect(target), GameEvent::FindItem(item_type, _)) => target.map_or(true, |t| t == *item_type),
+            _ => false,
+        }
+    }
+
+    fn describe(&self, count: u32) -> String {
+        match self {
+            Self::Walk(None) => format!("Take {} steps", count),
+            Self::Walk(Some(region)) => format!("Take {} steps in {}", count, region),
+            Self::Defeat(None) => format!("Defeat {} enemies", count),
+            Self::Defeat(Some(race)) => format!("Defeat {} {}s", count, race),
+            Self::Collect(None) => format!("Find {} items", count),
+            Self::Collect(Some(item_type)) => format!("Find {} {}s", count, item_type.to_string()),
+        }
+    }
+}
+
+pub struct Quest {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub giver: String,
+    objective: Objective,
+    count: u32,
+    /// the giver is only met in this region if there is one
+    region: Option<String>,
+    min_level: u32,
+    repeatable: bool,
+    reward_gold: u32,
+    reward_exp: u32,
+    reward_item: Option<(ItemType, ItemRarity)>,
+}
+
+impl Quest {
+    fn as_data(&self, progress: u32, state: QuestState) -> QuestData {
+        QuestData {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            description: self.description.clone(),
+            giver: self.giver.clone(),
+            objective: self.objective.describe(self.count),
+            progress,
+            goal: self.count,
+            reward_gold: self.reward_gold,
+            reward_exp: self.reward_exp,
+            reward_item: self.reward_item
+                .map(|(item_type, rarity)| format!("{} {}", rarity.to_string(), item_type.to_string()))
+                .unwrap_or_default(),
+            state,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct QuestFile {
+    quest: Option<Vec<RawQuest>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawQuest {
+    id: String,
+    name: String,
+    description: String,
+    giver: String,
+    objective: String,
+    target: Option<String>,
+    count: u32,
+    region: Option<String>,
+    min_level: Option<u32>,
+    repeatable: Option<bool>,
+    reward_gold: Option<u32>,
+    reward_exp: Option<u32>,
+    reward_item: Option<String>,
+    reward_rarity: Option<String>,
+}
+
+impl RawQuest {
+    fn parse(self) -> Result<Quest, String> {
+        let objective = match self.objective.to_ascii_lowercase().as_str() {
+            "walk" => Objective::Walk(self.target),
+            "defeat" => Objective::Defeat(self.target),
+            "collect" => match self.target {
+                Some(t) => Objective::Collect(Some(ItemType::from_str(t.as_str())
+                    .map_err(|_| format!("unknown item type '{}'", t))?)),
+                None => Objective::Collect(None),
+            },
+            other => return Err(format!("unknown objective '{}'", other)),
+        };
+        if self.count == 0 {
+            return Err(format!("the count must be at least 1"));
+        }
+        let reward_item = match self.reward_item {
+            Some(item) => {
+                let item_type = ItemType::from_str(item.as_str()).map_err(|_| format!("unknown item type '{}'", item))?;
+                let rarity = match self.reward_rarity {
+                    Some(r) => ItemRarity::from_str(r.as_str()).map_err(|_| format!("unknown rarity '{}'", r))?,
+                    None => ItemRarity::Common,
+                };
+                Some((item_type, rarity))
+            }
+            None => None,
+        };
+
+        Ok(Quest {
+            id: self.id,
+            name: self.name,
+            description: self.description,
+            giver: self.giver,
+            objective,
+            count: self.count,
+            region: self.region,
+            min_level: self.min_level.unwrap_or(1),
+            repeatable: self.repeatable.unwrap_or(false),
+            reward_gold: self.reward_gold.unwrap_or(0),
+            reward_exp: self.reward_exp.unwrap_or(0),
+            reward_item,
+        })
+    }
+}
+
+/// Every quest players can take, read from the data folder when the server starts,
+/// and the quest each player is being offered
+pub struct QuestList {
+    quests: Vec<Quest>,
+    /// the id of the quest offered to each player, a new offer replaces the old one.
+    /// This lock is never held while taking another
+    offers: Mutex<HashMap<Uuid, String>>,
+}
+
+impl QuestList {
+    pub fn load(config: &ServerConfig) -> Self {
+        let raw = read_data_file(config, QUESTS_FILE, DEFAULT_QUESTS);
+        let file = toml::from_str::<QuestFile>(raw.as_str()).unwrap_or_else(|e| {
+            error!(target:LOG_TARGET, "Invalid {}, using the default quests: {}", QUESTS_FILE, e);
+            toml::from_str(DEFAULT_QUESTS).expect("The default quests are invalid")
+        });
+
+        let mut quests: Vec<Quest> = Vec::new();
+        for raw in file.quest.unwrap_or_default() {
+            let id = raw.id.clone();
+            if quests.iter().any(|q| q.id == id) {
+                warn!(target:LOG_TARGET, "Skipping quest '{}': the id is used more than once", id);
+                continue;
+            }
+            match raw.parse() {
+                Ok(quest) => quests.push(quest),
+                Err(e) => warn!(target:LOG_TARGET, "Skipping quest '{}': {}", id, e),
+            }
+        }
+        info!(target:LOG_TARGET, "Loaded {} quests", quests.len());
+        Self { quests, offers: Mutex::new(HashMap::new()) }
+    }
+
+    fn by_id(&self, id: &str) -> Option<&Quest> {
+        self.quests.iter().find(|q| q.id == id)
+    }
+
+    /// finds a quest by the name players see, or its id
+    fn by_name(&self, name: &str) -> Option<&Quest> {
+        self.quests.iter().find(|q| q.name.eq_ignore_ascii_case(name) || q.id.eq_ignore_ascii_case(name))
+    }
+
+    /// the quests a player has accepted but not turned in yet
+    fn log(&self, db: &Database, uuid: &Uuid) -> Vec<QuestData> {
+        db.get_player_quests(uuid).into_iter()
+            .filter(|(_, _, done)| !done)
+            .filter_map(|(id, progress, _)| self.by_id(id.as_str()).map(|q| {
+                let state = if progress >= q.count { QuestState::Complete } else { QuestState::Active };
+                q.as_data(progress, state)
+            }))
+            .collect()
+    }
+}
+
+/// A quest giver offers the player a quest they can take, if there are any
+pub fn offer(state: &ServerState, uuid: &Uuid) {
+    let quests = &state.quests;
+    let quest = {
+        let db = state.db.lock().unwrap();
+        let level = db.get_player_level(uuid).unwrap_or(1);
+        let region = db.get_player_region(uuid).unwrap_or_default();
+        let taken = db.get_player_quests(uuid);
+        let available = quests.quests.iter()
+            .filter(|q| q.min_level <= level)
+            .filter(|q| q.region.as_ref().map_or(true, |r| r.eq_ignore_ascii_case(region.as_str())))
+            .filter(|q| match taken.iter().find(|(id, _, _)| *id == q.id) {
+                // quests that are turned in can only be taken again if they are repeatable
+                Some((_, _, done)) => *done && q.repeatable,
+                None => true,
+            })
+            .collect::<Vec<&Quest>>();
+        match available.choose(&mut thread_rng()) {
+            Some(q) => *q,
+            None => return,
+        }
+    };
+
+    quests.offers.lock().unwrap().insert(uuid.clone(), quest.id.clone());
+    state.sessions.lock().unwrap().send(uuid, SessionMessage::QuestOffer(quest.as_data(0, QuestState::Offered)));
+}
+
+/// Handles a quest action from a player.
+/// Returns an error to show the player if the action could not be done
+pub fn handle_action(state: &ServerState, config: &ServerConfig, uuid: &Uuid, username: &str, action: QuestActionData) -> Result<(), String> {
+    let quests = &state.quests;
+    match action.kind {
+        QuestActionKind::Accept => {
+            let id = quests.offers.lock().unwrap().remove(uuid).ok_or(format!("Nobody is offering you a quest"))?;
+            let quest = quests.by_id(id.as_str()).ok_or(format!("That quest no longer exists"))?;
+            {
+                let db = state.db.lock().unwrap();
+                if quests.log(&db, uuid).len() >= config.quest_max_active {
+                    return Err(format!("You can only have {} quests at once, abandon or turn one in first", config.quest_max_active));
+                }
+                if !db.start_quest(uuid, id.as_str()) {
+                    return Err(format!("Failed to start the quest"));
+                }
+            }
+            info!(target:LOG_TARGET, "{} accepted the quest '{}'", username, quest.id);
+            notify(state, uuid, format!("Accepted '{}': {}", quest.name, quest.objective.describe(quest.count)));
+        }
+        QuestActionKind::Decline => {
+            let id = quests.offers.lock().unwrap().remove(uuid).ok_or(format!("Nobody is offering you a quest"))?;
+            if let Some(quest) = quests.by_id(id.as_str()) {
+                notify(state, uuid, format!("{} nods and goes on their way", quest.giver));
+            }
+        }
+        QuestActionKind::Abandon => {
+            let quest = quests.by_name(action.quest.as_str()).ok_or(format!("There is no quest called '{}'", action.quest))?;
+            if !state.db.lock().unwrap().abandon_quest(uuid, quest.id.as_str()) {
+                return Err(format!("You are not on the quest '{}'", quest.name));
+            }
+            info!(target:LOG_TARGET, "{} abandoned the quest '{}'", username, quest.id);
+            notify(state, uuid, format!("Abandoned '{}'", quest.name));
+        }
+        QuestActionKind::TurnIn => {
+            let quest = quests.by_name(action.quest.as_str()).ok_or(format!("There is no quest called '{}'", action.quest))?;
+            let reward = {
+                let db = state.db.lock().unwrap();
+                let progress = db.get_player_quests(uuid).into_iter()
+                    .find(|(id, _, done)| *id == quest.id && !done)
+                    .map(|(_, progress, _)| progress)
+                    .ok_or(format!("You are not on the quest '{}'", quest.name))?;
+                if progress < quest.count {
+                    return Err(format!("You have not finished '{}' yet ({} / {})", quest.name, progress, quest.count));
+                }
+                if quest.reward_item.is_some() && inventory::is_full(&db, config, uuid) {
+                    return Err(format!("Make room in your inventory for the reward before turning in '{}'", quest.name));
+                }
+                // marking the quest as done first means the rewards can only ever be given once
+                if !db.finish_quest(uuid, quest.id.as_str()) {
+                    return Err(format!("You are not on the quest '{}'", quest.name));
+                }
+                db.add_player_gold(uuid, quest.reward_gold);
+                db.add_player_exp(uuid, quest.reward_exp);
+                db.check_levelup(uuid);
+                quest.reward_item.map(|(item_type, rarity)| {
+                    let item = Item::new_rand(item_type, uuid, db.get_player_level(uuid).unwrap_or(1), rarity);
+                    db.new_item(&item);
+                    item
+                })
+            };
+            info!(target:LOG_TARGET, "{} turned in the quest '{}'", username, quest.id);
+            let mut message = format!("{} thanks you for completing '{}'! You got {} gold and {} exp",
+                                      quest.giver, quest.name, quest.reward_gold, quest.reward_exp);
+            if let Some(item) = &reward {
+                message.push_str(format!(" and '{}'", item.name).as_str());
+            }
+            notify(state, uuid, message);
+            if let Some(item) = reward {
+                game_event::fire(state, uuid, GameEvent::FindItem(item.item_type, item.rarity));
+            }
+        }
+        QuestActionKind::Log => {}
+    }
+    send_log(state, uuid);
+    Ok(())
+}
+
+/// Counts the event towards any of the player's quests it is part of
+pub fn progress(state: &ServerState, uuid: &Uuid, event: &GameEvent) {
+    let quests = &state.quests;
+    let (log, completed) = {
+        let db = state.db.lock().unwrap();
+        let mut region = None;
+        let mut completed = Vec::new();
+        let mut changed = false;
+        for (id, progress, done) in db.get_player_quests(uuid) {
+            let quest = match quests.by_id(id.as_str()) {
+                Some(q) if !done && progress < q.count => q,
+                _ => continue,
+            };
+            let mut current_region = || region.get_or_insert_with(|| db.get_player_region(uuid)).clone();
+            if quest.objective.counts(event, &mut current_region) {
+                db.set_quest_progress(uuid, id.as_str(), progress + 1);
+                changed = true;
+                if progress + 1 == quest.count {
+                    completed.push(quest.name.clone());
+                }
+            }
+        }
+        if !changed {
+            return;
+        }
+        (quests.log(&db, uuid), completed)
+    };
+
+    let sessions = state.sessions.lock().unwrap();
+    for name in completed {
+        sessions.send(uuid, SessionMessage::Event(format!("Quest complete: '{}'! Use 'quest turnin {}' to get your reward", name, name)));
+    }
+    sessions.send(uuid, SessionMessage::Quests(log));
+}
+
+/// forgets the quest being offered to a player that disconnected
+pub fn disconnect(state: &ServerState, uuid: &Uuid) {
+    state.quests.offers.lock().unwrap().remove(uuid);
+}
+
+fn send_log(state: &ServerState, uuid: &Uuid) {
+    let log = state.quests.log(&state.db.lock().unwrap(), uuid);
+    state.sessions.lock().unwrap().send(uuid, SessionMessage::Quests(log));
+}
+
+fn notify<S: Into<String>>(state: &ServerState, uuid: &Uuid, msg: S) {
+    state.sessions.lock().unwrap().send(uuid, SessionMessage::Event(msg.into()));
+}
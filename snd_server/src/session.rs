@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::SystemTime;
+use uuid::Uuid;
+use snd_network_lib::achievement_data::AchievementData;
+use snd_network_lib::quest_data::QuestData;
+use snd_network_lib::broadcast_data::{BroadcastData, BroadcastKind};
+use snd_network_lib::chat_data::ChatData;
+use snd_network_lib::encounter_data::EncounterData;
+use snd_network_lib::party_data::PartyMemberData;
+
+/// A message pushed to a connected player's handler thread from elsewhere in the server
+#[derive(Clone, Debug)]
+pub enum SessionMessage {
+    /// text for the client to print out
+    Event(String),
+    /// disconnect the player with the given reason
+    Kick(String),
+    /// a chat message to deliver to the player
+    Chat(ChatData),
+    /// a server wide message such as an announcement
+    Broadcast(BroadcastData),
+    /// an update about the fight the player is in
+    Encounter(EncounterData),
+    /// the status of the player's party, empty if they are not in one
+    Party(Vec<PartyMemberData>),
+    /// the player unlocked an achievement
+    Achievement(AchievementData),
+    /// a quest giver is offering the player a quest
+    QuestOffer(QuestData),
+    /// the player's quest log after it changed
+    Quests(Vec<QuestData>),
+    /// the player's stats changed without them doing anything, such as being hit in their party's fight
+    StatsChanged,
+    /// the server is shutting down, disconnect with the given message
+    Shutdown(String),
+}
+
+/// A player that is currently connected to the server
+pub struct Session {
+    pub uuid: Uuid,
+    pub username: String,
+    pub ip: String,
+    pub connected_at: SystemTime,
+    sender: Sender<SessionMessage>,
+}
+
+/// Keeps track of every logged in player so the rest of the server can reach them
+pub struct SessionRegistry {
+    sessions: HashMap<Uuid, Session>,
+}
+
+impl SessionRegistry {
+    pub fn new() -> Self {
+        Self {
+            sessions: HashMap::new(),
+        }
+    }
+
+    /// registers a player's connection, returning the receiver their handler should poll for messages
+    pub fn register(&mut self, uuid: &Uuid, username: String, ip: String) -> Receiver<SessionMessage> {
+        let (sender, receiver) = channel();
+        self.sessions.insert(uuid.clone(), Session {
+            uuid: uuid.clone(),
+            username, ip,
+            connected_at: SystemTime::now(),
+            sender,
+        });
+        receiver
+    }
+
+    pub fn remove(&mut self, uuid: &Uuid) {
+        self.sessions.remove(uuid);
+    }
+
+    pub fn is_online(&self, uuid: &Uuid) -> bool {
+        self.sessions.contains_key(uuid)
+    }
+
+    /// finds the uuid of an online player by their username (case insensitive)
+    pub fn find_by_name(&self, username: &str) -> Option<Uuid> {
+        self.sessions.values()
+            .find(|s| s.username.eq_ignore_ascii_case(username))
+            .map(|s| s.uuid.clone())
+    }
+
+    pub fn get(&self, uuid: &Uuid) -> Option<&Session> {
+        self.sessions.get(uuid)
+    }
+
+    pub fn all(&self) -> Vec<&Session> {
+        let mut sessions = self.sessions.values().collect::<Vec<&Session>>();
+        sessions.sort_by(|a, b| a.username.cmp(&b.username));
+        sessions
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// sends a message to a single player, returns false if they are not online
+    pub fn send(&self, uuid: &Uuid, msg: SessionMessage) -> bool {
+        match self.sessions.get(uuid) {
+            Some(session) => session.sender.send(msg).is_ok(),
+            None => false,
+        }
+    }
+
+    /// sends a message to every online player
+    pub fn broadcast(&self, msg: SessionMessage) {
+        for session in self.sessions.values() {
+            let _ = session.sender.send(msg.clone());
+        }
+    }
+
+    /// shows a server wide message to every online player
+    pub fn announce<S: Into<String>>(&self, kind: BroadcastKind, message: S) {
+        self.broadcast(SessionMessage::Broadcast(BroadcastData { kind, message: message.into() }));
+    }
+}
@@ -0,0 +1,38 @@
+use std::sync::{Arc, Mutex};
+use crate::achievement::AchievementList;
+use crate::bestiary::Bestiary;
+use crate::class::ClassList;
+use crate::consumable::ConsumableList;
+use crate::crafting::CraftingList;
+use crate::database::Database;
+use crate::duel::DuelRegistry;
+use crate::encounter::EncounterRegistry;
+use crate::leaderboard::LeaderboardCache;
+use crate::party::PartyRegistry;
+use crate::quest::QuestList;
+use crate::session::SessionRegistry;
+
+/// The state shared between every connection handler, cloning it only clones the references.
+/// Locks are always taken in the order parties, encounters, duels, leaderboards, database then sessions
+/// so two handlers can never wait on each other
+#[derive(Clone)]
+pub struct ServerState {
+    pub db: Arc<Mutex<Database>>,
+    pub sessions: Arc<Mutex<SessionRegistry>>,
+    pub parties: Arc<Mutex<PartyRegistry>>,
+    pub encounters: Arc<Mutex<EncounterRegistry>>,
+    pub duels: Arc<Mutex<DuelRegistry>>,
+    pub leaderboards: Arc<Mutex<LeaderboardCache>>,
+    /// only read after the server starts so it needs no lock
+    pub achievements: Arc<AchievementList>,
+    /// the quests are only read after the server starts, the offers have their own lock
+    pub quests: Arc<QuestList>,
+    /// only read after the server starts so it needs no lock
+    pub consumables: Arc<ConsumableList>,
+    /// only read after the server starts so it needs no lock
+    pub crafting: Arc<CraftingList>,
+    /// only read after the server starts so it needs no lock
+    pub classes: Arc<ClassList>,
+    /// only read after the server starts so it needs no lock
+    pub bestiary: Arc<Bestiary>,
+}
@@ -0,0 +1,194 @@
+use snd_network_lib::status_data::{StatusEffectData, StatusKind};
+
+/// A timed effect on a player or the enemy in an encounter
+#[derive(Copy, Clone, Debug)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    /// the damage a round for poison, unused for stuns and a percent for everything else
+    pub amount: u32,
+    /// the rounds left before it wears off
+    pub turns: u32,
+}
+
+impl StatusEffect {
+    pub fn new(kind: StatusKind, amount: u32, turns: u32) -> Self {
+        Self { kind, amount, turns }
+    }
+
+    /// what the effect does to someone, e.g. `is poisoned for 4 damage a round for 3 rounds`
+    pub fn describe(&self) -> String {
+        let effect = match self.kind {
+            StatusKind::Poison => format!("is poisoned for {} damage a round", self.amount),
+            StatusKind::Bleed => format!("is bleeding for {}% of their health a round", self.amount),
+            StatusKind::Stun => format!("is stunned"),
+            StatusKind::Weaken => format!("deals {}% less damage", self.amount),
+            StatusKind::Strength => format!("deals {}% more damage", self.amount),
+            StatusKind::Shield => format!("takes {}% less damage", self.amount),
+        };
+        match self.turns {
+            1 => format!("{} for a round", effect),
+            turns => format!("{} for {} rounds", effect, turns),
+        }
+    }
+}
+
+/// Every status effect on a player or the enemy, there is at most one of each kind
+#[derive(Clone, Default, Debug)]
+pub struct StatusEffects(Vec<StatusEffect>);
+
+impl StatusEffects {
+    /// adds an effect, if one of the same kind is already on it keeps the stronger amount and the longer duration.
+    /// Stuns can not be added again until they wear off so nobody is kept stunned.
+    /// Returns false if nothing changed
+    pub fn apply(&mut self, effect: StatusEffect) -> bool {
+        if effect.turns == 0 {
+            return false;
+        }
+        match self.0.iter_mut().find(|e| e.kind == effect.kind) {
+            Some(_) if effect.kind == StatusKind::Stun => false,
+            Some(current) => {
+                let changed = effect.amount > current.amount || effect.turns > current.turns;
+                current.amount = current.amount.max(effect.amount);
+                current.turns = current.turns.max(effect.turns);
+                changed
+            }
+            None => {
+                self.0.push(effect);
+                true
+            }
+        }
+    }
+
+    pub fn has(&self, kind: StatusKind) -> bool {
+        self.0.iter().any(|e| e.kind == kind)
+    }
+
+    /// the amount of an effect, 0 if it is not on
+    pub fn amount(&self, kind: StatusKind) -> u32 {
+        self.0.iter().find(|e| e.kind == kind).map_or(0, |e| e.amount)
+    }
+
+    /// the damage poison and bleeding deal this round to someone with this much max health
+    pub fn damage(&self, max_health: u32) -> u32 {
+        let bleed = match self.amount(StatusKind::Bleed) {
+            0 => 0,
+            percent => (max_health * percent / 100).max(1),
+        };
+        self.amount(StatusKind::Poison) + bleed
+    }
+
+    /// changes damage by the strength and weaken effects
+    pub fn outgoing(&self, damage: u32) -> u32 {
+        damage * (100 + self.amount(StatusKind::Strength)) / 100 * (100 - self.amount(StatusKind::Weaken).min(100)) / 100
+    }
+
+    /// counts down the rounds left on every effect, removing the ones that ran out
+    pub fn tick(&mut self) {
+        self.0.iter_mut().for_each(|e| e.turns -= 1);
+        self.0.retain(|e| e.turns > 0);
+    }
+
+    /// the effects as sent to the players, `target` is the username of the player they are on or empty for the enemy
+    pub fn as_data(&self, target: &str) -> Vec<StatusEffectData> {
+        self.0.iter().map(|e| StatusEffectData {
+            target: target.to_string(),
+            kind: e.kind,
+            amount: e.amount,
+            turns: e.turns,
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn effects(list: &[StatusEffect]) -> StatusEffects {
+        let mut effects = StatusEffects::default();
+        for effect in list {
+            assert!(effects.apply(*effect));
+        }
+        effects
+    }
+
+    #[test]
+    fn stronger_refresh_keeps_the_higher_amount_and_longer_duration() {
+        let mut effects = effects(&[StatusEffect::new(StatusKind::Poison, 5, 3)]);
+        assert!(effects.apply(StatusEffect::new(StatusKind::Poison, 8, 1)));
+        assert_eq!(effects.amount(StatusKind::Poison), 8);
+        assert_eq!(effects.as_data("")[0].turns, 3);
+
+        assert!(effects.apply(StatusEffect::new(StatusKind::Poison, 2, 6)));
+        assert_eq!(effects.amount(StatusKind::Poison), 8);
+        assert_eq!(effects.as_data("")[0].turns, 6);
+        assert_eq!(effects.as_data("").len(), 1);
+    }
+
+    #[test]
+    fn weaker_refresh_changes_nothing() {
+        let mut effects = effects(&[StatusEffect::new(StatusKind::Shield, 30, 3)]);
+        assert!(!effects.apply(StatusEffect::new(StatusKind::Shield, 20, 2)));
+        assert!(!effects.apply(StatusEffect::new(StatusKind::Shield, 30, 3)));
+        assert_eq!(effects.amount(StatusKind::Shield), 30);
+        assert_eq!(effects.as_data("")[0].turns, 3);
+    }
+
+    #[test]
+    fn stuns_can_not_be_reapplied_while_active() {
+        let mut effects = effects(&[StatusEffect::new(StatusKind::Stun, 0, 1)]);
+        assert!(!effects.apply(StatusEffect::new(StatusKind::Stun, 0, 3)));
+        assert_eq!(effects.as_data("")[0].turns, 1);
+
+        effects.tick();
+        assert!(!effects.has(StatusKind::Stun));
+        assert!(effects.apply(StatusEffect::new(StatusKind::Stun, 0, 1)));
+    }
+
+    #[test]
+    fn zero_turn_effects_are_rejected() {
+        let mut effects = StatusEffects::default();
+        assert!(!effects.apply(StatusEffect::new(StatusKind::Strength, 50, 0)));
+        assert!(!effects.has(StatusKind::Strength));
+    }
+
+    #[test]
+    fn effects_expire_after_ticking() {
+        let mut effects = effects(&[StatusEffect::new(StatusKind::Poison, 5, 2), StatusEffect::new(StatusKind::Weaken, 25, 1)]);
+        effects.tick();
+        assert!(effects.has(StatusKind::Poison));
+        assert!(!effects.has(StatusKind::Weaken));
+        assert_eq!(effects.amount(StatusKind::Weaken), 0);
+
+        effects.tick();
+        assert!(!effects.has(StatusKind::Poison));
+        assert!(effects.as_data("").is_empty());
+    }
+
+    #[test]
+    fn outgoing_damage_is_changed_by_strength_and_weaken() {
+        assert_eq!(StatusEffects::default().outgoing(40), 40);
+        assert_eq!(effects(&[StatusEffect::new(StatusKind::Strength, 50, 1)]).outgoing(40), 60);
+        assert_eq!(effects(&[StatusEffect::new(StatusKind::Weaken, 25, 1)]).outgoing(40), 30);
+        assert_eq!(effects(&[StatusEffect::new(StatusKind::Strength, 50, 1), StatusEffect::new(StatusKind::Weaken, 50, 1)]).outgoing(40), 30);
+    }
+
+    #[test]
+    fn weaken_of_100_or_more_stops_all_damage() {
+        assert_eq!(effects(&[StatusEffect::new(StatusKind::Weaken, 100, 1)]).outgoing(40), 0);
+        assert_eq!(effects(&[StatusEffect::new(StatusKind::Weaken, 250, 1)]).outgoing(40), 0);
+    }
+
+    #[test]
+    fn wounds_add_poison_and_bleeding() {
+        assert_eq!(StatusEffects::default().damage(100), 0);
+        assert_eq!(effects(&[StatusEffect::new(StatusKind::Poison, 7, 1)]).damage(100), 7);
+        assert_eq!(effects(&[StatusEffect::new(StatusKind::Bleed, 10, 1)]).damage(200), 20);
+        assert_eq!(effects(&[StatusEffect::new(StatusKind::Poison, 7, 1), StatusEffect::new(StatusKind::Bleed, 10, 1)]).damage(200), 27);
+    }
+
+    #[test]
+    fn bleeding_deals_at_least_1() {
+        assert_eq!(effects(&[StatusEffect::new(StatusKind::Bleed, 5, 1)]).damage(10), 1);
+        assert_eq!(effects(&[StatusEffect::new(StatusKind::Bleed, 1, 1)]).damage(0), 1);
+    }
+}